@@ -0,0 +1,31 @@
+//! `Accept` header negotiation for render endpoints that can encode the
+//! same image in several formats. Only formats a client names explicitly
+//! are picked; wildcards such as `image/*` keep the endpoint's default, so
+//! browsers (which list `image/webp`, `image/avif` and `*/*`) still get PNG.
+
+use axum::http::{HeaderMap, header};
+
+/// The `offered` format (e.g. `bmp` for `image/bmp`) the client ranks
+/// highest, or `None` when it names none of them.
+pub fn preferred(headers: &HeaderMap, offered: &[&'static str]) -> Option<&'static str> {
+    let accept = headers.get(header::ACCEPT)?.to_str().ok()?;
+
+    let mut ranked: Vec<(f32, &'static str)> = accept
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let media_type = parts.next()?.trim().to_lowercase();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let name = media_type.strip_prefix("image/")?;
+            let format = offered.iter().find(|&&format| format == name)?;
+            (quality > 0.0).then_some((quality, *format))
+        })
+        .collect();
+
+    // Stable, so equally ranked types keep the client's order
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranked.first().map(|&(_, format)| format)
+}
@@ -0,0 +1,193 @@
+//! Brightness/contrast/gamma tweaks and sharpening applied to proxied camera
+//! and entity images before they are encoded or dithered.
+
+use image::{DynamicImage, GrayImage, ImageBuffer, Pixel, imageops};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Adjustments {
+    /// Multiplier on pixel values (1.0 = unchanged)
+    brightness: f32,
+    /// Stretch around mid-gray (1.0 = unchanged, 0.0 = flat gray)
+    contrast: f32,
+    /// Gamma correction; values above 1.0 lift the shadows
+    gamma: f32,
+    /// Stretch the luminance histogram to the full range first
+    auto_levels: bool,
+    /// Unsharp mask strength, applied after the tonal adjustments (0.0 = off)
+    sharpen: f32,
+}
+
+/// Share of pixels ignored at each end of the histogram by auto-levels, so a
+/// few specular highlights or dead pixels don't pin the range.
+const AUTO_LEVELS_CLIP: f32 = 0.005;
+
+/// Strongest accepted `sharpen`
+const MAX_SHARPEN: f32 = 3.0;
+/// Blur radius of the unsharp mask; small so it picks out edges and text
+/// rather than halos
+const SHARPEN_SIGMA: f32 = 1.0;
+
+impl Adjustments {
+    pub fn new(
+        brightness: Option<f32>,
+        contrast: Option<f32>,
+        gamma: Option<f32>,
+        auto_levels: Option<bool>,
+        sharpen: Option<f32>,
+    ) -> Result<Self, String> {
+        let brightness = brightness.unwrap_or(1.0);
+        let contrast = contrast.unwrap_or(1.0);
+        let gamma = gamma.unwrap_or(1.0);
+        let sharpen = sharpen.unwrap_or(0.0);
+
+        if !brightness.is_finite() || brightness < 0.0 {
+            return Err(format!("brightness must be 0 or more, got {}", brightness));
+        }
+        if !contrast.is_finite() || contrast < 0.0 {
+            return Err(format!("contrast must be 0 or more, got {}", contrast));
+        }
+        if !gamma.is_finite() || gamma <= 0.0 {
+            return Err(format!("gamma must be greater than 0, got {}", gamma));
+        }
+        if !(0.0..=MAX_SHARPEN).contains(&sharpen) {
+            return Err(format!(
+                "sharpen must be between 0 and {}, got {}",
+                MAX_SHARPEN, sharpen
+            ));
+        }
+
+        Ok(Self {
+            brightness,
+            contrast,
+            gamma,
+            auto_levels: auto_levels.unwrap_or(false),
+            sharpen,
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.tone_is_identity() && self.sharpen == 0.0
+    }
+
+    fn tone_is_identity(&self) -> bool {
+        !self.auto_levels && self.brightness == 1.0 && self.contrast == 1.0 && self.gamma == 1.0
+    }
+
+    fn lookup_table(&self) -> [u8; 256] {
+        let mut table = [0u8; 256];
+        for (value, entry) in table.iter_mut().enumerate() {
+            let mut level = (value as f32 / 255.0).powf(1.0 / self.gamma);
+            level = (level - 0.5) * self.contrast + 0.5;
+            level *= self.brightness;
+            *entry = (level * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+        table
+    }
+
+    /// Combined lookup table for an image with the given luminance histogram.
+    fn table_for(&self, histogram: &[u32; 256]) -> [u8; 256] {
+        let table = self.lookup_table();
+        if !self.auto_levels {
+            return table;
+        }
+        let levels = levels_table(histogram);
+        std::array::from_fn(|value| table[levels[value] as usize])
+    }
+
+    pub fn apply_luma(&self, image: &mut GrayImage) {
+        if !self.tone_is_identity() {
+            let mut histogram = [0u32; 256];
+            for pixel in image.pixels() {
+                histogram[pixel[0] as usize] += 1;
+            }
+            let table = self.table_for(&histogram);
+            for pixel in image.pixels_mut() {
+                pixel.0[0] = table[pixel[0] as usize];
+            }
+        }
+        if self.sharpen > 0.0 {
+            unsharp_mask(image, self.sharpen, 1);
+        }
+    }
+
+    /// Adjust the color channels, leaving alpha untouched.
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut rgba = image.to_rgba8();
+        if !self.tone_is_identity() {
+            let mut histogram = [0u32; 256];
+            for pixel in rgba.pixels() {
+                histogram[luminance(pixel.0[0], pixel.0[1], pixel.0[2]) as usize] += 1;
+            }
+            let table = self.table_for(&histogram);
+            for pixel in rgba.pixels_mut() {
+                for channel in &mut pixel.0[..3] {
+                    *channel = table[*channel as usize];
+                }
+            }
+        }
+        if self.sharpen > 0.0 {
+            unsharp_mask(&mut rgba, self.sharpen, 3);
+        }
+        DynamicImage::ImageRgba8(rgba)
+    }
+}
+
+/// Push the first `channels` channels of each pixel away from a blurred copy
+/// by `amount`, which steepens edges.
+pub fn unsharp_mask<P>(image: &mut ImageBuffer<P, Vec<u8>>, amount: f32, channels: usize)
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let blurred = imageops::blur(image, SHARPEN_SIGMA);
+    for (pixel, soft) in image.pixels_mut().zip(blurred.pixels()) {
+        let soft = soft.channels();
+        for (channel, &soft) in pixel.channels_mut()[..channels].iter_mut().zip(soft) {
+            let value = *channel as f32;
+            *channel = (value + amount * (value - soft as f32))
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+/// Map the clipped `[low, high]` range of the histogram onto `[0, 255]`.
+fn levels_table(histogram: &[u32; 256]) -> [u8; 256] {
+    let total: u32 = histogram.iter().sum();
+    let clip = (total as f32 * AUTO_LEVELS_CLIP) as u32;
+
+    let mut low = 0;
+    let mut seen = 0;
+    for (value, &count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen > clip {
+            low = value;
+            break;
+        }
+    }
+
+    let mut high = 255;
+    seen = 0;
+    for (value, &count) in histogram.iter().enumerate().rev() {
+        seen += count;
+        if seen > clip {
+            high = value;
+            break;
+        }
+    }
+
+    if high <= low {
+        // Flat image: nothing to stretch
+        return std::array::from_fn(|value| value as u8);
+    }
+
+    let range = (high - low) as f32;
+    std::array::from_fn(|value| {
+        ((value as f32 - low as f32) * 255.0 / range)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    })
+}
@@ -0,0 +1,163 @@
+//! Appliance cycles: a washing machine, dryer or dishwasher (Home Connect,
+//! SmartThings) reports its cycle across several entities - the operation
+//! state, the remaining time, the program progress and the door.
+//! `APPLIANCES` groups them under a name, shown as one row with a progress
+//! bar when `appliance.<name>` is listed among a screen's sensors, e.g.
+//! `APPLIANCES=washer=state:sensor.washer_operation_state,remaining:sensor.washer_remaining_program_time,progress:sensor.washer_program_progress,door:binary_sensor.washer_door`.
+//! A finished cycle is an attention state until the door is opened.
+
+use crate::{durations, timestamps};
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Sensor ID prefix of an appliance row
+pub const ID_PREFIX: &str = "appliance.";
+/// Attribute the cycle's progress (0-100) is added to the row's state under
+pub const PROGRESS_ATTRIBUTE: &str = "appliance_progress";
+
+/// The entities of one appliance.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Appliance {
+    /// Row label, the appliance's name with `_` as spaces if unset
+    pub label: Option<String>,
+    /// Operation state, e.g. `run`, `pause` or `finished`
+    pub state: String,
+    /// Remaining program time: a duration or the finish timestamp
+    pub remaining: Option<String>,
+    /// Program progress in percent
+    pub progress: Option<String>,
+    /// Door, open while `on` or `open`
+    pub door: Option<String>,
+}
+
+/// Where the cycle is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Off, ready or anything unrecognised
+    Idle,
+    Running,
+    /// Paused, waiting for a delayed start, or stopped for an error
+    Interrupted,
+    Finished,
+}
+
+/// `APPLIANCES`: `name=role:entity_id,...` entries separated by `;`, the
+/// roles being `state` (required), `remaining`, `progress`, `door` and
+/// `label` (text rather than an entity). The offending entry is returned
+/// on error.
+pub fn parse(spec: &str) -> Result<HashMap<String, Appliance>, String> {
+    let mut appliances = HashMap::new();
+
+    for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+        let (name, roles) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let name = name.trim().to_lowercase();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(entry.to_string());
+        }
+
+        let mut appliance = Appliance::default();
+        for role in roles.split(',').filter(|r| !r.trim().is_empty()) {
+            let (role, value) = role.split_once(':').ok_or_else(|| entry.to_string())?;
+            let value = value.trim().to_string();
+            let slot = match role.trim() {
+                "label" => {
+                    appliance.label = Some(value);
+                    continue;
+                }
+                "state" if appliance.state.is_empty() => {
+                    appliance.state = value.clone();
+                    None
+                }
+                "remaining" => appliance.remaining.replace(value.clone()),
+                "progress" => appliance.progress.replace(value.clone()),
+                "door" => appliance.door.replace(value.clone()),
+                _ => return Err(entry.to_string()),
+            };
+            if slot.is_some() || !value.contains('.') {
+                return Err(entry.to_string());
+            }
+        }
+
+        if appliance.state.is_empty() || appliances.insert(name, appliance).is_some() {
+            return Err(entry.to_string());
+        }
+    }
+
+    Ok(appliances)
+}
+
+/// The state without an enum prefix (`BSH.Common.EnumType.OperationState.Run`
+/// is `run`), lowercase and without separators.
+fn normalize(state: &str) -> String {
+    state
+        .rsplit('.')
+        .next()
+        .unwrap_or(state)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+pub fn phase(state: &str) -> Phase {
+    match normalize(state).as_str() {
+        "finished" | "finish" | "end" | "complete" | "completed" | "done" => Phase::Finished,
+        "run" | "running" | "inprogress" | "prewash" | "wash" | "washing" | "rinse" | "rinsing"
+        | "spin" | "spinning" | "dry" | "drying" | "cooling" | "weightsensing" => Phase::Running,
+        "pause" | "paused" | "delayedstart" | "actionrequired" | "error" | "aborting" => {
+            Phase::Interrupted
+        }
+        _ => Phase::Idle,
+    }
+}
+
+/// `Running`, `Delayed start`, `Rinse`, ...
+pub fn state_label(state: &str) -> String {
+    let label = match normalize(state).as_str() {
+        "run" | "running" | "inprogress" => "Running".to_string(),
+        "pause" | "paused" => "Paused".to_string(),
+        "delayedstart" => "Delayed start".to_string(),
+        "actionrequired" => "Action required".to_string(),
+        "finished" | "finish" | "end" | "complete" | "completed" | "done" => "Finished".to_string(),
+        _ => state.rsplit('.').next().unwrap_or(state).to_string(),
+    };
+    name_label(&label)
+}
+
+/// `dish_washer` as `Dish washer`.
+pub fn name_label(name: &str) -> String {
+    let name = name.replace('_', " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+pub fn door_open(state: &str) -> bool {
+    matches!(
+        state.trim().to_lowercase().as_str(),
+        "on" | "open" | "opened"
+    )
+}
+
+/// Seconds left in the cycle from the `remaining` entity: a duration in
+/// `unit`, or the time the cycle finishes.
+pub fn remaining_secs(state: &str, unit: Option<&str>, now: SystemTime) -> Option<u64> {
+    if let Some(seconds) = unit.and_then(durations::unit_seconds) {
+        let value = state.trim().parse::<f64>().ok()?;
+        return (value >= 0.0).then(|| (value * seconds).round() as u64);
+    }
+    let finish = timestamps::parse(state)?;
+    Some(finish.duration_since(now).unwrap_or_default().as_secs())
+}
+
+/// Whole minutes, rounded up: `42m`, `1h 05m`.
+pub fn format_remaining(secs: u64) -> String {
+    let minutes = secs.div_ceil(60);
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
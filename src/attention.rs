@@ -0,0 +1,89 @@
+//! Attention states: a triggered alarm, detected smoke or a water leak,
+//! conditions that should dominate the screen. Rows in one move to the top
+//! of TRMNL and `/multi-status` lists and are drawn inverted.
+//! `ATTENTION_STATES` replaces the built-in rules with comma-separated
+//! `selector=state` entries, the selector being an entity ID, a domain or
+//! `domain:device_class`, and `|` separating several states, e.g.
+//! `ATTENTION_STATES=alarm_control_panel=triggered,lock=jammed|open`.
+
+/// Attribute an entity in an attention state is marked with
+pub const ATTRIBUTE: &str = "attention";
+
+/// Rules used without `ATTENTION_STATES`
+const DEFAULT_RULES: &str = "alarm_control_panel=triggered,\
+    binary_sensor:smoke=on,binary_sensor:moisture=on,binary_sensor:gas=on,\
+    binary_sensor:carbon_monoxide=on,binary_sensor:safety=on";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Selector {
+    Entity(String),
+    Domain(String),
+    DeviceClass(String, String),
+}
+
+impl Selector {
+    fn parse(selector: &str) -> Option<Self> {
+        let selector = selector.trim();
+        let valid = |part: &str| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if let Some((domain, object_id)) = selector.split_once('.') {
+            (valid(domain) && valid(object_id)).then(|| Self::Entity(selector.to_string()))
+        } else if let Some((domain, class)) = selector.split_once(':') {
+            (valid(domain) && valid(class))
+                .then(|| Self::DeviceClass(domain.to_string(), class.to_string()))
+        } else {
+            valid(selector).then(|| Self::Domain(selector.to_string()))
+        }
+    }
+
+    fn matches(&self, entity_id: &str, device_class: Option<&str>) -> bool {
+        let domain = entity_id.split('.').next().unwrap_or("");
+        match self {
+            Self::Entity(id) => id == entity_id,
+            Self::Domain(d) => d == domain,
+            Self::DeviceClass(d, class) => d == domain && device_class == Some(class.as_str()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AttentionStates(Vec<(Selector, Vec<String>)>);
+
+impl Default for AttentionStates {
+    fn default() -> Self {
+        Self::parse(DEFAULT_RULES).expect("built-in attention states are valid")
+    }
+}
+
+impl AttentionStates {
+    /// `ATTENTION_STATES`; empty turns attention states off. The offending
+    /// entry is returned on error.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+
+        for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+            let (selector, states) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+            let selector = Selector::parse(selector).ok_or_else(|| entry.to_string())?;
+            let states: Vec<String> = states
+                .split('|')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            if states.is_empty() {
+                return Err(entry.to_string());
+            }
+            rules.push((selector, states));
+        }
+
+        Ok(Self(rules))
+    }
+
+    /// Whether `state` of `entity_id` is one a rule covering it lists.
+    pub fn matches(&self, entity_id: &str, state: &str, device_class: Option<&str>) -> bool {
+        self.0.iter().any(|(selector, states)| {
+            selector.matches(entity_id, device_class)
+                && states.iter().any(|s| s.eq_ignore_ascii_case(state))
+        })
+    }
+}
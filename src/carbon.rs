@@ -0,0 +1,230 @@
+//! Grid carbon intensity and electricity prices with a hint of when to run
+//! the dishwasher. Integrations (Carbon Intensity UK, Nord Pool, Energi
+//! Data Service, Octopus Energy) list their forecast in attribute arrays of
+//! timed values; `Fields` says where, and the lowest upcoming value decides
+//! between running appliances now and waiting for it.
+
+use crate::{currency, pollen::lookup, timestamps};
+use serde_json::Value;
+use std::time::{Duration, SystemTime};
+
+/// How close to the lowest upcoming value, as a fraction of the forecast's
+/// range, still counts as a good time
+const GOOD_ENOUGH: f64 = 0.1;
+/// Length assumed for a forecast slot when there's only one
+const DEFAULT_SLOT: Duration = Duration::from_secs(3600);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Point {
+    pub time: SystemTime,
+    pub value: f64,
+}
+
+/// What the sensor measures, which words the verdict
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Carbon,
+    Price,
+}
+
+impl Kind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "carbon" | "co2" => Some(Self::Carbon),
+            "price" => Some(Self::Price),
+            _ => None,
+        }
+    }
+
+    /// Carbon for units such as `gCO2eq/kWh`, price for anything else.
+    pub fn of_unit(unit: &str) -> Self {
+        if unit.to_lowercase().contains("co2") {
+            Self::Carbon
+        } else {
+            Self::Price
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Carbon => "GRID CARBON",
+            Self::Price => "ENERGY PRICE",
+        }
+    }
+
+    fn good_now(self) -> &'static str {
+        match self {
+            Self::Carbon => "Clean now",
+            Self::Price => "Cheap now",
+        }
+    }
+}
+
+/// When to run appliances.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    /// Now is about as good as it gets
+    Now,
+    /// The best time starts after this long
+    Wait(Duration),
+    /// No forecast to compare with
+    Unknown,
+}
+
+impl Verdict {
+    /// `Clean now`, `Wait 2h`, `Wait 45m`.
+    pub fn text(self, kind: Kind) -> String {
+        match self {
+            Self::Now => kind.good_now().to_string(),
+            Self::Wait(wait) => {
+                let minutes = wait.as_secs().div_ceil(60);
+                if minutes < 60 {
+                    format!("Wait {}m", minutes)
+                } else {
+                    format!("Wait {}h", (minutes + 30) / 60)
+                }
+            }
+            Self::Unknown => "No forecast".to_string(),
+        }
+    }
+}
+
+/// `182 gCO2eq/kWh`, or a price in its currency where the unit names one,
+/// e.g. `€0.31/kWh`.
+pub fn format_value(value: f64, unit: &str, kind: Kind, locale: Option<&str>) -> String {
+    let plain = |decimals: usize| {
+        format!("{:.*} {}", decimals, value, unit)
+            .trim_end()
+            .to_string()
+    };
+    match kind {
+        Kind::Carbon => plain(0),
+        Kind::Price => {
+            let (code, per) = unit.split_once('/').unwrap_or((unit, ""));
+            match currency::format(value, code.trim(), locale) {
+                Some(money) if per.is_empty() => money,
+                Some(money) => format!("{}/{}", money, per.trim()),
+                None => plain(2),
+            }
+        }
+    }
+}
+
+/// Attribute paths; `list` may name several `,`-separated arrays that are
+/// joined (e.g. today's and tomorrow's prices), `time` and `value` may list
+/// `|`-separated alternatives, the first present one wins.
+#[derive(Clone, Debug)]
+pub struct Fields {
+    pub list: String,
+    pub time: String,
+    pub value: String,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self {
+            list: "forecast,raw_today,raw_tomorrow,rates".to_string(),
+            time: "start|from|hour|datetime|start_time|valid_from".to_string(),
+            value: "intensity|value|price|value_inc_vat".to_string(),
+        }
+    }
+}
+
+impl Fields {
+    /// Forecast points in `attributes` in time order. Times without a UTC
+    /// offset are local, `offset_secs` ahead of UTC; numbers are Unix
+    /// seconds or milliseconds.
+    pub fn forecast(&self, attributes: &Value, offset_secs: i64) -> Vec<Point> {
+        let first = |entry: &'_ Value, paths: &str| {
+            paths
+                .split('|')
+                .find_map(|path| lookup(entry, path.trim()).filter(|value| !value.is_null()))
+                .cloned()
+        };
+
+        let mut points: Vec<Point> = self
+            .list
+            .split(',')
+            .filter_map(|path| lookup(attributes, path.trim())?.as_array())
+            .flatten()
+            .filter_map(|entry| {
+                let time = match first(entry, &self.time)? {
+                    Value::String(text) => timestamps::parse_local(&text, offset_secs)?,
+                    Value::Number(number) => {
+                        let secs = number.as_u64()?;
+                        let secs = if secs > 100_000_000_000 {
+                            secs / 1000
+                        } else {
+                            secs
+                        };
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+                    }
+                    _ => return None,
+                };
+                let value = match first(entry, &self.value)? {
+                    Value::Number(number) => number.as_f64()?,
+                    Value::String(text) => text.trim().parse().ok()?,
+                    _ => return None,
+                };
+                Some(Point { time, value })
+            })
+            .collect();
+        points.sort_by_key(|point| point.time);
+        points.dedup_by_key(|point| point.time);
+        points
+    }
+}
+
+/// Points of `forecast` whose slot hasn't ended by `now`, up to `horizon`
+/// ahead.
+pub fn upcoming(forecast: &[Point], now: SystemTime, horizon: Duration) -> Vec<Point> {
+    let slot = match forecast {
+        [first, second, ..] => second
+            .time
+            .duration_since(first.time)
+            .unwrap_or(DEFAULT_SLOT),
+        _ => DEFAULT_SLOT,
+    };
+    forecast
+        .iter()
+        .filter(|point| point.time + slot > now && point.time < now + horizon)
+        .copied()
+        .collect()
+}
+
+/// Whether to run appliances at `current` or wait for the lowest of
+/// `upcoming`, which is returned along with the verdict.
+pub fn verdict(current: f64, upcoming: &[Point], now: SystemTime) -> (Verdict, Option<Point>) {
+    let Some(best) = upcoming
+        .iter()
+        .copied()
+        .min_by(|a, b| a.value.total_cmp(&b.value))
+    else {
+        return (Verdict::Unknown, None);
+    };
+
+    let highest = upcoming
+        .iter()
+        .map(|point| point.value)
+        .fold(current, f64::max);
+    let lowest = best.value.min(current);
+    let close_enough = current - best.value <= (highest - lowest) * GOOD_ENOUGH;
+    match best.time.duration_since(now) {
+        Ok(wait) if !close_enough && !wait.is_zero() => (Verdict::Wait(wait), Some(best)),
+        _ => (Verdict::Now, Some(best)),
+    }
+}
+
+/// Values at or below this are drawn as good times on the forecast bars:
+/// the lowest third of the range.
+pub fn good_threshold(points: &[Point]) -> f64 {
+    let lowest = points
+        .iter()
+        .map(|point| point.value)
+        .fold(f64::INFINITY, f64::min);
+    let highest = points
+        .iter()
+        .map(|point| point.value)
+        .fold(f64::NEG_INFINITY, f64::max);
+    lowest + (highest - lowest) / 3.0
+}
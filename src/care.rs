@@ -0,0 +1,91 @@
+//! Care logs for the fridge display: when the baby was last fed, how many
+//! walks the dog has had today. `CARE_LOG` lists the entities kept that
+//! way - `input_datetime` helpers (or timestamp sensors) set at each
+//! feeding, and `counter` helpers bumped at each walk - with how long may
+//! pass before the next one is due, e.g.
+//! `CARE_LOG=input_datetime.last_fed=3h,counter.walks_today=6h,input_datetime.litter_changed`.
+//! Times are shown as `2h 10m ago`; a row whose interval has passed since
+//! the last entry is drawn as an attention state.
+
+use crate::timestamps;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Attribute the seconds since the last entry are added to a state under
+pub const SINCE_ATTRIBUTE: &str = "care_since";
+
+/// `CARE_LOG`: comma-separated `entity_id` or `entity_id=interval`
+/// entries, the interval in seconds or with an `s`, `m`, `h` or `d` unit.
+/// The offending entry is returned on error.
+pub fn parse(spec: &str) -> Result<HashMap<String, Option<Duration>>, String> {
+    let mut entries = HashMap::new();
+
+    for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+        let (entity_id, interval) = match entry.split_once('=') {
+            Some((entity_id, interval)) => (
+                entity_id.trim(),
+                Some(parse_interval(interval).ok_or_else(|| entry.to_string())?),
+            ),
+            None => (entry.trim(), None),
+        };
+
+        if !entity_id.contains('.') || entries.insert(entity_id.to_string(), interval).is_some() {
+            return Err(entry.to_string());
+        }
+    }
+
+    Ok(entries)
+}
+
+/// `90m`, `3h`, `1d` or plain seconds; never zero.
+fn parse_interval(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(pos) => text.split_at(pos),
+        None => (text, "s"),
+    };
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return None,
+    };
+    let number: u64 = number.trim().parse().ok().filter(|n| *n > 0)?;
+    Some(Duration::from_secs(number * seconds))
+}
+
+/// When the last entry was made: the time an `input_datetime` with a date
+/// holds (its `timestamp` attribute) or a timestamp sensor's state, and
+/// for counters and anything else, the time the state last changed.
+pub fn last_entry(
+    entity_id: &str,
+    state: &str,
+    attributes: &Value,
+    last_changed: Option<&str>,
+) -> Option<SystemTime> {
+    if entity_id.starts_with("input_datetime.") {
+        if attributes.get("has_date").and_then(|v| v.as_bool()) == Some(false) {
+            return None;
+        }
+        let secs = attributes.get("timestamp")?.as_f64()?;
+        return (secs >= 0.0).then(|| UNIX_EPOCH + Duration::from_secs_f64(secs));
+    }
+    if attributes.get("device_class").and_then(|v| v.as_str()) == Some("timestamp") {
+        return timestamps::parse(state);
+    }
+    timestamps::parse(last_changed?)
+}
+
+/// `just now`, `45m ago`, `2h 10m ago`, `3d 4h ago`.
+pub fn format_since(secs: u64) -> String {
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86_400 => format!("{}h {}m ago", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h ago", secs / 86_400, secs % 86_400 / 3600),
+    }
+}
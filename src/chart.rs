@@ -0,0 +1,182 @@
+//! Line chart primitives for the TRMNL screens. Positions within a chart
+//! are given as fractions of its area: `x` from left (0.0) to right (1.0),
+//! `y` from bottom (0.0) to top (1.0).
+
+use image::{GrayImage, Luma};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Area {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Area {
+    pub fn x_at(&self, fraction: f64) -> u32 {
+        self.x + (fraction.clamp(0.0, 1.0) * (self.width - 1) as f64).round() as u32
+    }
+
+    pub fn y_at(&self, fraction: f64) -> u32 {
+        self.y + self.height
+            - 1
+            - (fraction.clamp(0.0, 1.0) * (self.height - 1) as f64).round() as u32
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.y + self.height - 1
+    }
+
+    /// Baseline along the bottom of the area.
+    pub fn draw_axis(&self, image: &mut GrayImage, color: Luma<u8>) {
+        for x in self.x..(self.x + self.width) {
+            put(image, x as i64, self.bottom() as i64, color);
+        }
+    }
+
+    /// Connect `(x, y)` fractions with straight segments.
+    pub fn draw_series(
+        &self,
+        image: &mut GrayImage,
+        points: &[(f64, f64)],
+        color: Luma<u8>,
+        thickness: u32,
+    ) {
+        for pair in points.windows(2) {
+            let from = (self.x_at(pair[0].0), self.y_at(pair[0].1));
+            let to = (self.x_at(pair[1].0), self.y_at(pair[1].1));
+            draw_line(image, from, to, color, thickness);
+        }
+    }
+
+    /// Dashed vertical marker across the full height, e.g. for "now".
+    pub fn draw_marker(&self, image: &mut GrayImage, fraction: f64, color: Luma<u8>) {
+        let x = self.x_at(fraction);
+        for y in (self.y..=self.bottom()).filter(|y| y % 6 < 3) {
+            put(image, x as i64, y as i64, color);
+        }
+    }
+
+    /// Dashed horizontal line across the full width, e.g. for a threshold.
+    pub fn draw_level(&self, image: &mut GrayImage, fraction: f64, color: Luma<u8>) {
+        let y = self.y_at(fraction);
+        for x in (self.x..(self.x + self.width)).filter(|x| x % 10 < 6) {
+            put(image, x as i64, y as i64, color);
+            put(image, x as i64, y as i64 + 1, color);
+        }
+    }
+
+    /// Dotted shading over the full height from `left` to `right`, light
+    /// enough for a series drawn on top to stay readable in 1-bit.
+    pub fn draw_band(&self, image: &mut GrayImage, (left, right): (f64, f64), color: Luma<u8>) {
+        for x in self.x_at(left)..=self.x_at(right) {
+            for y in (self.y..=self.bottom()).filter(|y| y % 2 == 0 && (x + y) % 4 == 0) {
+                put(image, x as i64, y as i64, color);
+            }
+        }
+    }
+
+    /// Hatching from `low` to `high` between `left` and `right`, e.g. the
+    /// range a mean was taken over.
+    pub fn draw_range(
+        &self,
+        image: &mut GrayImage,
+        (left, right): (f64, f64),
+        (low, high): (f64, f64),
+        color: Luma<u8>,
+    ) {
+        for x in self.x_at(left)..=self.x_at(right) {
+            for y in (self.y_at(high)..=self.y_at(low)).filter(|y| (x + y) % 3 == 0) {
+                put(image, x as i64, y as i64, color);
+            }
+        }
+    }
+
+    /// Bar from the baseline up to `height`, spanning `left` to `right`;
+    /// hollow unless `filled`.
+    pub fn draw_bar(
+        &self,
+        image: &mut GrayImage,
+        (left, right): (f64, f64),
+        height: f64,
+        filled: bool,
+        color: Luma<u8>,
+    ) {
+        self.draw_column(image, (left, right), (0.0, height), filled, color);
+    }
+
+    /// Bar from `base` to `end`, which is below it for a negative value,
+    /// spanning `left` to `right`; hollow unless `filled`, with its edge
+    /// at `base` left open.
+    pub fn draw_column(
+        &self,
+        image: &mut GrayImage,
+        (left, right): (f64, f64),
+        (base, end): (f64, f64),
+        filled: bool,
+        color: Luma<u8>,
+    ) {
+        let (x0, x1) = (self.x_at(left), self.x_at(right));
+        let (from, to) = (self.y_at(base), self.y_at(end));
+        for x in x0..=x1 {
+            for y in from.min(to)..=from.max(to) {
+                let edge = x < x0 + 2 || x + 2 > x1 || y.abs_diff(to) < 2;
+                if filled || edge {
+                    put(image, x as i64, y as i64, color);
+                }
+            }
+        }
+    }
+
+    /// Short tick below the baseline.
+    pub fn draw_tick(&self, image: &mut GrayImage, fraction: f64, color: Luma<u8>) {
+        let x = self.x_at(fraction);
+        for y in self.bottom()..(self.bottom() + 6) {
+            put(image, x as i64, y as i64, color);
+        }
+    }
+}
+
+/// Bresenham line, `thickness` pixels wide.
+pub fn draw_line(
+    image: &mut GrayImage,
+    (x0, y0): (u32, u32),
+    (x1, y1): (u32, u32),
+    color: Luma<u8>,
+    thickness: u32,
+) {
+    let (mut x, mut y) = (x0 as i64, y0 as i64);
+    let (x1, y1) = (x1 as i64, y1 as i64);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let step_x = if x < x1 { 1 } else { -1 };
+    let step_y = if y < y1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    let half = thickness as i64 / 2;
+
+    loop {
+        for oy in -half..(thickness as i64 - half) {
+            for ox in -half..(thickness as i64 - half) {
+                put(image, x + ox, y + oy, color);
+            }
+        }
+        if x == x1 && y == y1 {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+fn put(image: &mut GrayImage, x: i64, y: i64, color: Luma<u8>) {
+    if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
@@ -0,0 +1,131 @@
+//! Monetary sensors (`device_class: monetary` - energy cost, account
+//! balance) shown as `€1.234,56` rather than `1234.56 EUR`: the currency's
+//! symbol and decimals, grouped the way `CURRENCY_LOCALE` writes numbers.
+//! Sensors without a unit are taken to be in `CURRENCY`.
+
+/// Attribute the configured locale is added to a monetary sensor's state under
+pub const LOCALE_ATTRIBUTE: &str = "currency_locale";
+
+/// Symbol and minor-unit digits per ISO 4217 code. Currencies without a
+/// symbol of their own (`CHF`, `SEK`) are written with their code.
+const CURRENCIES: &[(&str, &str, usize)] = &[
+    ("AUD", "A$", 2),
+    ("CAD", "C$", 2),
+    ("CHF", "CHF ", 2),
+    ("CNY", "¥", 2),
+    ("CZK", "CZK ", 2),
+    ("DKK", "DKK ", 2),
+    ("EUR", "€", 2),
+    ("GBP", "£", 2),
+    ("HUF", "HUF ", 0),
+    ("ISK", "ISK ", 0),
+    ("JPY", "¥", 0),
+    ("KRW", "KRW ", 0),
+    ("NOK", "NOK ", 2),
+    ("NZD", "NZ$", 2),
+    ("PLN", "PLN ", 2),
+    ("SEK", "SEK ", 2),
+    ("USD", "$", 2),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    /// `1,234.56`
+    #[default]
+    En,
+    /// `1.234,56`
+    De,
+    /// `1 234,56`
+    Fr,
+    /// `1'234.56`
+    Ch,
+}
+
+impl Locale {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" | "en-gb" | "en-ie" | "en-au" | "ja" | "zh" => Some(Self::En),
+            "de" | "de-de" | "de-at" | "nl" | "it" | "es" | "da" => Some(Self::De),
+            "fr" | "fr-fr" | "sv" | "nb" | "fi" | "pl" | "cs" => Some(Self::Fr),
+            "ch" | "de-ch" | "fr-ch" | "it-ch" => Some(Self::Ch),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Fr => "fr",
+            Self::Ch => "ch",
+        }
+    }
+
+    /// Thousands and decimal separators
+    fn separators(self) -> (char, char) {
+        match self {
+            Self::En => (',', '.'),
+            Self::De => ('.', ','),
+            Self::Fr => (' ', ','),
+            Self::Ch => ('\'', '.'),
+        }
+    }
+}
+
+/// `CURRENCY`: an ISO 4217 code such as `EUR`, upper-cased.
+pub fn parse_code(code: &str) -> Option<String> {
+    let code = code.trim();
+    (code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()))
+        .then(|| code.to_ascii_uppercase())
+}
+
+/// A monetary sensor's `value` in `unit` (an ISO code, or a symbol such as
+/// `€`) as money in `locale` (its `LOCALE_ATTRIBUTE`, `en` when unset);
+/// `None` when the unit isn't a currency.
+pub fn format(value: f64, unit: &str, locale: Option<&str>) -> Option<String> {
+    if !value.is_finite() {
+        return None;
+    }
+    let (prefix, decimals) = match CURRENCIES
+        .iter()
+        .find(|(code, symbol, _)| unit.eq_ignore_ascii_case(code) || unit == symbol.trim_end())
+    {
+        Some(&(_, symbol, decimals)) => (symbol.to_string(), decimals),
+        None => (format!("{} ", parse_code(unit)?), 2),
+    };
+    let locale = locale.and_then(Locale::parse).unwrap_or_default();
+
+    // No "-€0.00" for a balance that only rounds to zero
+    let rounds_to_zero = (value.abs() * 10f64.powi(decimals as i32)).round() == 0.0;
+    let sign = if value < 0.0 && !rounds_to_zero {
+        "-"
+    } else {
+        ""
+    };
+    Some(format!(
+        "{}{}{}",
+        sign,
+        prefix,
+        group(value.abs(), decimals, locale)
+    ))
+}
+
+/// `value` with `decimals` digits and the locale's separators.
+fn group(value: f64, decimals: usize, locale: Locale) -> String {
+    let (thousands, decimal) = locale.separators();
+    let text = format!("{:.*}", decimals, value);
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+
+    let mut grouped = String::with_capacity(text.len() + whole.len() / 3);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(thousands);
+        }
+        grouped.push(digit);
+    }
+    if !fraction.is_empty() {
+        grouped.push(decimal);
+        grouped.push_str(fraction);
+    }
+    grouped
+}
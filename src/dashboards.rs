@@ -0,0 +1,444 @@
+//! Named dashboards configured through `DASHBOARDS`, each pointing at one of
+//! the render endpoints, e.g.
+//! `DASHBOARDS=power=/trmnl?sensors=sensor.solar,sensor.grid;office=/multi-status?sensors=sensor.office_temp`.
+//!
+//! `DASHBOARD_SUMMARIES` gives a dashboard a Home Assistant template for its
+//! one-line text summary, e.g.
+//! `DASHBOARD_SUMMARIES=office={{ states('sensor.office_temp') }}° inside`.
+//!
+//! `DASHBOARD_TILES` spreads a dashboard over several panels side by side
+//! or stacked, e.g. `DASHBOARD_TILES=wall=2x1+40`: it is laid out once on a
+//! canvas the size of the whole installation, and each panel is served its
+//! part, with the width of the gap between panels left out so content
+//! lines up across it.
+//!
+//! With `DASHBOARD_CACHE_SECONDS` set, renders are reused until they expire
+//! or a calendar, to-do list or automation the dashboard names changes.
+//! Every render carries a checksum of its body as `ETag`, so firmware can
+//! poll `/dashboards/{name}/checksum` and only download changed screens.
+//! A render that looks the same as the dashboard's previous one (or differs
+//! in less than `DASHBOARD_MIN_CHANGE` percent of its pixels) is replaced
+//! by it, keeping its `ETag` and `Last-Modified`, so conditional requests
+//! are answered `304 Not Modified` until the screen really changes.
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tracing::debug;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Dashboard {
+    pub name: String,
+    /// Render path including the query string
+    pub path: String,
+    /// Template for the text summary, from `DASHBOARD_SUMMARIES`
+    pub summary: Option<String>,
+    /// Panels the dashboard is spread over, from `DASHBOARD_TILES`
+    pub tiles: Option<Tiles>,
+}
+
+/// Domains whose changes re-render the dashboards naming them. Their content
+/// changes rarely but should show up at once, unlike sensors that change all
+/// the time and are fine at the cache's pace.
+const WATCHED_DOMAINS: &[&str] = &["calendar", "todo", "automation"];
+
+impl Dashboard {
+    /// Entity IDs named anywhere in the render path, in order.
+    pub fn entities(&self) -> Vec<String> {
+        let Ok(url) = reqwest::Url::parse(&format!("http://dashboard{}", self.path)) else {
+            return Vec::new();
+        };
+
+        let segments = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .map(|segment| segment.to_string());
+        let values = url.query_pairs().map(|(_, value)| value.into_owned());
+        let mut entities: Vec<String> = Vec::new();
+        for text in segments.chain(values) {
+            for token in text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            {
+                let is_entity = token.split_once('.').is_some_and(|(domain, object_id)| {
+                    !domain.is_empty()
+                        && domain.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+                        && !object_id.is_empty()
+                        && !object_id.contains('.')
+                });
+                if is_entity && !entities.iter().any(|entity| entity == token) {
+                    entities.push(token.to_string());
+                }
+            }
+        }
+        entities
+    }
+
+    /// Entities of `WATCHED_DOMAINS` in the render path.
+    pub fn watched_entities(&self) -> Vec<String> {
+        let mut entities: Vec<String> = self
+            .entities()
+            .into_iter()
+            .filter(|entity| {
+                entity
+                    .split_once('.')
+                    .is_some_and(|(domain, _)| WATCHED_DOMAINS.contains(&domain))
+            })
+            .collect();
+        entities.sort_unstable();
+        entities
+    }
+}
+
+/// Paths that can't be used as a dashboard target (they would render other
+/// dashboards or trigger admin actions).
+const RESERVED_PREFIXES: &[&str] = &["/dashboards", "/admin"];
+
+pub fn parse(spec: &str) -> Result<Vec<Dashboard>, String> {
+    let mut dashboards: Vec<Dashboard> = Vec::new();
+
+    for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+        let (name, path) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let (name, path) = (name.trim(), path.trim());
+
+        if name.is_empty()
+            || !path.starts_with('/')
+            || RESERVED_PREFIXES
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+            || dashboards.iter().any(|d| d.name == name)
+        {
+            return Err(entry.to_string());
+        }
+
+        dashboards.push(Dashboard {
+            name: name.to_string(),
+            path: path.to_string(),
+            summary: None,
+            tiles: None,
+        });
+    }
+
+    Ok(dashboards)
+}
+
+/// Attach `name=template` entries separated by `;` to their dashboards.
+pub fn parse_summaries(dashboards: &mut [Dashboard], spec: &str) -> Result<(), String> {
+    for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+        let (name, template) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let dashboard = dashboards
+            .iter_mut()
+            .find(|d| d.name == name.trim())
+            .ok_or_else(|| entry.to_string())?;
+        if template.trim().is_empty() {
+            return Err(entry.to_string());
+        }
+        dashboard.summary = Some(template.trim().to_string());
+    }
+    Ok(())
+}
+
+/// Grid of panels a tiled dashboard is spread over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Tiles {
+    pub columns: u32,
+    pub rows: u32,
+    /// Pixels between neighbouring panels (their bezels), in the panel's
+    /// layout, that are laid out but not shown
+    pub gap: u32,
+}
+
+/// Most panels one dashboard can be spread over
+const MAX_TILES: u32 = 16;
+
+impl Tiles {
+    /// `COLUMNSxROWS`, optionally `+GAP`: `2x1`, `2x2+40`.
+    fn parse(spec: &str) -> Option<Self> {
+        let (grid, gap) = match spec.trim().split_once('+') {
+            Some((grid, gap)) => (grid, gap.trim().parse().ok()?),
+            None => (spec.trim(), 0),
+        };
+        let (columns, rows) = grid.split_once('x')?;
+        let tiles = Self {
+            columns: columns.trim().parse().ok()?,
+            rows: rows.trim().parse().ok()?,
+            gap,
+        };
+        (tiles.columns >= 1 && tiles.rows >= 1 && tiles.count() <= MAX_TILES).then_some(tiles)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// Size of the whole installation for panels laid out at `panel`.
+    pub fn layout(&self, panel: (u32, u32)) -> (u32, u32) {
+        (
+            self.columns * panel.0 + (self.columns - 1) * self.gap,
+            self.rows * panel.1 + (self.rows - 1) * self.gap,
+        )
+    }
+
+    /// `x, y, width, height` of panel `index` (left to right, then top to
+    /// bottom) in a render of `size`, laid out for panels of `panel`. A
+    /// render scaled with `width`/`height` is cut up in proportion.
+    pub fn rect(&self, index: u32, panel: (u32, u32), size: (u32, u32)) -> (u32, u32, u32, u32) {
+        let layout = self.layout(panel);
+        let scale = |value: u32, size: u32, layout: u32| {
+            (value as u64 * size as u64 / layout as u64) as u32
+        };
+        let (column, row) = (index % self.columns, index / self.columns);
+        let x = column * (panel.0 + self.gap);
+        let y = row * (panel.1 + self.gap);
+        let (left, top) = (scale(x, size.0, layout.0), scale(y, size.1, layout.1));
+        let right = scale(x + panel.0, size.0, layout.0);
+        let bottom = scale(y + panel.1, size.1, layout.1);
+        (left, top, right - left, bottom - top)
+    }
+}
+
+/// Attach `name=COLUMNSxROWS[+GAP]` entries separated by `;` to their
+/// dashboards.
+pub fn parse_tiles(dashboards: &mut [Dashboard], spec: &str) -> Result<(), String> {
+    for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+        let (name, grid) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let dashboard = dashboards
+            .iter_mut()
+            .find(|d| d.name == name.trim())
+            .ok_or_else(|| entry.to_string())?;
+        dashboard.tiles = Some(Tiles::parse(grid).ok_or_else(|| entry.to_string())?);
+    }
+    Ok(())
+}
+
+/// Hex digest identifying an image's bytes.
+pub fn checksum(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// A dashboard's last successful render.
+#[derive(Clone)]
+pub struct Rendered {
+    headers: HeaderMap,
+    body: bytes::Bytes,
+    checksum: String,
+}
+
+impl Rendered {
+    /// Hex digest of the body, also sent quoted as the `ETag`.
+    pub fn checksum(&self) -> &str {
+        &self.checksum
+    }
+
+    pub fn body(&self) -> &bytes::Bytes {
+        &self.body
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+    }
+
+    pub fn size(&self) -> usize {
+        self.body.len()
+    }
+
+    /// Whether an `If-None-Match` request header already names this render,
+    /// or, without one, `If-Modified-Since` is no earlier than it.
+    pub fn matches(&self, request: &HeaderMap) -> bool {
+        if request.contains_key(header::IF_NONE_MATCH) {
+            let etag = format!("\"{}\"", self.checksum);
+            return request
+                .get_all(header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*");
+        }
+
+        let date = |headers: &HeaderMap, name| {
+            let value = headers.get(name)?.to_str().ok()?;
+            httpdate::parse_http_date(value).ok()
+        };
+        match (
+            date(request, header::IF_MODIFIED_SINCE),
+            date(&self.headers, header::LAST_MODIFIED),
+        ) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    }
+
+    /// Whether `other` shows the same screen: the same bytes, or the same
+    /// size with under `min_change` percent of the pixels differing.
+    fn looks_like(&self, other: &Self, min_change: f64) -> bool {
+        if self.checksum == other.checksum {
+            return true;
+        }
+        if min_change <= 0.0 {
+            return false;
+        }
+        let (Ok(a), Ok(b)) = (
+            image::load_from_memory(&self.body),
+            image::load_from_memory(&other.body),
+        ) else {
+            return false;
+        };
+        if a.width() != b.width() || a.height() != b.height() {
+            return false;
+        }
+        let (a, b) = (a.to_rgb8(), b.to_rgb8());
+        let changed = a.pixels().zip(b.pixels()).filter(|(a, b)| a != b).count();
+        let total = u64::from(a.width()) * u64::from(a.height());
+        (changed as f64 * 100.0) < min_change * total as f64
+    }
+
+    /// `304 Not Modified` with the render's validators.
+    pub fn not_modified(&self) -> Response {
+        let mut headers = HeaderMap::new();
+        for name in [header::ETAG, header::LAST_MODIFIED] {
+            if let Some(value) = self.headers.get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        (StatusCode::NOT_MODIFIED, headers).into_response()
+    }
+}
+
+impl IntoResponse for Rendered {
+    fn into_response(self) -> Response {
+        (self.headers, self.body).into_response()
+    }
+}
+
+struct Entry {
+    rendered: Option<(SystemTime, Rendered)>,
+    /// Latest render, kept past expiry and invalidation to compare the next
+    /// one with
+    last: Option<Rendered>,
+    /// Bumped on every invalidation, so a render started before it isn't
+    /// stored after it
+    generation: u64,
+}
+
+#[derive(Clone)]
+pub struct DashboardCache {
+    max_age: Duration,
+    /// Percentage of pixels that must differ from the previous render for a
+    /// new one to count as changed (`DASHBOARD_MIN_CHANGE`)
+    min_change: f64,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl DashboardCache {
+    /// Renders are kept for `max_age`; zero disables the cache.
+    pub fn new(max_age: Duration, min_change: f64) -> Self {
+        Self {
+            max_age,
+            min_change,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.max_age.is_zero()
+    }
+
+    /// The unexpired render of `name`, or the generation to store a new one
+    /// under.
+    pub fn get(&self, name: &str) -> Result<Rendered, u64> {
+        let entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get(name) else {
+            return Err(0);
+        };
+        match &entry.rendered {
+            Some((rendered_at, rendered))
+                if rendered_at.elapsed().unwrap_or_default() < self.max_age =>
+            {
+                Ok(rendered.clone())
+            }
+            _ => Err(entry.generation),
+        }
+    }
+
+    /// Keep a render (when the cache is on) unless `name` was invalidated
+    /// since `generation`; without one the render is only wrapped up. A
+    /// render that looks like the previous one gives way to it.
+    pub fn store(
+        &self,
+        name: &str,
+        generation: Option<u64>,
+        mut headers: HeaderMap,
+        body: bytes::Bytes,
+    ) -> Rendered {
+        let rendered_at = SystemTime::now();
+        if !headers.contains_key(header::LAST_MODIFIED)
+            && let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(rendered_at))
+        {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+        let checksum = checksum(&body);
+        if let Ok(value) = HeaderValue::from_str(&format!("\"{}\"", checksum)) {
+            headers.insert(header::ETAG, value);
+        }
+        let mut rendered = Rendered {
+            headers,
+            body,
+            checksum,
+        };
+        let Some(generation) = generation else {
+            return rendered;
+        };
+
+        // Compared outside the lock, decoding takes a while
+        let last = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(name)
+            .and_then(|entry| entry.last.clone());
+        if let Some(last) = last
+            && last.looks_like(&rendered, self.min_change)
+        {
+            debug!("Dashboard {} unchanged since its last render", name);
+            rendered = last;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(name.to_string()).or_insert(Entry {
+            rendered: None,
+            last: None,
+            generation: 0,
+        });
+        entry.last = Some(rendered.clone());
+        if self.enabled() && entry.generation == generation {
+            entry.rendered = Some((rendered_at, rendered.clone()));
+        }
+        rendered
+    }
+
+    /// Drop the render of `name`; returns the generation to store the next
+    /// one under.
+    pub fn invalidate(&self, name: &str) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(name.to_string()).or_insert(Entry {
+            rendered: None,
+            last: None,
+            generation: 0,
+        });
+        entry.rendered = None;
+        entry.generation += 1;
+        entry.generation
+    }
+}
@@ -0,0 +1,63 @@
+//! Recommendation rules for the delta screen, e.g.
+//! `>2:Open windows,<-2:Close windows`: the first rule whose comparison
+//! holds for the difference between the two sensors supplies the message.
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    above: bool,
+    threshold: f64,
+    message: String,
+}
+
+impl Rule {
+    fn matches(&self, delta: f64) -> bool {
+        if self.above {
+            delta > self.threshold
+        } else {
+            delta < self.threshold
+        }
+    }
+}
+
+pub fn parse(spec: &str) -> Result<Vec<Rule>, String> {
+    let invalid = |entry: &str| {
+        format!(
+            "Invalid rule '{}'. Use >value:message or <value:message",
+            entry
+        )
+    };
+
+    spec.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (condition, message) = entry.split_once(':').ok_or_else(|| invalid(entry))?;
+            let condition = condition.trim();
+            let (above, threshold) = if let Some(threshold) = condition.strip_prefix('>') {
+                (true, threshold)
+            } else if let Some(threshold) = condition.strip_prefix('<') {
+                (false, threshold)
+            } else {
+                return Err(invalid(entry));
+            };
+            let threshold = threshold.trim().parse().map_err(|_| invalid(entry))?;
+            let message = message.trim();
+            if message.is_empty() {
+                return Err(invalid(entry));
+            }
+
+            Ok(Rule {
+                above,
+                threshold,
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Message of the first matching rule.
+pub fn recommendation(rules: &[Rule], delta: f64) -> Option<&str> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(delta))
+        .map(|rule| rule.message.as_str())
+}
@@ -0,0 +1,556 @@
+//! Per-device bookkeeping. A device is whatever polls a render endpoint with
+//! `?device=<id>` or the TRMNL firmware's `ID` header. Devices can also be
+//! registered up front with a friendly name, preset and screen, kept in
+//! `DEVICES_FILE` along with their last check-in so they survive restarts.
+
+use crate::{presets::Presets, timestamps};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, Uri},
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+use tracing::warn;
+
+/// Unregistered devices kept at most: any client can make up IDs, and each
+/// keeps its frames
+const MAX_UNREGISTERED: usize = 64;
+
+/// One image served to a device.
+#[derive(Clone)]
+pub struct Frame {
+    /// Per-device sequence number, stable while the frame is kept
+    pub seq: u64,
+    pub rendered_at: SystemTime,
+    /// Request path and query that produced the frame
+    pub path: String,
+    pub content_type: String,
+    pub data: bytes::Bytes,
+}
+
+/// What a device reports when it polls. TRMNL firmware sends these as
+/// `Refresh-Rate` (seconds), `Battery-Voltage`, `RSSI` and `FW-Version`
+/// headers.
+#[derive(Clone, Debug, Default)]
+pub struct Telemetry {
+    pub refresh_rate: Option<Duration>,
+    pub battery_voltage: Option<f32>,
+    pub rssi: Option<i32>,
+    pub firmware_version: Option<String>,
+}
+
+impl Telemetry {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+            headers.get(name)?.to_str().ok()?.trim().parse().ok()
+        }
+
+        Self {
+            refresh_rate: header::<u64>(headers, "Refresh-Rate")
+                .filter(|&secs| secs > 0)
+                .map(Duration::from_secs),
+            battery_voltage: header(headers, "Battery-Voltage"),
+            rssi: header(headers, "RSSI"),
+            firmware_version: header::<String>(headers, "FW-Version")
+                .filter(|version| !version.is_empty()),
+        }
+    }
+}
+
+/// Charge of a TRMNL's LiPo battery estimated from its voltage, along the
+/// cell's discharge curve.
+pub fn battery_percent(volts: f32) -> u8 {
+    const CURVE: &[(f32, f32)] = &[
+        (3.30, 0.0),
+        (3.60, 10.0),
+        (3.70, 30.0),
+        (3.80, 55.0),
+        (3.90, 70.0),
+        (4.00, 80.0),
+        (4.10, 90.0),
+        (4.20, 100.0),
+    ];
+
+    if volts <= CURVE[0].0 {
+        return 0;
+    }
+    let percent = CURVE
+        .windows(2)
+        .find(|pair| volts < pair[1].0)
+        .map_or(100.0, |pair| {
+            let ((v0, p0), (v1, p1)) = (pair[0], pair[1]);
+            p0 + (volts - v0) / (v1 - v0) * (p1 - p0)
+        });
+    percent.round() as u8
+}
+
+/// A device ID as the object ID of an entity: `AA:BB:CC:DD:EE:FF` as
+/// `aa_bb_cc_dd_ee_ff`.
+pub fn object_id(device_id: &str) -> String {
+    let id: String = device_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    id.split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// What `POST /devices` and `PATCH /devices/:id` set up for a device.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Registration {
+    /// Friendly name shown instead of the ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Device preset its renders are sized with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// `DEVICE_SCREENS_FILE` key or dashboard `/api/display` serves it,
+    /// ahead of the screens file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screen: Option<String>,
+}
+
+/// A `PATCH /devices/:id` body: fields left out are kept, `null` clears
+/// one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistrationUpdate {
+    #[serde(default, deserialize_with = "nullable")]
+    pub name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub preset: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub screen: Option<Option<String>>,
+}
+
+/// A field that is present, `null` or not.
+fn nullable<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+impl RegistrationUpdate {
+    fn apply(self, registration: &mut Registration) {
+        for (field, value) in [
+            (&mut registration.name, self.name),
+            (&mut registration.preset, self.preset),
+            (&mut registration.screen, self.screen),
+        ] {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+    }
+}
+
+/// A registered device as kept in `DEVICES_FILE`.
+#[derive(Deserialize, Serialize)]
+struct Stored {
+    #[serde(flatten)]
+    registration: Registration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_seen: Option<String>,
+}
+
+#[derive(Default)]
+struct Device {
+    frames: VecDeque<Frame>,
+    next_seq: u64,
+    last_seen: Option<SystemTime>,
+    telemetry: Telemetry,
+    offline: bool,
+    /// Dashboard `/api/display` last sent the device to
+    screen: Option<String>,
+    /// Set once registered through `POST /devices` or `PATCH /devices/:id`
+    registration: Option<Registration>,
+}
+
+/// When a device counts as offline: it has missed `misses` check-ins of its
+/// reported refresh rate (or `default_interval` if it never reported one).
+#[derive(Clone, Copy, Debug)]
+pub struct OfflinePolicy {
+    pub default_interval: Duration,
+    pub misses: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceStatus {
+    pub id: String,
+    pub online: bool,
+    pub last_seen: Option<String>,
+    pub seconds_since_seen: Option<u64>,
+    pub expected_interval_secs: u64,
+    pub battery_voltage: Option<f32>,
+    pub battery_percent: Option<u8>,
+    pub rssi: Option<i32>,
+    pub firmware_version: Option<String>,
+    pub screen: Option<String>,
+    pub registered: bool,
+    pub name: Option<String>,
+    pub preset: Option<String>,
+    pub assigned_screen: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DeviceRegistry {
+    devices: Arc<Mutex<HashMap<String, Device>>>,
+    /// Frames kept per device (`DEVICE_HISTORY`); 0 disables history
+    history_len: usize,
+    offline_policy: OfflinePolicy,
+    /// Where registered devices are kept (`DEVICES_FILE`); in memory only
+    /// if unset
+    file: Option<PathBuf>,
+    /// Held while the file is written, so writes land in the order their
+    /// contents were taken
+    writing: Arc<Mutex<()>>,
+    /// Check-ins of registered devices not yet written to the file
+    unsaved: Arc<AtomicBool>,
+}
+
+impl DeviceRegistry {
+    pub fn new(history_len: usize, offline_policy: OfflinePolicy) -> Self {
+        Self {
+            devices: Arc::new(Mutex::new(HashMap::new())),
+            history_len,
+            offline_policy,
+            file: None,
+            writing: Arc::new(Mutex::new(())),
+            unsaved: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Keep registered devices in `path`, loading the ones already there.
+    /// A missing file is created with the first registration.
+    pub fn with_file(mut self, path: Option<String>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(self);
+        };
+
+        let stored: BTreeMap<String, Stored> = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid devices in {}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        };
+        {
+            let mut devices = self.devices.lock().unwrap();
+            for (id, stored) in stored {
+                devices.insert(
+                    id,
+                    Device {
+                        last_seen: stored.last_seen.as_deref().and_then(timestamps::parse),
+                        registration: Some(stored.registration),
+                        ..Device::default()
+                    },
+                );
+            }
+        }
+
+        self.file = Some(PathBuf::from(path));
+        Ok(self)
+    }
+
+    /// Register a device that isn't yet; it needn't have polled. Returns
+    /// its status, or `None` if it was already registered.
+    pub fn register(&self, device_id: &str, registration: Registration) -> Option<DeviceStatus> {
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices.entry(device_id.to_string()).or_default();
+        if device.registration.is_some() {
+            return None;
+        }
+        device.registration = Some(registration);
+
+        let status = self.status(device_id, device);
+        drop(devices);
+        self.save();
+        Some(status)
+    }
+
+    /// Change a device's registration, registering it if it has only
+    /// polled so far. Returns its status, or `None` for unknown devices.
+    pub fn update(&self, device_id: &str, update: RegistrationUpdate) -> Option<DeviceStatus> {
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices.get_mut(device_id)?;
+        update.apply(
+            device
+                .registration
+                .get_or_insert_with(Registration::default),
+        );
+
+        let status = self.status(device_id, device);
+        drop(devices);
+        self.save();
+        Some(status)
+    }
+
+    pub fn registration(&self, device_id: &str) -> Option<Registration> {
+        let devices = self.devices.lock().unwrap();
+        devices.get(device_id)?.registration.clone()
+    }
+
+    /// Write the registered devices to `DEVICES_FILE`, through a temporary
+    /// file so a crash never leaves half of it. Blocks on the disk, so
+    /// check-ins are left to `save_check_ins`.
+    fn save(&self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+
+        let _writing = self.writing.lock().unwrap();
+        self.unsaved.store(false, Ordering::Relaxed);
+        let text = serialize(&self.devices.lock().unwrap());
+        let result = text
+            .map_err(std::io::Error::other)
+            .and_then(|text| write_file(path, &text));
+        if let Err(e) = result {
+            warn!("Failed to save devices to {}: {}", path.display(), e);
+        }
+    }
+
+    /// Write the last check-ins of registered devices if any came in since
+    /// the last save, on a blocking thread.
+    pub async fn save_check_ins(&self) {
+        if self.file.is_none() || !self.unsaved.load(Ordering::Relaxed) {
+            return;
+        }
+        let registry = self.clone();
+        if let Err(e) = tokio::task::spawn_blocking(move || registry.save()).await {
+            warn!("Failed to save devices: {}", e);
+        }
+    }
+
+    /// Record a poll. Returns the device's status if it had been reported
+    /// offline and is now back.
+    pub fn check_in(&self, device_id: &str, telemetry: Telemetry) -> Option<DeviceStatus> {
+        let mut devices = self.devices.lock().unwrap();
+        let device = entry(&mut devices, device_id);
+
+        device.last_seen = Some(SystemTime::now());
+        // Keep the last reported values for anything this poll didn't send
+        let previous = std::mem::take(&mut device.telemetry);
+        device.telemetry = Telemetry {
+            refresh_rate: telemetry.refresh_rate.or(previous.refresh_rate),
+            battery_voltage: telemetry.battery_voltage.or(previous.battery_voltage),
+            rssi: telemetry.rssi.or(previous.rssi),
+            firmware_version: telemetry.firmware_version.or(previous.firmware_version),
+        };
+
+        let back = if device.offline {
+            device.offline = false;
+            Some(self.status(device_id, device))
+        } else {
+            None
+        };
+        if device.registration.is_some() {
+            self.unsaved.store(true, Ordering::Relaxed);
+        }
+        back
+    }
+
+    /// Last battery voltage the device reported.
+    pub fn battery_voltage(&self, device_id: &str) -> Option<f32> {
+        let devices = self.devices.lock().unwrap();
+        devices.get(device_id)?.telemetry.battery_voltage
+    }
+
+    /// Record the dashboard `/api/display` sent a device to.
+    pub fn assign_screen(&self, device_id: &str, screen: &str) {
+        let mut devices = self.devices.lock().unwrap();
+        let device = entry(&mut devices, device_id);
+        device.screen = Some(screen.to_string());
+    }
+
+    /// Mark devices that have missed too many check-ins as offline and
+    /// return the ones that just went offline.
+    pub fn newly_offline(&self) -> Vec<DeviceStatus> {
+        let mut devices = self.devices.lock().unwrap();
+        let mut newly_offline = Vec::new();
+
+        for (id, device) in devices.iter_mut() {
+            let Some(last_seen) = device.last_seen else {
+                continue;
+            };
+            let allowed = self.expected_interval(device) * self.offline_policy.misses;
+            let silent_for = last_seen.elapsed().unwrap_or_default();
+
+            if !device.offline && silent_for > allowed {
+                device.offline = true;
+                newly_offline.push(self.status(id, device));
+            }
+        }
+
+        newly_offline
+    }
+
+    pub fn statuses(&self) -> Vec<DeviceStatus> {
+        let devices = self.devices.lock().unwrap();
+        let mut statuses: Vec<DeviceStatus> = devices
+            .iter()
+            .filter(|(_, device)| device.last_seen.is_some() || device.registration.is_some())
+            .map(|(id, device)| self.status(id, device))
+            .collect();
+        statuses.sort_by(|a, b| a.id.cmp(&b.id));
+        statuses
+    }
+
+    /// Refresh interval assumed for devices that don't report one
+    /// (`DEVICE_CHECKIN_INTERVAL`).
+    pub fn default_interval(&self) -> Duration {
+        self.offline_policy.default_interval
+    }
+
+    fn expected_interval(&self, device: &Device) -> Duration {
+        device
+            .telemetry
+            .refresh_rate
+            .unwrap_or(self.offline_policy.default_interval)
+    }
+
+    fn status(&self, id: &str, device: &Device) -> DeviceStatus {
+        let registration = device.registration.clone().unwrap_or_default();
+        DeviceStatus {
+            id: id.to_string(),
+            // Registered devices that never polled aren't online yet
+            online: !device.offline && device.last_seen.is_some(),
+            last_seen: device.last_seen.map(httpdate::fmt_http_date),
+            seconds_since_seen: device
+                .last_seen
+                .map(|seen| seen.elapsed().unwrap_or_default().as_secs()),
+            expected_interval_secs: self.expected_interval(device).as_secs(),
+            battery_voltage: device.telemetry.battery_voltage,
+            battery_percent: device.telemetry.battery_voltage.map(battery_percent),
+            rssi: device.telemetry.rssi,
+            firmware_version: device.telemetry.firmware_version.clone(),
+            screen: device.screen.clone(),
+            registered: device.registration.is_some(),
+            name: registration.name,
+            preset: registration.preset,
+            assigned_screen: registration.screen,
+        }
+    }
+
+    pub fn record_frame(
+        &self,
+        device_id: &str,
+        path: &str,
+        content_type: &str,
+        data: bytes::Bytes,
+    ) {
+        if self.history_len == 0 {
+            return;
+        }
+
+        let mut devices = self.devices.lock().unwrap();
+        let device = entry(&mut devices, device_id);
+
+        device.frames.push_back(Frame {
+            seq: device.next_seq,
+            rendered_at: SystemTime::now(),
+            path: path.to_string(),
+            content_type: content_type.to_string(),
+            data,
+        });
+        device.next_seq += 1;
+
+        while device.frames.len() > self.history_len {
+            device.frames.pop_front();
+        }
+    }
+
+    /// Kept frames, newest first.
+    pub fn history(&self, device_id: &str) -> Option<Vec<Frame>> {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .get(device_id)
+            .map(|device| device.frames.iter().rev().cloned().collect())
+    }
+
+    pub fn frame(&self, device_id: &str, seq: u64) -> Option<Frame> {
+        let devices = self.devices.lock().unwrap();
+        devices
+            .get(device_id)?
+            .frames
+            .iter()
+            .find(|frame| frame.seq == seq)
+            .cloned()
+    }
+}
+
+/// Device ID from the `device` query parameter, falling back to the `ID`
+/// header sent by TRMNL firmware. A `device` naming one of `presets` only
+/// selects that preset and isn't a device.
+pub fn device_id(headers: &HeaderMap, uri: &Uri, presets: &Presets) -> Option<String> {
+    let from_query = Query::<HashMap<String, String>>::try_from_uri(uri)
+        .ok()
+        .and_then(|Query(mut params)| params.remove("device"))
+        .filter(|v| !v.is_empty() && presets.get(v).is_none());
+
+    from_query.or_else(|| {
+        headers
+            .get("ID")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    })
+}
+
+/// The device `device_id`, added if new. Adding one past
+/// `MAX_UNREGISTERED` drops the unregistered device seen least recently.
+fn entry<'a>(devices: &'a mut HashMap<String, Device>, device_id: &str) -> &'a mut Device {
+    let unregistered = || {
+        devices
+            .iter()
+            .filter(|(_, device)| device.registration.is_none())
+    };
+    if !devices.contains_key(device_id)
+        && unregistered().count() >= MAX_UNREGISTERED
+        && let Some(oldest) = unregistered()
+            .min_by_key(|(_, device)| device.last_seen)
+            .map(|(id, _)| id.clone())
+    {
+        devices.remove(&oldest);
+    }
+    devices.entry(device_id.to_string()).or_default()
+}
+
+/// Registered devices as `DEVICES_FILE` keeps them.
+fn serialize(devices: &HashMap<String, Device>) -> serde_json::Result<String> {
+    let stored: BTreeMap<&str, Stored> = devices
+        .iter()
+        .filter_map(|(id, device)| {
+            let registration = device.registration.clone()?;
+            Some((
+                id.as_str(),
+                Stored {
+                    registration,
+                    last_seen: device.last_seen.map(timestamps::format_utc),
+                },
+            ))
+        })
+        .collect();
+    serde_json::to_string_pretty(&stored)
+}
+
+fn write_file(path: &Path, text: &str) -> std::io::Result<()> {
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, text)?;
+    std::fs::rename(&temp, path)
+}
@@ -0,0 +1,131 @@
+//! Grayscale to 1-bit (or few-level gray) conversion for the e-paper
+//! endpoints.
+
+use image::{GrayImage, Luma};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dither {
+    /// Plain threshold at mid-gray (crisp text and lines)
+    None,
+    /// Floyd–Steinberg error diffusion (best for photos)
+    FloydSteinberg,
+    /// Atkinson error diffusion: only 3/4 of the error is carried, which keeps
+    /// highlights clean and contrast high on e-paper
+    Atkinson,
+    /// 8x8 ordered Bayer matrix (regular pattern, less noisy for UI elements)
+    Bayer,
+}
+
+impl Dither {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "none" | "threshold" => Some(Self::None),
+            "floyd" | "floyd-steinberg" | "fs" => Some(Self::FloydSteinberg),
+            "atkinson" => Some(Self::Atkinson),
+            "bayer" | "ordered" => Some(Self::Bayer),
+            _ => None,
+        }
+    }
+
+    /// Reduce `image` to `levels` evenly spaced grays from black to white;
+    /// two levels give pure black and white.
+    pub fn quantize(self, image: &GrayImage, levels: u16) -> GrayImage {
+        let levels = levels.max(2);
+        match self {
+            Self::None => threshold(image, levels),
+            Self::FloydSteinberg => error_diffusion(image, FLOYD_STEINBERG, levels),
+            Self::Atkinson => error_diffusion(image, ATKINSON, levels),
+            Self::Bayer => bayer(image, levels),
+        }
+    }
+}
+
+/// Nearest of `levels` grays; values exactly halfway round down, so two
+/// levels split above mid-gray (128).
+fn nearest(value: f32, levels: u16) -> f32 {
+    let step = 255.0 / (levels - 1) as f32;
+    let index = ((value - 0.5 - step / 2.0) / step)
+        .ceil()
+        .clamp(0.0, (levels - 1) as f32);
+    (index * step).round()
+}
+
+fn threshold(image: &GrayImage, levels: u16) -> GrayImage {
+    let mut output = image.clone();
+    for pixel in output.pixels_mut() {
+        pixel.0[0] = nearest(pixel[0] as f32, levels) as u8;
+    }
+    output
+}
+
+/// Error diffusion kernels as `(dx, dy, weight)` offsets from the current pixel.
+pub(crate) type Kernel = &'static [(isize, usize, f32)];
+
+pub(crate) const FLOYD_STEINBERG: Kernel = &[
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+const ATKINSON: Kernel = &[
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+fn error_diffusion(image: &GrayImage, kernel: Kernel, levels: u16) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let (w, h) = (width as usize, height as usize);
+    let mut values: Vec<f32> = image.pixels().map(|p| p[0] as f32).collect();
+    let mut output = GrayImage::new(width, height);
+
+    for y in 0..h {
+        for x in 0..w {
+            let old = values[y * w + x];
+            let new = nearest(old, levels);
+            output.put_pixel(x as u32, y as u32, Luma([new as u8]));
+
+            let error = old - new;
+            for &(dx, dy, weight) in kernel {
+                let nx = x as isize + dx;
+                let ny = y + dy;
+                if nx >= 0 && (nx as usize) < w && ny < h {
+                    values[ny * w + nx as usize] += error * weight;
+                }
+            }
+        }
+    }
+
+    output
+}
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+fn bayer(image: &GrayImage, levels: u16) -> GrayImage {
+    let steps = levels - 1;
+    let mut output = image.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        // Map the matrix cell to a threshold in the middle of its 4-level band
+        let level = BAYER_8X8[(y % 8) as usize][(x % 8) as usize] as u16;
+        let threshold = level * 4 + 2;
+        // Position between the two surrounding grays, on a 0-255 scale
+        let scaled = pixel[0] as u16 * steps;
+        let (below, within) = (scaled / 255, scaled % 255);
+        let index = if within > threshold { below + 1 } else { below };
+        pixel.0[0] = (index * 255 / steps) as u8;
+    }
+    output
+}
@@ -0,0 +1,113 @@
+//! Duration sensors (uptime, runtime, time remaining) shown as `1h 42m` or
+//! `02:13:45` rather than `6123 s`. Sensors are recognised by a time unit
+//! (`ms`, `s`, `min`, `h` or `d`, as `device_class: duration` uses);
+//! `DURATION_FORMATS` picks the style per sensor, e.g.
+//! `DURATION_FORMATS=sensor.uptime=auto,sensor.dishwasher_remaining=clock`.
+
+use std::collections::HashMap;
+
+/// Attribute a configured style is added to a sensor's state under
+pub const FORMAT_ATTRIBUTE: &str = "duration_format";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DurationFormat {
+    /// The two largest units that matter: `42s`, `5m 12s`, `1h 42m`, `3d 4h`
+    Auto,
+    /// `02:13:45`, hours counting past a day
+    Clock,
+    /// The number and unit as reported
+    Off,
+}
+
+impl DurationFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "clock" => Some(Self::Clock),
+            "off" | "none" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Clock => "clock",
+            Self::Off => "off",
+        }
+    }
+}
+
+/// `DURATION_FORMATS`: comma-separated `entity_id=style` entries. The
+/// offending entry is returned on error.
+pub fn parse(spec: &str) -> Result<HashMap<String, DurationFormat>, String> {
+    let mut formats = HashMap::new();
+
+    for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+        let (entity_id, style) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let entity_id = entity_id.trim();
+        let style = DurationFormat::parse(style).ok_or_else(|| entry.to_string())?;
+
+        if !entity_id.contains('.') || formats.insert(entity_id.to_string(), style).is_some() {
+            return Err(entry.to_string());
+        }
+    }
+
+    Ok(formats)
+}
+
+/// Length of one `unit` in seconds, for the units Home Assistant reports
+/// durations in.
+pub fn unit_seconds(unit: &str) -> Option<f64> {
+    match unit {
+        "ms" => Some(0.001),
+        "s" | "sec" => Some(1.0),
+        "min" => Some(60.0),
+        "h" => Some(3600.0),
+        "d" => Some(86_400.0),
+        _ => None,
+    }
+}
+
+/// `value` in `unit` as a duration in the sensor's style (`style` being
+/// its `FORMAT_ATTRIBUTE`, `auto` when unset); `None` when the unit isn't
+/// a time unit, formatting is off, or an `auto` duration is under a second.
+pub fn format(value: f64, unit: &str, style: Option<&str>) -> Option<String> {
+    let seconds = value * unit_seconds(unit)?;
+    if !seconds.is_finite() {
+        return None;
+    }
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let seconds = seconds.abs();
+
+    let text = match style
+        .and_then(DurationFormat::parse)
+        .unwrap_or(DurationFormat::Auto)
+    {
+        DurationFormat::Off => return None,
+        // Latencies such as a 4 ms ping read better as reported
+        DurationFormat::Auto if seconds < 1.0 => return None,
+        DurationFormat::Clock => {
+            let total = seconds.round() as u64;
+            format!(
+                "{:02}:{:02}:{:02}",
+                total / 3600,
+                total % 3600 / 60,
+                total % 60
+            )
+        }
+        DurationFormat::Auto if seconds < 10.0 && seconds.fract() != 0.0 => {
+            format!("{:.1}s", seconds)
+        }
+        DurationFormat::Auto => {
+            let total = seconds.round() as u64;
+            match total {
+                0..60 => format!("{}s", total),
+                60..3600 => format!("{}m {}s", total / 60, total % 60),
+                3600..86_400 => format!("{}h {}m", total / 3600, total % 3600 / 60),
+                _ => format!("{}d {}h", total / 86_400, total % 86_400 / 3600),
+            }
+        }
+    };
+    Some(format!("{}{}", sign, text))
+}
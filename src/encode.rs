@@ -0,0 +1,549 @@
+//! Compact encodings for e-paper output: true 1-bit (or 2/4-bit) grayscale
+//! PNG, 1-bit BMP or XBM for the TRMNL endpoints and indexed color PNG at the smallest bit depth that
+//! holds a panel palette, or a bare framebuffer, optionally deflated. Photos
+//! and status renders are color PNG with configurable compression, or JPEG
+//! or lossless WebP.
+
+use crate::grayscale::GrayDepth;
+use flate2::{
+    Compression,
+    write::{DeflateEncoder, ZlibEncoder},
+};
+use image::{DynamicImage, GrayImage, ImageOutputFormat, Rgb};
+use image_webp::{ColorType, WebPEncoder};
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+};
+
+const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+/// Encoder settings for color PNGs (`PNG_COMPRESSION`, `PNG_FILTER` and
+/// `PNG_INDEXED`). The packed e-paper PNGs always use the smallest settings.
+#[derive(Clone, Copy, Debug)]
+pub struct PngOptions {
+    compression: png::Compression,
+    filter: png::FilterType,
+    adaptive: png::AdaptiveFilterType,
+    /// Write images of at most 256 opaque colors as indexed PNG
+    indexed: bool,
+}
+
+impl Default for PngOptions {
+    /// What the `image` crate's PNG encoder uses
+    fn default() -> Self {
+        Self {
+            compression: png::Compression::Fast,
+            filter: png::FilterType::Sub,
+            adaptive: png::AdaptiveFilterType::Adaptive,
+            indexed: false,
+        }
+    }
+}
+
+impl PngOptions {
+    /// `compression` is `fast`, `default` or `best`; `filter` is `adaptive`,
+    /// `none`, `sub`, `up`, `avg` or `paeth`.
+    pub fn new(
+        compression: Option<&str>,
+        filter: Option<&str>,
+        indexed: bool,
+    ) -> Result<Self, String> {
+        let mut options = Self {
+            indexed,
+            ..Self::default()
+        };
+
+        if let Some(name) = compression {
+            options.compression = match name.trim().to_lowercase().as_str() {
+                "fast" => png::Compression::Fast,
+                "default" => png::Compression::Default,
+                "best" => png::Compression::Best,
+                _ => {
+                    return Err(format!(
+                        "Unknown PNG compression '{}'. Use fast, default or best",
+                        name
+                    ));
+                }
+            };
+        }
+
+        if let Some(name) = filter {
+            (options.filter, options.adaptive) = match name.trim().to_lowercase().as_str() {
+                "adaptive" => (png::FilterType::Sub, png::AdaptiveFilterType::Adaptive),
+                "none" => (
+                    png::FilterType::NoFilter,
+                    png::AdaptiveFilterType::NonAdaptive,
+                ),
+                "sub" => (png::FilterType::Sub, png::AdaptiveFilterType::NonAdaptive),
+                "up" => (png::FilterType::Up, png::AdaptiveFilterType::NonAdaptive),
+                "avg" => (png::FilterType::Avg, png::AdaptiveFilterType::NonAdaptive),
+                "paeth" => (png::FilterType::Paeth, png::AdaptiveFilterType::NonAdaptive),
+                _ => {
+                    return Err(format!(
+                        "Unknown PNG filter '{}'. Use adaptive, none, sub, up, avg or paeth",
+                        name
+                    ));
+                }
+            };
+        }
+
+        Ok(options)
+    }
+
+    /// Encode an 8-bit color image, as indexed PNG when enabled and the
+    /// image has few enough colors.
+    fn encode(&self, image: &DynamicImage) -> anyhow::Result<Vec<u8>> {
+        if self.indexed
+            && let Some(encoded) = self.encode_indexed(image)
+        {
+            return encoded;
+        }
+
+        let (color, data) = if image.color().has_alpha() {
+            (png::ColorType::Rgba, image.to_rgba8().into_raw())
+        } else {
+            (png::ColorType::Rgb, image.to_rgb8().into_raw())
+        };
+        write_png(image.width(), image.height(), &data, |encoder| {
+            encoder.set_color(color);
+            encoder.set_depth(png::BitDepth::Eight);
+            self.configure(encoder);
+        })
+    }
+
+    /// `None` when the image has transparency or more than 256 colors.
+    fn encode_indexed(&self, image: &DynamicImage) -> Option<anyhow::Result<Vec<u8>>> {
+        if image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p[3] < 255) {
+            return None;
+        }
+
+        let rgb = image.to_rgb8();
+        let mut palette: Vec<Rgb<u8>> = Vec::new();
+        let mut lookup: HashMap<Rgb<u8>, u8> = HashMap::new();
+        let mut indices = Vec::with_capacity(rgb.len() / 3);
+        for &pixel in rgb.pixels() {
+            let index = match lookup.get(&pixel) {
+                Some(&index) => index,
+                None => {
+                    if palette.len() == 256 {
+                        return None;
+                    }
+                    let index = palette.len() as u8;
+                    palette.push(pixel);
+                    lookup.insert(pixel, index);
+                    index
+                }
+            };
+            indices.push(index);
+        }
+
+        Some(indexed_png(
+            rgb.width(),
+            rgb.height(),
+            &indices,
+            &palette,
+            |encoder| self.configure(encoder),
+        ))
+    }
+
+    fn configure(&self, encoder: &mut png::Encoder<'_, &mut Vec<u8>>) {
+        encoder.set_compression(self.compression);
+        encoder.set_filter(self.filter);
+        encoder.set_adaptive_filter(self.adaptive);
+    }
+}
+
+/// File format of a re-encoded photo (`?format=` and `?quality=`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhotoFormat {
+    Png,
+    Jpeg {
+        quality: u8,
+    },
+    /// Lossless
+    Webp,
+}
+
+impl PhotoFormat {
+    /// `quality` (1-100) only applies to JPEG.
+    pub fn parse(name: &str, quality: Option<u8>) -> Result<Self, String> {
+        let format = match name.trim().to_lowercase().as_str() {
+            "png" => Self::Png,
+            "jpeg" | "jpg" => Self::Jpeg {
+                quality: quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+            },
+            "webp" => Self::Webp,
+            _ => return Err(format!("Unknown format '{}'. Use png, jpeg or webp", name)),
+        };
+
+        match (format, quality) {
+            (Self::Jpeg { quality }, _) if !(1..=100).contains(&quality) => Err(format!(
+                "quality must be between 1 and 100, got {}",
+                quality
+            )),
+            (Self::Png | Self::Webp, Some(_)) => {
+                Err("quality only applies to format=jpeg".to_string())
+            }
+            _ => Ok(format),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    pub fn encode(self, image: &DynamicImage, png: &PngOptions) -> anyhow::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        match self {
+            // The `image` crate only decodes WebP at this version
+            Self::Webp => {
+                let encoder = WebPEncoder::new(&mut buffer);
+                if image.color().has_alpha() {
+                    let image = image.to_rgba8();
+                    let (width, height) = image.dimensions();
+                    encoder.encode(image.as_raw(), width, height, ColorType::Rgba8)
+                } else {
+                    let image = image.to_rgb8();
+                    let (width, height) = image.dimensions();
+                    encoder.encode(image.as_raw(), width, height, ColorType::Rgb8)
+                }
+                .map_err(|e| anyhow::anyhow!("Failed to encode WebP: {}", e))?;
+                return Ok(buffer);
+            }
+            Self::Png => return png.encode(image),
+            // JPEG has no alpha channel
+            Self::Jpeg { quality } => DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+                &mut Cursor::new(&mut buffer),
+                ImageOutputFormat::Jpeg(quality),
+            ),
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to encode image: {}", e))?;
+        Ok(buffer)
+    }
+}
+
+/// File format of a black and white render (`?format=` and `?depth=`, plus
+/// `?bit_order=` for raw output).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitmapFormat {
+    /// Grayscale PNG with `depth` bits per pixel
+    Png { depth: u8 },
+    /// 1-bit BMP as consumed natively by the TRMNL firmware
+    Bmp,
+    /// X BitMap: C source for Arduino/ESP-IDF code and u8g2's `drawXBM`
+    Xbm,
+    /// Bare framebuffer: `depth` bits per pixel (1, 2, 4 or 8, 0 = black), rows
+    /// padded to whole bytes, first pixel in the high bits unless
+    /// `lsb_first`
+    Raw { depth: u8, lsb_first: bool },
+}
+
+impl BitmapFormat {
+    pub fn parse(name: &str, depth: Option<u8>, bit_order: Option<&str>) -> Result<Self, String> {
+        let depth = GrayDepth::from_bits(depth.unwrap_or(1))?.bits();
+
+        let format = match name.trim().to_lowercase().as_str() {
+            "png" => Self::Png { depth },
+            "bmp" => Self::Bmp,
+            "xbm" => Self::Xbm,
+            "raw" => Self::Raw {
+                depth,
+                lsb_first: match bit_order.map(|order| order.trim().to_lowercase()) {
+                    None => false,
+                    Some(order) if order == "msb" => false,
+                    Some(order) if order == "lsb" => true,
+                    Some(order) => {
+                        return Err(format!("Unknown bit_order '{}'. Use msb or lsb", order));
+                    }
+                },
+            },
+            _ => {
+                return Err(format!(
+                    "Unknown format '{}'. Use png, bmp, xbm or raw",
+                    name
+                ));
+            }
+        };
+
+        match format {
+            Self::Bmp | Self::Xbm if depth > 1 => Err(format!(
+                "format={} only supports depth=1",
+                name.trim().to_lowercase()
+            )),
+            Self::Png { .. } | Self::Bmp | Self::Xbm if bit_order.is_some() => {
+                Err("bit_order only applies to format=raw".to_string())
+            }
+            _ => Ok(format),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png { .. } => "image/png",
+            Self::Bmp => "image/bmp",
+            Self::Xbm => "image/x-xbitmap",
+            Self::Raw { .. } => "application/octet-stream",
+        }
+    }
+
+    /// Gray levels the render is reduced to before encoding.
+    pub fn levels(self) -> u16 {
+        match self {
+            Self::Png { depth } | Self::Raw { depth, .. } => 1 << depth,
+            Self::Bmp | Self::Xbm => 2,
+        }
+    }
+
+    pub fn encode(self, image: &GrayImage) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Png { depth } => png_gray(image, depth),
+            Self::Bmp => Ok(bmp_1bit(image)),
+            Self::Xbm => Ok(xbm(image)),
+            Self::Raw { depth, lsb_first } => Ok(raw(image, depth, lsb_first)),
+        }
+    }
+}
+
+/// Pack one sample per pixel (each below `1 << bits`) into rows of
+/// `bits`-wide fields, most significant bits first and every row padded to
+/// a whole byte - the layout PNG uses for bit depths below 8.
+pub fn pack_rows(samples: &[u8], width: u32, bits: u8) -> Vec<u8> {
+    debug_assert!(matches!(bits, 1 | 2 | 4 | 8));
+    let width = width as usize;
+    if width == 0 {
+        return Vec::new();
+    }
+    let per_byte = 8 / bits as usize;
+    let row_bytes = width.div_ceil(per_byte);
+
+    let mut packed = Vec::with_capacity(row_bytes * samples.len() / width);
+    for row in samples.chunks(width) {
+        for group in row.chunks(per_byte) {
+            let mut byte = 0u8;
+            for (i, &sample) in group.iter().enumerate() {
+                byte |= sample << (8 - bits as usize * (i + 1));
+            }
+            packed.push(byte);
+        }
+    }
+    packed
+}
+
+/// Encode a quantized image as a grayscale PNG with `depth` bits per pixel;
+/// at depth 1 an eighth the raw size of 8-bit grayscale.
+pub fn png_gray(image: &GrayImage, depth: u8) -> anyhow::Result<Vec<u8>> {
+    let data = pack_rows(&samples(image, depth), image.width(), depth);
+    let bit_depth = match depth {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+
+    write_png(image.width(), image.height(), &data, |encoder| {
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(bit_depth);
+    })
+}
+
+/// Encode a black and white image as an uncompressed 1-bit BMP: bottom-up
+/// rows padded to 4 bytes, with black as palette entry 0 and white as 1.
+pub fn bmp_1bit(image: &GrayImage) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 14 + 40 + 2 * 4;
+    const PIXELS_PER_METER: u32 = 2835; // 72 DPI
+
+    let (width, height) = image.dimensions();
+    let bits: Vec<u8> = image.pixels().map(|p| u8::from(p[0] >= 128)).collect();
+    let packed = pack_rows(&bits, width, 1);
+    let packed_row = width.div_ceil(8) as usize;
+    let row_size = packed_row.next_multiple_of(4);
+    let data_size = (row_size * height as usize) as u32;
+
+    let mut bmp = Vec::with_capacity((HEADER_SIZE + data_size) as usize);
+    // BITMAPFILEHEADER
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&(HEADER_SIZE + data_size).to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    // BITMAPINFOHEADER; a positive height means bottom-up rows
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&(width as i32).to_le_bytes());
+    bmp.extend_from_slice(&(height as i32).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB, uncompressed
+    bmp.extend_from_slice(&data_size.to_le_bytes());
+    bmp.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    bmp.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    bmp.extend_from_slice(&2u32.to_le_bytes());
+    bmp.extend_from_slice(&2u32.to_le_bytes());
+    // Color table as BGRA: black, then white
+    bmp.extend_from_slice(&[0, 0, 0, 0, 255, 255, 255, 0]);
+
+    if packed_row > 0 {
+        for row in packed.chunks(packed_row).rev() {
+            bmp.extend_from_slice(row);
+            bmp.resize(bmp.len() + row_size - packed_row, 0);
+        }
+    }
+    bmp
+}
+
+/// Write a black and white image as an X BitMap: a C array named `screen`
+/// with black pixels as set bits, least significant bit first.
+pub fn xbm(image: &GrayImage) -> Vec<u8> {
+    /// Array values per line, as X11's `bitmap` writes them
+    const PER_LINE: usize = 12;
+
+    let (width, height) = image.dimensions();
+    let black: Vec<u8> = image.pixels().map(|p| u8::from(p[0] < 128)).collect();
+    // XBM wants the first pixel in the low bit
+    let bytes: Vec<u8> = pack_rows(&black, width, 1)
+        .into_iter()
+        .map(u8::reverse_bits)
+        .collect();
+
+    let mut text = format!(
+        "#define screen_width {}\n#define screen_height {}\nstatic unsigned char screen_bits[] = {{\n",
+        width, height
+    );
+    let lines: Vec<String> = bytes
+        .chunks(PER_LINE)
+        .map(|line| {
+            let values: Vec<String> = line.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+            format!("   {}", values.join(", "))
+        })
+        .collect();
+    text.push_str(&lines.join(",\n"));
+    text.push_str(" };\n");
+    text.into_bytes()
+}
+
+/// Pack a quantized grayscale image into a bare framebuffer with `depth`
+/// bits per pixel.
+pub fn raw(image: &GrayImage, depth: u8, lsb_first: bool) -> Vec<u8> {
+    let mut packed = pack_rows(&samples(image, depth), image.width(), depth);
+
+    if lsb_first {
+        // Reverse the order of the pixels within each byte
+        let mask = ((1u16 << depth) - 1) as u8;
+        for byte in &mut packed {
+            let mut reversed = 0u8;
+            for i in 0..(8 / depth) {
+                let sample = (*byte >> (i * depth)) & mask;
+                reversed |= sample << (8 - depth * (i + 1));
+            }
+            *byte = reversed;
+        }
+    }
+    packed
+}
+
+/// `?compress=` for raw framebuffers fetched over slow links by firmware
+/// with a small inflate routine (such as uzlib or miniz's `tinfl`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FramebufferCompression {
+    /// Deflate with the 2-byte zlib header and Adler-32 trailer
+    Zlib,
+    /// Bare deflate stream, for inflaters that don't skip the zlib header
+    Deflate,
+}
+
+impl FramebufferCompression {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "zlib" => Ok(Self::Zlib),
+            "deflate" => Ok(Self::Deflate),
+            _ => Err(format!("Unknown compress '{}'. Use zlib or deflate", name)),
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Gray levels of a quantized image as `depth`-bit samples.
+fn samples(image: &GrayImage, depth: u8) -> Vec<u8> {
+    let max = (1u16 << depth) - 1;
+    image
+        .pixels()
+        .map(|p| ((p[0] as u16 * max + 127) / 255) as u8)
+        .collect()
+}
+
+/// Encode palette indices as an indexed PNG using the smallest bit depth
+/// that fits the palette.
+pub fn png_indexed(
+    width: u32,
+    height: u32,
+    indices: &[u8],
+    palette: &[Rgb<u8>],
+) -> anyhow::Result<Vec<u8>> {
+    indexed_png(width, height, indices, palette, |_| {})
+}
+
+fn indexed_png(
+    width: u32,
+    height: u32,
+    indices: &[u8],
+    palette: &[Rgb<u8>],
+    configure: impl FnOnce(&mut png::Encoder<'_, &mut Vec<u8>>),
+) -> anyhow::Result<Vec<u8>> {
+    let (bits, depth) = match palette.len() {
+        0..=2 => (1, png::BitDepth::One),
+        3..=4 => (2, png::BitDepth::Two),
+        5..=16 => (4, png::BitDepth::Four),
+        _ => (8, png::BitDepth::Eight),
+    };
+    let data = pack_rows(indices, width, bits);
+    let palette: Vec<u8> = palette.iter().flat_map(|c| c.0).collect();
+
+    write_png(width, height, &data, |encoder| {
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(depth);
+        encoder.set_palette(palette);
+        configure(encoder);
+    })
+}
+
+fn write_png(
+    width: u32,
+    height: u32,
+    data: &[u8],
+    configure: impl FnOnce(&mut png::Encoder<'_, &mut Vec<u8>>),
+) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, width, height);
+        // Filtering rarely helps packed samples (the PNG spec recommends
+        // none below 8 bits per pixel)
+        encoder.set_filter(png::FilterType::NoFilter);
+        encoder.set_compression(png::Compression::Best);
+        configure(&mut encoder);
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| anyhow::anyhow!("Failed to write PNG header: {}", e))?;
+        writer
+            .write_image_data(data)
+            .map_err(|e| anyhow::anyhow!("Failed to encode PNG: {}", e))?;
+    }
+
+    Ok(buffer)
+}
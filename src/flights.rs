@@ -0,0 +1,105 @@
+//! Aircraft overhead from flight-tracking integrations (e.g. Flightradar24),
+//! which list them in an attribute array. `Fields` says where that list is
+//! and which keys of each entry hold the details.
+
+use crate::pollen::lookup;
+use serde_json::Value;
+
+/// Plane seen from above, nose to the right: 12 rows of 12 bits, bit 11
+/// being the leftmost column.
+pub const ICON: [u16; 12] = [
+    0b000011000000,
+    0b000011100000,
+    0b000001110000,
+    0b100001111000,
+    0b110001111100,
+    0b111111111111,
+    0b111111111111,
+    0b110001111100,
+    0b100001111000,
+    0b000001110000,
+    0b000011100000,
+    0b000011000000,
+];
+
+pub struct Flight {
+    pub callsign: String,
+    pub altitude: Option<String>,
+    /// `origin > destination`, or whichever of the two is known
+    pub route: Option<String>,
+}
+
+/// Attribute paths; each field may list `|`-separated alternatives, the
+/// first non-empty one wins.
+#[derive(Clone, Debug)]
+pub struct Fields {
+    pub list: String,
+    pub callsign: String,
+    pub altitude: String,
+    pub origin: String,
+    pub destination: String,
+    /// Appended to numeric altitudes
+    pub unit: String,
+}
+
+impl Default for Fields {
+    /// The Flightradar24 integration's `flights` attribute.
+    fn default() -> Self {
+        Self {
+            list: "flights".to_string(),
+            callsign: "callsign|flight_number".to_string(),
+            altitude: "altitude".to_string(),
+            origin: "airport_origin_code_iata".to_string(),
+            destination: "airport_destination_code_iata".to_string(),
+            unit: "ft".to_string(),
+        }
+    }
+}
+
+impl Fields {
+    /// Aircraft listed in `attributes`, or `None` if the list is missing.
+    pub fn flights(&self, attributes: &Value) -> Option<Vec<Flight>> {
+        let entries = first(attributes, &self.list)?.as_array()?;
+        Some(
+            entries
+                .iter()
+                .map(|entry| {
+                    let text = |paths: &str| first(entry, paths).and_then(as_text);
+                    let route = match (text(&self.origin), text(&self.destination)) {
+                        (Some(origin), Some(destination)) => {
+                            Some(format!("{} > {}", origin, destination))
+                        }
+                        (origin, destination) => origin.or(destination),
+                    };
+
+                    Flight {
+                        callsign: text(&self.callsign).unwrap_or_else(|| "?".to_string()),
+                        altitude: first(entry, &self.altitude).and_then(|value| match value {
+                            Value::Number(number) => {
+                                Some(format!("{:.0} {}", number.as_f64()?, self.unit))
+                            }
+                            _ => as_text(value),
+                        }),
+                        route,
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/// First of the `|`-separated paths holding a non-empty value.
+fn first<'a>(value: &'a Value, paths: &str) -> Option<&'a Value> {
+    paths
+        .split('|')
+        .filter_map(|path| lookup(value, path.trim()))
+        .find(|value| !value.is_null() && as_text(value).is_none_or(|text| !text.is_empty()))
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,50 @@
+//! Output bit depth (`?depth=1|2|4|8`) shared by the render endpoints: the
+//! image reduced to 2, 4, 16 or 256 evenly spaced grays and packed at that
+//! many bits per pixel. TRMNL screens always go through it; status renders
+//! and proxied images when `depth` is set, as a grayscale PNG.
+
+use crate::{dither::Dither, encode};
+use image::DynamicImage;
+
+/// Bits per pixel of grayscale output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GrayDepth(u8);
+
+impl GrayDepth {
+    pub fn from_bits(bits: u8) -> Result<Self, String> {
+        match bits {
+            1 | 2 | 4 | 8 => Ok(Self(bits)),
+            _ => Err(format!("depth must be 1, 2, 4 or 8, got {}", bits)),
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Grays from black to white the image is reduced to
+    pub fn levels(self) -> u16 {
+        1 << self.0
+    }
+}
+
+/// A color render or photo reduced to `depth`, with `dither` spreading the
+/// grays in between, and black and white swapped when `invert` is set.
+#[derive(Clone, Copy, Debug)]
+pub struct Grayscale {
+    pub depth: GrayDepth,
+    pub dither: Dither,
+    pub invert: bool,
+}
+
+impl Grayscale {
+    /// Grayscale PNG at the depth's bit depth.
+    pub fn encode(self, image: &DynamicImage) -> anyhow::Result<Vec<u8>> {
+        let mut quantized = self.dither.quantize(&image.to_luma8(), self.depth.levels());
+        // After quantizing, as for TRMNL screens, so thresholds don't shift
+        if self.invert {
+            image::imageops::invert(&mut quantized);
+        }
+        encode::png_gray(&quantized, self.depth.bits())
+    }
+}
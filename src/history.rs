@@ -0,0 +1,156 @@
+//! Sensor history charts: Home Assistant's recorded states of an entity
+//! over the last hours, thinned out to what a chart of a few hundred
+//! pixels can show, and clock ticks for their time axis. The same history,
+//! scaled to its own range, makes the trend sparklines of `/trmnl` rows.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest history a chart reaches back, a week
+pub const MAX_HOURS: u64 = 7 * 24;
+
+/// How far back a row's sparkline reaches
+pub const SPARKLINE_SPAN: Duration = Duration::from_secs(6 * 3600);
+/// Attribute a state's sparkline is added to it under, as `[x, y]`
+/// fractions
+pub const SPARKLINE_ATTRIBUTE: &str = "sparkline";
+/// Buckets a sparkline is thinned to, about one per pixel pair
+const SPARKLINE_BUCKETS: usize = 60;
+
+/// At most two samples per bucket of the span from `start` to `end` - its
+/// lowest and highest, in the order they came - so a chart keeps every
+/// peak and dip however many states were recorded. The last sample, the
+/// current value, is always kept.
+pub fn downsample(
+    samples: &[(SystemTime, f64)],
+    start: SystemTime,
+    end: SystemTime,
+    buckets: usize,
+) -> Vec<(SystemTime, f64)> {
+    if samples.len() <= buckets * 2 {
+        return samples.to_vec();
+    }
+    let span = end
+        .duration_since(start)
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(1.0);
+    let bucket_of = |time: SystemTime| {
+        let elapsed = time.duration_since(start).unwrap_or_default().as_secs_f64();
+        ((elapsed / span * buckets as f64) as usize).min(buckets - 1)
+    };
+
+    let mut thinned = Vec::with_capacity(buckets * 2 + 1);
+    let mut i = 0;
+    while i < samples.len() {
+        let bucket = bucket_of(samples[i].0);
+        let mut j = i;
+        while j < samples.len() && bucket_of(samples[j].0) == bucket {
+            j += 1;
+        }
+        let group = &samples[i..j];
+        let low = (0..group.len()).min_by(|&a, &b| group[a].1.total_cmp(&group[b].1));
+        let high = (0..group.len()).max_by(|&a, &b| group[a].1.total_cmp(&group[b].1));
+        if let (Some(low), Some(high)) = (low, high) {
+            thinned.push(group[low.min(high)]);
+            if low != high {
+                thinned.push(group[low.max(high)]);
+            }
+        }
+        i = j;
+    }
+    if let (Some(&last), Some(&kept)) = (samples.last(), thinned.last())
+        && last.0 != kept.0
+    {
+        thinned.push(last);
+    }
+    thinned
+}
+
+/// Hours between ticks for a chart of `hours`, about eight ticks or fewer.
+pub fn tick_step(hours: u64) -> u64 {
+    match hours {
+        0..=6 => 1,
+        7..=12 => 2,
+        13..=24 => 3,
+        25..=48 => 6,
+        49..=96 => 12,
+        _ => 24,
+    }
+}
+
+/// Ticks on the local hours divisible by `step` between `start` and
+/// `start + span`, as their fraction across it and time.
+pub fn hour_ticks(
+    start: SystemTime,
+    span: Duration,
+    step: u64,
+    offset_secs: i64,
+) -> Vec<(f64, SystemTime)> {
+    let step = (step.max(1) * 3600) as i64;
+    let start_secs = start
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    // First local multiple of the step after `start`, in seconds from it
+    let first = ((start_secs + offset_secs).div_euclid(step) + 1) * step - offset_secs - start_secs;
+    let span_secs = span.as_secs() as i64;
+
+    (first..span_secs)
+        .step_by(step as usize)
+        .map(|secs| {
+            (
+                secs as f64 / span_secs.max(1) as f64,
+                start + Duration::from_secs(secs as u64),
+            )
+        })
+        .collect()
+}
+
+/// Sparkline of `samples` from `start` until `end` as `(x, y)` fractions,
+/// scaled to their own range so small changes still show; a flat series is
+/// drawn across the middle. The last value is held until `end`. `None`
+/// with fewer than two samples, where there is no trend to show.
+pub fn sparkline(
+    samples: &[(SystemTime, f64)],
+    start: SystemTime,
+    end: SystemTime,
+) -> Option<Vec<(f64, f64)>> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let samples = downsample(samples, start, end, SPARKLINE_BUCKETS);
+    let low = samples
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(f64::INFINITY, f64::min);
+    let high = samples
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let span = end
+        .duration_since(start)
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(1.0);
+
+    let y_of = |value: f64| {
+        if high > low {
+            (value - low) / (high - low)
+        } else {
+            0.5
+        }
+    };
+    let mut points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|&(time, value)| {
+            let elapsed = time.duration_since(start).unwrap_or_default().as_secs_f64();
+            ((elapsed / span).min(1.0), y_of(value))
+        })
+        .collect();
+    if let Some(&(x, y)) = points.last()
+        && x < 1.0
+    {
+        points.push((1.0, y));
+    }
+    Some(points)
+}
@@ -0,0 +1,111 @@
+//! Leaderboards: entities ranked by their current value or by a statistic
+//! of today - the top power consumers, the rooms that used the most energy,
+//! the busiest motion sensors - as numbered rows with a bar proportional to
+//! the leader's.
+
+use crate::timestamps;
+use serde_json::Value;
+use std::time::SystemTime;
+
+/// Rows shown without `?limit=`
+pub const DEFAULT_LIMIT: usize = 5;
+/// Most rows a leaderboard shows
+pub const MAX_LIMIT: usize = 10;
+/// Most entities that can be listed with `?sensors=`
+pub const MAX_SENSORS: usize = 50;
+
+/// What entities are ranked by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    /// The current state
+    Value,
+    /// Today's highest value
+    Max,
+    /// Today's average, weighted by how long each value was held
+    Mean,
+    /// Today's rises added up, what a meter counted
+    Increase,
+    /// Times the entity turned `on` today
+    Count,
+}
+
+impl Stat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "value" => Ok(Self::Value),
+            "max" => Ok(Self::Max),
+            "mean" => Ok(Self::Mean),
+            "increase" => Ok(Self::Increase),
+            "count" => Ok(Self::Count),
+            _ => Err(format!(
+                "Unknown stat '{}'. Use value, max, mean, increase or count",
+                name
+            )),
+        }
+    }
+
+    /// Whether the ranking needs today's history rather than the current
+    /// states.
+    pub fn needs_history(self) -> bool {
+        self != Self::Value
+    }
+
+    /// Caption above the rows, saying what they're ranked by.
+    pub fn caption(self) -> &'static str {
+        match self {
+            Self::Value => "Now",
+            Self::Max => "Peak today",
+            Self::Mean => "Average today",
+            Self::Increase => "Used today",
+            Self::Count => "Times on today",
+        }
+    }
+}
+
+/// How often an entity turned `on` since `start`, from its list of a
+/// `/api/history/period` response; the state it was already in at `start`
+/// doesn't count.
+pub fn count_on(entries: &[Value], start: SystemTime) -> usize {
+    entries
+        .iter()
+        .filter(|entry| entry.get("state").and_then(Value::as_str) == Some("on"))
+        .filter(|entry| {
+            entry
+                .get("last_changed")
+                .or_else(|| entry.get("last_updated"))
+                .and_then(Value::as_str)
+                .and_then(timestamps::parse)
+                .is_some_and(|time| time > start)
+        })
+        .count()
+}
+
+pub struct Entry {
+    pub name: String,
+    pub score: f64,
+    /// The score as shown, with its unit
+    pub value: String,
+}
+
+/// The `limit` highest scores, highest first; ties keep their order.
+pub fn top(mut entries: Vec<Entry>, limit: usize) -> Vec<Entry> {
+    entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+    entries.truncate(limit);
+    entries
+}
+
+/// Length of each entry's bar as a fraction of the leader's. Entries at
+/// or below zero get none.
+pub fn bar_fractions(entries: &[Entry]) -> Vec<f64> {
+    let leader = entries.first().map_or(0.0, |entry| entry.score);
+    entries
+        .iter()
+        .map(|entry| {
+            if leader > 0.0 {
+                (entry.score / leader).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
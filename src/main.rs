@@ -1,542 +1,8781 @@
 use axum::{
     Router,
+    body::Body,
     extract::{Path, Query, State},
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderValue, Request, StatusCode, Uri, header},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, patch, post},
 };
+use base64::Engine;
 use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io::Cursor, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+use tower::ServiceExt;
 use tower_http::cors::CorsLayer;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
+
+mod accept;
+mod adjust;
+mod appliances;
+mod attention;
+mod carbon;
+mod care;
+mod chart;
+mod currency;
+mod dashboards;
+mod delta;
+mod devices;
+mod dither;
+mod durations;
+mod encode;
+mod flights;
+mod font;
+mod grayscale;
+mod history;
+mod leaderboard;
+mod markup;
+mod meters;
+mod motion;
+mod noise;
+mod palette;
+mod pdf;
+mod pollen;
+mod postprocess;
+mod prefixes;
+mod presets;
+mod priority;
+mod privacy;
+mod proxy_auth;
+mod refresh;
+mod reminders;
+mod report;
+mod resize;
+mod screens;
+mod standby;
+mod state_cache;
+mod statistics;
+mod svg;
+mod theme;
+mod tide;
+mod timestamps;
+mod timetable;
+mod units;
+mod watchdog;
+mod webhooks;
+mod websocket;
+mod zones;
+
+use adjust::Adjustments;
+use dashboards::{Dashboard, DashboardCache, Rendered, Tiles};
+use devices::{
+    DeviceRegistry, DeviceStatus, OfflinePolicy, Registration, RegistrationUpdate, Telemetry,
+};
+use dither::Dither;
+use encode::{BitmapFormat, FramebufferCompression, PhotoFormat, PngOptions};
+use grayscale::{GrayDepth, Grayscale};
+use motion::CameraFrames;
+use palette::Palette;
+use postprocess::PostProcess;
+use prefixes::SiPrefixes;
+use presets::{Canvas, Presets};
+use priority::{Priorities, Priority};
+use privacy::Privacy;
+use proxy_auth::{ProxyAuth, ProxyHeaders};
+use resize::{Crop, FitMode, Resize, Rotation};
+use screens::{Screen, Screens, ServiceCall, Slot};
+use state_cache::StateCache;
+use svg::Svg;
+use theme::{Theme, blend_colors, darken};
+use units::Units;
+use watchdog::Watchdog;
+use websocket::HaSocket;
+
+tokio::task_local! {
+    /// Layout of the whole installation while a tiled dashboard renders,
+    /// in place of `TRMNL_WIDTH` x `TRMNL_HEIGHT`
+    static TILED_LAYOUT: (u32, u32);
+    /// Battery voltage of the device being served while it's below
+    /// `DEVICE_LOW_BATTERY`, badging its TRMNL screens
+    static LOW_BATTERY: f32;
+    /// Set while a dashboard renders for `/share/{name}`, hiding the
+    /// private entities of `ENTITIES_FILE`
+    static SHARING: ();
+}
 
 #[derive(Clone)]
 struct AppState {
     http_client: Client,
     ha_config: HomeAssistantConfig,
+    /// Shared WebSocket connection for commands the REST API lacks
+    socket: HaSocket,
+    /// Entity states kept current over the socket (`STATE_CACHE`)
+    state_cache: Option<StateCache>,
+    /// Entities hidden from shared renders (`ENTITIES_FILE`)
+    privacy: Privacy,
+    /// Checks of composed renders (`RENDER_WATCHDOG`)
+    watchdog: Watchdog,
+    abbreviations: Abbreviations,
+    /// Default palette for RGB renders (`THEME` plus `THEME_COLORS` overrides)
+    theme: Theme,
+    theme_overrides: Option<String>,
+    /// Last formatted value seen per entity, used to detect changes between renders
+    last_values: Arc<Mutex<HashMap<String, String>>>,
+    /// Named render URLs from `DASHBOARDS`
+    dashboards: Vec<Dashboard>,
+    /// Dashboard renders kept for `DASHBOARD_CACHE_SECONDS`
+    dashboard_cache: DashboardCache,
+    /// Recent frames and check-ins of each device
+    devices: DeviceRegistry,
+    /// URL notified with a JSON POST when a device goes offline or comes back
+    device_webhook: Option<String>,
+    /// Set `sensor.trmnl_<id>_battery`/`_rssi` in Home Assistant from what
+    /// devices report (`DEVICE_SENSORS`)
+    device_sensors: bool,
+    /// Voltage below which screens served to a device carry a low-battery
+    /// badge (`DEVICE_LOW_BATTERY`), zero for never
+    low_battery_volts: f32,
+    /// Stamp the build version and render time in a corner (`RENDER_STAMP`)
+    render_stamp: bool,
+    /// Size the TRMNL screens are laid out at, 800x480 unless configured
+    trmnl_layout: (u32, u32),
+    /// Encoder settings for color PNGs
+    png: PngOptions,
+    /// Daily budget per meter entity from `METER_BUDGETS`
+    meter_budgets: HashMap<String, f64>,
+    /// Care log entities and when each is due again, from `CARE_LOG`
+    care_log: HashMap<String, Option<Duration>>,
+    /// Entities of each appliance in `APPLIANCES`, by name
+    appliances: HashMap<String, appliances::Appliance>,
+    /// Labelled time zones of the `clock.world` row, from `WORLD_CLOCKS`
+    world_clocks: Vec<(String, jiff::tz::TimeZone)>,
+    /// Duration style per sensor from `DURATION_FORMATS`
+    duration_formats: HashMap<String, durations::DurationFormat>,
+    /// Currency of monetary sensors without a unit, from `CURRENCY`
+    currency: Option<String>,
+    /// Number grouping for monetary sensors from `CURRENCY_LOCALE`
+    currency_locale: currency::Locale,
+    /// States that invert a row and move it to the top, from `ATTENTION_STATES`
+    attention_states: attention::AttentionStates,
+    /// Built-in device presets plus those from `DEVICE_PRESETS_FILE`
+    presets: Presets,
+    /// Dashboard each device is served by `/api/display`, from
+    /// `DEVICE_SCREENS_FILE`
+    screens: Screens,
+    /// Base of the image URLs `/api/display` hands out (`PUBLIC_URL`);
+    /// taken from the request's `Host` header if unset
+    public_url: Option<String>,
+    /// Last camera screen render per URL, reused while the scene is still
+    camera_frames: CameraFrames,
+    /// Dashboards pushed to TRMNL plugin webhooks, from `TRMNL_WEBHOOKS`
+    webhooks: Vec<webhooks::Webhook>,
+    /// Time between webhook pushes (`TRMNL_WEBHOOK_INTERVAL`)
+    webhook_interval: Duration,
+    /// How tokens for a proxy in front of Home Assistant are obtained
+    /// (`HA_PROXY_AUTH`)
+    proxy_auth: Option<ProxyAuth>,
 }
 
 #[derive(Clone)]
 struct HomeAssistantConfig {
+    /// `HA_URL` without a trailing slash; may end in a path prefix for an
+    /// instance behind a reverse proxy, e.g. `https://example.com/homeassistant`
     base_url: String,
     token: String,
+    /// Extra headers every Home Assistant request carries: `HA_HEADERS`,
+    /// e.g. Cloudflare Access service token headers, and the token of
+    /// `HA_PROXY_AUTH`
+    extra_headers: ProxyHeaders,
 }
 
-#[derive(Deserialize)]
-struct ImageQuery {
-    entity_id: Option<String>,
-    width: Option<u32>,
-    height: Option<u32>,
-    cache: Option<bool>,
+impl HomeAssistantConfig {
+    /// Headers of a Home Assistant API request: the token and `HA_HEADERS`.
+    fn headers(&self) -> reqwest::header::HeaderMap {
+        let mut headers = self.extra_headers.current();
+        if let Ok(authorization) =
+            reqwest::header::HeaderValue::from_str(&format!("Bearer {}", self.token))
+        {
+            headers.insert(reqwest::header::AUTHORIZATION, authorization);
+        }
+        headers
+    }
 }
 
-#[derive(Deserialize)]
-struct MultiSensorQuery {
-    sensors: String, // Comma-separated list of sensor entity IDs
-    width: Option<u32>,
-    height: Option<u32>,
-    title: Option<String>,
-}
+/// `HA_HEADERS`: `Name=value` entries separated by `;`. The offending entry
+/// is returned on error, without its value.
+fn parse_ha_headers(spec: &str) -> Result<reqwest::header::HeaderMap, String> {
+    let mut headers = reqwest::header::HeaderMap::new();
+
+    for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+        let (name, value) = entry
+            .split_once('=')
+            .ok_or_else(|| entry.trim().to_string())?;
+        let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| name.trim().to_string())?;
+        let value =
+            reqwest::header::HeaderValue::from_str(value.trim()).map_err(|_| name.to_string())?;
+        headers.append(name, value);
+    }
 
-#[derive(Deserialize)]
-struct TrmnlQuery {
-    sensors: String, // Comma-separated list of sensor entity IDs
-    title: Option<String>,
+    Ok(headers)
 }
 
-#[derive(Serialize, Deserialize)]
-struct EntityState {
-    entity_id: String,
-    state: String,
-    attributes: serde_json::Value,
+/// Word/phrase replacements applied when a label has to be shortened to fit.
+/// Entries are kept sorted longest-first so "Living Room" wins over "Room".
+#[derive(Clone)]
+struct Abbreviations {
+    entries: Vec<(String, String)>,
 }
 
-impl AppState {
-    fn new() -> anyhow::Result<Self> {
-        let ha_url =
-            std::env::var("HA_URL").unwrap_or_else(|_| "http://localhost:8123".to_string());
-        let ha_token = std::env::var("HA_TOKEN")
-            .map_err(|_| anyhow::anyhow!("HA_TOKEN environment variable is required. Please set it in your .env file or as an environment variable."))?;
+impl Abbreviations {
+    const DEFAULTS: &'static [(&'static str, &'static str)] = &[
+        ("Temperature", "Temp"),
+        ("Humidity", "Hum"),
+        ("Battery", "Bat"),
+        ("Pressure", "Press"),
+        ("Illuminance", "Lux"),
+        ("Production", "Prod"),
+        ("Consumption", "Cons"),
+        ("Current", "Cur"),
+        ("Power", "Pwr"),
+        ("Energy", "Enrg"),
+        ("Outdoor", "Out"),
+        ("Indoor", "In"),
+        ("Living Room", "Living"),
+        ("Dining Room", "Dining"),
+        ("Bedroom", "Bdrm"),
+        ("Bathroom", "Bath"),
+        ("Kitchen", "Kit"),
+        ("Basement", "Bsmt"),
+        ("Garage", "Gar"),
+    ];
 
-        let http_client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+    /// Built-in dictionary, extended/overridden by `ABBREVIATIONS`
+    /// (comma-separated `Long=Short` pairs, e.g. `Office=Ofc,Sensor=`).
+    fn from_env() -> Self {
+        let mut entries: Vec<(String, String)> = Self::DEFAULTS
+            .iter()
+            .map(|(long, short)| (long.to_string(), short.to_string()))
+            .collect();
+
+        if let Ok(custom) = std::env::var("ABBREVIATIONS") {
+            for pair in custom.split(',') {
+                let Some((long, short)) = pair.split_once('=') else {
+                    if !pair.trim().is_empty() {
+                        warn!("Ignoring malformed abbreviation entry: {}", pair);
+                    }
+                    continue;
+                };
+                let long = long.trim();
+                if long.is_empty() {
+                    continue;
+                }
+                entries.retain(|(existing, _)| !existing.eq_ignore_ascii_case(long));
+                entries.push((long.to_string(), short.trim().to_string()));
+            }
+        }
 
-        Ok(Self {
-            http_client,
-            ha_config: HomeAssistantConfig {
-                base_url: ha_url,
-                token: ha_token,
-            },
-        })
+        entries.sort_by_key(|(long, _)| std::cmp::Reverse(long.len()));
+        Self { entries }
     }
 
-    async fn get_entity_state(&self, entity_id: &str) -> anyhow::Result<EntityState> {
-        let url = format!("{}/api/states/{}", self.ha_config.base_url, entity_id);
-
-        let response = self
-            .http_client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.ha_config.token))
-            .header("Content-Type", "application/json")
-            .send()
-            .await?;
+    /// Shorten `text` to fit within `max_width` pixels at the given text
+    /// scale, applying dictionary replacements one at a time before falling
+    /// back to ellipsis truncation.
+    fn shorten(&self, text: &str, max_width: u32, scale: u32) -> String {
+        if text_width(text, scale) <= max_width {
+            return text.to_string();
+        }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to get entity state: {}",
-                response.status()
-            ));
+        let mut shortened = text.to_string();
+        for (long, short) in &self.entries {
+            shortened = replace_word(&shortened, long, short);
+            if text_width(&shortened, scale) <= max_width {
+                return shortened;
+            }
         }
 
-        let entity_state: EntityState = response.json().await?;
-        Ok(entity_state)
+        truncate_to_width(&shortened, max_width, scale)
     }
 
-    async fn fetch_image_from_url(
-        &self,
-        image_url: &str,
-    ) -> anyhow::Result<(bytes::Bytes, String)> {
-        let response = self
-            .http_client
-            .get(image_url)
-            .header("Authorization", format!("Bearer {}", self.ha_config.token))
-            .send()
-            .await?;
+    /// Like [`Self::shorten`], but a label that still doesn't fit on one
+    /// line after abbreviating is wrapped over up to `max_lines` lines
+    /// instead, keeping the full wording when that fits.
+    fn wrap(&self, text: &str, max_width: u32, scale: u32, max_lines: usize) -> Vec<String> {
+        let shortened = self.shorten(text, max_width, scale);
+        if max_lines <= 1 || !shortened.ends_with('…') || shortened == text {
+            return vec![shortened];
+        }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to fetch image: {}",
-                response.status()
-            ));
+        let full = wrap_to_width(text, max_width, scale, max_lines);
+        if full.last().is_none_or(|line| !line.ends_with('…')) {
+            return full;
         }
 
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("image/jpeg")
-            .to_string();
+        let abbreviated = self
+            .entries
+            .iter()
+            .fold(text.to_string(), |text, (long, short)| {
+                replace_word(&text, long, short)
+            });
+        wrap_to_width(&abbreviated, max_width, scale, max_lines)
+    }
+}
 
-        let bytes = response.bytes().await?;
-        Ok((bytes, content_type))
+/// Case-insensitive whole-word replacement. Collapses the double spaces left
+/// behind when a word is abbreviated to nothing.
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    let haystack = text.to_ascii_lowercase();
+    let needle = word.to_ascii_lowercase();
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, _) in haystack.match_indices(&needle) {
+        let end = start + needle.len();
+        if start < last {
+            continue;
+        }
+        let before_ok = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        let after_ok = text[end..]
+            .chars()
+            .next()
+            .is_none_or(|c| !c.is_alphanumeric());
+        if before_ok && after_ok {
+            result.push_str(&text[last..start]);
+            result.push_str(replacement);
+            last = end;
+        }
     }
+    result.push_str(&text[last..]);
 
-    async fn get_camera_snapshot(&self, entity_id: &str) -> anyhow::Result<(bytes::Bytes, String)> {
-        let url = format!("{}/api/camera_proxy/{}", self.ha_config.base_url, entity_id);
+    if replacement.is_empty() {
+        result.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        result
+    }
+}
 
-        let response = self
-            .http_client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.ha_config.token))
-            .send()
-            .await?;
+/// Colors applied to numeric values at or above each threshold, parsed from
+/// `value:color` pairs such as `20:green,25:orange,30:red`. Colors may also
+/// name a theme role (`ok`, `warn`, `critical`).
+#[derive(Default)]
+struct ValueThresholds {
+    steps: Vec<(f64, Rgb<u8>)>,
+}
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to get camera snapshot: {}",
-                response.status()
-            ));
+impl ValueThresholds {
+    fn parse(spec: Option<&str>, theme: &Theme) -> Result<Self, AppError> {
+        let mut steps = Vec::new();
+
+        for pair in spec
+            .unwrap_or("")
+            .split(',')
+            .filter(|p| !p.trim().is_empty())
+        {
+            let (value, color) = pair.split_once(':').ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Invalid threshold '{}', expected value:color",
+                    pair
+                ))
+            })?;
+            let value = value.trim().parse::<f64>().map_err(|_| {
+                AppError::BadRequest(format!("Invalid threshold value '{}'", value))
+            })?;
+            let color = theme
+                .color(color)
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown color '{}'", color)))?;
+            steps.push((value, color));
         }
 
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("image/jpeg")
-            .to_string();
+        steps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { steps })
+    }
 
-        let bytes = response.bytes().await?;
-        Ok((bytes, content_type))
+    fn color_for(&self, sensor: &EntityState) -> Option<Rgb<u8>> {
+        let value = sensor.state.parse::<f64>().ok()?;
+        self.steps
+            .iter()
+            .rev()
+            .find(|(threshold, _)| value >= *threshold)
+            .map(|(_, color)| *color)
     }
 }
 
-async fn health_check() -> impl IntoResponse {
-    "OK"
+/// Per-request look of the RGB renderers.
+struct RenderStyle<'a> {
+    theme: Theme,
+    thresholds: ValueThresholds,
+    abbreviations: &'a Abbreviations,
+    /// Accessibility profile: double-size bold text, flat fills, no shadows
+    accessible: bool,
+    /// Version/render time drawn in the bottom-right corner
+    stamp: Option<String>,
+    rotation: Option<Rotation>,
+    /// Fixed panel colors the output is reduced to
+    palette: Option<Palette>,
+    /// Grays the output is reduced to (`?depth=`), as a grayscale PNG
+    grayscale: Option<Grayscale>,
+    format: PhotoFormat,
+    /// Vector output (`format=svg`) instead of `format`
+    svg: bool,
+    /// Border in the background color, inside the requested size
+    margin: u32,
+    /// Size a layout drawn smaller than the output is enlarged to
+    upscale: Option<(u32, u32)>,
+    png: PngOptions,
+    /// Engineering prefixes on values (`12.4 kW`)
+    si: SiPrefixes,
 }
 
-async fn serve_entity_image(
-    State(state): State<Arc<AppState>>,
-    Path(entity_id): Path<String>,
-    Query(_params): Query<ImageQuery>,
-) -> Result<Response, AppError> {
-    info!("Serving image for entity: {}", entity_id);
+impl RenderStyle<'_> {
+    fn text_scale(&self) -> u32 {
+        if self.accessible { 2 } else { 1 }
+    }
 
-    // First try to get it as a camera entity
-    if entity_id.starts_with("camera.") {
-        match state.get_camera_snapshot(&entity_id).await {
-            Ok((image_data, content_type)) => {
-                return Ok(create_image_response(image_data, content_type));
-            }
-            Err(e) => {
-                warn!("Failed to get camera snapshot: {}", e);
-            }
+    /// Draw text at the style's scale, emboldened in the accessibility profile.
+    fn draw_text(&self, image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>) {
+        let scale = self.text_scale();
+        draw_text_pattern(image, x, y, text, color, scale);
+        if self.accessible {
+            draw_text_pattern(image, x + 1, y, text, color, scale);
         }
     }
 
-    // If not a camera or camera failed, try to get entity state and look for image URL
-    match state.get_entity_state(&entity_id).await {
-        Ok(entity_state) => {
-            // Look for image URL in various possible attributes
-            let possible_image_attrs = [
-                "entity_picture",
-                "image_url",
-                "picture",
-                "thumbnail",
-                "media_content_id",
-            ];
-
-            for attr in &possible_image_attrs {
-                if let Some(image_url) = entity_state.attributes.get(attr) {
-                    if let Some(url_str) = image_url.as_str() {
-                        let full_url = if url_str.starts_with("http") {
-                            url_str.to_string()
-                        } else {
-                            format!("{}{}", state.ha_config.base_url, url_str)
-                        };
-
-                        match state.fetch_image_from_url(&full_url).await {
-                            Ok((image_data, content_type)) => {
-                                return Ok(create_image_response(image_data, content_type));
-                            }
-                            Err(e) => {
-                                warn!("Failed to fetch image from {}: {}", full_url, e);
-                                continue;
-                            }
-                        }
-                    }
-                }
-            }
+    /// `draw_text` for vector output.
+    fn svg_text(&self, svg: &mut Svg, x: u32, y: u32, text: &str, color: Rgb<u8>) {
+        svg.text(x, y, text, color, self.text_scale(), self.accessible);
+    }
 
-            Err(AppError::NotFound(format!(
-                "No image found for entity: {}",
-                entity_id
-            )))
+    /// Top and bottom colors for a decorative gradient; flat in the
+    /// accessibility profile.
+    fn gradient(&self, top: Rgb<u8>, bottom: Rgb<u8>) -> (Rgb<u8>, Rgb<u8>) {
+        if self.accessible {
+            (top, top)
+        } else {
+            (top, bottom)
         }
-        Err(e) => {
-            error!("Failed to get entity state for {}: {}", entity_id, e);
-            Err(AppError::Internal(format!(
-                "Failed to get entity state: {}",
-                e
-            )))
+    }
+
+    fn multi_sensor_line_height(&self) -> u32 {
+        if self.accessible { 60 } else { 40 }
+    }
+
+    /// Size left for the render inside the margin.
+    fn inner_size(&self, width: u32, height: u32) -> Result<(u32, u32), AppError> {
+        if self.margin * 2 >= width.min(height) {
+            return Err(AppError::BadRequest(format!(
+                "margin {} leaves no room in {}x{}",
+                self.margin, width, height
+            )));
         }
+        Ok((width - self.margin * 2, height - self.margin * 2))
     }
-}
 
-async fn serve_image_by_url(
-    State(state): State<Arc<AppState>>,
-    Query(params): Query<HashMap<String, String>>,
-) -> Result<Response, AppError> {
-    let image_url = params
-        .get("url")
-        .ok_or_else(|| AppError::BadRequest("Missing 'url' parameter".to_string()))?;
+    /// Frame a finished render in its margin, rotate it and stamp it in its
+    /// final orientation.
+    fn finish(&self, mut image: RgbImage) -> RgbImage {
+        watchdog::inspect_rgb(&image, self.theme.background_top);
+
+        if let Some((width, height)) = self.upscale {
+            image = image::imageops::resize(
+                &image,
+                width,
+                height,
+                image::imageops::FilterType::Nearest,
+            );
+        }
+        if self.margin > 0 {
+            let mut framed = RgbImage::from_pixel(
+                image.width() + self.margin * 2,
+                image.height() + self.margin * 2,
+                self.theme.background_top,
+            );
+            let margin = self.margin as i64;
+            image::imageops::replace(&mut framed, &image, margin, margin);
+            image = framed;
+        }
 
-    info!("Serving image from URL: {}", image_url);
+        let mut image = match self.rotation {
+            Some(rotation) => rotation.apply(&image),
+            None => image,
+        };
+        if let Some(stamp) = &self.stamp {
+            let x = image.width().saturating_sub(text_width(stamp, 1) + 6);
+            let y = image.height().saturating_sub(12);
+            draw_text_pattern(&mut image, x, y, stamp, self.theme.muted_text, 1);
+        }
+        if let Some(palette) = self.palette {
+            palette.quantize(&mut image);
+        }
+        image
+    }
 
-    let full_url = if image_url.starts_with("http") {
-        image_url.clone()
-    } else {
-        format!("{}{}", state.ha_config.base_url, image_url)
-    };
+    /// `finish` for vector output, returning the document.
+    fn finish_svg(&self, mut svg: Svg) -> bytes::Bytes {
+        if let Some((width, height)) = self.upscale {
+            svg.scale_to(width, height);
+        }
+        if self.margin > 0 {
+            svg.frame(self.margin, self.theme.background_top);
+        }
+        if let Some(rotation) = self.rotation {
+            svg.rotate(rotation);
+        }
+        if let Some(stamp) = &self.stamp {
+            let x = svg.width().saturating_sub(text_width(stamp, 1) + 6);
+            let y = svg.height().saturating_sub(12);
+            svg.text(x, y, stamp, self.theme.muted_text, 1, false);
+        }
+        bytes::Bytes::from(svg.finish())
+    }
 
-    match state.fetch_image_from_url(&full_url).await {
-        Ok((image_data, content_type)) => Ok(create_image_response(image_data, content_type)),
-        Err(e) => {
-            error!("Failed to fetch image from {}: {}", full_url, e);
-            Err(AppError::Internal(format!("Failed to fetch image: {}", e)))
+    /// Encode the output (`?format=`, `?quality=`); palette output is
+    /// always an indexed PNG.
+    fn with_format(
+        mut self,
+        format: Option<&str>,
+        quality: Option<u8>,
+        headers: &HeaderMap,
+    ) -> Result<Self, AppError> {
+        if format.is_some_and(|format| format.trim().eq_ignore_ascii_case("svg")) {
+            if quality.is_some() {
+                return Err(AppError::BadRequest(
+                    "quality only applies to format=jpeg".to_string(),
+                ));
+            }
+            if let Some(reduced) = self.png_only() {
+                return Err(AppError::BadRequest(format!(
+                    "{} output is always PNG",
+                    reduced
+                )));
+            }
+            self.svg = true;
+            return Ok(self);
         }
+
+        self.format = parse_photo_format(format, quality, self.png_only(), headers)?
+            .unwrap_or(PhotoFormat::Png);
+        Ok(self)
     }
-}
 
-async fn list_camera_entities(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
-    let url = format!("{}/api/states", state.ha_config.base_url);
-
-    let response = state
-        .http_client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", state.ha_config.token))
-        .header("Content-Type", "application/json")
-        .send()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to fetch states: {}", e)))?;
+    /// Reduce the output to `?depth=` bits of gray, dithered with
+    /// `?dither=` (a plain threshold by default, which keeps text crisp) and
+    /// inverted with `?invert=true`.
+    fn with_depth(
+        mut self,
+        depth: Option<u8>,
+        dither: Option<&str>,
+        invert: Option<bool>,
+    ) -> Result<Self, AppError> {
+        self.grayscale = parse_grayscale(depth, dither, invert, self.palette, Dither::None)?;
+        Ok(self)
+    }
 
-    if !response.status().is_success() {
-        return Err(AppError::Internal(format!(
-            "Failed to get states: {}",
-            response.status()
-        )));
+    fn png_only(&self) -> Option<&'static str> {
+        png_only(self.palette, self.grayscale)
     }
 
-    let states: Vec<EntityState> = response
-        .json()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to parse states: {}", e)))?;
+    /// Keep a border of `?margin=` pixels, e.g. under a panel's bezel.
+    fn with_margin(mut self, margin: Option<u32>) -> Result<Self, AppError> {
+        self.margin = presets::check_margin(margin.unwrap_or(0)).map_err(AppError::BadRequest)?;
+        Ok(self)
+    }
 
-    let camera_entities: Vec<&EntityState> = states
-        .iter()
-        .filter(|state| state.entity_id.starts_with("camera."))
-        .collect();
+    /// `?si=`: engineering prefixes everywhere, nowhere, or (unset) for the
+    /// device classes that default to them.
+    fn with_si(mut self, si: Option<bool>) -> Self {
+        self.si = SiPrefixes::from_query(si);
+        self
+    }
 
-    let json_response = serde_json::to_string_pretty(&camera_entities)
-        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+    /// Enlarge the render to `size` (inside the margin) before finishing it.
+    fn with_upscale(mut self, size: Option<(u32, u32)>) -> Self {
+        self.upscale = size;
+        self
+    }
 
-    Ok((
-        StatusCode::OK,
-        [(header::CONTENT_TYPE, "application/json")],
-        json_response,
-    )
-        .into_response())
+    /// Bytes of a finished render in the style's format, indexed PNG when a
+    /// palette is set and grayscale PNG with a depth.
+    fn encode(&self, image: &RgbImage) -> anyhow::Result<bytes::Bytes> {
+        if let Some(palette) = self.palette {
+            return Ok(bytes::Bytes::from(palette.encode_png(image)?));
+        }
+        if let Some(grayscale) = self.grayscale {
+            let image = image::DynamicImage::ImageRgb8(image.clone());
+            return Ok(bytes::Bytes::from(grayscale.encode(&image)?));
+        }
+
+        let buffer = self
+            .format
+            .encode(&image::DynamicImage::ImageRgb8(image.clone()), &self.png)?;
+        Ok(bytes::Bytes::from(buffer))
+    }
+
+    fn content_type(&self) -> &'static str {
+        if self.svg {
+            return "image/svg+xml";
+        }
+        match self.png_only() {
+            Some(_) => "image/png",
+            None => self.format.content_type(),
+        }
+    }
 }
 
-async fn render_entity_status(
-    State(state): State<Arc<AppState>>,
-    Path(entity_id): Path<String>,
-    Query(params): Query<ImageQuery>,
-) -> Result<Response, AppError> {
-    info!("Rendering status image for entity: {}", entity_id);
+/// Color that best represents the entity itself: the actual light color for
+/// lights that are on, the HVAC mode for climate entities, or the matching
+/// value threshold for numeric sensors.
+fn entity_accent_color(entity: &EntityState, thresholds: &ValueThresholds) -> Option<Rgb<u8>> {
+    let domain = entity.entity_id.split('.').next().unwrap_or("");
 
-    // Get entity state
-    let entity_state = state
-        .get_entity_state(&entity_id)
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to get entity state: {}", e)))?;
+    match domain {
+        "light" if entity.state == "on" => {
+            let rgb = entity.attributes.get("rgb_color")?.as_array()?;
+            let channel = |i: usize| rgb.get(i)?.as_u64().map(|v| v.min(255) as u8);
+            Some(Rgb([channel(0)?, channel(1)?, channel(2)?]))
+        }
+        "climate" => {
+            let action = entity
+                .attributes
+                .get("hvac_action")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&entity.state);
+            match action {
+                "heat" | "heating" => Some(Rgb([230u8, 110u8, 30u8])),
+                "cool" | "cooling" => Some(Rgb([40u8, 120u8, 220u8])),
+                "heat_cool" | "auto" => Some(Rgb([140u8, 80u8, 190u8])),
+                "dry" | "drying" => Some(Rgb([210u8, 170u8, 40u8])),
+                "fan_only" | "fan" => Some(Rgb([30u8, 160u8, 150u8])),
+                "off" | "idle" => Some(Rgb([150u8, 150u8, 150u8])),
+                _ => None,
+            }
+        }
+        _ => thresholds.color_for(entity),
+    }
+}
 
-    // Extract dimensions from query params or use defaults
-    let width = params.width.unwrap_or(400);
-    let height = params.height.unwrap_or(200);
+#[derive(Deserialize)]
+struct ImageQuery {
+    #[allow(dead_code)] // Accepted for compatibility with existing dashboard URLs
+    entity_id: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    #[allow(dead_code)]
+    cache: Option<bool>,
+    thresholds: Option<String>, // e.g. "20:green,25:orange,30:red"
+    theme: Option<String>,      // light, dark, high-contrast
+    accessible: Option<bool>,   // Large bold text, maximum contrast, no decoration
+    brightness: Option<f32>,    // Proxied images only; 1.0 = unchanged
+    contrast: Option<f32>,
+    gamma: Option<f32>,
+    auto_levels: Option<bool>, // Stretch the histogram before the adjustments above
+    sharpen: Option<f32>,      // Unsharp mask strength, 0-3
+    fit: Option<String>,       // Proxied images: fit (letterbox), fill, stretch
+    background: Option<String>, // Letterbox color for fit=fit
+    crop: Option<String>,      // x,y,w,h or center, top, bottom, detail (before resizing)
+    rotate: Option<u32>,       // 90, 180 or 270 degrees clockwise, applied last
+    palette: Option<String>,   // bwr for tri-color, spectra6 for six-color e-paper
+    format: Option<String>,    // png, jpeg or webp
+    quality: Option<u8>,       // JPEG quality, 1-100
+    depth: Option<u8>,         // 1, 2, 4 or 8 bits of gray instead of color
+    dither: Option<String>,    // With depth; atkinson for proxied images, none for status
+    invert: Option<bool>,      // With depth: swap black and white after quantizing
+    margin: Option<u32>,       // Status renders only: border inside width x height
+    si: Option<bool>,          // Status renders only: engineering prefixes (12.4 kW)
+    units: Option<String>,     // Status renders only: metric, imperial or marine, entity_id=unit
+}
+
+#[derive(Deserialize)]
+struct ImageUrlQuery {
+    url: Option<String>,
+    brightness: Option<f32>, // 1.0 = unchanged
+    contrast: Option<f32>,
+    gamma: Option<f32>,
+    auto_levels: Option<bool>,
+    sharpen: Option<f32>, // 0-3
+    width: Option<u32>,
+    height: Option<u32>,
+    fit: Option<String>, // fit (letterbox), fill, stretch
+    background: Option<String>,
+    crop: Option<String>,    // x,y,w,h or center, top, bottom, detail
+    rotate: Option<u32>,     // 90, 180 or 270 degrees clockwise
+    palette: Option<String>, // bwr or spectra6
+    format: Option<String>,  // png, jpeg or webp
+    quality: Option<u8>,     // JPEG quality, 1-100
+    depth: Option<u8>,       // 1, 2, 4 or 8 bits of gray instead of color
+    dither: Option<String>,  // With depth: atkinson (default), floyd, bayer or none
+    invert: Option<bool>,    // With depth: swap black and white after quantizing
+}
+
+#[derive(Deserialize)]
+struct MultiSensorQuery {
+    sensors: String, // Comma-separated list of sensor entity IDs
+    width: Option<u32>,
+    height: Option<u32>,
+    title: Option<String>,
+    animate: Option<bool>, // Return a GIF flashing rows whose value changed since the last render
+    thresholds: Option<String>, // e.g. "20:green,25:orange,30:red"
+    theme: Option<String>, // light, dark, high-contrast
+    accessible: Option<bool>, // Large bold text, maximum contrast, no decoration
+    rotate: Option<u32>,   // 90, 180 or 270 degrees clockwise
+    palette: Option<String>, // bwr or spectra6
+    margin: Option<u32>,   // Border inside width x height, in pixels
+    format: Option<String>, // png, jpeg, webp or svg
+    quality: Option<u8>,   // JPEG quality, 1-100
+    depth: Option<u8>,     // 1, 2, 4 or 8 bits of gray instead of color
+    dither: Option<String>, // With depth: none (default), floyd, atkinson or bayer
+    invert: Option<bool>,  // With depth: swap black and white after quantizing
+    si: Option<bool>,      // Engineering prefixes (12.4 kW); default per device class
+    units: Option<String>, // metric, imperial or marine, plus entity_id=unit overrides
+}
+
+#[derive(Deserialize)]
+struct TrmnlQuery {
+    #[serde(default)]
+    sensors: String, // Comma-separated list of sensor entity IDs
+    camera: Option<String>, // Camera entity shown as a dithered photo instead of sensors
+    title: Option<String>,
+    side_label: Option<String>, // Rotated label down the left edge (e.g. room name)
+    accessible: Option<bool>,   // Larger bold text, solid gauges, fewer rows
+    si: Option<bool>,           // Engineering prefixes (12.4 kW); default per device class
+    units: Option<String>,      // metric, imperial or marine, plus entity_id=unit overrides
+    priority: Option<String>,   // entity_id=low|normal|high; low rows are dropped first
+    dither: Option<String>, // floyd, atkinson, bayer, none (default: none, atkinson for cameras)
+    brightness: Option<f32>, // Camera photo only; 1.0 = unchanged
+    contrast: Option<f32>,
+    gamma: Option<f32>,
+    auto_levels: Option<bool>,
+    sharpen: Option<f32>,      // Camera photo only; unsharp mask strength 0-3
+    crop: Option<String>,      // Camera photo only; x,y,w,h or center, top, bottom, detail
+    rotate: Option<u32>,       // 90, 180 or 270 degrees clockwise, e.g. for portrait mounting
+    invert: Option<bool>,      // White on black
+    format: Option<String>,    // png (default), bmp, xbm or raw
+    depth: Option<u8>,         // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>,        // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+    sparklines: Option<bool>, // Trend of the last 6 hours beside each numeric value
+}
+
+#[derive(Deserialize)]
+struct TrmnlMarkupQuery {
+    sensors: String, // Comma-separated list of sensor entity IDs
+    title: Option<String>,
+    view: Option<String>, // full (default), half_horizontal, half_vertical or quadrant
+    si: Option<bool>,
+    units: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TrmnlAutoQuery {
+    domains: Option<String>, // Comma-separated entity domains (default: sensor)
+    area: Option<String>,    // Area ID or name
+    limit: Option<usize>,
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    si: Option<bool>,
+    units: Option<String>,
+    priority: Option<String>, // entity_id=low|normal|high; picks and keeps rows before recency
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlActivityQuery {
+    entities: Option<String>, // Comma-separated scene/script IDs (default: all of `domains`)
+    domains: Option<String>,  // Default: scene,script
+    limit: Option<usize>,
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlEventsQuery {
+    events: String, // Comma-separated Label=entity_id[:attribute] holding each event's time
+    details: Option<String>, // Attributes shown after the label, default visibility,max_elevation
+    limit: Option<usize>,
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlDeltaQuery {
+    a: String,             // First sensor, e.g. inside temperature
+    b: String,             // Subtracted from `a`, e.g. outside temperature
+    rules: Option<String>, // e.g. ">2:Open windows,<-2:Close windows"
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    units: Option<String>, // Both sensors converted alike: metric, imperial, marine
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlPollenQuery {
+    allergens: String, // Comma-separated entity IDs, optionally labeled: Grass=sensor.grass_pollen
+    source: Option<String>, // Adapter preset: default, dwd
+    today: Option<String>, // Attribute path of today's level (default: the state)
+    tomorrow: Option<String>, // Attribute path of tomorrow's level, e.g. forecast.1.value
+    max: Option<f64>,  // Top of the numeric scale
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlTideQuery {
+    tide: String,              // Tide sensor, e.g. sensor.noaa_tides
+    marine: Option<String>,    // Comma-separated wind/wave sensors shown below the chart
+    high_time: Option<String>, // Attribute names, default high_tide_time etc.
+    high_height: Option<String>,
+    low_time: Option<String>,
+    low_height: Option<String>,
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    units: Option<String>, // Marine sensors: metric, imperial or marine (knots)
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlCarbonQuery {
+    sensor: String,       // Carbon intensity or electricity price sensor
+    kind: Option<String>, // carbon or price; from the sensor's unit by default
+    hours: Option<u32>,   // Hours of forecast considered and drawn, default 12
+    list: Option<String>, // Attribute paths of the forecast, see carbon::Fields
+    time: Option<String>,
+    value: Option<String>,
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlFlightsQuery {
+    sensor: String,       // Flight-tracking sensor listing aircraft in an attribute
+    list: Option<String>, // Attribute paths, default the Flightradar24 integration's
+    callsign: Option<String>,
+    altitude: Option<String>,
+    origin: Option<String>,
+    destination: Option<String>,
+    unit: Option<String>, // Appended to numeric altitudes (default: ft)
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlSleepQuery {
+    until: Option<String>, // HH:MM the device wakes at, shown under the moon
+    rotate: Option<u32>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+}
+
+#[derive(Deserialize)]
+struct TrmnlFleetQuery {
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+/// A `POST /devices` body.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewDevice {
+    id: String, // MAC address (the firmware's ID header) or any ?device= ID
+    name: Option<String>,
+    preset: Option<String>,
+    screen: Option<String>, // DEVICE_SCREENS_FILE key or dashboard name
+}
+
+#[derive(Deserialize)]
+struct TrmnlTimetableQuery {
+    calendar: Option<String>, // Comma-separated calendars holding the lessons
+    days: Option<usize>,      // 5 (Monday to Friday, default) or 7
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    hours: Option<u64>, // How far back the chart reaches, default 24
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct StatisticsQuery {
+    period: Option<String>, // hour, day (default), week or month
+    count: Option<u32>,     // Periods shown, by default 24 hours, 14 days, 12 weeks or 12 months
+    stat: Option<String>,   // change, mean, min or max; meters default to change, others to mean
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    si: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlNoiseQuery {
+    sensor: String,         // Sound level sensor, e.g. sensor.nursery_sound_level
+    quiet: Option<String>,  // Quiet hours shaded on the chart, HH:MM-HH:MM (default 23:00-07:00)
+    threshold: Option<f64>, // Level drawn across the chart; time above it in quiet hours is shown
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlLeaderboardQuery {
+    sensors: Option<String>,      // Comma-separated entities to rank
+    device_class: Option<String>, // Rank every entity of this device class instead, e.g. power
+    stat: Option<String>, // value (default), max, mean, increase or count, the last four today
+    limit: Option<usize>, // Rows shown, 1-10 (default 5)
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    si: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct TrmnlRemindersQuery {
+    items: Option<String>,    // Comma-separated HH:MM Label=input_boolean.entity
+    calendar: Option<String>, // Comma-separated calendars whose events today are reminders
+    title: Option<String>,
+    side_label: Option<String>,
+    accessible: Option<bool>,
+    dither: Option<String>,
+    rotate: Option<u32>,
+    invert: Option<bool>,
+    format: Option<String>,
+    depth: Option<u8>, // 1 (default), 2, 4 or 8 bits per pixel; bmp and xbm are 1-bit
+    bit_order: Option<String>, // Raw output only: msb (default) or lsb
+    width: Option<u32>, // Output size; the layout is scaled to fit
+    height: Option<u32>,
+    margin: Option<u32>, // White border around the scaled layout, in output pixels
+    post: Option<String>, // Post-processing: sharpen, edges, despeckle
+}
+
+#[derive(Deserialize)]
+struct SummaryQuery {
+    sensors: String, // Comma-separated list of sensor entity IDs
+    title: Option<String>,
+    si: Option<bool>, // Engineering prefixes (12.4 kW); default per device class
+    units: Option<String>, // metric, imperial or marine, plus entity_id=unit overrides
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    sensors: String,       // Comma-separated list of sensor entity IDs
+    title: Option<String>, // Default: "Daily Report"
+    day: Option<String>,   // yesterday (default) or today
+}
+
+#[derive(Deserialize)]
+struct CycleQuery {
+    screens: String,        // Comma-separated dashboard names
+    interval: Option<u32>,  // Seconds per screen (default 10)
+    format: Option<String>, // gif (the only format)
+}
+
+#[derive(Deserialize)]
+struct DashboardSummaryQuery {
+    format: Option<String>, // "json" (default) or "text"
+}
+
+#[derive(Serialize, Deserialize)]
+struct EntityState {
+    entity_id: String,
+    state: String,
+    attributes: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_changed: Option<String>,
+}
+
+impl AppState {
+    fn new() -> anyhow::Result<Self> {
+        let ha_url = std::env::var("HA_URL")
+            .unwrap_or_else(|_| "http://localhost:8123".to_string())
+            .trim()
+            .trim_end_matches('/')
+            .to_string();
+        let ha_token = std::env::var("HA_TOKEN")
+            .map_err(|_| anyhow::anyhow!("HA_TOKEN environment variable is required. Please set it in your .env file or as an environment variable."))?;
+        let ha_headers = match std::env::var("HA_HEADERS") {
+            Ok(spec) => parse_ha_headers(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid HA_HEADERS entry '{}'", entry))?,
+            Err(_) => reqwest::header::HeaderMap::new(),
+        };
+        let ha_headers = ProxyHeaders::new(ha_headers);
+        let proxy_auth = ProxyAuth::from_env()?;
+
+        let http_client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+
+        let theme_name = std::env::var("THEME").unwrap_or_else(|_| "light".to_string());
+        let mut theme = Theme::by_name(&theme_name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown THEME '{}' (light, dark, high-contrast)",
+                theme_name
+            )
+        })?;
+        let theme_overrides = std::env::var("THEME_COLORS").ok();
+        if let Some(overrides) = &theme_overrides {
+            theme
+                .apply_overrides(overrides)
+                .map_err(|entry| anyhow::anyhow!("Invalid THEME_COLORS entry '{}'", entry))?;
+        }
+
+        let mut dashboards = match std::env::var("DASHBOARDS") {
+            Ok(spec) => dashboards::parse(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid DASHBOARDS entry '{}'", entry))?,
+            Err(_) => Vec::new(),
+        };
+        if let Ok(spec) = std::env::var("DASHBOARD_SUMMARIES") {
+            dashboards::parse_summaries(&mut dashboards, &spec).map_err(|entry| {
+                anyhow::anyhow!("Invalid DASHBOARD_SUMMARIES entry '{}'", entry)
+            })?;
+        }
+        if let Ok(spec) = std::env::var("DASHBOARD_TILES") {
+            dashboards::parse_tiles(&mut dashboards, &spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid DASHBOARD_TILES entry '{}'", entry))?;
+        }
+
+        let webhooks = match std::env::var("TRMNL_WEBHOOKS") {
+            Ok(spec) => webhooks::parse(&spec, &dashboards)
+                .map_err(|entry| anyhow::anyhow!("Invalid TRMNL_WEBHOOKS entry '{}'", entry))?,
+            Err(_) => Vec::new(),
+        };
+        let webhook_interval = env_number("TRMNL_WEBHOOK_INTERVAL", 900)?;
+        if webhook_interval < 60 {
+            return Err(anyhow::anyhow!(
+                "TRMNL_WEBHOOK_INTERVAL must be at least 60 seconds, got {}",
+                webhook_interval
+            ));
+        }
+
+        let min_change: f64 = env_number("DASHBOARD_MIN_CHANGE", 0.0)?;
+        if !(0.0..=100.0).contains(&min_change) {
+            return Err(anyhow::anyhow!(
+                "DASHBOARD_MIN_CHANGE must be between 0 and 100 percent, got {}",
+                min_change
+            ));
+        }
+        let dashboard_cache = DashboardCache::new(
+            Duration::from_secs(env_number("DASHBOARD_CACHE_SECONDS", 0)?),
+            min_change,
+        );
+
+        let meter_budgets = match std::env::var("METER_BUDGETS") {
+            Ok(spec) => meters::parse(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid METER_BUDGETS entry '{}'", entry))?,
+            Err(_) => HashMap::new(),
+        };
+
+        let care_log = match std::env::var("CARE_LOG") {
+            Ok(spec) => care::parse(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid CARE_LOG entry '{}'", entry))?,
+            Err(_) => HashMap::new(),
+        };
+
+        let appliances = match std::env::var("APPLIANCES") {
+            Ok(spec) => appliances::parse(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid APPLIANCES entry '{}'", entry))?,
+            Err(_) => HashMap::new(),
+        };
+
+        let world_clocks = match std::env::var("WORLD_CLOCKS") {
+            Ok(spec) => zones::parse_clocks(&spec)
+                .map_err(|e| anyhow::anyhow!("Invalid WORLD_CLOCKS entry {}", e))?,
+            Err(_) => Vec::new(),
+        };
+
+        let duration_formats = match std::env::var("DURATION_FORMATS") {
+            Ok(spec) => durations::parse(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid DURATION_FORMATS entry '{}'", entry))?,
+            Err(_) => HashMap::new(),
+        };
+
+        let currency = match std::env::var("CURRENCY") {
+            Ok(code) => Some(
+                currency::parse_code(&code)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid CURRENCY '{}'", code))?,
+            ),
+            Err(_) => None,
+        };
+        let currency_locale = match std::env::var("CURRENCY_LOCALE") {
+            Ok(name) => currency::Locale::parse(&name)
+                .ok_or_else(|| anyhow::anyhow!("Invalid CURRENCY_LOCALE '{}'", name))?,
+            Err(_) => currency::Locale::default(),
+        };
+
+        let attention_states = match std::env::var("ATTENTION_STATES") {
+            Ok(spec) => attention::AttentionStates::parse(&spec)
+                .map_err(|entry| anyhow::anyhow!("Invalid ATTENTION_STATES entry '{}'", entry))?,
+            Err(_) => attention::AttentionStates::default(),
+        };
+
+        let trmnl_layout = (
+            env_number("TRMNL_WIDTH", 800)?,
+            env_number("TRMNL_HEIGHT", 480)?,
+        );
+        if !(TRMNL_MIN_LAYOUT.0..=resize::MAX_DIMENSION).contains(&trmnl_layout.0)
+            || !(TRMNL_MIN_LAYOUT.1..=resize::MAX_DIMENSION).contains(&trmnl_layout.1)
+        {
+            return Err(anyhow::anyhow!(
+                "TRMNL_WIDTH x TRMNL_HEIGHT must be between {}x{} and {}x{}, got {}x{}",
+                TRMNL_MIN_LAYOUT.0,
+                TRMNL_MIN_LAYOUT.1,
+                resize::MAX_DIMENSION,
+                resize::MAX_DIMENSION,
+                trmnl_layout.0,
+                trmnl_layout.1
+            ));
+        }
+        for dashboard in &dashboards {
+            if let Some(tiles) = dashboard.tiles {
+                let (width, height) = tiles.layout(trmnl_layout);
+                if width > resize::MAX_DIMENSION || height > resize::MAX_DIMENSION {
+                    return Err(anyhow::anyhow!(
+                        "Dashboard {} tiles add up to {}x{}, more than {}x{}",
+                        dashboard.name,
+                        width,
+                        height,
+                        resize::MAX_DIMENSION,
+                        resize::MAX_DIMENSION
+                    ));
+                }
+            }
+        }
+
+        let presets = Presets::load(std::env::var("DEVICE_PRESETS_FILE").ok().as_deref())?;
+        let screens = Screens::load(
+            std::env::var("DEVICE_SCREENS_FILE").ok().as_deref(),
+            &dashboards,
+        )?;
+        let public_url = match std::env::var("PUBLIC_URL") {
+            Ok(url) => {
+                let url = url.trim().trim_end_matches('/').to_string();
+                if reqwest::Url::parse(&url)
+                    .map_or(true, |parsed| !matches!(parsed.scheme(), "http" | "https"))
+                {
+                    return Err(anyhow::anyhow!("Invalid PUBLIC_URL '{}'", url));
+                }
+                Some(url)
+            }
+            Err(_) => None,
+        };
+
+        let png = PngOptions::new(
+            std::env::var("PNG_COMPRESSION").ok().as_deref(),
+            std::env::var("PNG_FILTER").ok().as_deref(),
+            env_number("PNG_INDEXED", false)?,
+        )
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+        let device_history = env_number("DEVICE_HISTORY", 10)?;
+
+        let offline_policy = OfflinePolicy {
+            default_interval: Duration::from_secs(env_number("DEVICE_CHECKIN_INTERVAL", 900)?),
+            misses: env_number("DEVICE_OFFLINE_MISSES", 3)?,
+        };
+
+        let socket = HaSocket::new(&ha_url, &ha_token, ha_headers.clone());
+        let state_cache =
+            env_number("STATE_CACHE", true)?.then(|| StateCache::start(socket.clone()));
+
+        let privacy = Privacy::load(std::env::var("ENTITIES_FILE").ok().as_deref())?;
+        let watchdog_mode = watchdog::Mode::parse(std::env::var("RENDER_WATCHDOG").ok().as_deref())
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(Self {
+            http_client,
+            socket,
+            state_cache,
+            privacy,
+            watchdog: Watchdog::new(watchdog_mode),
+            ha_config: HomeAssistantConfig {
+                base_url: ha_url,
+                token: ha_token,
+                extra_headers: ha_headers,
+            },
+            proxy_auth,
+            abbreviations: Abbreviations::from_env(),
+            theme,
+            theme_overrides,
+            last_values: Arc::new(Mutex::new(HashMap::new())),
+            dashboards,
+            dashboard_cache,
+            devices: DeviceRegistry::new(device_history, offline_policy)
+                .with_file(std::env::var("DEVICES_FILE").ok())?,
+            device_webhook: std::env::var("DEVICE_OFFLINE_WEBHOOK").ok(),
+            device_sensors: env_number("DEVICE_SENSORS", true)?,
+            low_battery_volts: env_number("DEVICE_LOW_BATTERY", 3.6)?,
+            render_stamp: env_number("RENDER_STAMP", false)?,
+            trmnl_layout,
+            png,
+            meter_budgets,
+            care_log,
+            world_clocks,
+            appliances,
+            duration_formats,
+            currency,
+            currency_locale,
+            attention_states,
+            presets,
+            screens,
+            public_url,
+            camera_frames: CameraFrames::default(),
+            webhooks,
+            webhook_interval: Duration::from_secs(webhook_interval),
+        })
+    }
+
+    /// Size TRMNL screens are laid out at: the configured panel's, or the
+    /// whole installation's while a tiled dashboard renders.
+    fn trmnl_layout(&self) -> (u32, u32) {
+        TILED_LAYOUT
+            .try_with(|layout| *layout)
+            .unwrap_or(self.trmnl_layout)
+    }
+
+    /// `v0.1.0 16 Oct 13:21` (UTC) when `RENDER_STAMP` is enabled, so a
+    /// stale cached image can be told apart from a fresh render.
+    fn render_stamp(&self) -> Option<String> {
+        if !self.render_stamp {
+            return None;
+        }
+
+        // "Fri, 16 Oct 2026 13:21:07 GMT"
+        let date = httpdate::fmt_http_date(SystemTime::now());
+        let parts: Vec<&str> = date.split(' ').collect();
+        let (day, month, time) = (parts[1], parts[2], &parts[4][..5]);
+        Some(format!(
+            "v{} {} {} {}",
+            env!("CARGO_PKG_VERSION"),
+            day,
+            month,
+            time
+        ))
+    }
+
+    /// Palette for a request: the configured default, or a built-in theme
+    /// selected with `?theme=` (configured color overrides still apply).
+    fn resolve_theme(&self, name: Option<&str>) -> Result<Theme, AppError> {
+        let Some(name) = name else {
+            return Ok(self.theme);
+        };
+
+        let mut theme = Theme::by_name(name)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown theme '{}'", name)))?;
+        if let Some(overrides) = &self.theme_overrides {
+            // Already validated at startup
+            let _ = theme.apply_overrides(overrides);
+        }
+        Ok(theme)
+    }
+
+    fn render_style(
+        &self,
+        theme: Option<&str>,
+        thresholds: Option<&str>,
+        accessible: bool,
+        rotate: Option<u32>,
+        palette: Option<&str>,
+    ) -> Result<RenderStyle<'_>, AppError> {
+        let palette = parse_palette(palette)?;
+
+        // Panel palettes and the accessibility profile bring their own
+        // default themes
+        let theme = match theme {
+            None if palette == Some(Palette::BlackWhiteRed) => {
+                self.resolve_theme(Some("tri-color"))?
+            }
+            None if palette == Some(Palette::Spectra6) => self.resolve_theme(Some("six-color"))?,
+            None if accessible => self.resolve_theme(Some("high-contrast"))?,
+            theme => self.resolve_theme(theme)?,
+        };
+        let thresholds = ValueThresholds::parse(thresholds, &theme)?;
+        Ok(RenderStyle {
+            theme,
+            thresholds,
+            abbreviations: &self.abbreviations,
+            accessible,
+            stamp: self.render_stamp(),
+            rotation: parse_rotation(rotate)?,
+            palette,
+            grayscale: None,
+            format: PhotoFormat::Png,
+            svg: false,
+            margin: 0,
+            upscale: None,
+            png: self.png,
+            si: SiPrefixes::default(),
+        })
+    }
+
+    /// Set a device's `sensor.trmnl_<id>_battery` and `sensor.trmnl_<id>_rssi`
+    /// in Home Assistant to the battery voltage and signal strength it just
+    /// reported. States set this way don't survive a Home Assistant
+    /// restart; the device's next poll sets them again.
+    async fn report_device_sensors(&self, device_id: &str, telemetry: Telemetry) {
+        let object_id = devices::object_id(device_id);
+        let readings = [
+            telemetry.battery_voltage.map(|volts| {
+                (
+                    "battery",
+                    format!("{:.2}", volts),
+                    serde_json::json!({
+                        "friendly_name": format!("TRMNL {} Battery", device_id),
+                        "unit_of_measurement": "V",
+                        "device_class": "voltage",
+                        "state_class": "measurement",
+                    }),
+                )
+            }),
+            telemetry.rssi.map(|rssi| {
+                (
+                    "rssi",
+                    rssi.to_string(),
+                    serde_json::json!({
+                        "friendly_name": format!("TRMNL {} Signal", device_id),
+                        "unit_of_measurement": "dBm",
+                        "device_class": "signal_strength",
+                        "state_class": "measurement",
+                    }),
+                )
+            }),
+        ];
+
+        for (suffix, state, attributes) in readings.into_iter().flatten() {
+            let entity_id = format!("sensor.trmnl_{}_{}", object_id, suffix);
+            let url = format!("{}/api/states/{}", self.ha_config.base_url, entity_id);
+            let result = self
+                .http_client
+                .post(&url)
+                .headers(self.ha_config.headers())
+                .json(&serde_json::json!({ "state": state, "attributes": attributes }))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => debug!("Set {} to {}", entity_id, state),
+                Err(e) => warn!("Failed to set {}: {}", entity_id, e),
+            }
+        }
+    }
+
+    /// Tell Home Assistant (as a `trmnl_renderer_device_offline` /
+    /// `trmnl_renderer_device_online` event) and the optional webhook that a
+    /// device changed state.
+    async fn notify_device_status(&self, status: &DeviceStatus) {
+        let event = if status.online {
+            "trmnl_renderer_device_online"
+        } else {
+            "trmnl_renderer_device_offline"
+        };
+        info!("Device {}: {}", status.id, event);
+
+        let url = format!("{}/api/events/{}", self.ha_config.base_url, event);
+        let result = self
+            .http_client
+            .post(&url)
+            .headers(self.ha_config.headers())
+            .json(status)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to fire {} event: {}", event, e);
+        }
+
+        if let Some(webhook) = &self.device_webhook {
+            let payload = serde_json::json!({ "event": event, "device": status });
+            let result = self
+                .http_client
+                .post(webhook)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                warn!("Failed to call device webhook: {}", e);
+            }
+        }
+    }
+
+    /// A device's button was pressed: fire a `trmnl_renderer_button_pressed`
+    /// event and call the screen's `button` service, if it has one.
+    async fn press_button(&self, device_id: &str, call: Option<ServiceCall>) {
+        info!("Device {} reported a button press", device_id);
+
+        let url = format!(
+            "{}/api/events/trmnl_renderer_button_pressed",
+            self.ha_config.base_url
+        );
+        let result = self
+            .http_client
+            .post(&url)
+            .headers(self.ha_config.headers())
+            .json(&serde_json::json!({ "device_id": device_id }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to fire trmnl_renderer_button_pressed event: {}", e);
+        }
+
+        let Some(call) = call else {
+            return;
+        };
+        let (domain, service) = call.service.split_once('.').unwrap_or_default();
+        let url = format!(
+            "{}/api/services/{}/{}",
+            self.ha_config.base_url, domain, service
+        );
+        let result = self
+            .http_client
+            .post(&url)
+            .headers(self.ha_config.headers())
+            .json(&call.data.unwrap_or_else(|| serde_json::json!({})))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => info!("Called {} for device {}", call.service, device_id),
+            Err(e) => warn!(
+                "Failed to call {} for device {}: {}",
+                call.service, device_id, e
+            ),
+        }
+    }
+
+    /// Record the current formatted values and return the entity IDs whose
+    /// value differs from the previous render. Entities seen for the first
+    /// time are not reported as changed.
+    fn track_value_changes(&self, sensors: &[EntityState], si: SiPrefixes) -> HashSet<String> {
+        let mut last_values = self.last_values.lock().unwrap();
+        let mut changed = HashSet::new();
+
+        for sensor in sensors {
+            let value = format_sensor_value(sensor, si);
+            if let Some(previous) = last_values.insert(sensor.entity_id.clone(), value.clone())
+                && previous != value
+            {
+                changed.insert(sensor.entity_id.clone());
+            }
+        }
+
+        changed
+    }
+
+    /// Whether `entity_id` is private and a dashboard is rendering for
+    /// sharing.
+    fn is_hidden(&self, entity_id: &str) -> bool {
+        SHARING.try_with(|_| ()).is_ok() && self.privacy.is_private(entity_id)
+    }
+
+    /// A private entity under a generic name with its value hidden, while
+    /// sharing.
+    fn redacted(&self, entity: EntityState) -> EntityState {
+        if !self.is_hidden(&entity.entity_id) {
+            return entity;
+        }
+        EntityState {
+            attributes: privacy::redact_attributes(&entity.entity_id, &entity.attributes),
+            state: privacy::HIDDEN_STATE.to_string(),
+            last_changed: None,
+            entity_id: entity.entity_id,
+        }
+    }
+
+    async fn get_entity_state(&self, entity_id: &str) -> anyhow::Result<EntityState> {
+        Ok(self.redacted(self.fetch_entity_state(entity_id).await?))
+    }
+
+    /// State of an entity, from the state cache while it's warm.
+    async fn fetch_entity_state(&self, entity_id: &str) -> anyhow::Result<EntityState> {
+        if let Some(cached) = self.state_cache.as_ref().and_then(|c| c.get(entity_id)) {
+            return match cached {
+                Some(state) => Ok(serde_json::from_value(state)?),
+                None => Err(anyhow::anyhow!(
+                    "Failed to get entity state: {}",
+                    StatusCode::NOT_FOUND
+                )),
+            };
+        }
+
+        let url = format!("{}/api/states/{}", self.ha_config.base_url, entity_id);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .headers(self.ha_config.headers())
+            .header("Content-Type", "application/json")
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get entity state: {}",
+                response.status()
+            ));
+        }
+
+        let entity_state: EntityState = response.json().await?;
+        Ok(entity_state)
+    }
+
+    async fn get_all_states(&self) -> anyhow::Result<Vec<EntityState>> {
+        let states = self.fetch_all_states().await?;
+        Ok(states
+            .into_iter()
+            .map(|entity| self.redacted(entity))
+            .collect())
+    }
+
+    async fn fetch_all_states(&self) -> anyhow::Result<Vec<EntityState>> {
+        if let Some(cached) = self.state_cache.as_ref().and_then(StateCache::all) {
+            return cached
+                .into_iter()
+                .map(|state| Ok(serde_json::from_value(state)?))
+                .collect();
+        }
+
+        let url = format!("{}/api/states", self.ha_config.base_url);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .headers(self.ha_config.headers())
+            .header("Content-Type", "application/json")
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get states: {}",
+                response.status()
+            ));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Render a template over the shared WebSocket, falling back to the REST
+    /// API when the socket is unavailable (e.g. behind a proxy that doesn't
+    /// pass WebSockets through).
+    async fn render_template(
+        &self,
+        template: &str,
+        variables: serde_json::Value,
+    ) -> anyhow::Result<String> {
+        match self
+            .socket
+            .render_template(template, variables.clone())
+            .await
+        {
+            Ok(text) => return Ok(text),
+            Err(e) => debug!("Rendering template over REST instead: {}", e),
+        }
+
+        let url = format!("{}/api/template", self.ha_config.base_url);
+        let body = serde_json::json!({ "template": template, "variables": variables });
+
+        let response = self
+            .http_client
+            .post(&url)
+            .headers(self.ha_config.headers())
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to render template: {}",
+                response.status()
+            ));
+        }
+
+        Ok(response.text().await?)
+    }
+
+    /// Entity IDs assigned to an area (by area ID or name), resolved with a
+    /// template since the REST API doesn't expose the area registry.
+    async fn get_area_entities(&self, area: &str) -> anyhow::Result<HashSet<String>> {
+        let text = self
+            .render_template(
+                "{{ area_entities(area) | tojson }}",
+                serde_json::json!({ "area": area }),
+            )
+            .await?;
+        serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Unexpected area template result: {}", e))
+    }
+
+    /// Start of the current day in Home Assistant's time zone.
+    async fn get_start_of_today(&self) -> anyhow::Result<String> {
+        let text = self
+            .render_template("{{ today_at().isoformat() }}", serde_json::json!({}))
+            .await?;
+        Ok(text.trim().to_string())
+    }
+
+    /// Start of the day `days_ago` days before today in Home Assistant's
+    /// time zone; negative for days ahead.
+    async fn get_start_of_day(&self, days_ago: i64) -> anyhow::Result<String> {
+        let text = self
+            .render_template(
+                &format!(
+                    "{{{{ (today_at() - timedelta(days={})).isoformat() }}}}",
+                    days_ago
+                ),
+                serde_json::json!({}),
+            )
+            .await?;
+        Ok(text.trim().to_string())
+    }
+
+    /// Numeric history of several entities from `start` until `end`, both
+    /// Home Assistant timestamps. Private entities have none while sharing.
+    async fn get_history(
+        &self,
+        entity_ids: &[String],
+        start: &str,
+        end: &str,
+    ) -> anyhow::Result<Vec<Vec<serde_json::Value>>> {
+        let entity_ids: Vec<&str> = entity_ids
+            .iter()
+            .map(String::as_str)
+            .filter(|entity_id| !self.is_hidden(entity_id))
+            .collect();
+        if entity_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let url = format!("{}/api/history/period/{}", self.ha_config.base_url, start);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .query(&[
+                ("filter_entity_id", entity_ids.join(",").as_str()),
+                ("end_time", end),
+            ])
+            .query(&[("minimal_response", ""), ("no_attributes", "")])
+            .headers(self.ha_config.headers())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get history: {}",
+                response.status()
+            ));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// What Home Assistant records about a long-term statistic, `None` for
+    /// one it doesn't keep. Private entities lose their name while sharing.
+    async fn get_statistics_metadata(
+        &self,
+        statistic_id: &str,
+    ) -> anyhow::Result<Option<statistics::Metadata>> {
+        let result = self
+            .socket
+            .command(serde_json::json!({
+                "type": "recorder/get_statistics_metadata",
+                "statistic_ids": [statistic_id],
+            }))
+            .await?;
+        let metadata = statistics::Metadata::parse(&result, statistic_id);
+        if self.is_hidden(statistic_id) {
+            return Ok(metadata.map(|metadata| statistics::Metadata {
+                name: None,
+                ..metadata
+            }));
+        }
+        Ok(metadata)
+    }
+
+    /// Long-term statistics of `statistic_id` since `start`, a Home
+    /// Assistant timestamp, added up per `period`. Only the WebSocket API
+    /// serves them. Private entities have none while sharing.
+    async fn get_statistics(
+        &self,
+        statistic_id: &str,
+        start: &str,
+        period: statistics::Period,
+        stat: statistics::Stat,
+    ) -> anyhow::Result<Vec<statistics::Bucket>> {
+        if self.is_hidden(statistic_id) {
+            return Ok(Vec::new());
+        }
+        let result = self
+            .socket
+            .command(serde_json::json!({
+                "type": "recorder/statistics_during_period",
+                "start_time": start,
+                "statistic_ids": [statistic_id],
+                "period": period.name(),
+                "types": stat.types(),
+            }))
+            .await?;
+        Ok(statistics::parse_buckets(&result, statistic_id))
+    }
+
+    /// Events of a calendar entity between `start` and `end`, both Home
+    /// Assistant timestamps; only their times while sharing a private one.
+    /// Fetched over REST: the WebSocket API can only change events, not
+    /// list them.
+    async fn get_calendar_events(
+        &self,
+        entity_id: &str,
+        start: &str,
+        end: &str,
+    ) -> anyhow::Result<serde_json::Value> {
+        let url = format!("{}/api/calendars/{}", self.ha_config.base_url, entity_id);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .query(&[("start", start), ("end", end)])
+            .headers(self.ha_config.headers())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get events of {}: {}",
+                entity_id,
+                response.status()
+            ));
+        }
+
+        let events = response.json().await?;
+        if self.is_hidden(entity_id) {
+            return Ok(privacy::redact_events(&events));
+        }
+        Ok(events)
+    }
+
+    /// A sensor's numeric value at the start of the day, from its history.
+    async fn get_value_at_start_of_today(&self, entity_id: &str) -> anyhow::Result<f64> {
+        let start = self.get_start_of_today().await?;
+        let url = format!("{}/api/history/period/{}", self.ha_config.base_url, start);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .query(&[("filter_entity_id", entity_id)])
+            .query(&[("minimal_response", ""), ("no_attributes", "")])
+            .headers(self.ha_config.headers())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get history: {}",
+                response.status()
+            ));
+        }
+
+        // One list per entity, starting with the state at `start`
+        let history: Vec<Vec<serde_json::Value>> = response.json().await?;
+        history
+            .first()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.get("state")?.as_str()?.parse::<f64>().ok())
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No history for {} since {}", entity_id, start))
+    }
+
+    /// Attach today's usage and the daily budget to meters listed in
+    /// `METER_BUDGETS`. Meters that count up forever are measured against
+    /// their value at midnight.
+    async fn with_meter_budget(&self, mut entity: EntityState) -> EntityState {
+        let Some(&budget) = self.meter_budgets.get(&entity.entity_id) else {
+            return entity;
+        };
+        let Ok(value) = entity.state.parse::<f64>() else {
+            return entity;
+        };
+
+        let usage = if meters::is_running_total(&entity.attributes) {
+            match self.get_value_at_start_of_today(&entity.entity_id).await {
+                Ok(start) => (value - start).max(0.0),
+                Err(e) => {
+                    warn!("Failed to get today's usage of {}: {}", entity.entity_id, e);
+                    return entity;
+                }
+            }
+        } else {
+            value
+        };
+
+        if let Some(attributes) = entity.attributes.as_object_mut() {
+            attributes.insert(meters::USAGE_ATTRIBUTE.to_string(), usage.into());
+            attributes.insert(meters::BUDGET_ATTRIBUTE.to_string(), budget.into());
+        }
+        entity
+    }
+
+    /// Attach a sparkline of the last `SPARKLINE_SPAN` to every sensor with
+    /// a numeric state, from one history request for them all. Without
+    /// history the rows are drawn as before.
+    async fn with_sparklines(&self, mut sensors: Vec<EntityState>) -> Vec<EntityState> {
+        let entity_ids: Vec<String> = sensors
+            .iter()
+            .filter(|sensor| sensor.state.parse::<f64>().is_ok())
+            .map(|sensor| sensor.entity_id.clone())
+            .collect();
+        if entity_ids.is_empty() {
+            return sensors;
+        }
+
+        let end = SystemTime::now();
+        let start = end - history::SPARKLINE_SPAN;
+        let recorded = match self
+            .get_history(
+                &entity_ids,
+                &timestamps::format_utc(start),
+                &timestamps::format_utc(end),
+            )
+            .await
+        {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                warn!("Failed to get history for sparklines: {}", e);
+                return sensors;
+            }
+        };
+        let series = report::parse_history(&recorded, start);
+
+        for sensor in &mut sensors {
+            let Some(points) = series
+                .get(&sensor.entity_id)
+                .and_then(|samples| history::sparkline(samples, start, end))
+            else {
+                continue;
+            };
+            if let Some(attributes) = sensor.attributes.as_object_mut() {
+                attributes.insert(
+                    history::SPARKLINE_ATTRIBUTE.to_string(),
+                    serde_json::json!(points),
+                );
+            }
+        }
+        sensors
+    }
+
+    /// One row for an appliance's cycle: its state and the time left, the
+    /// progress as a gauge, and an attention state once it has finished,
+    /// until the door is opened. Only the operation state is required to be
+    /// available.
+    async fn get_appliance_state(&self, name: &str) -> anyhow::Result<EntityState> {
+        let appliance = self
+            .appliances
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown appliance '{}' (see APPLIANCES)", name))?;
+        let operation = self.get_entity_state(&appliance.state).await?;
+        let optional = |entity_id: &Option<String>| {
+            let entity_id = entity_id.clone();
+            async move {
+                let entity_id = entity_id?;
+                match self.get_entity_state(&entity_id).await {
+                    Ok(entity) => Some(entity),
+                    Err(e) => {
+                        warn!("Failed to get state for {}: {}", entity_id, e);
+                        None
+                    }
+                }
+            }
+        };
+        let remaining = optional(&appliance.remaining).await;
+        let progress = optional(&appliance.progress).await;
+        let door = optional(&appliance.door).await;
+
+        let phase = appliances::phase(&operation.state);
+        let label = appliances::state_label(&operation.state);
+        let active = matches!(
+            phase,
+            appliances::Phase::Running | appliances::Phase::Interrupted
+        );
+        let left = remaining
+            .filter(|_| active)
+            .and_then(|remaining| {
+                let unit = remaining
+                    .attributes
+                    .get("unit_of_measurement")
+                    .and_then(|v| v.as_str());
+                appliances::remaining_secs(&remaining.state, unit, SystemTime::now())
+            })
+            .filter(|&secs| secs > 0)
+            .map(appliances::format_remaining);
+        let state = match (phase, left) {
+            (appliances::Phase::Running, Some(left)) => format!("{} left", left),
+            (_, Some(left)) => format!("{}, {} left", label, left),
+            (_, None) if operation.state == "unavailable" => operation.state.clone(),
+            (_, None) => label,
+        };
+
+        let percent = match phase {
+            appliances::Phase::Finished => Some(100.0),
+            _ if active => progress.and_then(|progress| progress.state.parse::<f64>().ok()),
+            _ => None,
+        };
+        // The door opening since the cycle finished means it's been emptied
+        let finished_at = operation
+            .last_changed
+            .as_deref()
+            .and_then(timestamps::parse);
+        let emptied = door.is_some_and(|door| {
+            appliances::door_open(&door.state)
+                || door
+                    .last_changed
+                    .as_deref()
+                    .and_then(timestamps::parse)
+                    .zip(finished_at)
+                    .is_some_and(|(opened, finished)| opened > finished)
+        });
+
+        let mut attributes = serde_json::json!({
+            "friendly_name": appliance
+                .label
+                .clone()
+                .unwrap_or_else(|| appliances::name_label(name)),
+        });
+        if let Some(percent) = percent {
+            attributes[appliances::PROGRESS_ATTRIBUTE] = percent.into();
+        }
+        if phase == appliances::Phase::Finished && !emptied {
+            attributes[attention::ATTRIBUTE] = true.into();
+        }
+        Ok(EntityState {
+            entity_id: format!("{}{}", appliances::ID_PREFIX, name),
+            state,
+            attributes,
+            last_changed: operation.last_changed,
+        })
+    }
+
+    /// Attach the time since the last entry to `CARE_LOG` entities kept as
+    /// times (counters show their count), and mark them once their interval
+    /// has passed.
+    fn with_care_log(&self, mut entity: EntityState) -> EntityState {
+        let Some(&interval) = self.care_log.get(&entity.entity_id) else {
+            return entity;
+        };
+        let Some(last) = care::last_entry(
+            &entity.entity_id,
+            &entity.state,
+            &entity.attributes,
+            entity.last_changed.as_deref(),
+        ) else {
+            return entity;
+        };
+
+        let since = SystemTime::now().duration_since(last).unwrap_or_default();
+        if let Some(attributes) = entity.attributes.as_object_mut() {
+            if !entity.entity_id.starts_with("counter.") {
+                attributes.insert(care::SINCE_ATTRIBUTE.to_string(), since.as_secs().into());
+            }
+            if interval.is_some_and(|interval| since > interval) {
+                attributes.insert(attention::ATTRIBUTE.to_string(), true.into());
+            }
+        }
+        entity
+    }
+
+    /// Attach the duration style configured in `DURATION_FORMATS`.
+    fn with_duration_format(&self, mut entity: EntityState) -> EntityState {
+        if let Some(format) = self.duration_formats.get(&entity.entity_id)
+            && let Some(attributes) = entity.attributes.as_object_mut()
+        {
+            attributes.insert(
+                durations::FORMAT_ATTRIBUTE.to_string(),
+                format.name().into(),
+            );
+        }
+        entity
+    }
+
+    /// Attach the `CURRENCY_LOCALE` to monetary sensors, and `CURRENCY` as
+    /// the unit of those that report none.
+    fn with_currency(&self, mut entity: EntityState) -> EntityState {
+        if !is_monetary(&entity) {
+            return entity;
+        }
+        if let Some(attributes) = entity.attributes.as_object_mut() {
+            if let Some(code) = &self.currency
+                && attributes
+                    .get("unit_of_measurement")
+                    .and_then(|v| v.as_str())
+                    .is_none_or(str::is_empty)
+            {
+                attributes.insert("unit_of_measurement".to_string(), code.clone().into());
+            }
+            attributes.insert(
+                currency::LOCALE_ATTRIBUTE.to_string(),
+                self.currency_locale.name().into(),
+            );
+        }
+        entity
+    }
+
+    /// Mark entities in one of the `ATTENTION_STATES`.
+    fn with_attention(&self, mut entity: EntityState) -> EntityState {
+        let device_class = entity
+            .attributes
+            .get("device_class")
+            .and_then(|v| v.as_str());
+        if self
+            .attention_states
+            .matches(&entity.entity_id, &entity.state, device_class)
+            && let Some(attributes) = entity.attributes.as_object_mut()
+        {
+            attributes.insert(attention::ATTRIBUTE.to_string(), true.into());
+        }
+        entity
+    }
+
+    /// Attach everything configured about how an entity is shown: its
+    /// duration style, currency and attention state.
+    fn with_display_settings(&self, entity: EntityState) -> EntityState {
+        self.with_attention(self.with_currency(self.with_duration_format(entity)))
+    }
+
+    /// Fetch each sensor in order. Sensors that can't be fetched come back as
+    /// `unavailable` so a single failure doesn't break the whole render.
+    /// `zone.*` stands for a single row with the occupancy of every zone,
+    /// `appliance.<name>` for the cycle of one of the `APPLIANCES`, and
+    /// `clock.world` for the time in each of the `WORLD_CLOCKS`.
+    async fn get_sensor_states(&self, sensor_ids: &[String]) -> Vec<EntityState> {
+        let mut sensor_data = Vec::new();
+        for sensor_id in sensor_ids {
+            let result = if sensor_id == ZONE_OCCUPANCY_ID {
+                self.get_all_states()
+                    .await
+                    .map(|states| zone_occupancy(&states))
+            } else if sensor_id == WORLD_CLOCK_ID {
+                world_clock(&self.world_clocks, SystemTime::now())
+            } else if let Some(name) = sensor_id.strip_prefix(appliances::ID_PREFIX) {
+                self.get_appliance_state(name).await
+            } else {
+                self.get_entity_state(sensor_id).await
+            };
+            match result {
+                Ok(entity_state) => {
+                    let entity_state = self.with_meter_budget(entity_state).await;
+                    let entity_state = self.with_care_log(entity_state);
+                    sensor_data.push(self.with_display_settings(entity_state));
+                }
+                Err(e) => {
+                    warn!("Failed to get state for sensor {}: {}", sensor_id, e);
+                    sensor_data.push(EntityState {
+                        entity_id: sensor_id.clone(),
+                        state: "unavailable".to_string(),
+                        attributes: serde_json::Value::Object(serde_json::Map::new()),
+                        last_changed: None,
+                    });
+                }
+            }
+        }
+        sensor_data
+    }
+
+    async fn fetch_image_from_url(
+        &self,
+        image_url: &str,
+    ) -> anyhow::Result<(bytes::Bytes, String)> {
+        // HA_HEADERS only go to Home Assistant itself
+        let request = self.http_client.get(image_url);
+        let request = if image_url.starts_with(&self.ha_config.base_url) {
+            request.headers(self.ha_config.headers())
+        } else {
+            request.header("Authorization", format!("Bearer {}", self.ha_config.token))
+        };
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to fetch image: {}",
+                response.status()
+            ));
+        }
+
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_string();
+
+        let bytes = response.bytes().await?;
+        Ok((bytes, content_type))
+    }
+
+    /// A camera's current image, or a placeholder while sharing a private
+    /// one.
+    async fn get_camera_snapshot(&self, entity_id: &str) -> anyhow::Result<(bytes::Bytes, String)> {
+        if self.is_hidden(entity_id) {
+            return Ok((private_placeholder()?, "image/png".to_string()));
+        }
+
+        let url = format!("{}/api/camera_proxy/{}", self.ha_config.base_url, entity_id);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .headers(self.ha_config.headers())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get camera snapshot: {}",
+                response.status()
+            ));
+        }
+
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_string();
+
+        let bytes = response.bytes().await?;
+        Ok((bytes, content_type))
+    }
+}
+
+/// Sensor ID that expands to the zone occupancy row
+const ZONE_OCCUPANCY_ID: &str = "zone.*";
+
+/// People in a zone: the zone's own count (its state), or for Home Assistant
+/// versions without one, the persons whose state names the zone.
+fn zone_people(zone: &EntityState, states: &[EntityState]) -> usize {
+    if let Ok(count) = zone.state.parse() {
+        return count;
+    }
+
+    let name = if zone.entity_id == "zone.home" {
+        "home"
+    } else {
+        zone.attributes
+            .get("friendly_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&zone.entity_id)
+    };
+    states
+        .iter()
+        .filter(|entity| entity.entity_id.starts_with("person.") && entity.state == name)
+        .count()
+}
+
+/// One row summing up where everyone is, e.g. `Home: 3, Work: 1`. Home is
+/// always listed, other zones only while someone is in them.
+fn zone_occupancy(states: &[EntityState]) -> EntityState {
+    let mut zones: Vec<&EntityState> = states
+        .iter()
+        .filter(|entity| entity.entity_id.starts_with("zone."))
+        .collect();
+    zones.sort_by_key(|zone| (zone.entity_id != "zone.home", zone.entity_id.as_str()));
+
+    let counts: Vec<String> = zones
+        .into_iter()
+        .map(|zone| (zone, zone_people(zone, states)))
+        .filter(|(zone, count)| *count > 0 || zone.entity_id == "zone.home")
+        .map(|(zone, count)| {
+            let name = zone
+                .attributes
+                .get("friendly_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&zone.entity_id);
+            format!("{}: {}", name, count)
+        })
+        .collect();
+
+    EntityState {
+        entity_id: ZONE_OCCUPANCY_ID.to_string(),
+        state: if counts.is_empty() {
+            "unknown".to_string()
+        } else {
+            counts.join(", ")
+        },
+        attributes: serde_json::json!({ "friendly_name": "Zones" }),
+        last_changed: None,
+    }
+}
+
+/// Sensor ID that expands to the world clock row
+const WORLD_CLOCK_ID: &str = "clock.world";
+
+/// One row with the time in each of the `WORLD_CLOCKS`, e.g.
+/// `NYC 08:12 • London 13:12 • Tokyo 21:12`.
+fn world_clock(
+    clocks: &[(String, jiff::tz::TimeZone)],
+    now: SystemTime,
+) -> anyhow::Result<EntityState> {
+    if clocks.is_empty() {
+        return Err(anyhow::anyhow!("No WORLD_CLOCKS configured"));
+    }
+    let times: Vec<String> = clocks
+        .iter()
+        .map(|(label, zone)| {
+            let time = timestamps::format_clock(now, zones::offset_at(zone, now));
+            format!("{} {}", label, time)
+        })
+        .collect();
+
+    Ok(EntityState {
+        entity_id: WORLD_CLOCK_ID.to_string(),
+        state: times.join(" • "),
+        attributes: serde_json::json!({ "friendly_name": "World clock" }),
+        last_changed: None,
+    })
+}
+
+/// Run a render path (e.g. a dashboard's `/trmnl?...` URL) through the router
+/// without going over the network.
+async fn render_path(state: &Arc<AppState>, path: &str) -> Result<Response, AppError> {
+    let request = Request::get(path)
+        .body(Body::empty())
+        .map_err(|e| AppError::Internal(format!("Invalid render path '{}': {}", path, e)))?;
+
+    match app(state.clone()).oneshot(request).await {
+        Ok(response) => Ok(response),
+        Err(never) => match never {},
+    }
+}
+
+async fn render_dashboard(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let dashboard = find_dashboard(&state, &name)?;
+
+    let mut response = match current_render(&state, dashboard).await? {
+        Ok(rendered) if rendered.matches(&headers) => rendered.not_modified(),
+        Ok(rendered) => rendered.into_response(),
+        Err(response) => response,
+    };
+
+    let link = format!(
+        "</dashboards/{}/summary>; rel=\"alternate\"; type=\"application/json\"",
+        dashboard.name
+    );
+    if let Ok(link) = HeaderValue::from_str(&link) {
+        response.headers_mut().insert(header::LINK, link);
+    }
+    Ok(response)
+}
+
+/// A dashboard rendered for posting publicly: the entities `ENTITIES_FILE`
+/// marks private keep their place under a generic name with their values,
+/// history and calendar events hidden and cameras replaced by a
+/// placeholder. Never cached, so a shared render can't end up on a device.
+async fn share_dashboard(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let dashboard = find_dashboard(&state, &name)?;
+
+    let rendered = SHARING
+        .scope((), render_cached(&state, dashboard, None))
+        .await?;
+    Ok(match rendered {
+        Ok(rendered) => rendered.into_response(),
+        Err(response) => response,
+    })
+}
+
+/// Checksum of a dashboard's current render, for firmware that polls it
+/// and only downloads the screen when it changes. Answers `304` when
+/// `If-None-Match` already names it; a failed render is passed through.
+async fn dashboard_checksum(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let dashboard = find_dashboard(&state, &name)?;
+    let rendered = match current_render(&state, dashboard).await? {
+        Ok(rendered) => rendered,
+        Err(response) => return Ok(response),
+    };
+
+    if rendered.matches(&headers) {
+        return Ok(rendered.not_modified());
+    }
+
+    let report = serde_json::json!({
+        "name": dashboard.name,
+        "checksum": rendered.checksum(),
+        "size": rendered.size(),
+    });
+    let json_response = serde_json::to_string(&report)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::ETAG, format!("\"{}\"", rendered.checksum())),
+            (header::CONTENT_TYPE, "application/json".to_string()),
+        ],
+        json_response,
+    )
+        .into_response())
+}
+
+/// One panel's part of a tiled dashboard (`DASHBOARD_TILES`), cut from a
+/// single render of the whole installation so every panel shows the same
+/// moment. The part keeps the render's format and bit depth.
+async fn render_dashboard_tile(
+    State(state): State<Arc<AppState>>,
+    Path((name, index)): Path<(String, u32)>,
+) -> Result<Response, AppError> {
+    let dashboard = find_dashboard(&state, &name)?;
+    let tiles = dashboard
+        .tiles
+        .ok_or_else(|| AppError::NotFound(format!("Dashboard {} isn't tiled", name)))?;
+    if index >= tiles.count() {
+        return Err(AppError::NotFound(format!(
+            "Dashboard {} has tiles 0 to {}",
+            name,
+            tiles.count() - 1
+        )));
+    }
+
+    let rendered = match current_render(&state, dashboard).await? {
+        Ok(rendered) => rendered,
+        Err(response) => return Ok(response),
+    };
+    let (data, content_type) = cut_tile(&rendered, tiles, index, state.trmnl_layout)
+        .map_err(|e| AppError::Internal(format!("Dashboard {}: {}", name, e)))?;
+
+    Ok(create_image_response(data, content_type.to_string()))
+}
+
+/// Cut panel `index` out of a tiled dashboard's render: 1-bit BMPs stay
+/// BMPs, grayscale PNGs keep their bit depth, anything else becomes a
+/// color PNG.
+fn cut_tile(
+    rendered: &Rendered,
+    tiles: Tiles,
+    index: u32,
+    panel: (u32, u32),
+) -> anyhow::Result<(bytes::Bytes, &'static str)> {
+    let screen = image::load_from_memory(rendered.body())
+        .map_err(|_| anyhow::anyhow!("tiles need a PNG, BMP or JPEG render"))?;
+    let (x, y, width, height) = tiles.rect(index, panel, (screen.width(), screen.height()));
+    let tile = screen.crop_imm(x, y, width, height);
+
+    if rendered.content_type() == Some("image/bmp") {
+        return Ok((encode::bmp_1bit(&tile.to_luma8()).into(), "image/bmp"));
+    }
+
+    let gray_depth = png::Decoder::new(std::io::Cursor::new(rendered.body()))
+        .read_info()
+        .ok()
+        .map(|reader| reader.info().clone())
+        .filter(|info| info.color_type == png::ColorType::Grayscale)
+        .map(|info| info.bit_depth as u8)
+        .filter(|&depth| depth <= 8);
+    let data = match gray_depth {
+        Some(depth) => encode::png_gray(&tile.to_luma8(), depth)?,
+        None => {
+            let mut buffer = Vec::new();
+            tile.to_rgb8().write_to(
+                &mut std::io::Cursor::new(&mut buffer),
+                image::ImageFormat::Png,
+            )?;
+            buffer
+        }
+    };
+    Ok((data.into(), "image/png"))
+}
+
+/// Encodings compared by `/dashboards/{name}/encodings`, as the query
+/// parameters that select them on a TRMNL endpoint
+const COMPARED_ENCODINGS: &[(&str, BitmapFormat, Option<FramebufferCompression>)] = &[
+    ("format=png&depth=1", BitmapFormat::Png { depth: 1 }, None),
+    ("format=png&depth=2", BitmapFormat::Png { depth: 2 }, None),
+    ("format=png&depth=4", BitmapFormat::Png { depth: 4 }, None),
+    ("format=png&depth=8", BitmapFormat::Png { depth: 8 }, None),
+    ("format=bmp", BitmapFormat::Bmp, None),
+    ("format=xbm", BitmapFormat::Xbm, None),
+    (
+        "format=raw",
+        BitmapFormat::Raw {
+            depth: 1,
+            lsb_first: false,
+        },
+        None,
+    ),
+    (
+        "format=raw&compress=zlib",
+        BitmapFormat::Raw {
+            depth: 1,
+            lsb_first: false,
+        },
+        Some(FramebufferCompression::Zlib),
+    ),
+];
+
+#[derive(Serialize)]
+struct EncodingResult {
+    query: &'static str,
+    content_type: &'static str,
+    bytes: usize,
+    encode_us: u128,
+}
+
+/// Debug report of what a dashboard's screen costs in every encoding the
+/// TRMNL endpoints offer: the render is reduced to each format's grays and
+/// encoded, with the size and encode time of each, smallest first.
+async fn dashboard_encodings(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let dashboard = find_dashboard(&state, &name)?;
+
+    let render_started = Instant::now();
+    let rendered = match current_render(&state, dashboard).await? {
+        Ok(rendered) => rendered,
+        Err(response) => return Ok(response),
+    };
+    let render_ms = render_started.elapsed().as_millis();
+    let screen = image::load_from_memory(rendered.body())
+        .map_err(|_| {
+            AppError::BadRequest(format!(
+                "Dashboard {} doesn't render a PNG, BMP or JPEG image",
+                name
+            ))
+        })?
+        .to_luma8();
+
+    let mut encodings = Vec::new();
+    for &(query, format, compression) in COMPARED_ENCODINGS {
+        let quantized = Dither::None.quantize(&screen, format.levels());
+        let started = Instant::now();
+        let mut data = format
+            .encode(&quantized)
+            .map_err(|e| AppError::Internal(format!("Failed to encode {}: {}", query, e)))?;
+        if let Some(compression) = compression {
+            data = compression
+                .compress(&data)
+                .map_err(|e| AppError::Internal(format!("Failed to encode {}: {}", query, e)))?;
+        }
+        encodings.push(EncodingResult {
+            query,
+            content_type: format.content_type(),
+            bytes: data.len(),
+            encode_us: started.elapsed().as_micros(),
+        });
+    }
+    encodings.sort_by_key(|encoding| encoding.bytes);
+
+    let report = serde_json::json!({
+        "name": dashboard.name,
+        "width": screen.width(),
+        "height": screen.height(),
+        "rendered": {
+            "content_type": rendered.content_type(),
+            "bytes": rendered.size(),
+            "render_ms": render_ms,
+        },
+        "encodings": encodings,
+    });
+    let json_response = serde_json::to_string_pretty(&report)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// Dashboards cycled through in one `/cycle` GIF at most
+const MAX_CYCLE_SCREENS: usize = 10;
+/// Longest `/cycle` interval in seconds (GIF delays top out at 655s)
+const MAX_CYCLE_INTERVAL: u32 = 600;
+
+/// Animated GIF showing several dashboards in turn, for signage players or
+/// to preview a TRMNL rotation. Frames are the size of the first screen;
+/// other sizes are fit inside it on white.
+async fn render_cycle(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CycleQuery>,
+) -> Result<Response, AppError> {
+    if let Some(format) = params.format.as_deref()
+        && !format.trim().eq_ignore_ascii_case("gif")
+    {
+        return Err(AppError::BadRequest(format!(
+            "Unknown format '{}'. /cycle only returns gif",
+            format
+        )));
+    }
+
+    let names: Vec<&str> = params
+        .screens
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Err(AppError::BadRequest(
+            "No screens provided. Use ?screens=dashboard1,dashboard2".to_string(),
+        ));
+    }
+    if names.len() > MAX_CYCLE_SCREENS {
+        return Err(AppError::BadRequest(format!(
+            "Too many screens (max {} allowed)",
+            MAX_CYCLE_SCREENS
+        )));
+    }
+
+    let interval = params.interval.unwrap_or(10);
+    if !(1..=MAX_CYCLE_INTERVAL).contains(&interval) {
+        return Err(AppError::BadRequest(format!(
+            "interval must be between 1 and {} seconds, got {}",
+            MAX_CYCLE_INTERVAL, interval
+        )));
+    }
+
+    info!("Rendering cycle of {} screens", names.len());
+
+    let mut screens = Vec::new();
+    for name in names {
+        let dashboard = find_dashboard(&state, name)?;
+        let rendered = match current_render(&state, dashboard).await? {
+            Ok(rendered) => rendered,
+            Err(response) => return Ok(response),
+        };
+        let screen = image::load_from_memory(rendered.body()).map_err(|_| {
+            AppError::BadRequest(format!(
+                "Dashboard {} doesn't render a PNG, BMP or JPEG image",
+                name
+            ))
+        })?;
+        screens.push(screen);
+    }
+
+    let image_data = generate_cycle_animation(&screens, interval)
+        .map_err(|e| AppError::Internal(format!("Failed to generate animation: {}", e)))?;
+
+    Ok(create_image_response(image_data, "image/gif".to_string()))
+}
+
+fn generate_cycle_animation(
+    screens: &[image::DynamicImage],
+    interval: u32,
+) -> anyhow::Result<bytes::Bytes> {
+    use image::{
+        Delay, Frame,
+        codecs::gif::{GifEncoder, Repeat},
+    };
+
+    let (width, height) = (screens[0].width(), screens[0].height());
+    let fit = Resize::new(
+        Some(width),
+        Some(height),
+        FitMode::Fit,
+        image::Rgba([255, 255, 255, 255]),
+    )
+    .map_err(|e| anyhow::anyhow!(e))?;
+    let delay = Delay::from_numer_denom_ms(interval * 1000, 1);
+
+    let frames = screens.iter().map(|screen| {
+        let screen = match &fit {
+            Some(fit) if screen.width() != width || screen.height() != height => fit.apply(screen),
+            _ => screen.clone(),
+        };
+        Frame::from_parts(screen.to_rgba8(), 0, 0, delay)
+    });
+
+    let mut buffer = Vec::new();
+    {
+        // Speed 10 keeps several full-size frames quick to quantize
+        let mut encoder = GifEncoder::new_with_speed(&mut buffer, 10);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| anyhow::anyhow!("Failed to encode GIF: {}", e))?;
+        encoder
+            .encode_frames(frames)
+            .map_err(|e| anyhow::anyhow!("Failed to encode GIF: {}", e))?;
+    }
+
+    Ok(bytes::Bytes::from(buffer))
+}
+
+fn find_dashboard<'a>(state: &'a AppState, name: &str) -> Result<&'a Dashboard, AppError> {
+    state
+        .dashboards
+        .iter()
+        .find(|d| d.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("Unknown dashboard: {}", name)))
+}
+
+/// The cached render of a dashboard, or a fresh one; a failed render comes
+/// back as the render endpoint's response.
+async fn current_render(
+    state: &Arc<AppState>,
+    dashboard: &Dashboard,
+) -> Result<Result<Rendered, Response>, AppError> {
+    // A low-battery badge is for one device only, so its render is neither
+    // taken from the cache nor stored in it
+    if LOW_BATTERY.try_with(|_| ()).is_ok() {
+        return render_cached(state, dashboard, None).await;
+    }
+
+    match state.dashboard_cache.get(&dashboard.name) {
+        Ok(rendered) => {
+            debug!("Serving cached dashboard {}", dashboard.name);
+            Ok(Ok(rendered))
+        }
+        Err(generation) => {
+            info!(
+                "Rendering dashboard {} ({})",
+                dashboard.name, dashboard.path
+            );
+            render_cached(state, dashboard, Some(generation)).await
+        }
+    }
+}
+
+/// Short text version of a dashboard ("21.4° inside, 3 lights on") for
+/// voice assistants and text-only device modes, with the entities it names.
+/// Uses the dashboard's `DASHBOARD_SUMMARIES` template when it has one,
+/// otherwise lists every entity as `Name: value`.
+async fn dashboard_summary(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(params): Query<DashboardSummaryQuery>,
+) -> Result<Response, AppError> {
+    let dashboard = find_dashboard(&state, &name)?;
+
+    let text = match params.format.as_deref() {
+        None | Some("json") => false,
+        Some("text") => true,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown format '{}' (expected json or text)",
+                other
+            )));
+        }
+    };
+
+    let report = dashboard_report(&state, dashboard).await?;
+
+    if text {
+        let summary = report["summary"].as_str().unwrap_or("").to_string();
+        return Ok((
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            summary,
+        )
+            .into_response());
+    }
+
+    let json_response = serde_json::to_string_pretty(&report)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// `name`, `path`, `summary` and `entities` of a dashboard's summary.
+async fn dashboard_report(
+    state: &AppState,
+    dashboard: &Dashboard,
+) -> Result<serde_json::Value, AppError> {
+    let sensor_data = state.get_sensor_states(&dashboard.entities()).await;
+    let entities: Vec<(&EntityState, &str, String)> = sensor_data
+        .iter()
+        .map(|sensor| {
+            let name = sensor
+                .attributes
+                .get("friendly_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&sensor.entity_id);
+            (
+                sensor,
+                name,
+                format_sensor_value(sensor, SiPrefixes::default()),
+            )
+        })
+        .collect();
+
+    let summary = match &dashboard.summary {
+        Some(template) => state
+            .render_template(template, serde_json::json!({}))
+            .await
+            .map_err(|e| {
+                AppError::Internal(format!(
+                    "Failed to render summary for dashboard {}: {}",
+                    dashboard.name, e
+                ))
+            })?
+            .trim()
+            .to_string(),
+        None => entities
+            .iter()
+            .map(|(_, name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    let entities: Vec<serde_json::Value> = entities
+        .iter()
+        .map(|(sensor, name, value)| {
+            serde_json::json!({
+                "entity_id": sensor.entity_id,
+                "name": name,
+                "state": sensor.state,
+                "value": value,
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({
+        "name": dashboard.name,
+        "path": dashboard.path,
+        "summary": summary,
+        "entities": entities,
+    }))
+}
+
+/// Render a dashboard, keeping a successful render under `generation` when
+/// the cache is on.
+async fn render_cached(
+    state: &Arc<AppState>,
+    dashboard: &Dashboard,
+    generation: Option<u64>,
+) -> Result<Result<Rendered, Response>, AppError> {
+    let response = match dashboard.tiles {
+        Some(tiles) => {
+            TILED_LAYOUT
+                .scope(
+                    tiles.layout(state.trmnl_layout),
+                    render_path(state, &dashboard.path),
+                )
+                .await?
+        }
+        None => render_path(state, &dashboard.path).await?,
+    };
+    if response.extensions().get::<UnknownEndpoint>().is_some() {
+        warn!(
+            "Dashboard {} names an unknown endpoint ({}), serving a placeholder",
+            dashboard.name, dashboard.path
+        );
+        let layout = match dashboard.tiles {
+            Some(tiles) => tiles.layout(state.trmnl_layout),
+            None => state.trmnl_layout,
+        };
+        let image_data = unsupported_placeholder(dashboard, layout)
+            .map_err(|e| AppError::Internal(format!("Failed to generate placeholder: {}", e)))?;
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        return Ok(Ok(state.dashboard_cache.store(
+            &dashboard.name,
+            generation,
+            headers,
+            image_data,
+        )));
+    }
+    if !response.status().is_success() {
+        return Ok(Err(response));
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read dashboard render: {}", e)))?;
+    Ok(Ok(state.dashboard_cache.store(
+        &dashboard.name,
+        generation,
+        parts.headers,
+        body,
+    )))
+}
+
+/// Marks the `404` of a path no route serves, as opposed to a route's own
+/// "not found" for an unknown entity or dashboard.
+#[derive(Clone, Copy, Debug)]
+struct UnknownEndpoint;
+
+async fn unknown_endpoint(uri: Uri) -> Response {
+    let mut response =
+        AppError::NotFound(format!("Unknown endpoint: {}", uri.path())).into_response();
+    response.extensions_mut().insert(UnknownEndpoint);
+    response
+}
+
+/// Whether a route serves `path`, found without rendering it: a method no
+/// route takes gets `405` from a known path and the fallback otherwise.
+async fn endpoint_exists(state: &Arc<AppState>, path: &str) -> bool {
+    let Ok(request) = Request::delete(path).body(Body::empty()) else {
+        return false;
+    };
+    match app(state.clone()).oneshot(request).await {
+        Ok(response) => response.extensions().get::<UnknownEndpoint>().is_none(),
+        Err(never) => match never {},
+    }
+}
+
+/// Stand-in for a dashboard whose path names an endpoint this version
+/// doesn't have, e.g. from a config written for a newer one, so the
+/// device shows what's wrong instead of the whole dashboard failing.
+fn unsupported_placeholder(
+    dashboard: &Dashboard,
+    layout: (u32, u32),
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = layout;
+    let mut image = GrayImage::from_pixel(width, height, Luma([255u8]));
+    draw_trmnl_header(&mut image, "UNSUPPORTED SCREEN", false);
+
+    // Dashed frame marking the cell the screen would have filled
+    let (left, top, right, bottom) = (40, 85, width - 41, height - 30);
+    for x in left..=right {
+        if (x / 8) % 2 == 0 {
+            for y in [top, top + 1, bottom - 1, bottom] {
+                image.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+    }
+    for y in top..=bottom {
+        if (y / 8) % 2 == 0 {
+            for x in [left, left + 1, right - 1, right] {
+                image.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+    }
+
+    let endpoint = dashboard.path.split('?').next().unwrap_or(&dashboard.path);
+    let version = format!("not supported by version {}", env!("CARGO_PKG_VERSION"));
+    let lines = [
+        (dashboard.name.as_str(), 3),
+        (endpoint, 2),
+        (version.as_str(), 2),
+    ];
+    let mut y = top + (bottom - top) / 2 - 40;
+    for (text, scale) in lines {
+        let text = truncate_to_width(text, right - left - 40, scale);
+        let x = (width - text_width(&text, scale)) / 2;
+        if scale == 3 {
+            draw_trmnl_text_bold(&mut image, x, y, &text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(&mut image, x, y, &text, Luma([0u8]), scale);
+        }
+        y += 10 * scale + 6;
+    }
+
+    TrmnlOutput {
+        layout,
+        dither: Dither::None,
+        stamp: None,
+        rotation: None,
+        invert: false,
+        post: PostProcess::NONE,
+        format: BitmapFormat::Png { depth: 1 },
+        canvas: None,
+    }
+    .finish(image)
+}
+
+/// Stand-in for a private camera's image in shared renders: a hatched
+/// frame labelled `PRIVATE`, at the 4:3 of most cameras.
+fn private_placeholder() -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = (640, 480);
+    let mut image = GrayImage::from_pixel(width, height, Luma([255u8]));
+    for y in 0..height {
+        for x in 0..width {
+            if (x + y) % 16 < 2 {
+                image.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+    }
+
+    let label = "PRIVATE";
+    let scale = 5;
+    let (box_width, box_height) = (text_width(label, scale) + 60, 10 * scale + 40);
+    let (left, top) = ((width - box_width) / 2, (height - box_height) / 2);
+    for y in top..top + box_height {
+        for x in left..left + box_width {
+            image.put_pixel(x, y, Luma([255u8]));
+        }
+    }
+    draw_trmnl_text_bold(&mut image, left + 30, top + 20, label, Luma([0u8]), scale);
+
+    Ok(bytes::Bytes::from(encode::png_gray(&image, 8)?))
+}
+
+/// Re-render cached dashboards as soon as a calendar, to-do list or
+/// automation they name changes in Home Assistant, rather than when their
+/// cached render expires.
+async fn watch_dashboard_entities(state: Arc<AppState>) {
+    let mut entity_ids: Vec<String> = state
+        .dashboards
+        .iter()
+        .flat_map(Dashboard::watched_entities)
+        .collect();
+    entity_ids.sort_unstable();
+    entity_ids.dedup();
+    if entity_ids.is_empty() {
+        return;
+    }
+
+    info!("Watching {} for dashboard changes", entity_ids.join(", "));
+    let mut events = state.socket.subscribe(serde_json::json!({
+        "type": "subscribe_entities",
+        "entity_ids": entity_ids,
+    }));
+
+    let mut subscribed = false;
+    while let Some(event) = events.recv().await {
+        // `a` lists every entity each time the subscription starts, `c` and
+        // `r` carry changed and removed ones
+        let changed: Vec<String> = if let Some(added) = event.get("a").and_then(|a| a.as_object()) {
+            if !std::mem::replace(&mut subscribed, true) {
+                continue;
+            }
+            // Renewed after a reconnect; anything may have changed meanwhile
+            added.keys().cloned().collect()
+        } else {
+            let changes = event
+                .get("c")
+                .and_then(|c| c.as_object())
+                .into_iter()
+                .flat_map(|c| c.keys().cloned());
+            let removals = event
+                .get("r")
+                .and_then(|r| r.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|id| id.as_str().map(str::to_string));
+            changes.chain(removals).collect()
+        };
+
+        for dashboard in &state.dashboards {
+            let watched = dashboard.watched_entities();
+            let Some(entity_id) = changed.iter().find(|id| watched.contains(id)) else {
+                continue;
+            };
+
+            info!(
+                "{} changed, re-rendering dashboard {}",
+                entity_id, dashboard.name
+            );
+            let generation = state.dashboard_cache.invalidate(&dashboard.name);
+            let state = state.clone();
+            let dashboard = dashboard.clone();
+            tokio::spawn(async move {
+                match render_cached(&state, &dashboard, Some(generation)).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(response)) => warn!(
+                        "Re-rendering dashboard {} failed with {}",
+                        dashboard.name,
+                        response.status()
+                    ),
+                    Err(e) => warn!("Re-rendering dashboard {} failed: {:?}", dashboard.name, e),
+                }
+            });
+        }
+    }
+}
+
+/// Push each dashboard in `TRMNL_WEBHOOKS` to its webhook every
+/// `TRMNL_WEBHOOK_INTERVAL`, skipping pushes that would send the same
+/// screen or values again (TRMNL limits how often a plugin can be updated).
+async fn push_webhooks(state: Arc<AppState>) {
+    info!(
+        "Pushing {} dashboards to TRMNL webhooks every {}s",
+        state.webhooks.len(),
+        state.webhook_interval.as_secs()
+    );
+    let mut interval = tokio::time::interval(state.webhook_interval);
+    let mut pushed: HashMap<String, String> = HashMap::new();
+    loop {
+        interval.tick().await;
+        for webhook in &state.webhooks {
+            let (variables, fingerprint) = match webhook_variables(&state, webhook).await {
+                Ok(variables) => variables,
+                Err(e) => {
+                    warn!(
+                        "Failed to render dashboard {} for its webhook: {:?}",
+                        webhook.dashboard, e
+                    );
+                    continue;
+                }
+            };
+            if pushed.get(&webhook.url) == Some(&fingerprint) {
+                debug!("Dashboard {} unchanged, not pushed", webhook.dashboard);
+                continue;
+            }
+
+            let result = state
+                .http_client
+                .post(&webhook.url)
+                .json(&webhooks::body(variables))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => {
+                    info!("Pushed dashboard {} to its webhook", webhook.dashboard);
+                    pushed.insert(webhook.url.clone(), fingerprint);
+                }
+                Err(e) => warn!(
+                    "Failed to push dashboard {} to its webhook: {}",
+                    webhook.dashboard, e
+                ),
+            }
+        }
+    }
+}
+
+/// Merge variables of a webhook push, with a fingerprint that changes
+/// whenever they do (other than the render time).
+async fn webhook_variables(
+    state: &Arc<AppState>,
+    webhook: &webhooks::Webhook,
+) -> Result<(serde_json::Value, String), AppError> {
+    let dashboard = find_dashboard(state, &webhook.dashboard)?;
+    match webhook.payload {
+        webhooks::Payload::Image => {
+            let rendered = match current_render(state, dashboard).await? {
+                Ok(rendered) => rendered,
+                Err(response) => {
+                    return Err(AppError::Internal(format!(
+                        "render failed with {}",
+                        response.status()
+                    )));
+                }
+            };
+            let content_type = rendered.content_type().unwrap_or("image/png");
+            Ok((
+                image_envelope(rendered.body(), content_type),
+                rendered.checksum().to_string(),
+            ))
+        }
+        webhooks::Payload::Merge => {
+            let report = dashboard_report(state, dashboard).await?;
+            let fingerprint = report.to_string();
+            Ok((report, fingerprint))
+        }
+    }
+}
+
+/// Dashboards rendered at the same time by `/admin/warm`
+const WARM_CONCURRENCY: usize = 4;
+
+#[derive(Serialize)]
+struct WarmResult {
+    name: String,
+    path: String,
+    status: u16,
+    duration_ms: u128,
+    bytes: usize,
+    error: Option<String>,
+}
+
+/// Render every configured dashboard once, e.g. from a deploy script, and
+/// report how long each took. Responds with 500 if any render failed.
+async fn warm_dashboards(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    info!("Warming {} dashboards", state.dashboards.len());
+
+    let started = Instant::now();
+    let semaphore = Arc::new(Semaphore::new(WARM_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+
+    for (index, dashboard) in state.dashboards.iter().cloned().enumerate() {
+        let state = state.clone();
+        let semaphore = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire().await;
+            let render_started = Instant::now();
+
+            let generation = state.dashboard_cache.invalidate(&dashboard.name);
+            let (status, bytes, error) =
+                match render_cached(&state, &dashboard, Some(generation)).await {
+                    Ok(Ok(rendered)) => (StatusCode::OK, rendered.size(), None),
+                    Ok(Err(response)) => {
+                        let status = response.status();
+                        match axum::body::to_bytes(response.into_body(), usize::MAX).await {
+                            Ok(body) => (
+                                status,
+                                body.len(),
+                                Some(String::from_utf8_lossy(&body).into_owned()),
+                            ),
+                            Err(e) => (status, 0, Some(format!("Failed to read body: {}", e))),
+                        }
+                    }
+                    Err(e) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        0,
+                        Some(format!("{:?}", e)),
+                    ),
+                };
+
+            if let Some(error) = &error {
+                warn!("Warming dashboard {} failed: {}", dashboard.name, error);
+            }
+
+            (
+                index,
+                WarmResult {
+                    name: dashboard.name,
+                    path: dashboard.path,
+                    status: status.as_u16(),
+                    duration_ms: render_started.elapsed().as_millis(),
+                    bytes,
+                    error,
+                },
+            )
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let result = joined.map_err(|e| AppError::Internal(format!("Warm task failed: {}", e)))?;
+        results.push(result);
+    }
+    results.sort_by_key(|(index, _)| *index);
+    let results: Vec<WarmResult> = results.into_iter().map(|(_, result)| result).collect();
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    let report = serde_json::json!({
+        "total_ms": started.elapsed().as_millis(),
+        "failed": failed,
+        "dashboards": results,
+    });
+
+    let json_response = serde_json::to_string_pretty(&report)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    let status = if failed == 0 {
+        StatusCode::OK
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    Ok((
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+#[derive(Serialize)]
+struct ValidationResult {
+    name: String,
+    path: String,
+    /// Whether this version has the endpoint the path names; if not, the
+    /// dashboard is served as a placeholder
+    supported: bool,
+}
+
+/// Check the configured dashboards without rendering them, e.g. after
+/// syncing a config from a newer version. Responds with 422 if any names
+/// an endpoint this version doesn't have.
+async fn validate_dashboards(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let mut results = Vec::new();
+    for dashboard in &state.dashboards {
+        let supported = endpoint_exists(&state, &dashboard.path).await;
+        if !supported {
+            warn!(
+                "Dashboard {} names an unknown endpoint ({})",
+                dashboard.name, dashboard.path
+            );
+        }
+        results.push(ValidationResult {
+            name: dashboard.name.clone(),
+            path: dashboard.path.clone(),
+            supported,
+        });
+    }
+
+    let unsupported = results.iter().filter(|r| !r.supported).count();
+    let report = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "unsupported": unsupported,
+        "dashboards": results,
+    });
+
+    let json_response = serde_json::to_string_pretty(&report)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    let status = if unsupported == 0 {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+
+    Ok((
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// Record check-ins of identified devices and keep a copy of every image
+/// served to them so their history can be inspected later. Devices low on
+/// battery get their TRMNL screens badged.
+async fn track_devices(
+    State(state): State<Arc<AppState>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let device_id = devices::device_id(request.headers(), request.uri(), &state.presets);
+    let path = request.uri().to_string();
+
+    if let Some(device_id) = &device_id {
+        let telemetry = Telemetry::from_headers(request.headers());
+        if let Some(status) = state.devices.check_in(device_id, telemetry.clone()) {
+            let state = state.clone();
+            tokio::spawn(async move { state.notify_device_status(&status).await });
+        }
+        if state.device_sensors && (telemetry.battery_voltage.is_some() || telemetry.rssi.is_some())
+        {
+            let state = state.clone();
+            let device_id = device_id.clone();
+            tokio::spawn(async move { state.report_device_sensors(&device_id, telemetry).await });
+        }
+    }
+
+    let low_battery = device_id
+        .as_deref()
+        .and_then(|device_id| state.devices.battery_voltage(device_id))
+        .filter(|&volts| volts < state.low_battery_volts);
+    let response = match low_battery {
+        Some(volts) => LOW_BATTERY.scope(volts, next.run(request)).await,
+        None => next.run(request).await,
+    };
+
+    let Some(device_id) = device_id else {
+        return response;
+    };
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    if !response.status().is_success() || !content_type.starts_with("image/") {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(data) => {
+            state
+                .devices
+                .record_frame(&device_id, &path, &content_type, data.clone());
+            Response::from_parts(parts, Body::from(data))
+        }
+        Err(e) => {
+            AppError::Internal(format!("Failed to read response body: {}", e)).into_response()
+        }
+    }
+}
+
+/// Check what each request composes with the render watchdog. A
+/// suspicious render is logged and, with `RENDER_WATCHDOG=fallback`,
+/// replaced by the last good render of the same URL, marked with an
+/// `X-Render-Anomaly` header either way. Shared renders are never kept or
+/// used in place of one another.
+async fn watch_renders(
+    State(state): State<Arc<AppState>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let mode = state.watchdog.mode();
+    if mode == watchdog::Mode::Off {
+        return next.run(request).await;
+    }
+    let path = request.uri().to_string();
+    let (response, outcome) = watchdog::watch(next.run(request)).await;
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let kept = mode == watchdog::Mode::Fallback && SHARING.try_with(|_| ()).is_err();
+    if !response.status().is_success() {
+        return response;
+    }
+
+    match outcome {
+        watchdog::Outcome::Untouched => response,
+        watchdog::Outcome::Good if !kept => response,
+        watchdog::Outcome::Good => {
+            let (parts, body) = response.into_parts();
+            match axum::body::to_bytes(body, usize::MAX).await {
+                Ok(data) => {
+                    state
+                        .watchdog
+                        .keep(&path, &content_type, parts.headers.clone(), data.clone());
+                    Response::from_parts(parts, Body::from(data))
+                }
+                Err(e) => AppError::Internal(format!("Failed to read response body: {}", e))
+                    .into_response(),
+            }
+        }
+        watchdog::Outcome::Suspicious(anomaly) => {
+            let previous = kept
+                .then(|| state.watchdog.previous(&path, &content_type))
+                .flatten();
+            state.watchdog.record(&path, anomaly, previous.is_some());
+            if previous.is_some() {
+                warn!(
+                    "Render of {} looks broken ({}), serving its last good render",
+                    path,
+                    anomaly.describe()
+                );
+            } else {
+                warn!("Render of {} looks broken ({})", path, anomaly.describe());
+            }
+
+            let mut response = match previous {
+                Some((headers, body)) => (headers, body).into_response(),
+                None => response,
+            };
+            response.headers_mut().insert(
+                "x-render-anomaly",
+                HeaderValue::from_static(anomaly.describe()),
+            );
+            response
+        }
+    }
+}
+
+/// Counts of suspicious renders since startup and the latest of them.
+async fn watchdog_report(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let json_response = serde_json::to_string_pretty(&state.watchdog.report())
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// `?format=json` on any render endpoint: the image is rendered in the
+/// endpoint's default format and returned as a data URI inside
+/// `{"image", "rendered_at", "width", "height"}`, for TRMNL's webhook
+/// plugin and other consumers that want JSON rather than raw bytes.
+async fn json_envelope(mut request: Request<Body>, next: Next) -> Response {
+    let Some(uri) = without_json_format(request.uri()) else {
+        return next.run(request).await;
+    };
+    *request.uri_mut() = uri;
+    // The default format, whatever the client accepts
+    request.headers_mut().remove(header::ACCEPT);
+
+    let response = next.run(request).await;
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    if !response.status().is_success() || !content_type.starts_with("image/") {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let data = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(data) => data,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to read response body: {}", e))
+                .into_response();
+        }
+    };
+    let envelope = image_envelope(&data, &content_type);
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::VARY);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Response::from_parts(parts, Body::from(envelope.to_string()))
+}
+
+/// `{"image", "rendered_at", "width", "height"}` of a render, the image as
+/// a data URI.
+fn image_envelope(data: &[u8], content_type: &str) -> serde_json::Value {
+    let dimensions = image::io::Reader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+
+    serde_json::json!({
+        "image": format!(
+            "data:{};base64,{}",
+            content_type,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        ),
+        "rendered_at": timestamps::format_utc(SystemTime::now()),
+        "width": dimensions.map(|(width, _)| width),
+        "height": dimensions.map(|(_, height)| height),
+    })
+}
+
+/// `?compress=zlib` (or `deflate`) on a `format=raw` render: the
+/// framebuffer is compressed, with its uncompressed size in
+/// `X-Uncompressed-Length` so firmware can size its buffer before inflating.
+async fn compress_framebuffer(mut request: Request<Body>, next: Next) -> Response {
+    let Some((uri, name)) = without_param(request.uri(), |name, _| name == "compress") else {
+        return next.run(request).await;
+    };
+    let compression = match FramebufferCompression::parse(&name) {
+        Ok(compression) => compression,
+        Err(e) => return AppError::BadRequest(e).into_response(),
+    };
+    *request.uri_mut() = uri;
+
+    let response = next.run(request).await;
+    if !response.status().is_success() {
+        return response;
+    }
+    let is_raw = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v == "application/octet-stream");
+    if !is_raw {
+        return AppError::BadRequest("compress only applies to format=raw".to_string())
+            .into_response();
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let data = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(data) => data,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to read response body: {}", e))
+                .into_response();
+        }
+    };
+    let compressed = match compression.compress(&data) {
+        Ok(compressed) => compressed,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to compress framebuffer: {}", e))
+                .into_response();
+        }
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert("x-uncompressed-length", HeaderValue::from(data.len()));
+    Response::from_parts(parts, Body::from(compressed))
+}
+
+/// `uri` without its `format=json` parameter, or `None` when it has none.
+fn without_json_format(uri: &Uri) -> Option<Uri> {
+    without_param(uri, |name, value| {
+        name == "format" && value.eq_ignore_ascii_case("json")
+    })
+    .map(|(uri, _)| uri)
+}
+
+/// `uri` without the query parameters `matches` picks, and the value of the
+/// first of them; `None` when there are none.
+fn without_param(uri: &Uri, matches: impl Fn(&str, &str) -> bool) -> Option<(Uri, String)> {
+    let query = uri.query()?;
+    let matching = |pair: &&str| {
+        pair.split_once('=')
+            .is_some_and(|(name, value)| matches(name, value))
+    };
+    let value = query
+        .split('&')
+        .find(matching)?
+        .split_once('=')?
+        .1
+        .to_string();
+
+    let query: Vec<&str> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && !matching(pair))
+        .collect();
+    let path_and_query = if query.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), query.join("&"))
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    Some((Uri::from_parts(parts).ok()?, value))
+}
+
+/// Expand `?preset=` (or a `?device=` ID naming a preset) into the preset's
+/// `width`, `height`, `depth`, `rotate` and `margin` parameters, so every
+/// render endpoint picks them up. Parameters in the URL take precedence.
+/// Registered devices get the preset they were assigned.
+async fn apply_device_preset(
+    State(state): State<Arc<AppState>>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let has_preset = request
+        .uri()
+        .query()
+        .is_some_and(|query| query.split('&').any(|pair| pair.starts_with("preset=")));
+    let assigned = devices::device_id(request.headers(), request.uri(), &state.presets)
+        .and_then(|id| state.devices.registration(&id)?.preset)
+        .filter(|preset| state.presets.get(preset).is_some())
+        // As presets are looked up, which keeps it to the query-safe
+        // characters `Presets::load` allows
+        .map(|preset| preset.trim().to_lowercase());
+    if let (false, Some(preset)) = (has_preset, assigned) {
+        let uri = request.uri();
+        let query = match uri.query() {
+            Some(query) => format!("{}&preset={}", query, preset),
+            None => format!("preset={}", preset),
+        };
+        let mut parts = uri.clone().into_parts();
+        parts.path_and_query = format!("{}?{}", uri.path(), query).parse().ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *request.uri_mut() = uri;
+        }
+    }
+
+    match state.presets.expand(request.uri()) {
+        Ok(Some(uri)) => *request.uri_mut() = uri,
+        Ok(None) => {}
+        Err(message) => return AppError::BadRequest(message).into_response(),
+    }
+
+    next.run(request).await
+}
+
+/// Devices seen, with last check-in and telemetry
+async fn list_devices(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let json_response = serde_json::to_string_pretty(&state.devices.statuses())
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// Register a device with a friendly name, preset and screen, before or
+/// after it first polls.
+async fn register_device(
+    State(state): State<Arc<AppState>>,
+    body: bytes::Bytes,
+) -> Result<Response, AppError> {
+    let device: NewDevice = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid device: {}", e)))?;
+    let id = device.id.trim();
+    if id.is_empty() {
+        return Err(AppError::BadRequest("Missing device id".to_string()));
+    }
+    let registration = Registration {
+        name: device.name,
+        preset: device.preset,
+        screen: device.screen,
+    };
+    check_registration(&state, &registration)?;
+
+    let status = state.devices.register(id, registration).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Device '{}' is already registered; change it with PATCH /devices/{}",
+            id, id
+        ))
+    })?;
+    info!("Registered device {}", id);
+    device_response(StatusCode::CREATED, &status)
+}
+
+/// Change a device's name, preset or screen; `null` clears one.
+async fn update_device(
+    State(state): State<Arc<AppState>>,
+    Path(device_id): Path<String>,
+    body: bytes::Bytes,
+) -> Result<Response, AppError> {
+    let update: RegistrationUpdate = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid device update: {}", e)))?;
+    let changed = Registration {
+        name: update.name.clone().flatten(),
+        preset: update.preset.clone().flatten(),
+        screen: update.screen.clone().flatten(),
+    };
+    check_registration(&state, &changed)?;
+
+    let status = state
+        .devices
+        .update(&device_id, update)
+        .ok_or_else(|| AppError::NotFound(format!("Unknown device: {}", device_id)))?;
+    info!("Updated device {}", device_id);
+    device_response(StatusCode::OK, &status)
+}
+
+/// Reject blank names and presets or screens that don't exist.
+fn check_registration(state: &AppState, registration: &Registration) -> Result<(), AppError> {
+    if registration
+        .name
+        .as_deref()
+        .is_some_and(|name| name.trim().is_empty())
+    {
+        return Err(AppError::BadRequest("Device name is empty".to_string()));
+    }
+    if let Some(preset) = &registration.preset
+        && state.presets.get(preset).is_none()
+    {
+        return Err(AppError::BadRequest(format!("Unknown preset: {}", preset)));
+    }
+    if let Some(screen) = &registration.screen
+        && named_screen(state, screen).is_none()
+    {
+        return Err(AppError::BadRequest(format!("Unknown screen: {}", screen)));
+    }
+    Ok(())
+}
+
+fn device_response(status: StatusCode, device: &DeviceStatus) -> Result<Response, AppError> {
+    let json_response = serde_json::to_string_pretty(device)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// TRMNL display API: tells a polling device where to fetch its screen
+/// (`DEVICE_SCREENS_FILE`, looked up by its `ID` and `Access-Token`
+/// headers) and when to poll again, the way TRMNL's own server does.
+async fn trmnl_display(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let device_id = devices::device_id(&headers, &uri, &state.presets);
+    let access_token = headers
+        .get("Access-Token")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    if device_id.is_none() && access_token.is_none() {
+        return Err(AppError::BadRequest(
+            "Missing ID or Access-Token header".to_string(),
+        ));
+    }
+
+    let device = device_id.as_deref().or(access_token).unwrap_or_default();
+
+    // A screen assigned with PATCH /devices/:id comes first
+    let registered = [device_id.as_deref(), access_token]
+        .into_iter()
+        .flatten()
+        .find_map(|id| state.devices.registration(id)?.screen);
+    let screen = match registered
+        .as_deref()
+        .map(|name| (name, named_screen(&state, name)))
+    {
+        Some((_, Some(screen))) => screen,
+        found => {
+            if let Some((name, None)) = found {
+                warn!("Device {} is assigned unknown screen '{}'", device, name);
+            }
+            state
+                .screens
+                .find(device_id.as_deref(), access_token)
+                .cloned()
+                .ok_or_else(|| {
+                    AppError::NotFound(format!("No screen assigned to device '{}'", device))
+                })?
+        }
+    };
+
+    screen_response(&state, &headers, &screen, device_id.as_deref(), device).await
+}
+
+/// Screen named `name`: a `DEVICE_SCREENS_FILE` key, or else a dashboard
+/// from `DASHBOARDS` on its own.
+fn named_screen(state: &AppState, name: &str) -> Option<Screen> {
+    match state.screens.get(name) {
+        Some(screen) => Some(screen.clone()),
+        None if state.dashboards.iter().any(|d| d.name == name) => Some(Screen::of_dashboard(name)),
+        None => None,
+    }
+}
+
+/// TRMNL display API for one screen by name: a `DEVICE_SCREENS_FILE` key,
+/// or a dashboard from `DASHBOARDS`, so a device (or TRMNL's redirect
+/// plugin) can be pointed at it without a screens file entry for its ID.
+async fn screen_display(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    Path(name): Path<String>,
+) -> Result<Response, AppError> {
+    let screen = named_screen(&state, &name)
+        .ok_or_else(|| AppError::NotFound(format!("Unknown screen: {}", name)))?;
+    // Devices that identify themselves keep their own playlist place and
+    // get their low-battery badge; anything else shares the screen's
+    let device_id = devices::device_id(&headers, &uri, &state.presets);
+    let device = match &device_id {
+        Some(device_id) => device_id.clone(),
+        None => format!("screen:{}", name),
+    };
+
+    screen_response(&state, &headers, &screen, device_id.as_deref(), &device).await
+}
+
+/// The `/api/display` JSON for `screen`, polled by `device` (its MAC
+/// address when known as `device_id`, else its API key or another key
+/// it keeps its playlist place under).
+async fn screen_response(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    screen: &Screen,
+    device_id: Option<&str>,
+    device: &str,
+) -> Result<Response, AppError> {
+    // Firmware reports the special function button with this header
+    let pressed = headers
+        .get("Special-Function")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_lowercase())
+        .is_some_and(|v| !matches!(v.as_str(), "" | "false" | "0" | "none"));
+    if pressed {
+        let state = state.clone();
+        let device = device.to_string();
+        let call = screen.button.clone();
+        tokio::spawn(async move { state.press_button(&device, call).await });
+    }
+
+    let base_url = match &state.public_url {
+        Some(url) => url.clone(),
+        None => {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| {
+                    AppError::BadRequest("Missing Host header; set PUBLIC_URL".to_string())
+                })?;
+            let scheme = headers
+                .get("X-Forwarded-Proto")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("http");
+            format!("{}://{}", scheme, host)
+        }
+    };
+    let minute = minute_of_day(state).await;
+    let (image_path, filename, refresh_rate) = match (screen.sleep, minute) {
+        // The filename stays the same all night, so the device draws the
+        // sleeping frame once rather than on every poll
+        (Some(sleep), Some(minute)) if sleep.contains(minute) => {
+            let until = sleep.end_clock();
+            (
+                format!("/trmnl/sleep?until={}", until),
+                format!("sleep-{}", until.replace(':', "")),
+                u64::from(sleep.minutes_until_end(minute)).max(1) * 60,
+            )
+        }
+        _ => {
+            let escalation = match &screen.escalate {
+                Some(escalate) => escalation_reason(state, escalate)
+                    .await
+                    .map(|reason| (escalate, reason)),
+                None => None,
+            };
+            if state.screens.set_escalated(device, escalation.is_some()) {
+                match &escalation {
+                    Some((escalate, reason)) => info!(
+                        "Device {} escalated to '{}': {}",
+                        device, escalate.dashboard, reason
+                    ),
+                    None => info!("Device {} is back on standby", device),
+                }
+            }
+            // The playlist holds its place while escalated
+            let slot = match &escalation {
+                Some((escalate, _)) => Slot {
+                    dashboard: escalate.dashboard.clone(),
+                    tile: escalate.tile,
+                    polls: 1,
+                },
+                None => state.screens.next_slot(device, screen).clone(),
+            };
+            let dashboard = find_dashboard(state, &slot.dashboard)?;
+            if let Some(device_id) = device_id {
+                state.devices.assign_screen(device_id, &dashboard.name);
+            }
+
+            let (image_path, filename) = match slot.tile {
+                Some(tile) => (
+                    format!("/dashboards/{}/tiles/{}", dashboard.name, tile),
+                    format!("{}-{}", dashboard.name, tile),
+                ),
+                None => (
+                    format!("/dashboards/{}", dashboard.name),
+                    dashboard.name.clone(),
+                ),
+            };
+            // The firmware skips redrawing when the filename is unchanged,
+            // so it only changes along with the pixels; a screen that can't
+            // be rendered now is always fetched
+            let version = match screen_checksum(state, dashboard, slot.tile).await {
+                Some(checksum) => checksum,
+                None => SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+                    .to_string(),
+            };
+            (
+                image_path,
+                format!("{}-{}", filename, version),
+                screen_refresh_rate(state, screen, dashboard, minute).await,
+            )
+        }
+    };
+
+    // Naming the device lets the image request pick up its low-battery badge
+    let mut image_url = reqwest::Url::parse(&format!("{}{}", base_url, image_path))
+        .map_err(|e| AppError::BadRequest(format!("Invalid image URL: {}", e)))?;
+    if let Some(device_id) = device_id {
+        image_url.query_pairs_mut().append_pair("device", device_id);
+    }
+
+    let display = serde_json::json!({
+        "status": 0,
+        "image_url": image_url.as_str(),
+        "filename": filename,
+        "refresh_rate": refresh_rate,
+        "reset_firmware": false,
+        "update_firmware": false,
+        "firmware_url": null,
+        "special_function": screen.special_function.as_deref().unwrap_or("none"),
+    });
+    let json_response = serde_json::to_string(&display)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+/// Checksum of the image a device fetches for `dashboard` (or its `tile`),
+/// rendered the way the fetch will be: from the cache, or with the
+/// device's low-battery badge.
+async fn screen_checksum(
+    state: &Arc<AppState>,
+    dashboard: &Dashboard,
+    tile: Option<u32>,
+) -> Option<String> {
+    let rendered = match current_render(state, dashboard).await {
+        Ok(Ok(rendered)) => rendered,
+        Ok(Err(response)) => {
+            warn!(
+                "Rendering dashboard {} for /api/display failed with {}",
+                dashboard.name,
+                response.status()
+            );
+            return None;
+        }
+        Err(e) => {
+            warn!(
+                "Rendering dashboard {} for /api/display failed: {:?}",
+                dashboard.name, e
+            );
+            return None;
+        }
+    };
+    match (tile, dashboard.tiles) {
+        (Some(index), Some(tiles)) => cut_tile(&rendered, tiles, index, state.trmnl_layout)
+            .ok()
+            .map(|(data, _)| dashboards::checksum(&data)),
+        _ => Some(rendered.checksum().to_string()),
+    }
+}
+
+/// The first of `escalate`'s rules that matches, described, or `None`
+/// while all is well. Entities that can't be fetched don't match.
+async fn escalation_reason(state: &AppState, escalate: &standby::Escalation) -> Option<String> {
+    for rule in &escalate.when {
+        match state.get_entity_state(&rule.entity).await {
+            Ok(entity) => {
+                let last_changed = entity.last_changed.as_deref().and_then(timestamps::parse);
+                if rule.matches(&entity.state, last_changed) {
+                    return Some(rule.describe());
+                }
+            }
+            Err(e) => warn!("Failed to check {} for escalation: {}", rule.entity, e),
+        }
+    }
+    None
+}
+
+/// Minutes since midnight in Home Assistant's time zone.
+async fn minute_of_day(state: &AppState) -> Option<u32> {
+    let start_of_today = match state.get_start_of_today().await {
+        Ok(text) => timestamps::parse(&text)?,
+        Err(e) => {
+            warn!("Failed to get start of day for the display schedule: {}", e);
+            return None;
+        }
+    };
+    let elapsed = SystemTime::now().duration_since(start_of_today).ok()?;
+    Some((elapsed.as_secs() / 60).min(24 * 60 - 1) as u32)
+}
+
+/// Seconds until a device showing `screen` polls again: the screen's
+/// `refresh_rate` or one hinted from what its dashboard shows, and during
+/// its night (`minute` being the time of day) the longer
+/// `night_refresh_rate`.
+async fn screen_refresh_rate(
+    state: &AppState,
+    screen: &Screen,
+    dashboard: &Dashboard,
+    minute: Option<u32>,
+) -> u64 {
+    let day_rate = match screen.refresh_rate {
+        Some(rate) => rate,
+        None => {
+            let mut entities = Vec::new();
+            for entity_id in dashboard.entities() {
+                // Only sensors are told apart by device class
+                let device_class = if entity_id.starts_with("sensor.") {
+                    state
+                        .get_entity_state(&entity_id)
+                        .await
+                        .ok()
+                        .and_then(|entity| {
+                            entity
+                                .attributes
+                                .get("device_class")
+                                .and_then(|class| class.as_str())
+                                .map(str::to_string)
+                        })
+                } else {
+                    None
+                };
+                entities.push((entity_id, device_class));
+            }
+            refresh::content_rate(&entities)
+                .unwrap_or_else(|| state.devices.default_interval().as_secs())
+        }
+    };
+
+    let night = screen.night.unwrap_or(refresh::Window::NIGHT);
+
+    match minute {
+        Some(minute) if night.contains(minute) => screen
+            .night_refresh_rate
+            .unwrap_or(day_rate.max(refresh::SLOW)),
+        _ => day_rate,
+    }
+}
+
+/// How often devices are checked for missed check-ins, and registered
+/// devices' check-ins written to `DEVICES_FILE`
+const DEVICE_WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+async fn watch_devices(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(DEVICE_WATCH_INTERVAL);
+    loop {
+        interval.tick().await;
+        for status in state.devices.newly_offline() {
+            warn!(
+                "Device {} missed its check-ins (last seen {})",
+                status.id,
+                status.last_seen.as_deref().unwrap_or("never")
+            );
+            state.notify_device_status(&status).await;
+        }
+        state.devices.save_check_ins().await;
+    }
+}
+
+/// HTML strip of the frames recently served to a device, newest first.
+async fn device_history(
+    State(state): State<Arc<AppState>>,
+    Path(device_id): Path<String>,
+) -> Result<Response, AppError> {
+    let frames = state
+        .devices
+        .history(&device_id)
+        .ok_or_else(|| AppError::NotFound(format!("No history for device: {}", device_id)))?;
+
+    let device = html_escape(&device_id);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0} history</title></head>\n<body>\n<h1>{0}</h1>\n",
+        device
+    );
+    // Device IDs may hold `/`, `?` or `%`, so links take it percent-encoded
+    let base = reqwest::Url::parse("http://localhost/devices")
+        .map_err(|e| AppError::Internal(format!("Invalid frame link: {}", e)))?;
+    for frame in &frames {
+        let mut link = base.clone();
+        if let Ok(mut segments) = link.path_segments_mut() {
+            segments
+                .push(&device_id)
+                .push("history")
+                .push(&frame.seq.to_string());
+        }
+        let src = html_escape(link.path());
+        html.push_str(&format!(
+            "<figure>\n<a href=\"{src}\" download><img src=\"{src}\" alt=\"frame {seq}\"></a>\n<figcaption>#{seq} {time} - <code>{path}</code></figcaption>\n</figure>\n",
+            src = src,
+            seq = frame.seq,
+            time = httpdate::fmt_http_date(frame.rendered_at),
+            path = html_escape(&frame.path),
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response())
+}
+
+async fn device_history_frame(
+    State(state): State<Arc<AppState>>,
+    Path((device_id, seq)): Path<(String, u64)>,
+) -> Result<Response, AppError> {
+    let frame = state
+        .devices
+        .frame(&device_id, seq)
+        .ok_or_else(|| AppError::NotFound(format!("No frame {} for device: {}", seq, device_id)))?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, frame.content_type),
+            (
+                header::LAST_MODIFIED,
+                httpdate::fmt_http_date(frame.rendered_at),
+            ),
+        ],
+        frame.data,
+    )
+        .into_response())
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+async fn health_check() -> impl IntoResponse {
+    "OK"
+}
+
+async fn serve_entity_image(
+    State(state): State<Arc<AppState>>,
+    Path(entity_id): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<ImageQuery>,
+) -> Result<Response, AppError> {
+    info!("Serving image for entity: {}", entity_id);
+
+    let processing = ImageProcessing::new(
+        Adjustments::new(
+            params.brightness,
+            params.contrast,
+            params.gamma,
+            params.auto_levels,
+            params.sharpen,
+        )
+        .map_err(AppError::BadRequest)?,
+        params.width,
+        params.height,
+        params.fit.as_deref(),
+        params.background.as_deref(),
+        params.crop.as_deref(),
+        params.rotate,
+    )?
+    .with_palette(params.palette.as_deref())?
+    .with_depth(params.depth, params.dither.as_deref(), params.invert)?
+    .with_format(params.format.as_deref(), params.quality, &headers)?;
+
+    // First try to get it as a camera entity
+    if entity_id.starts_with("camera.") {
+        match state.get_camera_snapshot(&entity_id).await {
+            Ok((image_data, content_type)) => {
+                let (image_data, content_type) = processing
+                    .apply(image_data, content_type, &state.png)
+                    .map_err(|e| AppError::Internal(format!("Failed to process image: {}", e)))?;
+                return Ok(create_image_response(image_data, content_type));
+            }
+            Err(e) => {
+                warn!("Failed to get camera snapshot: {}", e);
+            }
+        }
+    }
+
+    // If not a camera or camera failed, try to get entity state and look for image URL
+    match state.get_entity_state(&entity_id).await {
+        Ok(entity_state) => {
+            // Look for image URL in various possible attributes
+            let possible_image_attrs = [
+                "entity_picture",
+                "image_url",
+                "picture",
+                "thumbnail",
+                "media_content_id",
+            ];
+
+            for attr in &possible_image_attrs {
+                if let Some(url_str) = entity_state.attributes.get(attr).and_then(|v| v.as_str()) {
+                    let full_url = if url_str.starts_with("http") {
+                        url_str.to_string()
+                    } else {
+                        format!("{}{}", state.ha_config.base_url, url_str)
+                    };
+
+                    match state.fetch_image_from_url(&full_url).await {
+                        Ok((image_data, content_type)) => {
+                            let (image_data, content_type) = processing
+                                .apply(image_data, content_type, &state.png)
+                                .map_err(|e| {
+                                    AppError::Internal(format!("Failed to process image: {}", e))
+                                })?;
+                            return Ok(create_image_response(image_data, content_type));
+                        }
+                        Err(e) => {
+                            warn!("Failed to fetch image from {}: {}", full_url, e);
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            Err(AppError::NotFound(format!(
+                "No image found for entity: {}",
+                entity_id
+            )))
+        }
+        Err(e) => {
+            error!("Failed to get entity state for {}: {}", entity_id, e);
+            Err(AppError::Internal(format!(
+                "Failed to get entity state: {}",
+                e
+            )))
+        }
+    }
+}
+
+async fn serve_image_by_url(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<ImageUrlQuery>,
+) -> Result<Response, AppError> {
+    let image_url = params
+        .url
+        .as_ref()
+        .ok_or_else(|| AppError::BadRequest("Missing 'url' parameter".to_string()))?;
+
+    info!("Serving image from URL: {}", image_url);
+
+    let processing = ImageProcessing::new(
+        Adjustments::new(
+            params.brightness,
+            params.contrast,
+            params.gamma,
+            params.auto_levels,
+            params.sharpen,
+        )
+        .map_err(AppError::BadRequest)?,
+        params.width,
+        params.height,
+        params.fit.as_deref(),
+        params.background.as_deref(),
+        params.crop.as_deref(),
+        params.rotate,
+    )?
+    .with_palette(params.palette.as_deref())?
+    .with_depth(params.depth, params.dither.as_deref(), params.invert)?
+    .with_format(params.format.as_deref(), params.quality, &headers)?;
+
+    let full_url = if image_url.starts_with("http") {
+        image_url.clone()
+    } else {
+        format!("{}{}", state.ha_config.base_url, image_url)
+    };
+
+    match state.fetch_image_from_url(&full_url).await {
+        Ok((image_data, content_type)) => {
+            let (image_data, content_type) = processing
+                .apply(image_data, content_type, &state.png)
+                .map_err(|e| AppError::Internal(format!("Failed to process image: {}", e)))?;
+            Ok(create_image_response(image_data, content_type))
+        }
+        Err(e) => {
+            error!("Failed to fetch image from {}: {}", full_url, e);
+            Err(AppError::Internal(format!("Failed to fetch image: {}", e)))
+        }
+    }
+}
+
+async fn list_camera_entities(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let states = state
+        .get_all_states()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to fetch states: {}", e)))?;
+
+    let camera_entities: Vec<&EntityState> = states
+        .iter()
+        .filter(|state| state.entity_id.starts_with("camera."))
+        .collect();
+
+    let json_response = serde_json::to_string_pretty(&camera_entities)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        json_response,
+    )
+        .into_response())
+}
+
+async fn render_entity_status(
+    State(state): State<Arc<AppState>>,
+    Path(entity_id): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<ImageQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering status image for entity: {}", entity_id);
+
+    // Get entity state
+    let entity_state = state
+        .get_entity_state(&entity_id)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get entity state: {}", e)))?;
+    let entity_state = state.with_meter_budget(entity_state).await;
+    let entity_state = state.with_display_settings(entity_state);
+    let entity_state = with_units(entity_state, &parse_units(params.units.as_deref())?);
+
+    let style = state
+        .render_style(
+            params.theme.as_deref(),
+            params.thresholds.as_deref(),
+            params.accessible.unwrap_or(false),
+            params.rotate,
+            params.palette.as_deref(),
+        )?
+        .with_depth(params.depth, params.dither.as_deref(), params.invert)?
+        .with_format(params.format.as_deref(), params.quality, &headers)?
+        .with_margin(params.margin)?
+        .with_si(params.si);
+
+    // Extract dimensions from query params or use defaults
+    let (width, height) =
+        style.inner_size(params.width.unwrap_or(400), params.height.unwrap_or(200))?;
+
+    // Generate the status image
+    let image_data = generate_status_image(&entity_state, width, height, &style)
+        .map_err(|e| AppError::Internal(format!("Failed to generate image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        style.content_type().to_string(),
+    ))
+}
+
+fn generate_status_image(
+    entity: &EntityState,
+    width: u32,
+    height: u32,
+    style: &RenderStyle,
+) -> anyhow::Result<bytes::Bytes> {
+    if style.svg {
+        return Ok(style.finish_svg(status_svg(entity, width, height, style)));
+    }
+
+    // For now, let's use a simpler approach without external fonts
+    // We'll create a basic text rendering without rusttype
+    generate_simple_status_image(entity, width, height, style)
+}
+
+/// Vector version of the `/status` layout.
+fn status_svg(entity: &EntityState, width: u32, height: u32, style: &RenderStyle) -> Svg {
+    let theme = &style.theme;
+    let scale = style.text_scale();
+    let mut svg = Svg::new(width, height);
+
+    let (bg_start, bg_end) = get_status_gradient(&entity.state, style);
+    svg.gradient_rect(0, 0, width, height, bg_start, bg_end);
+    svg.outline(0, 0, width, height, 3, theme.frame);
+
+    // Header
+    let entity_name = entity
+        .attributes
+        .get("friendly_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(&entity.entity_id);
+    let (header_start, header_end) = style.gradient(theme.header_top, theme.header_bottom);
+    svg.gradient_rect(8, 8, width - 16, 32, header_start, header_end);
+    svg.outline(8, 8, width - 16, 32, 1, theme.header_border);
+    let entity_name = truncate_to_width(entity_name, width.saturating_sub(30), scale);
+    let text_x = centered_text_x(&entity_name, width, scale);
+    style.svg_text(
+        &mut svg,
+        text_x,
+        24 - 4 * scale,
+        &entity_name,
+        theme.header_text,
+    );
+
+    // Status
+    let state_color = theme.state_color(&entity.state);
+    let (status_start, status_end) =
+        style.gradient(darken(state_color, 0.15), darken(state_color, 0.25));
+    svg.gradient_rect(8, 48, width - 16, 37, status_start, status_end);
+    svg.outline(8, 48, width - 16, 37, 1, theme.panel_border);
+    let status = truncate_to_width(
+        &format_entity_status(entity, style.si),
+        width.saturating_sub(70),
+        scale,
+    );
+    let text_x = centered_text_x(&status, width, scale);
+    let text_y = 69 - 4 * scale;
+    if !style.accessible {
+        svg.text(
+            text_x + 1,
+            text_y + 1,
+            &status,
+            Rgb([0u8, 0u8, 0u8]),
+            1,
+            false,
+        );
+    }
+    style.svg_text(&mut svg, text_x, text_y, &status, theme.header_text);
+
+    // Details
+    svg.rect(8, 92, width - 16, height.saturating_sub(100), theme.panel);
+    svg.outline(
+        8,
+        92,
+        width - 16,
+        height.saturating_sub(100),
+        1,
+        theme.panel_border,
+    );
+    for (y, line) in entity_info_lines(entity, width, height, scale) {
+        style.svg_text(&mut svg, 15, y, &line, theme.text);
+    }
+
+    // Indicator
+    let x_pos = width - 24 - 15;
+    if x_pos + 24 < width && 52 + 24 < height {
+        let color = entity_accent_color(entity, &style.thresholds)
+            .unwrap_or_else(|| theme.state_color(&entity.state));
+        let (cx, cy) = (x_pos as f32 + 12.0, 64.0);
+        svg.circle(cx, cy, 12.0, darken(color, 0.35));
+        svg.circle(cx, cy, 10.0, color);
+        svg.circle(x_pos as f32 + 6.5, 58.5, 3.0, Rgb([255u8, 255u8, 255u8]));
+    }
+
+    svg
+}
+
+/// Left edge of a line centered in a layout `width` pixels wide.
+fn centered_text_x(text: &str, width: u32, scale: u32) -> u32 {
+    let text_width = text_width(text, scale);
+    if text_width < width - 20 {
+        (width - text_width) / 2
+    } else {
+        15
+    }
+}
+
+fn generate_simple_status_image(
+    entity: &EntityState,
+    width: u32,
+    height: u32,
+    style: &RenderStyle,
+) -> anyhow::Result<bytes::Bytes> {
+    let theme = &style.theme;
+
+    // Create a new RGB image with white background
+    let mut image: RgbImage =
+        ImageBuffer::from_fn(width, height, |_x, _y| Rgb([255u8, 255u8, 255u8]));
+
+    // Draw a gradient background based on entity state
+    let (bg_start, bg_end) = get_status_gradient(&entity.state, style);
+    for y in 0..height {
+        let blend_factor = y as f32 / height as f32;
+        let blended_color = blend_colors(bg_start, bg_end, blend_factor);
+        for x in 0..width {
+            image.put_pixel(x, y, blended_color);
+        }
+    }
+
+    // Draw a decorative border
+    draw_border(&mut image, width, height, theme.frame);
+
+    // Draw header section with entity name
+    let entity_name = entity
+        .attributes
+        .get("friendly_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(&entity.entity_id);
+
+    draw_header_section(&mut image, width, entity_name, style);
+
+    // Draw main status section with enhanced formatting
+    let formatted_status = format_entity_status(entity, style.si);
+    draw_status_section(&mut image, width, &formatted_status, &entity.state, style);
+
+    // Draw additional entity information
+    draw_entity_info(&mut image, width, height, entity, style);
+
+    // Draw status indicator (visual representation of state)
+    let accent = entity_accent_color(entity, &style.thresholds);
+    draw_status_indicator(&mut image, width, height, &entity.state, accent, theme);
+
+    style.encode(&style.finish(image))
+}
+
+async fn render_multi_sensor_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<MultiSensorQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering multi-sensor status image");
+
+    // Parse sensor list
+    let sensor_ids: Vec<String> = params
+        .sensors
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if sensor_ids.is_empty() {
+        return Err(AppError::BadRequest(
+            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
+        ));
+    }
+
+    if sensor_ids.len() > 10 {
+        return Err(AppError::BadRequest(
+            "Too many sensors (max 10 allowed)".to_string(),
+        ));
+    }
+
+    // Fetch all sensor states
+    let units = parse_units(params.units.as_deref())?;
+    let mut sensor_data: Vec<EntityState> = state
+        .get_sensor_states(&sensor_ids)
+        .await
+        .into_iter()
+        .map(|sensor| with_units(sensor, &units))
+        .collect();
+    // Attention states move to the top, the order otherwise kept
+    sensor_data.sort_by_key(|sensor| !is_attention(sensor));
+
+    let style = state
+        .render_style(
+            params.theme.as_deref(),
+            params.thresholds.as_deref(),
+            params.accessible.unwrap_or(false),
+            params.rotate,
+            params.palette.as_deref(),
+        )?
+        .with_depth(params.depth, params.dither.as_deref(), params.invert)?
+        .with_format(params.format.as_deref(), params.quality, &headers)?
+        .with_margin(params.margin)?
+        .with_si(params.si);
+
+    // Calculate dimensions
+    let width = params.width.unwrap_or(MULTI_SENSOR_WIDTH);
+    let base_height = 80; // Header height
+    let line_height = style.multi_sensor_line_height(); // Height per sensor
+    let padding = 20; // Bottom padding
+    let content_height = base_height + (sensor_data.len() as u32 * line_height) + padding;
+    let height = params.height.unwrap_or(content_height + style.margin * 2);
+    let (width, height) = style.inner_size(width, height)?;
+
+    // On a canvas several times the layout's size, draw the layout at a
+    // whole fraction of it and enlarge it, rather than leaving the rows
+    // small at the top
+    let zoom = (width / MULTI_SENSOR_WIDTH)
+        .min(height / content_height)
+        .max(1);
+    let style = style.with_upscale((zoom > 1).then_some((width, height)));
+    let (width, height) = (width / zoom, height / zoom);
+
+    let changed = state.track_value_changes(&sensor_data, style.si);
+
+    if params.animate.unwrap_or(false) {
+        if params.format.is_some() || params.quality.is_some() || params.depth.is_some() {
+            return Err(AppError::BadRequest(
+                "animate always returns a GIF".to_string(),
+            ));
+        }
+
+        let image_data = generate_multi_sensor_animation(
+            &sensor_data,
+            width,
+            height,
+            params.title.as_deref(),
+            &style,
+            &changed,
+        )
+        .map_err(|e| AppError::Internal(format!("Failed to generate animation: {}", e)))?;
+
+        return Ok(create_image_response(image_data, "image/gif".to_string()));
+    }
+
+    // Generate the combined image
+    let image_data =
+        generate_multi_sensor_image(&sensor_data, width, height, params.title.as_deref(), &style)
+            .map_err(|e| AppError::Internal(format!("Failed to generate image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        style.content_type().to_string(),
+    ))
+}
+
+async fn render_trmnl_sensors(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(params): Query<TrmnlQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL sensor display");
+
+    // Parse sensor list
+    let sensor_ids: Vec<String> = params
+        .sensors
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    if let Some(camera_id) = params.camera.as_deref() {
+        if !sensor_ids.is_empty() {
+            return Err(AppError::BadRequest(
+                "Use either ?sensors= or ?camera=, not both".to_string(),
+            ));
+        }
+
+        let (snapshot, _) = state
+            .get_camera_snapshot(camera_id)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to get camera snapshot: {}", e)))?;
+
+        let adjustments = Adjustments::new(
+            params.brightness,
+            params.contrast,
+            params.gamma,
+            params.auto_levels,
+            params.sharpen,
+        )
+        .map_err(AppError::BadRequest)?;
+        let crop = params
+            .crop
+            .as_deref()
+            .map(Crop::parse)
+            .transpose()
+            .map_err(AppError::BadRequest)?;
+
+        // A still scene reuses the last render of the same URL and format
+        let key = format!("{} {}", uri, format.content_type());
+        let check = state
+            .camera_frames
+            .check(&key, &snapshot)
+            .map_err(|e| AppError::Internal(format!("Failed to decode camera snapshot: {}", e)))?;
+        let (image_data, changed_at) = match check {
+            motion::Check::Unchanged { output, changed_at } => {
+                info!("Camera {} unchanged, reusing the last render", camera_id);
+                (output, changed_at)
+            }
+            motion::Check::Changed { photo, signature } => {
+                // Photos default to Atkinson, which keeps highlights clean on e-paper
+                let image_data = generate_trmnl_camera_image(
+                    &photo,
+                    params.title.as_deref().unwrap_or(camera_id),
+                    params.side_label.as_deref(),
+                    &state.abbreviations,
+                    crop.as_ref(),
+                    &adjustments,
+                    &TrmnlOutput {
+                        dither: dither.unwrap_or(Dither::Atkinson),
+                        stamp: state.render_stamp(),
+                        layout: state.trmnl_layout(),
+                        rotation,
+                        invert: params.invert.unwrap_or(false),
+                        post,
+                        format,
+                        canvas,
+                    },
+                )
+                .map_err(|e| {
+                    AppError::Internal(format!("Failed to generate TRMNL image: {}", e))
+                })?;
+                let changed_at =
+                    state
+                        .camera_frames
+                        .store(&key, &snapshot, signature, image_data.clone());
+                (image_data, changed_at)
+            }
+        };
+
+        // When the scene last changed, for clients deciding whether to refresh
+        let mut response = create_image_response(image_data, format.content_type().to_string());
+        if let Ok(value) = httpdate::fmt_http_date(changed_at).parse() {
+            response.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+        return Ok(response);
+    }
+
+    if sensor_ids.is_empty() {
+        return Err(AppError::BadRequest(
+            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
+        ));
+    }
+
+    if sensor_ids.len() > TRMNL_MAX_SENSORS {
+        return Err(AppError::BadRequest(
+            "Too many sensors for TRMNL display (max 15 allowed)".to_string(),
+        ));
+    }
+
+    // Fetch all sensor states
+    let units = parse_units(params.units.as_deref())?;
+    let priorities = parse_priorities(params.priority.as_deref())?;
+    let mut sensor_data: Vec<EntityState> = state
+        .get_sensor_states(&sensor_ids)
+        .await
+        .into_iter()
+        .map(|sensor| with_priority(with_units(sensor, &units), &priorities))
+        .collect();
+    if params.sparklines.unwrap_or(false) {
+        sensor_data = state.with_sparklines(sensor_data).await;
+    }
+
+    // Generate TRMNL image (1-bit)
+    let image_data = generate_trmnl_image(
+        &sensor_data,
+        params.title.as_deref(),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        SiPrefixes::from_query(params.si),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Most entities a TRMNL screen can show
+const TRMNL_MAX_SENSORS: usize = 15;
+
+/// Smallest `TRMNL_WIDTH` x `TRMNL_HEIGHT` the screens' fixed margins,
+/// gauges and columns still fit in
+const TRMNL_MIN_LAYOUT: (u32, u32) = (640, 384);
+/// Shortest sensor row on a TRMNL screen; lists that would need shorter
+/// ones drop rows instead
+const TRMNL_MIN_LINE_HEIGHT: u32 = 24;
+
+/// The sensors as HTML using the TRMNL framework's classes, for a private
+/// plugin that leaves rendering to TRMNL's cloud and polls this server for
+/// its Home Assistant data.
+async fn render_trmnl_markup(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TrmnlMarkupQuery>,
+) -> Result<Response, AppError> {
+    let sensor_ids: Vec<String> = params
+        .sensors
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if sensor_ids.is_empty() {
+        return Err(AppError::BadRequest(
+            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
+        ));
+    }
+    if sensor_ids.len() > TRMNL_MAX_SENSORS {
+        return Err(AppError::BadRequest(
+            "Too many sensors for TRMNL display (max 15 allowed)".to_string(),
+        ));
+    }
+
+    let view = match params.view.as_deref() {
+        Some(name) => markup::View::parse(name).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unknown view '{}'. Use full, half_horizontal, half_vertical or quadrant",
+                name
+            ))
+        })?,
+        None => markup::View::default(),
+    };
+    let units = parse_units(params.units.as_deref())?;
+    let si = SiPrefixes::from_query(params.si);
+
+    let mut sensors: Vec<EntityState> = state
+        .get_sensor_states(&sensor_ids)
+        .await
+        .into_iter()
+        .map(|sensor| with_units(sensor, &units))
+        .collect();
+    // Attention states move to the top, the order otherwise kept
+    sensors.sort_by_key(|sensor| !is_attention(sensor));
+
+    let items: Vec<markup::Item> = sensors
+        .iter()
+        .map(|sensor| markup::Item {
+            name: sensor
+                .attributes
+                .get("friendly_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&sensor.entity_id)
+                .to_string(),
+            value: format_sensor_value(sensor, si),
+            attention: is_attention(sensor),
+        })
+        .collect();
+    let html = markup::render(
+        &items,
+        params.title.as_deref().unwrap_or("Sensor Status"),
+        "Home Assistant",
+        view,
+    );
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response())
+}
+
+/// Zero-config TRMNL screen built from every entity in the given domains
+/// (and area), most recently changed first.
+async fn render_trmnl_auto(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlAutoQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering automatic TRMNL display");
+
+    let domains: Vec<&str> = params
+        .domains
+        .as_deref()
+        .unwrap_or("sensor")
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .collect();
+    let limit = params.limit.unwrap_or(12);
+    if !(1..=TRMNL_MAX_SENSORS).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            TRMNL_MAX_SENSORS
+        )));
+    }
+    let dither = parse_dither(params.dither.as_deref())?;
+    let units = parse_units(params.units.as_deref())?;
+    let priorities = parse_priorities(params.priority.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let area_entities =
+        match params.area.as_deref() {
+            Some(area) => Some(state.get_area_entities(area).await.map_err(|e| {
+                AppError::Internal(format!("Failed to look up area {}: {}", area, e))
+            })?),
+            None => None,
+        };
+
+    let mut entities: Vec<EntityState> = state
+        .get_all_states()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to fetch states: {}", e)))?
+        .into_iter()
+        .filter(|entity| {
+            let domain = entity.entity_id.split('.').next().unwrap_or("");
+            domains.contains(&domain)
+        })
+        .filter(|entity| {
+            area_entities
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&entity.entity_id))
+        })
+        .map(|entity| with_priority(state.with_display_settings(entity), &priorities))
+        .collect();
+
+    // Attention states and higher priorities first, then live values, most
+    // recently changed first (HA reports UTC timestamps, so they sort as
+    // strings)
+    entities.sort_by(|a, b| {
+        let live = |e: &EntityState| !matches!(e.state.as_str(), "unavailable" | "unknown");
+        row_rank(b)
+            .cmp(&row_rank(a))
+            .then_with(|| live(b).cmp(&live(a)))
+            .then_with(|| b.last_changed.cmp(&a.last_changed))
+    });
+    entities.truncate(limit);
+    let entities: Vec<EntityState> = entities
+        .into_iter()
+        .map(|entity| with_units(entity, &units))
+        .collect();
+
+    if entities.is_empty() {
+        return Err(AppError::NotFound(
+            "No entities match the given domains and area".to_string(),
+        ));
+    }
+
+    let title = params
+        .title
+        .clone()
+        .or_else(|| params.area.as_deref().map(str::to_uppercase));
+    let image_data = generate_trmnl_image(
+        &entities,
+        title.as_deref(),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        SiPrefixes::from_query(params.si),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// When a scene or script last ran: scenes keep it as their state,
+/// scripts (and automations) in the `last_triggered` attribute.
+fn last_activated(entity: &EntityState) -> Option<SystemTime> {
+    let domain = entity.entity_id.split('.').next().unwrap_or("");
+    match domain {
+        "scene" => timestamps::parse(&entity.state),
+        _ => timestamps::parse(entity.attributes.get("last_triggered")?.as_str()?),
+    }
+}
+
+/// TRMNL board of scenes and scripts with how long ago each last ran, most
+/// recent first.
+async fn render_trmnl_activity(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlActivityQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL activity board");
+
+    let limit = params.limit.unwrap_or(12);
+    if !(1..=TRMNL_MAX_SENSORS).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            TRMNL_MAX_SENSORS
+        )));
+    }
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let entities: Vec<EntityState> = match params.entities.as_deref() {
+        Some(ids) => {
+            let ids: Vec<String> = ids
+                .split(',')
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect();
+            state.get_sensor_states(&ids).await
+        }
+        None => {
+            let domains: Vec<&str> = params
+                .domains
+                .as_deref()
+                .unwrap_or("scene,script")
+                .split(',')
+                .map(str::trim)
+                .collect();
+            state
+                .get_all_states()
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to fetch states: {}", e)))?
+                .into_iter()
+                .filter(|entity| {
+                    let domain = entity.entity_id.split('.').next().unwrap_or("");
+                    domains.contains(&domain)
+                })
+                .collect()
+        }
+    };
+
+    let mut activity: Vec<(EntityState, Option<SystemTime>)> = entities
+        .into_iter()
+        .map(|entity| {
+            let activated = last_activated(&entity);
+            (entity, activated)
+        })
+        .collect();
+    // Most recent first, never-activated last
+    activity.sort_by_key(|(_, activated)| std::cmp::Reverse(*activated));
+    activity.truncate(limit);
+
+    if activity.is_empty() {
+        return Err(AppError::NotFound("No scenes or scripts found".to_string()));
+    }
+
+    // Each row shows the entity's name with the relative time as its value
+    let rows: Vec<EntityState> = activity
+        .into_iter()
+        .map(|(entity, activated)| {
+            let name = entity
+                .attributes
+                .get("friendly_name")
+                .cloned()
+                .unwrap_or_else(|| serde_json::Value::String(entity.entity_id.clone()));
+            EntityState {
+                state: activated
+                    .map(timestamps::format_ago)
+                    .unwrap_or_else(|| "never".to_string()),
+                attributes: serde_json::json!({ "friendly_name": name }),
+                last_changed: None,
+                entity_id: entity.entity_id,
+            }
+        })
+        .collect();
+
+    let image_data = generate_trmnl_image(
+        &rows,
+        Some(params.title.as_deref().unwrap_or("ACTIVITY")),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        SiPrefixes::default(),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Upcoming events such as ISS passes, sunrise or moonrise with a
+/// countdown to each, soonest first.
+async fn render_trmnl_events(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlEventsQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL upcoming events");
+
+    let limit = params.limit.unwrap_or(TRMNL_MAX_SENSORS);
+    if !(1..=TRMNL_MAX_SENSORS).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            TRMNL_MAX_SENSORS
+        )));
+    }
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    // `Label=entity_id:attribute`; without an attribute the state is the
+    // time, without a label the friendly name is shown
+    let events: Vec<(Option<String>, String, Option<String>)> = params
+        .events
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (label, source) = match entry.split_once('=') {
+                Some((label, source)) => (Some(label.trim().to_string()), source),
+                None => (None, entry),
+            };
+            let (id, attribute) = match source.split_once(':') {
+                Some((id, attribute)) => (id, Some(attribute.trim().to_string())),
+                None => (source, None),
+            };
+            (label, id.trim().to_string(), attribute)
+        })
+        .collect();
+    if events.is_empty() {
+        return Err(AppError::BadRequest(
+            "No events provided. Use ?events=ISS pass=binary_sensor.iss:next_rise".to_string(),
+        ));
+    }
+    let details: Vec<&str> = params
+        .details
+        .as_deref()
+        .unwrap_or("visibility,max_elevation")
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    // Clock times in Home Assistant's time zone
+    let offset_secs = match state.get_start_of_today().await {
+        Ok(start) => timestamps::offset_of(&start).unwrap_or(0),
+        Err(e) => {
+            warn!("Failed to get time zone, showing UTC: {}", e);
+            0
+        }
+    };
+
+    let ids: Vec<String> = events.iter().map(|(_, id, _)| id.clone()).collect();
+    let sensors = state.get_sensor_states(&ids).await;
+    let mut upcoming: Vec<(EntityState, Option<SystemTime>)> = events
+        .into_iter()
+        .zip(sensors)
+        .map(|((label, _, attribute), sensor)| {
+            let time = match &attribute {
+                Some(attribute) => sensor.attributes.get(attribute).and_then(|v| v.as_str()),
+                None => Some(sensor.state.as_str()),
+            }
+            .and_then(|text| timestamps::parse_local(text, offset_secs));
+
+            let mut name = label.unwrap_or_else(|| {
+                sensor
+                    .attributes
+                    .get("friendly_name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&sensor.entity_id)
+                    .to_string()
+            });
+            let shown: Vec<String> = details
+                .iter()
+                .filter_map(|detail| match sensor.attributes.get(*detail)? {
+                    serde_json::Value::String(text) => Some(text.clone()),
+                    serde_json::Value::Number(number) => Some(number.to_string()),
+                    serde_json::Value::Bool(visible) => {
+                        Some(if *visible { "visible" } else { "not visible" }.to_string())
+                    }
+                    _ => None,
+                })
+                .collect();
+            if !shown.is_empty() {
+                name = format!("{} ({})", name, shown.join(", "));
+            }
+
+            // Each row shows the event with its countdown as the value
+            let row = EntityState {
+                state: match time {
+                    Some(time) => format!(
+                        "{}, {}",
+                        timestamps::format_until(time),
+                        timestamps::format_clock(time, offset_secs)
+                    ),
+                    None => "unknown".to_string(),
+                },
+                attributes: serde_json::json!({ "friendly_name": name }),
+                last_changed: None,
+                entity_id: sensor.entity_id,
+            };
+            (row, time)
+        })
+        .collect();
+    // Soonest first, unknown times last
+    upcoming.sort_by_key(|(_, time)| (time.is_none(), *time));
+    upcoming.truncate(limit);
+    let rows: Vec<EntityState> = upcoming.into_iter().map(|(row, _)| row).collect();
+
+    let image_data = generate_trmnl_image(
+        &rows,
+        Some(params.title.as_deref().unwrap_or("UPCOMING")),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        SiPrefixes::default(),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Two sensors side by side with their difference in large type, e.g.
+/// inside vs outside temperature, plus a recommendation from `rules`.
+async fn render_trmnl_delta(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlDeltaQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL delta of {} and {}", params.a, params.b);
+
+    let rules =
+        delta::parse(params.rules.as_deref().unwrap_or("")).map_err(AppError::BadRequest)?;
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let units = parse_units(params.units.as_deref())?;
+    let sensors: Vec<EntityState> = state
+        .get_sensor_states(&[params.a.clone(), params.b.clone()])
+        .await
+        .into_iter()
+        .map(|sensor| with_units(sensor, &units))
+        .collect();
+
+    let image_data = generate_trmnl_delta_image(
+        (&sensors[0], &sensors[1]),
+        &rules,
+        params.title.as_deref().unwrap_or("DIFFERENCE"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Most marine sensors a tide screen can show
+const TRMNL_MAX_MARINE: usize = 4;
+
+/// The day's tide curve with the next high and low tide, plus wind and wave
+/// readings.
+async fn render_trmnl_tide(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlTideQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL tides for {}", params.tide);
+
+    let dither = parse_dither(params.dither.as_deref())?;
+    let units = parse_units(params.units.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let marine_ids: Vec<String> = params
+        .marine
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if marine_ids.len() > TRMNL_MAX_MARINE {
+        return Err(AppError::BadRequest(format!(
+            "Too many marine sensors for TRMNL display (max {} allowed)",
+            TRMNL_MAX_MARINE
+        )));
+    }
+
+    let defaults = tide::Attributes::default();
+    let attributes = tide::Attributes {
+        high_time: params.high_time.unwrap_or(defaults.high_time),
+        high_height: params.high_height.unwrap_or(defaults.high_height),
+        low_time: params.low_time.unwrap_or(defaults.low_time),
+        low_height: params.low_height.unwrap_or(defaults.low_height),
+    };
+
+    // Local midnight decides both the chart's day and the clock offset
+    let start_of_today = state
+        .get_start_of_today()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get start of day: {}", e)))?;
+    let start = timestamps::parse(&start_of_today).ok_or_else(|| {
+        AppError::Internal(format!("Unexpected start of day '{}'", start_of_today))
+    })?;
+    let offset_secs = timestamps::offset_of(&start_of_today).unwrap_or(0);
+    let end = start + Duration::from_secs(86_400);
+
+    let sensor = state
+        .get_entity_state(&params.tide)
+        .await
+        .map_err(|e| AppError::NotFound(format!("Tide sensor {}: {}", params.tide, e)))?;
+    let events = match attributes.events(&sensor.attributes, offset_secs) {
+        Some((high, low)) => tide::events_between(high, low, start, end),
+        None => {
+            warn!("No tide times in {} attributes", params.tide);
+            Vec::new()
+        }
+    };
+    let marine: Vec<EntityState> = state
+        .get_sensor_states(&marine_ids)
+        .await
+        .into_iter()
+        .map(|sensor| with_units(sensor, &units))
+        .collect();
+
+    let image_data = generate_trmnl_tide_image(
+        &TideDay {
+            events,
+            start,
+            end,
+            offset_secs,
+        },
+        &marine,
+        params.title.as_deref().unwrap_or("TIDES"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Aircraft overhead with callsign, altitude and route.
+async fn render_trmnl_flights(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlFlightsQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL flights from {}", params.sensor);
+
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let defaults = flights::Fields::default();
+    let fields = flights::Fields {
+        list: params.list.unwrap_or(defaults.list),
+        callsign: params.callsign.unwrap_or(defaults.callsign),
+        altitude: params.altitude.unwrap_or(defaults.altitude),
+        origin: params.origin.unwrap_or(defaults.origin),
+        destination: params.destination.unwrap_or(defaults.destination),
+        unit: params.unit.unwrap_or(defaults.unit),
+    };
+
+    let sensor = state
+        .get_entity_state(&params.sensor)
+        .await
+        .map_err(|e| AppError::NotFound(format!("Flight sensor {}: {}", params.sensor, e)))?;
+    let flights = fields.flights(&sensor.attributes).unwrap_or_else(|| {
+        warn!("No '{}' list in {} attributes", fields.list, params.sensor);
+        Vec::new()
+    });
+
+    let image_data = generate_trmnl_flights_image(
+        &flights,
+        params.title.as_deref().unwrap_or("OVERHEAD"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Longest forecast a carbon screen considers
+const TRMNL_MAX_CARBON_HOURS: u32 = 48;
+
+/// Carbon intensity or electricity price now, whether to run appliances now
+/// or wait, and the upcoming forecast as bars.
+async fn render_trmnl_carbon(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlCarbonQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL carbon forecast from {}", params.sensor);
+
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let hours = params.hours.unwrap_or(12);
+    if !(1..=TRMNL_MAX_CARBON_HOURS).contains(&hours) {
+        return Err(AppError::BadRequest(format!(
+            "hours must be between 1 and {}, got {}",
+            TRMNL_MAX_CARBON_HOURS, hours
+        )));
+    }
+    let kind = params
+        .kind
+        .as_deref()
+        .map(|name| {
+            carbon::Kind::parse(name).ok_or_else(|| {
+                AppError::BadRequest(format!("Unknown kind '{}'. Use carbon or price", name))
+            })
+        })
+        .transpose()?;
+
+    let defaults = carbon::Fields::default();
+    let fields = carbon::Fields {
+        list: params.list.unwrap_or(defaults.list),
+        time: params.time.unwrap_or(defaults.time),
+        value: params.value.unwrap_or(defaults.value),
+    };
+
+    let start_of_today = state
+        .get_start_of_today()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get start of day: {}", e)))?;
+    let offset_secs = timestamps::offset_of(&start_of_today).unwrap_or(0);
+
+    let sensor = state
+        .get_entity_state(&params.sensor)
+        .await
+        .map_err(|e| AppError::NotFound(format!("Carbon sensor {}: {}", params.sensor, e)))?;
+    let now = SystemTime::now();
+    let upcoming = carbon::upcoming(
+        &fields.forecast(&sensor.attributes, offset_secs),
+        now,
+        Duration::from_secs(u64::from(hours) * 3600),
+    );
+    if upcoming.is_empty() {
+        warn!("No forecast in {} attributes", params.sensor);
+    }
+    let current = sensor
+        .state
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .or_else(|| upcoming.first().map(|point| point.value));
+    let (verdict, best) = match current {
+        Some(current) => carbon::verdict(current, &upcoming, now),
+        None => (carbon::Verdict::Unknown, None),
+    };
+    let kind = kind.unwrap_or_else(|| {
+        carbon::Kind::of_unit(
+            sensor
+                .attributes
+                .get("unit_of_measurement")
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+        )
+    });
+
+    let image_data = generate_trmnl_carbon_image(
+        &CarbonForecast {
+            kind,
+            unit: sensor
+                .attributes
+                .get("unit_of_measurement")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            locale: sensor
+                .attributes
+                .get(currency::LOCALE_ATTRIBUTE)
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            current,
+            verdict,
+            best,
+            upcoming,
+            offset_secs,
+        },
+        params.title.as_deref().unwrap_or(kind.title()),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Every device that has polled this server with its battery, signal,
+/// firmware, last check-in and screen; offline devices first, inverted.
+async fn render_trmnl_fleet(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlFleetQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL fleet status");
+
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let mut devices = state.devices.statuses();
+    devices.sort_by_key(|device| device.online);
+
+    let image_data = generate_trmnl_fleet_image(
+        &devices,
+        params.title.as_deref().unwrap_or("DEVICES"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// This week's lessons from school calendars as a grid of weekdays and
+/// start times, today's column inverted.
+async fn render_trmnl_timetable(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlTimetableQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL timetable");
+
+    let calendars: Vec<&str> = params
+        .calendar
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .collect();
+    if calendars.is_empty() {
+        return Err(AppError::BadRequest(
+            "No calendar provided. Use ?calendar=calendar.school".to_string(),
+        ));
+    }
+    let days = params.days.unwrap_or(5);
+    if days != 5 && days != 7 {
+        return Err(AppError::BadRequest(format!(
+            "Invalid days '{}'. Use 5 or 7",
+            days
+        )));
+    }
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    // Today's date in Home Assistant's time zone
+    let today = match state.get_start_of_today().await {
+        Ok(start) => timetable::local_day(&start),
+        Err(e) => {
+            warn!("Failed to get time zone, using UTC: {}", e);
+            None
+        }
+    }
+    .or_else(|| timetable::local_day(&timestamps::format_utc(SystemTime::now())))
+    .unwrap_or(0);
+
+    let mut week = timetable::Week::new(today, days);
+    let (start, end) = week.range();
+    for calendar in &calendars {
+        match state.get_calendar_events(calendar, &start, &end).await {
+            Ok(events) => week.add_events(&events),
+            Err(e) => warn!("Failed to get lessons from {}: {}", calendar, e),
+        }
+    }
+
+    let image_data = generate_trmnl_timetable_image(
+        &week,
+        params.title.as_deref().unwrap_or("TIMETABLE"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Today's medication or other reminders as a checklist in time order,
+/// ticked off by their `input_boolean` helpers.
+async fn render_trmnl_reminders(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlRemindersQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL reminders");
+
+    let mut items =
+        reminders::parse(params.items.as_deref().unwrap_or("")).map_err(AppError::BadRequest)?;
+    let calendars: Vec<&str> = params
+        .calendar
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .collect();
+    if items.is_empty() && calendars.is_empty() {
+        return Err(AppError::BadRequest(
+            "No reminders provided. Use ?items=08:00 Vitamin D=input_boolean.vitamin_d or ?calendar=calendar.medication"
+                .to_string(),
+        ));
+    }
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let day = match (
+        state.get_start_of_today().await,
+        state.get_start_of_day(-1).await,
+    ) {
+        (Ok(start), Ok(end)) => Some((start, end)),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("Failed to get the start of today for reminders: {}", e);
+            None
+        }
+    };
+    let start_of_today = day
+        .as_ref()
+        .and_then(|(start, _)| timestamps::parse(start))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    if let Some((start, end)) = &day {
+        for calendar in &calendars {
+            match state.get_calendar_events(calendar, start, end).await {
+                Ok(events) => items.extend(reminders::from_calendar(&events, start_of_today)),
+                Err(e) => warn!("Failed to get reminders from {}: {}", calendar, e),
+            }
+        }
+    }
+    items.sort_by_key(|reminder| reminder.minute);
+
+    let ids: Vec<String> = items.iter().filter_map(|r| r.entity.clone()).collect();
+    let helpers = state.get_sensor_states(&ids).await;
+    let minute_now = SystemTime::now()
+        .duration_since(start_of_today)
+        .map_or(0, |elapsed| {
+            (elapsed.as_secs() / 60).min(24 * 60 - 1) as u32
+        });
+    let rows: Vec<(reminders::Reminder, reminders::Status)> = items
+        .into_iter()
+        .map(|reminder| {
+            let taken = reminder.entity.as_ref().map(|id| {
+                helpers
+                    .iter()
+                    .find(|h| &h.entity_id == id)
+                    .is_some_and(|h| {
+                        reminders::taken(&h.state, h.last_changed.as_deref(), start_of_today)
+                    })
+            });
+            let status = reminders::status(&reminder, taken, minute_now);
+            (reminder, status)
+        })
+        .collect();
+
+    let image_data = generate_trmnl_reminders_image(
+        &rows,
+        params.title.as_deref().unwrap_or("REMINDERS"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Line chart of a sensor's recorded values over the last `hours`, for
+/// e.g. the temperature over the last day. The path may end in `.png`.
+async fn render_history_chart(
+    State(state): State<Arc<AppState>>,
+    Path(entity_id): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<HistoryQuery>,
+) -> Result<Response, AppError> {
+    let entity_id = entity_id
+        .strip_suffix(".png")
+        .unwrap_or(&entity_id)
+        .to_string();
+    let hours = params.hours.unwrap_or(24);
+    if !(1..=history::MAX_HOURS).contains(&hours) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid hours '{}'. Use 1 to {}",
+            hours,
+            history::MAX_HOURS
+        )));
+    }
+    info!("Rendering {}h history chart for {}", hours, entity_id);
+
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    // Clock times are in Home Assistant's time zone
+    let offset_secs = match state.get_start_of_today().await {
+        Ok(start) => timestamps::offset_of(&start).unwrap_or(0),
+        Err(e) => {
+            warn!("Failed to get time zone, using UTC: {}", e);
+            0
+        }
+    };
+    let end = SystemTime::now();
+    let start = end - Duration::from_secs(hours * 3600);
+
+    let sensor = state
+        .get_entity_state(&entity_id)
+        .await
+        .map_err(|e| AppError::NotFound(format!("Entity {}: {}", entity_id, e)))?;
+    let sensor = state.with_display_settings(sensor);
+    let recorded = state
+        .get_history(
+            std::slice::from_ref(&entity_id),
+            &timestamps::format_utc(start),
+            &timestamps::format_utc(end),
+        )
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get history: {}", e)))?;
+    let samples = report::parse_history(&recorded, start)
+        .remove(&entity_id)
+        .unwrap_or_default();
+
+    let title = match params.title {
+        Some(title) => title,
+        None => sensor
+            .attributes
+            .get("friendly_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&entity_id)
+            .to_uppercase(),
+    };
+    let image_data = generate_history_image(
+        &HistoryChart {
+            sensor,
+            samples,
+            start,
+            end,
+            offset_secs,
+            hours,
+        },
+        &title,
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate history chart: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Chart of a statistic's long-term statistics per hour, day, week or
+/// month, e.g. daily energy use over the last two weeks: bars of a meter's
+/// change, or a line of means over each period's range. The path may end
+/// in `.png`.
+async fn render_statistics_chart(
+    State(state): State<Arc<AppState>>,
+    Path(statistic_id): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<StatisticsQuery>,
+) -> Result<Response, AppError> {
+    let statistic_id = statistic_id
+        .strip_suffix(".png")
+        .unwrap_or(&statistic_id)
+        .to_string();
+    let period = match params.period.as_deref() {
+        Some(name) => statistics::Period::parse(name).map_err(AppError::BadRequest)?,
+        None => statistics::Period::Day,
+    };
+    let count = params.count.unwrap_or(period.default_count());
+    if !(1..=period.max_count()).contains(&count) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid count '{}'. Use 1 to {} for {}s",
+            count,
+            period.max_count(),
+            period.name()
+        )));
+    }
+    let stat = params
+        .stat
+        .as_deref()
+        .map(statistics::Stat::parse)
+        .transpose()
+        .map_err(AppError::BadRequest)?;
+    let si = SiPrefixes::from_query(params.si);
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let metadata = state
+        .get_statistics_metadata(&statistic_id)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get statistics: {}", e)))?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("No long-term statistics for {}", statistic_id))
+        })?;
+    let stat = stat.unwrap_or(metadata.default_stat());
+    info!(
+        "Rendering {} {} statistics chart for {} ({:?})",
+        count,
+        period.name(),
+        statistic_id,
+        stat
+    );
+
+    // Days and their labels are in Home Assistant's time zone
+    let now = SystemTime::now();
+    let (start, offset_secs) = match period.days_back(count) {
+        Some(days) => {
+            let start = state
+                .get_start_of_day(days)
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to get start of day: {}", e)))?;
+            let time = timestamps::parse(&start)
+                .ok_or_else(|| AppError::Internal(format!("Invalid start of day '{}'", start)))?;
+            (time, timestamps::offset_of(&start).unwrap_or(0))
+        }
+        None => {
+            let offset_secs = match state.get_start_of_today().await {
+                Ok(start) => timestamps::offset_of(&start).unwrap_or(0),
+                Err(e) => {
+                    warn!("Failed to get time zone, using UTC: {}", e);
+                    0
+                }
+            };
+            let since_hour = now
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                % 3600;
+            let start = now - Duration::from_secs(since_hour + u64::from(count - 1) * 3600);
+            (start, offset_secs)
+        }
+    };
+    let mut buckets = state
+        .get_statistics(&statistic_id, &timestamps::format_utc(start), period, stat)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get statistics: {}", e)))?;
+    buckets.drain(..buckets.len().saturating_sub(count as usize));
+
+    // Entities' own statistics are named and classed after the entity
+    let entity = if statistic_id.contains(':') {
+        None
+    } else {
+        state.get_entity_state(&statistic_id).await.ok()
+    };
+    let name = metadata
+        .name
+        .clone()
+        .or_else(|| {
+            entity
+                .as_ref()?
+                .attributes
+                .get("friendly_name")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| statistic_id.clone());
+    let mut attributes = serde_json::Map::new();
+    if let Some(unit) = &metadata.unit {
+        attributes.insert(
+            "unit_of_measurement".to_string(),
+            serde_json::Value::String(unit.clone()),
+        );
+    }
+    if let Some(device_class) = entity
+        .as_ref()
+        .and_then(|entity| entity.attributes.get("device_class"))
+    {
+        attributes.insert("device_class".to_string(), device_class.clone());
+    }
+    let sensor = EntityState {
+        entity_id: statistic_id.clone(),
+        state: String::new(),
+        attributes: serde_json::Value::Object(attributes),
+        last_changed: None,
+    };
+
+    let title = params.title.unwrap_or_else(|| name.to_uppercase());
+    let image_data = generate_statistics_image(
+        &StatisticsChart {
+            sensor,
+            buckets,
+            period,
+            count,
+            stat,
+            now,
+            offset_secs,
+            si,
+        },
+        &title,
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate statistics chart: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// A sound level sensor's last 24 hours with its quiet hours shaded, for
+/// nurseries and apartments, and how long it was louder than `threshold`
+/// during them.
+async fn render_trmnl_noise(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlNoiseQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL noise history for {}", params.sensor);
+
+    let quiet = match params.quiet.as_deref() {
+        Some(text) => refresh::Window::parse(text).ok_or_else(|| {
+            AppError::BadRequest(format!("Invalid quiet hours '{}', use HH:MM-HH:MM", text))
+        })?,
+        None => refresh::Window::NIGHT,
+    };
+    if let Some(threshold) = params.threshold.filter(|threshold| !threshold.is_finite()) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid threshold '{}'",
+            threshold
+        )));
+    }
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    // Quiet hours and clock times are in Home Assistant's time zone
+    let offset_secs = match state.get_start_of_today().await {
+        Ok(start) => timestamps::offset_of(&start).unwrap_or(0),
+        Err(e) => {
+            warn!("Failed to get time zone, using UTC: {}", e);
+            0
+        }
+    };
+    let now = SystemTime::now();
+    let start = now - noise::SPAN;
+
+    let sensor = state
+        .get_entity_state(&params.sensor)
+        .await
+        .map_err(|e| AppError::NotFound(format!("Noise sensor {}: {}", params.sensor, e)))?;
+    let history = state
+        .get_history(
+            std::slice::from_ref(&params.sensor),
+            &timestamps::format_utc(start),
+            &timestamps::format_utc(now),
+        )
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get history: {}", e)))?;
+    let samples = report::parse_history(&history, start)
+        .remove(&params.sensor)
+        .unwrap_or_default();
+
+    let image_data = generate_trmnl_noise_image(
+        &NoiseChart {
+            sensor,
+            samples,
+            start,
+            now,
+            offset_secs,
+            quiet,
+            threshold: params.threshold,
+        },
+        params.title.as_deref().unwrap_or("NOISE"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Entities ranked by their current value or a statistic of today, e.g.
+/// the top power consumers or the busiest motion sensors.
+async fn render_trmnl_leaderboard(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlLeaderboardQuery>,
+) -> Result<Response, AppError> {
+    let stat = match params.stat.as_deref() {
+        Some(name) => leaderboard::Stat::parse(name).map_err(AppError::BadRequest)?,
+        None => leaderboard::Stat::Value,
+    };
+    let limit = params.limit.unwrap_or(leaderboard::DEFAULT_LIMIT);
+    if !(1..=leaderboard::MAX_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            leaderboard::MAX_LIMIT
+        )));
+    }
+    let si = SiPrefixes::from_query(params.si);
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    let sensors: Vec<EntityState> =
+        match (params.sensors.as_deref(), params.device_class.as_deref()) {
+            (Some(sensors), None) => {
+                let sensor_ids: Vec<String> = sensors
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+                if sensor_ids.len() > leaderboard::MAX_SENSORS {
+                    return Err(AppError::BadRequest(format!(
+                        "Too many sensors for a leaderboard (max {} allowed)",
+                        leaderboard::MAX_SENSORS
+                    )));
+                }
+                state.get_sensor_states(&sensor_ids).await
+            }
+            (None, Some(device_class)) => state
+                .get_all_states()
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to fetch states: {}", e)))?
+                .into_iter()
+                .filter(|entity| {
+                    entity
+                        .attributes
+                        .get("device_class")
+                        .and_then(|v| v.as_str())
+                        == Some(device_class.trim())
+                })
+                .map(|entity| state.with_display_settings(entity))
+                .collect(),
+            (Some(_), Some(_)) => {
+                return Err(AppError::BadRequest(
+                    "Use either ?sensors= or ?device_class=, not both".to_string(),
+                ));
+            }
+            (None, None) => {
+                return Err(AppError::BadRequest(
+                    "No sensors provided. Use ?sensors=sensor1,sensor2 or ?device_class=power"
+                        .to_string(),
+                ));
+            }
+        };
+    info!(
+        "Rendering TRMNL leaderboard of {} entities by {:?}",
+        sensors.len(),
+        stat
+    );
+
+    // Today's statistics come from one history request for every entity
+    let now = SystemTime::now();
+    let mut recorded = Vec::new();
+    let mut start = now;
+    if stat.needs_history() && !sensors.is_empty() {
+        let start_of_today = state
+            .get_start_of_today()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to get start of today: {}", e)))?;
+        start = timestamps::parse(&start_of_today).ok_or_else(|| {
+            AppError::Internal(format!("Invalid start of today '{}'", start_of_today))
+        })?;
+        let entity_ids: Vec<String> = sensors.iter().map(|s| s.entity_id.clone()).collect();
+        recorded = state
+            .get_history(
+                &entity_ids,
+                &timestamps::format_utc(start),
+                &timestamps::format_utc(now),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to get history: {}", e)))?;
+    }
+    let series = report::parse_history(&recorded, start);
+
+    let entries: Vec<leaderboard::Entry> = sensors
+        .iter()
+        .filter_map(|sensor| {
+            let score = match stat {
+                leaderboard::Stat::Value => sensor.state.parse::<f64>().ok()?,
+                leaderboard::Stat::Count => recorded
+                    .iter()
+                    .find(|entries| {
+                        entries
+                            .iter()
+                            .find_map(|entry| entry.get("entity_id")?.as_str())
+                            == Some(sensor.entity_id.as_str())
+                    })
+                    .map_or(0, |entries| leaderboard::count_on(entries, start))
+                    as f64,
+                _ => {
+                    let stats = report::day_stats(series.get(&sensor.entity_id)?, now)?;
+                    match stat {
+                        leaderboard::Stat::Max => stats.max,
+                        leaderboard::Stat::Mean => stats.mean,
+                        _ => stats.increase,
+                    }
+                }
+            };
+            if !score.is_finite() {
+                return None;
+            }
+            let value = match stat {
+                leaderboard::Stat::Value => format_sensor_value(sensor, si),
+                leaderboard::Stat::Count => match score as u64 {
+                    1 => "once".to_string(),
+                    count => format!("{} times", count),
+                },
+                _ => format_stat_value(sensor, score, si),
+            };
+            let name = sensor
+                .attributes
+                .get("friendly_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&sensor.entity_id)
+                .to_string();
+            Some(leaderboard::Entry { name, score, value })
+        })
+        .collect();
+    if entries.is_empty() {
+        return Err(AppError::NotFound(
+            "None of the entities has a value to rank".to_string(),
+        ));
+    }
+    let entries = leaderboard::top(entries, limit);
+
+    let image_data = generate_trmnl_leaderboard_image(
+        &entries,
+        stat,
+        params.title.as_deref().unwrap_or("LEADERBOARD"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// A statistic of `sensor` in its unit, formatted like its state would be.
+fn format_stat_value(sensor: &EntityState, value: f64, si: SiPrefixes) -> String {
+    let mut attributes = serde_json::Map::new();
+    for name in ["unit_of_measurement", "device_class"] {
+        if let Some(attribute) = sensor.attributes.get(name) {
+            attributes.insert(name.to_string(), attribute.clone());
+        }
+    }
+    format_sensor_value(
+        &EntityState {
+            entity_id: sensor.entity_id.clone(),
+            state: format_range_value(value),
+            attributes: serde_json::Value::Object(attributes),
+            last_changed: None,
+        },
+        si,
+    )
+}
+
+/// Mostly white frame for a device's quiet hours, sparing the e-ink panel
+/// and the battery overnight.
+async fn render_trmnl_sleep(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlSleepQuery>,
+) -> Result<Response, AppError> {
+    let until = params.until.as_deref().map(str::trim);
+    if let Some(until) = until.filter(|until| refresh::clock_minutes(until).is_none()) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid until '{}', use HH:MM",
+            until
+        )));
+    }
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+
+    let image_data = generate_trmnl_sleep_image(
+        until,
+        &TrmnlOutput {
+            dither: Dither::None,
+            stamp: None,
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: false,
+            post: PostProcess::NONE,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Most allergens a pollen screen can show
+const TRMNL_MAX_ALLERGENS: usize = 8;
+
+/// Severity bars per allergen for today and tomorrow.
+async fn render_trmnl_pollen(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<TrmnlPollenQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering TRMNL pollen forecast");
+
+    let source = params.source.as_deref().unwrap_or("default");
+    let adapter = pollen::Adapter::preset(source)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("Unknown source '{}'. Use default or dwd", source))
+        })?
+        .with_overrides(
+            params.today.as_deref(),
+            params.tomorrow.as_deref(),
+            params.max,
+        )
+        .map_err(AppError::BadRequest)?;
+    let dither = parse_dither(params.dither.as_deref())?;
+    let rotation = parse_rotation(params.rotate)?;
+    let format = parse_bitmap_format(
+        params.format.as_deref(),
+        &headers,
+        params.depth,
+        params.bit_order.as_deref(),
+    )?;
+    let canvas = parse_canvas(params.width, params.height, params.margin)?;
+    let post = parse_post(params.post.as_deref())?;
+
+    // `Label=entity_id`, or just the entity ID to use its friendly name
+    let allergens: Vec<(Option<String>, String)> = params
+        .allergens
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((label, id)) => (Some(label.trim().to_string()), id.trim().to_string()),
+            None => (None, entry.to_string()),
+        })
+        .collect();
+    if allergens.is_empty() {
+        return Err(AppError::BadRequest(
+            "No allergens provided. Use ?allergens=Grass=sensor.grass_pollen".to_string(),
+        ));
+    }
+    if allergens.len() > TRMNL_MAX_ALLERGENS {
+        return Err(AppError::BadRequest(format!(
+            "Too many allergens for TRMNL display (max {} allowed)",
+            TRMNL_MAX_ALLERGENS
+        )));
+    }
+
+    let ids: Vec<String> = allergens.iter().map(|(_, id)| id.clone()).collect();
+    let sensors = state.get_sensor_states(&ids).await;
+    let forecasts: Vec<pollen::Forecast> = allergens
+        .into_iter()
+        .zip(&sensors)
+        .map(|((label, _), sensor)| pollen::Forecast {
+            label: label.unwrap_or_else(|| {
+                sensor
+                    .attributes
+                    .get("friendly_name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&sensor.entity_id)
+                    .to_string()
+            }),
+            today: adapter.today(&sensor.state, &sensor.attributes),
+            tomorrow: adapter.tomorrow(&sensor.attributes),
+        })
+        .collect();
+
+    let image_data = generate_trmnl_pollen_image(
+        &forecasts,
+        adapter.segments(),
+        params.title.as_deref().unwrap_or("POLLEN"),
+        params.side_label.as_deref(),
+        &state.abbreviations,
+        params.accessible.unwrap_or(false),
+        &TrmnlOutput {
+            dither: dither.unwrap_or(Dither::None),
+            stamp: state.render_stamp(),
+            layout: state.trmnl_layout(),
+            rotation,
+            invert: params.invert.unwrap_or(false),
+            post,
+            format,
+            canvas,
+        },
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+
+    Ok(create_image_response(
+        image_data,
+        format.content_type().to_string(),
+    ))
+}
+
+/// Plain-text version of a sensor dashboard (one `Name: value` line per
+/// sensor) for screen readers and braille displays.
+async fn render_sensor_summary(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SummaryQuery>,
+) -> Result<Response, AppError> {
+    info!("Rendering plain-text sensor summary");
+
+    let sensor_ids: Vec<String> = params
+        .sensors
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if sensor_ids.is_empty() {
+        return Err(AppError::BadRequest(
+            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
+        ));
+    }
+
+    if sensor_ids.len() > 15 {
+        return Err(AppError::BadRequest(
+            "Too many sensors (max 15 allowed)".to_string(),
+        ));
+    }
+
+    let units = parse_units(params.units.as_deref())?;
+    let sensor_data: Vec<EntityState> = state
+        .get_sensor_states(&sensor_ids)
+        .await
+        .into_iter()
+        .map(|sensor| with_units(sensor, &units))
+        .collect();
+
+    let mut summary = String::new();
+    if let Some(title) = params.title.as_deref() {
+        summary.push_str(title);
+        summary.push('\n');
+    }
+    for sensor in &sensor_data {
+        let name = sensor
+            .attributes
+            .get("friendly_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&sensor.entity_id);
+        summary.push_str(&format!(
+            "{}: {}\n",
+            name,
+            format_sensor_value(sensor, SiPrefixes::from_query(params.si))
+        ));
+    }
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        summary,
+    )
+        .into_response())
+}
+
+/// Most sensors on one report page
+const MAX_REPORT_SENSORS: usize = 6;
+
+/// Printable A4 PDF with each sensor's minimum, maximum and average over a
+/// day and a chart of its history - yesterday's by default, for pinning to
+/// the fridge.
+async fn render_daily_report(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ReportQuery>,
+) -> Result<Response, AppError> {
+    let sensor_ids: Vec<String> = params
+        .sensors
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if sensor_ids.is_empty() {
+        return Err(AppError::BadRequest(
+            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
+        ));
+    }
+
+    if sensor_ids.len() > MAX_REPORT_SENSORS {
+        return Err(AppError::BadRequest(format!(
+            "Too many sensors (max {} allowed)",
+            MAX_REPORT_SENSORS
+        )));
+    }
+
+    let days_ago = match params.day.as_deref().unwrap_or("yesterday") {
+        "yesterday" => 1,
+        "today" => 0,
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Unknown day '{}'. Use yesterday or today",
+                other
+            )));
+        }
+    };
+    info!("Rendering daily report for {} day(s) ago", days_ago);
+
+    let day = async {
+        let start = state.get_start_of_day(days_ago).await?;
+        let end = state.get_start_of_day(days_ago - 1).await?;
+        anyhow::Ok((start, end))
+    };
+    let (start_text, end_text) = day
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get the day's start: {}", e)))?;
+    let (Some(start), Some(end)) = (timestamps::parse(&start_text), timestamps::parse(&end_text))
+    else {
+        return Err(AppError::Internal(format!(
+            "Unexpected day start '{}'",
+            start_text
+        )));
+    };
+    let offset_secs = timestamps::offset_of(&start_text).unwrap_or(0);
+
+    let sensors = state.get_sensor_states(&sensor_ids).await;
+    let history = state
+        .get_history(&sensor_ids, &start_text, &end_text)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get history: {}", e)))?;
+    let history = report::parse_history(&history, start);
+
+    let date = start_text.get(..10).unwrap_or(&start_text);
+    let title = params.title.as_deref().unwrap_or("Daily Report");
+    let subtitle = if days_ago == 0 {
+        format!("{} (so far)", date)
+    } else {
+        date.to_string()
+    };
+    let page = generate_daily_report(
+        &sensors,
+        &history,
+        (title, &subtitle),
+        (start, end, offset_secs),
+        &state.abbreviations,
+    );
+    let pdf = pdf::single_page(&page, pdf::A4, title)
+        .map_err(|e| AppError::Internal(format!("Failed to write PDF: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"report-{}.pdf\"", date),
+            ),
+        ],
+        pdf,
+    )
+        .into_response())
+}
+
+/// A4 at 150 dpi
+const REPORT_PAGE: (u32, u32) = (1240, 1754);
+
+/// The daily report page: a header, then for each sensor its name, the
+/// day's statistics and a chart of its history with a tick every three
+/// hours.
+fn generate_daily_report(
+    sensors: &[EntityState],
+    history: &HashMap<String, Vec<(SystemTime, f64)>>,
+    (title, subtitle): (&str, &str),
+    (start, end, offset_secs): (SystemTime, SystemTime, i64),
+    abbreviations: &Abbreviations,
+) -> GrayImage {
+    let (width, height) = REPORT_PAGE;
+    let margin = 80;
+    let black = Luma([0u8]);
+    let gray = Luma([90u8]);
+    let mut image = GrayImage::from_pixel(width, height, Luma([255u8]));
+
+    let title = truncate_to_width(title, width - 2 * margin, 5);
+    draw_trmnl_text_bold(&mut image, margin, margin, &title, black, 5);
+    draw_trmnl_text(&mut image, margin, margin + 60, subtitle, gray, 3);
+    for y in (margin + 100)..(margin + 103) {
+        for x in margin..(width - margin) {
+            image.put_pixel(x, y, black);
+        }
+    }
+
+    let top = margin + 130;
+    let block_height = ((height - margin - top) / sensors.len().max(1) as u32).min(420);
+    let now = SystemTime::now();
+    let label_scale = 2;
+
+    for (i, sensor) in sensors.iter().enumerate() {
+        let y = top + i as u32 * block_height;
+        let name = sensor
+            .attributes
+            .get("friendly_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&sensor.entity_id);
+        let name = abbreviations.shorten(name, width - 2 * margin, 3);
+        draw_trmnl_text_bold(&mut image, margin, y, &name, black, 3);
+
+        let samples = history
+            .get(&sensor.entity_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let Some(stats) = report::day_stats(samples, end.min(now)) else {
+            let note = "No numeric history for this day";
+            draw_trmnl_text(&mut image, margin, y + 40, note, gray, label_scale);
+            continue;
+        };
+
+        // Meters report what they counted, everything else its range
+        let unit = sensor
+            .attributes
+            .get("unit_of_measurement")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let with_unit = |value: f64| {
+            if unit.is_empty() {
+                format_range_value(value)
+            } else {
+                format!("{} {}", format_range_value(value), unit)
+            }
+        };
+        let state_class = sensor
+            .attributes
+            .get("state_class")
+            .and_then(|v| v.as_str());
+        let summary = if matches!(state_class, Some("total" | "total_increasing")) {
+            format!(
+                "Used {}   Reading {}",
+                with_unit(stats.increase),
+                with_unit(stats.last)
+            )
+        } else {
+            format!(
+                "Min {}   Max {}   Avg {}",
+                with_unit(stats.min),
+                with_unit(stats.max),
+                with_unit(stats.mean)
+            )
+        };
+        let summary = truncate_to_width(&summary, width - 2 * margin, label_scale);
+        draw_trmnl_text(&mut image, margin, y + 40, &summary, black, label_scale);
+
+        // Chart, with the minimum and maximum labelled on the left
+        let label_width = 120;
+        let area = chart::Area {
+            x: margin + label_width,
+            y: y + 80,
+            width: width - 2 * margin - label_width,
+            height: block_height.saturating_sub(140).max(40),
+        };
+        let axis = stats.axis();
+        let fraction = |value: f64| (value - axis.0) / (axis.1 - axis.0);
+        for value in [stats.max, stats.min] {
+            let label =
+                truncate_to_width(&format_range_value(value), label_width - 10, label_scale);
+            let x = area.x - 10 - text_width(&label, label_scale);
+            let y = area.y_at(fraction(value)).saturating_sub(4 * label_scale);
+            draw_trmnl_text(&mut image, x, y, &label, gray, label_scale);
+        }
+
+        let points = report::chart_points(samples, start, end, now, axis);
+        area.draw_axis(&mut image, black);
+        area.draw_series(&mut image, &points, black, 3);
+
+        let span = end.duration_since(start).unwrap_or_default().as_secs_f64();
+        for hour in (0..=24).step_by(3) {
+            let fraction = (hour * 3600) as f64 / span.max(1.0);
+            area.draw_tick(&mut image, fraction, black);
+            if hour == 24 {
+                continue;
+            }
+            let clock = timestamps::format_clock(
+                start + std::time::Duration::from_secs(hour * 3600),
+                offset_secs,
+            );
+            let x = area
+                .x_at(fraction)
+                .saturating_sub(text_width(&clock, label_scale) / 2);
+            draw_trmnl_text(&mut image, x, area.bottom() + 12, &clock, gray, label_scale);
+        }
+        if now > start && now < end {
+            let elapsed = now.duration_since(start).unwrap_or_default().as_secs_f64();
+            area.draw_marker(&mut image, elapsed / span.max(1.0), black);
+        }
+    }
+
+    image
+}
+
+fn generate_trmnl_image(
+    sensors: &[EntityState],
+    title: Option<&str>,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    si: SiPrefixes,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    // Attention states move to the top, the order otherwise kept
+    let mut sensors: Vec<&EntityState> = sensors.iter().collect();
+    sensors.sort_by_key(|sensor| !is_attention(sensor));
+
+    // Create a new grayscale image with white background
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+
+    // Draw header section
+    let header_text = title.unwrap_or("SENSOR STATUS");
+    draw_trmnl_header(&mut image, header_text, accessible);
+
+    // Calculate layout - larger line height for bigger titles. The
+    // accessibility profile keeps a fixed tall row and drops sensors that
+    // don't fit rather than shrinking the text.
+    let content_start_y = 80;
+    let available_height = height - content_start_y - 20;
+    let line_height = if accessible {
+        90
+    } else if sensors.len() > 6 {
+        (available_height / sensors.len() as u32).clamp(TRMNL_MIN_LINE_HEIGHT, 55)
+    } else {
+        65
+    };
+
+    // Measure how many rows fit. When some don't, the lowest-priority rows
+    // make way for a last one saying how many were left out.
+    let capacity = ((height - 10 - content_start_y) / line_height) as usize;
+    let mut hidden = 0;
+    if sensors.len() > capacity {
+        let shown = capacity.saturating_sub(1);
+        let ranks: Vec<_> = sensors.iter().map(|sensor| row_rank(sensor)).collect();
+        hidden = sensors.len() - shown;
+        sensors = priority::keep(&ranks, shown)
+            .into_iter()
+            .map(|i| sensors[i])
+            .collect();
+    }
+
+    // Draw each sensor
+    for (i, sensor) in sensors.iter().enumerate() {
+        let y_pos = content_start_y + (i as u32 * line_height);
+        if y_pos + line_height <= height - 10 {
+            draw_trmnl_sensor_line(
+                &mut image,
+                y_pos,
+                line_height,
+                sensor,
+                abbreviations,
+                accessible,
+                si,
+            );
+            if is_attention(sensor) {
+                invert_trmnl_row(&mut image, y_pos, line_height);
+            }
+        }
+    }
+    if hidden > 0 && capacity > 0 {
+        let more = format!("+{} more", hidden);
+        let y_pos = content_start_y + sensors.len() as u32 * line_height;
+        if accessible {
+            draw_trmnl_text_bold(&mut image, 40, y_pos + 8, &more, Luma([0u8]), 3);
+        } else {
+            draw_trmnl_text(&mut image, 40, y_pos + 8, &more, Luma([0u8]), 2);
+        }
+    }
+
+    // Draw rotated side label in the left margin
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+/// Turn a drawn row white on black, leaving the side label margin and the
+/// separator below it alone.
+fn invert_trmnl_row(image: &mut GrayImage, y_pos: u32, line_height: u32) {
+    let width = image.width();
+    let bottom = (y_pos + line_height.saturating_sub(4)).min(image.height());
+    for y in y_pos..bottom {
+        for x in 32..width.saturating_sub(12) {
+            let pixel = image.get_pixel_mut(x, y);
+            pixel.0 = [255 - pixel[0]];
+        }
+    }
+}
+
+/// Camera snapshot scaled to fit the area below the header, centered.
+fn generate_trmnl_camera_image(
+    photo: &image::DynamicImage,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    crop: Option<&Crop>,
+    adjustments: &Adjustments,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, false);
+
+    // Leave the left margin free when a side label is drawn
+    let content_start_y = 80;
+    let left = if side_label.is_some() { 40 } else { 20 };
+    let area_width = width - left - 20;
+    let area_height = height - content_start_y - 10;
+
+    let cropped;
+    let photo = match crop {
+        Some(crop) => {
+            cropped = crop.apply(photo, Some(area_width as f32 / area_height as f32))?;
+            &cropped
+        }
+        None => photo,
+    };
+    let mut photo = photo
+        .resize(
+            area_width,
+            area_height,
+            image::imageops::FilterType::Triangle,
+        )
+        .to_luma8();
+    adjustments.apply_luma(&mut photo);
+    let x = left + (area_width - photo.width()) / 2;
+    let y = content_start_y + (area_height - photo.height()) / 2;
+    image::imageops::replace(&mut image, &photo, x as i64, y as i64);
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+/// `a - b`, or `None` unless both sensors have a numeric state.
+fn sensor_delta(a: &EntityState, b: &EntityState) -> Option<f64> {
+    Some(a.state.parse::<f64>().ok()? - b.state.parse::<f64>().ok()?)
+}
+
+fn generate_trmnl_delta_image(
+    (a, b): (&EntityState, &EntityState),
+    rules: &[delta::Rule],
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, color: Luma<u8>, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, color, scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, color, scale);
+        }
+    };
+    let centered_x = |text: &str, scale: u32| (width - text_width(text, scale).min(width)) / 2;
+
+    // Both readings in columns either side of a divider
+    let (name_scale, value_scale) = if accessible { (3, 5) } else { (2, 4) };
+    let column_width = width / 2 - 60;
+    for (sensor, left) in [(a, 40), (b, width / 2 + 20)] {
+        let name = sensor
+            .attributes
+            .get("friendly_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&sensor.entity_id);
+        let name = abbreviations.shorten(name, column_width, name_scale);
+        draw(&mut image, left, 85, &name, Luma([0u8]), name_scale);
+
+        let value = truncate_to_width(
+            &format_sensor_value(sensor, SiPrefixes::default()),
+            column_width,
+            value_scale,
+        );
+        draw(&mut image, left, 125, &value, Luma([0u8]), value_scale);
+    }
+    for y in 85..170 {
+        image.put_pixel(width / 2, y, Luma([0u8]));
+    }
+
+    // The difference, as large as fits
+    let delta = sensor_delta(a, b);
+    let delta_text = match delta {
+        Some(delta) => {
+            let unit = a
+                .attributes
+                .get("unit_of_measurement")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            format!("{:+.1} {}", delta, unit).trim_end().to_string()
+        }
+        None => "--".to_string(),
+    };
+    let delta_scale = (1..=9)
+        .rev()
+        .find(|&scale| text_width(&delta_text, scale) <= width - 80)
+        .unwrap_or(1);
+    draw(
+        &mut image,
+        centered_x(&delta_text, delta_scale),
+        190,
+        &delta_text,
+        Luma([0u8]),
+        delta_scale,
+    );
+
+    // Recommendation, white on black
+    if let Some(message) = delta.and_then(|delta| delta::recommendation(rules, delta)) {
+        let scale = if accessible { 4 } else { 3 };
+        let message = truncate_to_width(message, width - 100, scale);
+        let box_width = text_width(&message, scale) + 40;
+        let box_x = (width - box_width) / 2;
+        let box_y = 320;
+        for y in box_y..(box_y + 8 * scale + 24) {
+            for x in box_x..(box_x + box_width) {
+                image.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+        draw(
+            &mut image,
+            box_x + 20,
+            box_y + 12,
+            &message,
+            Luma([255u8]),
+            scale,
+        );
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+    }
+
+    output.finish(image)
+}
+
+fn generate_trmnl_pollen_image(
+    forecasts: &[pollen::Forecast],
+    segments: u32,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    const BAR_WIDTH: u32 = 200;
+    // Right-aligned, so wider layouts give the allergen names the room
+    let columns = [("TODAY", width - 470), ("TOMORROW", width - 240)];
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+
+    for (heading, x) in columns {
+        draw(&mut image, x, 80, heading, 2);
+    }
+
+    let content_start_y = 110;
+    let line_height = ((height - 20 - content_start_y) / forecasts.len().max(1) as u32).min(70);
+    let segment_width = (BAR_WIDTH - 4 * (segments - 1)) / segments;
+
+    for (i, forecast) in forecasts.iter().enumerate() {
+        let y = content_start_y + i as u32 * line_height;
+        let label = abbreviations.shorten(&forecast.label, columns[0].1 - 60, 2);
+        draw(&mut image, 40, y + 8, &label, 2);
+
+        for ((_, x), level) in columns.into_iter().zip([forecast.today, forecast.tomorrow]) {
+            let Some(level) = level else {
+                draw(&mut image, x, y + 8, "--", 2);
+                continue;
+            };
+
+            // One block per step, filled up to the level
+            let filled = (level * segments as f64).round() as u32;
+            for segment in 0..segments {
+                let left = x + segment * (segment_width + 4);
+                for py in (y + 6)..(y + 24) {
+                    for px in left..(left + segment_width) {
+                        let edge = py == y + 6
+                            || py == y + 23
+                            || px == left
+                            || px == left + segment_width - 1;
+                        if segment < filled || edge {
+                            image.put_pixel(px, py, Luma([0u8]));
+                        }
+                    }
+                }
+            }
+
+            if line_height >= 50 {
+                draw(
+                    &mut image,
+                    x,
+                    y + 30,
+                    pollen::label(level),
+                    if accessible { 2 } else { 1 },
+                );
+            }
+        }
+
+        // Separator between allergens
+        if i + 1 < forecasts.len() {
+            for x in 40..(width - 40) {
+                image.put_pixel(x, y + line_height - 4, Luma([200u8]));
+            }
+        }
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+/// A day of tides between local midnights, with the local UTC offset for
+/// clock times.
+struct TideDay {
+    events: Vec<tide::TideEvent>,
+    start: SystemTime,
+    end: SystemTime,
+    offset_secs: i64,
+}
+
+impl TideDay {
+    /// Position of `time` across the day, from 0.0 to 1.0.
+    fn fraction(&self, time: SystemTime) -> f64 {
+        let span = self.end.duration_since(self.start).unwrap_or_default();
+        let elapsed = time.duration_since(self.start).unwrap_or_default();
+        elapsed.as_secs_f64() / span.as_secs_f64().max(1.0)
+    }
+
+    /// The first high and low tide still to come.
+    fn next(&self, high: bool) -> Option<&tide::TideEvent> {
+        let now = SystemTime::now();
+        self.events
+            .iter()
+            .find(|event| event.high == high && event.time >= now)
+    }
+}
+
+fn generate_trmnl_tide_image(
+    day: &TideDay,
+    marine: &[EntityState],
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+    let (text_scale, label_scale) = if accessible { (3, 2) } else { (2, 1) };
+
+    if day.events.is_empty() {
+        draw(&mut image, 60, height / 2 - 40, "No tide data", 4);
+    } else {
+        // Next high and low, soonest first
+        let mut next: Vec<_> = [true, false]
+            .into_iter()
+            .filter_map(|high| day.next(high))
+            .collect();
+        next.sort_by_key(|event| event.time);
+        let mut x = 60;
+        for event in next {
+            let text = format!(
+                "{} {}",
+                if event.high { "HIGH" } else { "LOW" },
+                timestamps::format_clock(event.time, day.offset_secs)
+            );
+            let text = match event.height {
+                Some(height) => format!("{} ({:.1})", text, height),
+                None => text,
+            };
+            draw(&mut image, x, 80, &text, text_scale);
+            x += text_width(&text, text_scale) + 60;
+        }
+
+        // The day's curve, leaving room for the marine values below
+        let area = chart::Area {
+            x: 60,
+            y: 115,
+            width: width - 100,
+            height: if marine.is_empty() {
+                height - 190
+            } else {
+                height - 280
+            },
+        };
+        let samples = 24 * 6;
+        let points: Vec<(f64, f64)> = (0..=samples)
+            .filter_map(|i| {
+                let fraction = i as f64 / samples as f64;
+                let time = day.start + (day.end.duration_since(day.start).ok()? / samples) * i;
+                Some((fraction, tide::level_at(&day.events, time)?))
+            })
+            .collect();
+        area.draw_axis(&mut image, Luma([0u8]));
+        area.draw_series(&mut image, &points, Luma([0u8]), 3);
+
+        // Tide events label the x axis
+        let label_y = area.bottom() + 10;
+        for event in day
+            .events
+            .iter()
+            .filter(|event| event.time >= day.start && event.time <= day.end)
+        {
+            let fraction = day.fraction(event.time);
+            area.draw_tick(&mut image, fraction, Luma([0u8]));
+            let clock = timestamps::format_clock(event.time, day.offset_secs);
+            let x = area
+                .x_at(fraction)
+                .saturating_sub(text_width(&clock, label_scale) / 2)
+                .min(width - 20 - text_width(&clock, label_scale));
+            draw(&mut image, x, label_y, &clock, label_scale);
+        }
+
+        let now = SystemTime::now();
+        if now >= day.start && now <= day.end {
+            area.draw_marker(&mut image, day.fraction(now), Luma([0u8]));
+        }
+    }
+
+    // Wind and wave readings side by side
+    if !marine.is_empty() {
+        let top = height - 110;
+        let column_width = (width - 100) / marine.len() as u32;
+        for (i, sensor) in marine.iter().enumerate() {
+            let left = 60 + i as u32 * column_width;
+            let name = sensor
+                .attributes
+                .get("friendly_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&sensor.entity_id);
+            let name = abbreviations.shorten(name, column_width - 20, label_scale + 1);
+            draw(&mut image, left, top, &name, label_scale + 1);
+
+            let value_scale = text_scale + 1;
+            let value = truncate_to_width(
+                &format_sensor_value(sensor, SiPrefixes::default()),
+                column_width - 20,
+                value_scale,
+            );
+            draw(&mut image, left, top + 35, &value, value_scale);
+
+            if i > 0 {
+                for y in top..(top + 80) {
+                    image.put_pixel(left - 15, y, Luma([0u8]));
+                }
+            }
+        }
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+    }
+
+    output.finish(image)
+}
+
+fn generate_trmnl_flights_image(
+    flights: &[flights::Flight],
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    const ROW_HEIGHT: u32 = 62;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+    let scale = if accessible { 4 } else { 3 };
+
+    let content_start_y = 85;
+    if flights.is_empty() {
+        let text = "No aircraft overhead";
+        draw(
+            &mut image,
+            (width - text_width(text, scale)) / 2,
+            height / 2,
+            text,
+            scale,
+        );
+    }
+
+    // Whole rows only; the last one counts any that don't fit
+    let rows = ((height - 20 - content_start_y) / ROW_HEIGHT) as usize;
+    let shown = if flights.len() > rows { rows - 1 } else { rows };
+    for (i, flight) in flights.iter().take(shown).enumerate() {
+        let y = content_start_y + i as u32 * ROW_HEIGHT;
+        draw_trmnl_icon(&mut image, 45, y + 4, &flights::ICON, 3);
+
+        let callsign = truncate_to_width(&flight.callsign, 220, scale);
+        draw(&mut image, 100, y + 8, &callsign, scale);
+        if let Some(altitude) = &flight.altitude {
+            let altitude = truncate_to_width(altitude, 190, scale);
+            draw(&mut image, 330, y + 8, &altitude, scale);
+        }
+        if let Some(route) = &flight.route {
+            let route = truncate_to_width(route, width - 40 - 540, scale);
+            draw(&mut image, 540, y + 8, &route, scale);
+        }
+
+        for x in 40..(width - 40) {
+            image.put_pixel(x, y + ROW_HEIGHT - 6, Luma([200u8]));
+        }
+    }
+    if flights.len() > shown {
+        let more = format!("+{} more", flights.len() - shown);
+        let y = content_start_y + shown as u32 * ROW_HEIGHT;
+        draw(&mut image, 100, y + 8, &more, scale);
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+struct CarbonForecast {
+    kind: carbon::Kind,
+    unit: String,
+    /// The sensor's `CURRENCY_LOCALE`, for prices
+    locale: Option<String>,
+    current: Option<f64>,
+    verdict: carbon::Verdict,
+    /// Lowest upcoming point
+    best: Option<carbon::Point>,
+    upcoming: Vec<carbon::Point>,
+    offset_secs: i64,
+}
+
+/// The verdict in large type, the current and best values below it and the
+/// forecast as bars, the good times among them filled.
+fn generate_trmnl_carbon_image(
+    forecast: &CarbonForecast,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+    let (text_scale, label_scale) = if accessible { (3, 2) } else { (2, 1) };
+
+    let verdict = forecast.verdict.text(forecast.kind);
+    draw_trmnl_text_bold(&mut image, 60, 80, &verdict, Luma([0u8]), 6);
+
+    let value = |value: f64| {
+        carbon::format_value(
+            value,
+            &forecast.unit,
+            forecast.kind,
+            forecast.locale.as_deref(),
+        )
+    };
+    let mut details = match forecast.current {
+        Some(current) => format!("Now {}", value(current)),
+        None => "Now unavailable".to_string(),
+    };
+    if let (Some(best), carbon::Verdict::Wait(_)) = (forecast.best, forecast.verdict) {
+        details.push_str(&format!(
+            " - best {} at {}",
+            value(best.value),
+            timestamps::format_clock(best.time, forecast.offset_secs)
+        ));
+    }
+    let details = truncate_to_width(&details, width - 100, text_scale);
+    draw(&mut image, 60, 145, &details, text_scale);
+
+    if !forecast.upcoming.is_empty() {
+        let area = chart::Area {
+            x: 60,
+            y: 190,
+            width: width - 100,
+            height: height - 250,
+        };
+        let points = &forecast.upcoming;
+        let highest = points
+            .iter()
+            .map(|point| point.value)
+            .fold(f64::NEG_INFINITY, f64::max);
+        // Bars start from zero unless values go negative, as prices can
+        let lowest = points.iter().map(|point| point.value).fold(0.0, f64::min);
+        let span = (highest - lowest).max(f64::EPSILON);
+        let good = carbon::good_threshold(points);
+
+        let slot = 1.0 / points.len() as f64;
+        for (i, point) in points.iter().enumerate() {
+            let left = i as f64 * slot;
+            // A gap between bars, unless they're too thin for one
+            let gap = if points.len() > 48 { 0.0 } else { slot * 0.2 };
+            area.draw_bar(
+                &mut image,
+                (left + gap / 2.0, left + slot - gap / 2.0),
+                ((point.value - lowest) / span).max(0.02),
+                point.value <= good,
+                Luma([0u8]),
+            );
+        }
+        area.draw_axis(&mut image, Luma([0u8]));
+
+        // About six clock labels along the axis
+        let every = points.len().div_ceil(6);
+        for (i, point) in points.iter().enumerate().step_by(every) {
+            let clock = timestamps::format_clock(point.time, forecast.offset_secs);
+            let x = area
+                .x_at(i as f64 * slot)
+                .min(width - 20 - text_width(&clock, label_scale));
+            draw(&mut image, x, area.bottom() + 10, &clock, label_scale);
+        }
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+    }
+
+    output.finish(image)
+}
+
+/// Two lines per device: its ID and battery charge, then signal, firmware,
+/// last check-in and screen.
+fn generate_trmnl_fleet_image(
+    devices: &[DeviceStatus],
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    const ROW_HEIGHT: u32 = 70;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+    // Accessible rows are emboldened rather than enlarged to keep two lines
+    let (name_scale, detail_scale) = (3, 2);
+
+    let content_start_y = 85;
+    if devices.is_empty() {
+        let text = "No devices have checked in";
+        draw(
+            &mut image,
+            (width - text_width(text, detail_scale)) / 2,
+            height / 2,
+            text,
+            detail_scale,
+        );
+    }
+
+    // Whole rows only; the last one counts any that don't fit
+    let rows = ((height - 20 - content_start_y) / ROW_HEIGHT) as usize;
+    let shown = if devices.len() > rows { rows - 1 } else { rows };
+    for (i, device) in devices.iter().take(shown).enumerate() {
+        let y = content_start_y + i as u32 * ROW_HEIGHT;
+
+        let battery = match device.battery_percent {
+            Some(percent) => format!("{}%", percent),
+            None => "-".to_string(),
+        };
+        let battery_x = width - 50 - text_width(&battery, name_scale);
+        let name = device.name.as_deref().unwrap_or(&device.id);
+        let name = truncate_to_width(name, battery_x - 60, name_scale);
+        draw(&mut image, 45, y + 6, &name, name_scale);
+        draw(&mut image, battery_x, y + 6, &battery, name_scale);
+
+        let mut details = Vec::new();
+        if !device.online {
+            details.push("OFFLINE".to_string());
+        }
+        if let Some(rssi) = device.rssi {
+            details.push(format!("{} dBm", rssi));
+        }
+        if let Some(version) = &device.firmware_version {
+            details.push(format!("FW {}", version));
+        }
+        if let Some(secs) = device.seconds_since_seen {
+            let seen = SystemTime::now() - Duration::from_secs(secs);
+            details.push(timestamps::format_ago(seen));
+        }
+        if let Some(screen) = &device.screen {
+            details.push(screen.clone());
+        }
+        let details = truncate_to_width(&details.join(" · "), width - 95, detail_scale);
+        draw(&mut image, 45, y + 40, &details, detail_scale);
+
+        if device.online {
+            for x in 40..(width - 40) {
+                image.put_pixel(x, y + ROW_HEIGHT - 6, Luma([200u8]));
+            }
+        } else {
+            invert_trmnl_row(&mut image, y, ROW_HEIGHT);
+        }
+    }
+    if devices.len() > shown {
+        let more = format!("+{} more", devices.len() - shown);
+        let y = content_start_y + shown as u32 * ROW_HEIGHT;
+        draw(&mut image, 45, y + 6, &more, name_scale);
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+/// Weekday columns and a row per lesson start time, with a row for all-day
+/// events above when there are any. Rows that don't fit are left out.
+fn generate_trmnl_timetable_image(
+    week: &timetable::Week,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    let scale = if accessible { 3 } else { 2 };
+    let text_height = 8 * scale;
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+
+    let content_start_y = 85;
+    let left = 40;
+    let right = width - 20;
+    let bottom = height - 15;
+    let time_width = text_width("00:00", scale) + 16;
+    let column_width = (right - left - time_width) / week.days as u32;
+    let column_x = |column: usize| left + time_width + column as u32 * column_width;
+
+    // Rows: the weekdays, all-day events if any, then one per start time
+    let mut rows: Vec<(String, Vec<Option<String>>)> = Vec::new();
+    if week.all_day.iter().any(Option::is_some) {
+        rows.push((String::new(), week.all_day.clone()));
+    }
+    for (minute, cells) in &week.slots {
+        rows.push((
+            format!("{:02}:{:02}", minute / 60, minute % 60),
+            cells.clone(),
+        ));
+    }
+    let header_height = text_height + 16;
+    let min_row_height = text_height + 12;
+    let available = bottom - content_start_y - header_height;
+    let fit = (available / min_row_height) as usize;
+    rows.truncate(fit);
+    let row_height = if rows.is_empty() {
+        min_row_height
+    } else {
+        (available / rows.len() as u32).min(text_height * 3)
+    };
+    let grid_bottom = content_start_y + header_height + rows.len() as u32 * row_height;
+
+    for (column, name) in timetable::WEEKDAYS.iter().take(week.days).enumerate() {
+        let x = column_x(column) + (column_width.saturating_sub(text_width(name, scale))) / 2;
+        draw(&mut image, x, content_start_y + 8, name);
+    }
+    for (i, (time, cells)) in rows.iter().enumerate() {
+        let y = content_start_y + header_height + i as u32 * row_height;
+        let text_y = y + (row_height - text_height) / 2;
+        draw(&mut image, left, text_y, time);
+        for (column, cell) in cells.iter().enumerate() {
+            if let Some(text) = cell {
+                let text = abbreviations.shorten(text, column_width - 12, scale);
+                draw(&mut image, column_x(column) + 6, text_y, &text);
+            }
+        }
+    }
+
+    // Grid lines: under the weekdays and between rows and columns
+    for x in left..right {
+        image.put_pixel(x, content_start_y + header_height - 2, Luma([0u8]));
+        image.put_pixel(x, content_start_y + header_height - 1, Luma([0u8]));
+        for i in 1..=rows.len() as u32 {
+            image.put_pixel(
+                x,
+                content_start_y + header_height + i * row_height - 1,
+                Luma([0u8]),
+            );
+        }
+    }
+    for column in 0..=week.days {
+        let x = column_x(column).min(right - 1);
+        for y in content_start_y..grid_bottom {
+            image.put_pixel(x, y, Luma([0u8]));
+        }
+    }
+
+    if let Some(today) = week.today {
+        for y in content_start_y..grid_bottom {
+            for x in column_x(today) + 1..column_x(today + 1) {
+                let pixel = image.get_pixel_mut(x, y);
+                pixel.0 = [255 - pixel[0]];
+            }
+        }
+    }
+
+    if rows.is_empty() {
+        let text = "No lessons this week";
+        draw(
+            &mut image,
+            width.saturating_sub(text_width(text, scale)) / 2,
+            (grid_bottom + bottom) / 2,
+            text,
+        );
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+/// One row per reminder: a checkbox, ticked once taken, then the time and
+/// label. Reminders whose time has passed unticked are inverted.
+fn generate_trmnl_reminders_image(
+    rows: &[(reminders::Reminder, reminders::Status)],
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    let (row_height, scale, box_size) = if accessible { (76, 4, 40) } else { (56, 3, 30) };
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, Luma([0u8]), scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, Luma([0u8]), scale);
+        }
+    };
+
+    let content_start_y = 85;
+    if rows.is_empty() {
+        let text = "Nothing for today";
+        draw(
+            &mut image,
+            width.saturating_sub(text_width(text, scale)) / 2,
+            height / 2,
+            text,
+        );
+    }
+
+    // Whole rows only; the last one counts any that don't fit
+    let fit = ((height - 20 - content_start_y) / row_height) as usize;
+    let shown = if rows.len() > fit { fit - 1 } else { fit };
+    let time_x = 45 + box_size + 20;
+    let label_x = time_x + text_width("All day", scale) + 24;
+    for (i, (reminder, status)) in rows.iter().take(shown).enumerate() {
+        let y = content_start_y + i as u32 * row_height;
+        let text_y = y + (row_height - 8 * scale) / 2 - 2;
+
+        // Checkbox with a thick outline, ticked with a bold check mark
+        let box_y = y + (row_height - box_size) / 2 - 2;
+        for by in 0..box_size {
+            for bx in 0..box_size {
+                let edge = bx < 3 || by < 3 || bx >= box_size - 3 || by >= box_size - 3;
+                if edge {
+                    image.put_pixel(45 + bx, box_y + by, Luma([0u8]));
+                }
+            }
+        }
+        if *status == reminders::Status::Taken {
+            // Short stroke down to the bottom, then a long one up to the right
+            let points = |t: f32| -> (f32, f32) {
+                let s = box_size as f32;
+                if t < 0.35 {
+                    let t = t / 0.35;
+                    (s * (0.2 + 0.2 * t), s * (0.5 + 0.25 * t))
+                } else {
+                    let t = (t - 0.35) / 0.65;
+                    (s * (0.4 + 0.4 * t), s * (0.75 - 0.55 * t))
+                }
+            };
+            for step in 0..=200 {
+                let (cx, cy) = points(step as f32 / 200.0);
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let px = 45 + (cx as i32 + dx).max(0) as u32;
+                        let py = box_y + (cy as i32 + dy).max(0) as u32;
+                        image.put_pixel(px, py, Luma([0u8]));
+                    }
+                }
+            }
+        }
+
+        draw(&mut image, time_x, text_y, &reminder.time());
+        let label =
+            abbreviations.shorten(&reminder.label, width.saturating_sub(label_x + 45), scale);
+        draw(&mut image, label_x, text_y, &label);
+
+        if *status == reminders::Status::Missed {
+            invert_trmnl_row(&mut image, y, row_height);
+        } else {
+            for x in 40..(width - 40) {
+                image.put_pixel(x, y + row_height - 4, Luma([200u8]));
+            }
+        }
+    }
+    if rows.len() > shown {
+        let more = format!("+{} more", rows.len() - shown);
+        let y = content_start_y + shown as u32 * row_height;
+        draw(&mut image, 45, y + 10, &more);
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(
+            &mut image,
+            content_start_y,
+            height - 10,
+            label,
+            abbreviations,
+        );
+    }
+
+    output.finish(image)
+}
+
+/// A sensor's recorded values from `start` until `end`, with the local UTC
+/// offset for clock times.
+struct HistoryChart {
+    sensor: EntityState,
+    samples: Vec<(SystemTime, f64)>,
+    start: SystemTime,
+    end: SystemTime,
+    offset_secs: i64,
+    hours: u64,
+}
+
+/// The current value above a line chart of the history, its value axis
+/// labelled at the bottom, middle and top, clock ticks along the time
+/// axis, and the lowest and highest values marked where they were reached.
+fn generate_history_image(
+    chart: &HistoryChart,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    let black = Luma([0u8]);
+
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    let title = abbreviations.shorten(title, width - 80, 3);
+    draw_trmnl_header(&mut image, &title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, black, scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, black, scale);
+        }
+    };
+    let (text_scale, label_scale) = if accessible { (3, 2) } else { (2, 1) };
+
+    let current = format!(
+        "Now {}",
+        format_sensor_value(&chart.sensor, SiPrefixes::default())
+    );
+    draw(&mut image, 60, 80, &current, text_scale + 1);
+    let period = format!("Last {} hours", chart.hours);
+    let period_x = width - 60 - text_width(&period, label_scale + 1);
+    draw(&mut image, period_x, 86, &period, label_scale + 1);
+
+    let label_width = 80;
+    let area = chart::Area {
+        x: 40 + label_width,
+        y: 140,
+        width: width - 80 - label_width,
+        height: height - 200,
+    };
+    let span = chart.end.duration_since(chart.start).unwrap_or_default();
+    for (fraction, time) in history::hour_ticks(
+        chart.start,
+        span,
+        history::tick_step(chart.hours),
+        chart.offset_secs,
+    ) {
+        area.draw_tick(&mut image, fraction, black);
+        let clock = timestamps::format_clock(time, chart.offset_secs);
+        let x = area
+            .x_at(fraction)
+            .saturating_sub(text_width(&clock, label_scale) / 2);
+        draw(&mut image, x, area.bottom() + 10, &clock, label_scale);
+    }
+    area.draw_axis(&mut image, black);
+
+    let samples = history::downsample(&chart.samples, chart.start, chart.end, area.width as usize);
+    let Some(stats) = report::day_stats(&samples, chart.end) else {
+        draw(
+            &mut image,
+            60,
+            height / 2,
+            "No numeric history",
+            text_scale + 1,
+        );
+        if let Some(label) = side_label {
+            draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+        }
+        return output.finish(image);
+    };
+
+    // Value axis labelled at its bottom, middle and top, with a dotted
+    // gridline at each; more headroom than usual leaves room for the
+    // min and max labels
+    let axis = match stats.max - stats.min {
+        range if range > 0.0 => (stats.min - range * 0.15, stats.max + range * 0.15),
+        _ => stats.axis(),
+    };
+    let fraction = |value: f64| (value - axis.0) / (axis.1 - axis.0);
+    for step in 0..=2 {
+        let level = step as f64 / 2.0;
+        let value = axis.0 + (axis.1 - axis.0) * level;
+        let y = area.y_at(level);
+        for x in (area.x..area.x + area.width).step_by(6) {
+            image.put_pixel(x, y, black);
+        }
+        let label = truncate_to_width(&format_range_value(value), label_width - 10, label_scale);
+        let x = area.x - 10 - text_width(&label, label_scale);
+        draw(
+            &mut image,
+            x,
+            y.saturating_sub(4 * label_scale),
+            &label,
+            label_scale,
+        );
+    }
+
+    let points = report::chart_points(&samples, chart.start, chart.end, chart.end, axis);
+    area.draw_series(&mut image, &points, black, 3);
+
+    // The lowest and highest values, labelled beside a dot where they were
+    // first reached
+    let first_at = |value: f64| samples.iter().find(|&&(_, v)| v == value).map(|&(t, _)| t);
+    let marks = [("Max", stats.max, true), ("Min", stats.min, false)];
+    for (name, value, above) in marks {
+        let Some(time) = first_at(value) else {
+            continue;
+        };
+        let at = time.duration_since(chart.start).unwrap_or_default();
+        let x = area.x_at(at.as_secs_f64() / span.as_secs_f64().max(1.0));
+        let y = area.y_at(fraction(value));
+        for dy in 0..7 {
+            for dx in 0..7 {
+                let (px, py) = ((x + dx).saturating_sub(3), (y + dy).saturating_sub(3));
+                if px < width && py < height {
+                    image.put_pixel(px, py, black);
+                }
+            }
+        }
+
+        let label = format!(
+            "{} {} {}",
+            name,
+            format_range_value(value),
+            timestamps::format_clock(time, chart.offset_secs)
+        );
+        let label_w = text_width(&label, label_scale);
+        let label_h = 8 * label_scale;
+        let label_x = x
+            .saturating_sub(label_w / 2)
+            .clamp(area.x + 6, (area.x + area.width).saturating_sub(label_w));
+        // On its own side of the point unless that runs off the chart
+        let fits_above = y >= area.y + label_h - 10;
+        let fits_below = y + label_h + 10 <= area.bottom();
+        let label_y = if (above && fits_above) || !fits_below {
+            y.saturating_sub(label_h + 8)
+        } else {
+            y + 10
+        };
+        draw(&mut image, label_x, label_y, &label, label_scale);
+    }
 
-    // Generate the status image
-    let image_data = generate_status_image(&entity_state, width, height)
-        .map_err(|e| AppError::Internal(format!("Failed to generate image: {}", e)))?;
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+    }
 
-    Ok(create_image_response(image_data, "image/png".to_string()))
+    output.finish(image)
 }
 
-fn generate_status_image(
-    entity: &EntityState,
-    width: u32,
-    height: u32,
-) -> anyhow::Result<bytes::Bytes> {
-    // For now, let's use a simpler approach without external fonts
-    // We'll create a basic text rendering without rusttype
-    generate_simple_status_image(entity, width, height)
+/// Long-term statistics of a sensor, with the local UTC offset for labels.
+struct StatisticsChart {
+    /// Carries the statistic's unit and device class for formatting
+    sensor: EntityState,
+    buckets: Vec<statistics::Bucket>,
+    period: statistics::Period,
+    count: u32,
+    stat: statistics::Stat,
+    now: SystemTime,
+    offset_secs: i64,
+    si: SiPrefixes,
 }
 
-fn generate_simple_status_image(
-    entity: &EntityState,
-    width: u32,
-    height: u32,
+/// The total or average above the chart, its value axis labelled at the
+/// bottom, middle and top, and a date or clock label under every few
+/// periods. Changes are bars, the current period's hollow while it's still
+/// counting; other statistics a line, the mean over each period's range.
+fn generate_statistics_image(
+    chart: &StatisticsChart,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
 ) -> anyhow::Result<bytes::Bytes> {
-    // Create a new RGB image with white background
-    let mut image: RgbImage =
-        ImageBuffer::from_fn(width, height, |_x, _y| Rgb([255u8, 255u8, 255u8]));
+    let (width, height) = output.layout;
+    let black = Luma([0u8]);
 
-    // Draw a gradient background based on entity state
-    let (bg_start, bg_end) = get_status_gradient(&entity.state);
-    for y in 0..height {
-        let blend_factor = y as f32 / height as f32;
-        let blended_color = blend_colors(bg_start, bg_end, blend_factor);
-        for x in 0..width {
-            image.put_pixel(x, y, blended_color);
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    let title = abbreviations.shorten(title, width - 80, 3);
+    draw_trmnl_header(&mut image, &title, accessible);
+
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, black, scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, black, scale);
         }
-    }
+    };
+    let (text_scale, label_scale) = if accessible { (3, 2) } else { (2, 1) };
 
-    // Draw a decorative border
-    draw_border(&mut image, width, height);
+    let period = chart.period.describe(chart.count);
+    let period_x = width - 60 - text_width(&period, label_scale + 1);
+    draw(&mut image, period_x, 86, &period, label_scale + 1);
 
-    // Draw header section with entity name
-    let entity_name = entity
-        .attributes
-        .get("friendly_name")
-        .and_then(|v| v.as_str())
-        .unwrap_or(&entity.entity_id);
+    let stat = chart.stat;
+    let Some(summary) = statistics::summary(&chart.buckets, stat) else {
+        draw(&mut image, 60, height / 2, "No statistics", text_scale + 1);
+        if let Some(label) = side_label {
+            draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+        }
+        return output.finish(image);
+    };
+    let summary = format!(
+        "{} {}",
+        stat.caption(),
+        format_stat_value(&chart.sensor, summary, chart.si)
+    );
+    // A size smaller rather than cut short when it meets the period
+    let summary_scale = if text_width(&summary, text_scale + 1) <= period_x - 80 {
+        text_scale + 1
+    } else {
+        text_scale
+    };
+    let summary = truncate_to_width(&summary, period_x - 80, summary_scale);
+    draw(&mut image, 60, 80, &summary, summary_scale);
+
+    let label_width = 80;
+    let area = chart::Area {
+        x: 40 + label_width,
+        y: 140,
+        width: width - 80 - label_width,
+        height: height - 200,
+    };
+    let slot = 1.0 / chart.buckets.len() as f64;
+    let center = |i: usize| (i as f64 + 0.5) * slot;
+
+    // Bars rise from zero unless a change is negative, as a meter that
+    // also counts exports can; lines are scaled to their range
+    let plotted = chart.buckets.iter().flat_map(|bucket| match stat {
+        statistics::Stat::Mean => [bucket.mean, bucket.min, bucket.max],
+        _ => [bucket.value(stat), None, None],
+    });
+    let (lowest, highest) = plotted
+        .flatten()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    let axis = if stat == statistics::Stat::Change {
+        let lowest = lowest.min(0.0);
+        (lowest, highest.max(lowest + f64::EPSILON) * 1.1)
+    } else if highest > lowest {
+        let padding = (highest - lowest) * 0.15;
+        (lowest - padding, highest + padding)
+    } else {
+        (lowest - 1.0, highest + 1.0)
+    };
+    let fraction = |value: f64| (value - axis.0) / (axis.1 - axis.0);
+
+    for step in 0..=2 {
+        let level = step as f64 / 2.0;
+        let value = axis.0 + (axis.1 - axis.0) * level;
+        let y = area.y_at(level);
+        for x in (area.x..area.x + area.width).step_by(6) {
+            image.put_pixel(x, y, black);
+        }
+        let label = truncate_to_width(&format_range_value(value), label_width - 10, label_scale);
+        let x = area.x - 10 - text_width(&label, label_scale);
+        draw(
+            &mut image,
+            x,
+            y.saturating_sub(4 * label_scale),
+            &label,
+            label_scale,
+        );
+    }
 
-    draw_header_section(&mut image, width, entity_name);
+    if stat == statistics::Stat::Change {
+        // A gap between bars, unless they're too thin for one
+        let gap = if chart.buckets.len() > 48 {
+            0.0
+        } else {
+            slot * 0.2
+        };
+        for (i, bucket) in chart.buckets.iter().enumerate() {
+            let Some(change) = bucket.change else {
+                continue;
+            };
+            let left = i as f64 * slot;
+            area.draw_column(
+                &mut image,
+                (left + gap / 2.0, left + slot - gap / 2.0),
+                (fraction(0.0), fraction(change)),
+                !bucket.is_current(chart.now),
+                black,
+            );
+        }
+        if axis.0 < 0.0 {
+            area.draw_level(&mut image, fraction(0.0), black);
+        }
+    } else {
+        if stat == statistics::Stat::Mean {
+            for (i, bucket) in chart.buckets.iter().enumerate() {
+                if let (Some(min), Some(max)) = (bucket.min, bucket.max) {
+                    let left = i as f64 * slot;
+                    area.draw_range(
+                        &mut image,
+                        (left + slot * 0.1, left + slot * 0.9),
+                        (fraction(min), fraction(max)),
+                        black,
+                    );
+                }
+            }
+        }
+        let points: Vec<(f64, f64)> = chart
+            .buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, bucket)| Some((center(i), fraction(bucket.value(stat)?))))
+            .collect();
+        area.draw_series(&mut image, &points, black, 3);
+        // A dot per period while they're far enough apart to tell
+        if points.len() <= 31 {
+            for &(x, y) in &points {
+                let (x, y) = (area.x_at(x), area.y_at(y));
+                for dy in 0..7 {
+                    for dx in 0..7 {
+                        let (px, py) = ((x + dx).saturating_sub(3), (y + dy).saturating_sub(3));
+                        if px < width && py < height {
+                            image.put_pixel(px, py, black);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    area.draw_axis(&mut image, black);
+
+    // About seven labels along the axis, under the periods they name
+    let every = chart.buckets.len().div_ceil(7);
+    for (i, bucket) in chart.buckets.iter().enumerate().step_by(every) {
+        let label = chart.period.label(bucket.start, chart.offset_secs);
+        area.draw_tick(&mut image, center(i), black);
+        let x = area
+            .x_at(center(i))
+            .saturating_sub(text_width(&label, label_scale) / 2)
+            .min(width - 20 - text_width(&label, label_scale));
+        draw(&mut image, x, area.bottom() + 10, &label, label_scale);
+    }
 
-    // Draw main status section with enhanced formatting
-    let formatted_status = format_entity_status(&entity);
-    draw_status_section(&mut image, width, &formatted_status, &entity.state);
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+    }
 
-    // Draw additional entity information
-    draw_entity_info(&mut image, width, height, entity);
+    output.finish(image)
+}
 
-    // Draw status indicator (visual representation of state)
-    draw_status_indicator(&mut image, width, height, &entity.state);
+/// A noise sensor's history from `start` until `now`, with the local UTC
+/// offset for clock times.
+struct NoiseChart {
+    sensor: EntityState,
+    samples: Vec<(SystemTime, f64)>,
+    start: SystemTime,
+    now: SystemTime,
+    offset_secs: i64,
+    quiet: refresh::Window,
+    threshold: Option<f64>,
+}
 
-    // Convert image to PNG bytes
-    let mut buffer = Vec::new();
-    {
-        let mut cursor = Cursor::new(&mut buffer);
-        image
-            .write_to(&mut cursor, image::ImageOutputFormat::Png)
-            .map_err(|e| anyhow::anyhow!("Failed to encode image: {}", e))?;
-    }
+/// The current level large, the day's peak and the time spent above the
+/// threshold in quiet hours beside it, and the 24-hour chart below with a
+/// tick every three hours.
+fn generate_trmnl_noise_image(
+    chart: &NoiseChart,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    let black = Luma([0u8]);
 
-    Ok(bytes::Bytes::from(buffer))
-}
+    let mut image: GrayImage = ImageBuffer::from_fn(width, height, |_x, _y| Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
 
-async fn render_multi_sensor_status(
-    State(state): State<Arc<AppState>>,
-    Query(params): Query<MultiSensorQuery>,
-) -> Result<Response, AppError> {
-    info!("Rendering multi-sensor status image");
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, black, scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, black, scale);
+        }
+    };
+    let (text_scale, label_scale) = if accessible { (3, 2) } else { (2, 1) };
 
-    // Parse sensor list
-    let sensor_ids: Vec<String> = params
-        .sensors
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
+    let unit = chart
+        .sensor
+        .attributes
+        .get("unit_of_measurement")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let with_unit = |value: f64| {
+        if unit.is_empty() {
+            format_range_value(value)
+        } else {
+            format!("{} {}", format_range_value(value), unit)
+        }
+    };
 
-    if sensor_ids.is_empty() {
-        return Err(AppError::BadRequest(
-            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
+    // Current level, then what the last 24 hours came to
+    let current = format_sensor_value(&chart.sensor, SiPrefixes::default());
+    let value_scale = text_scale + 2;
+    draw(&mut image, 60, 80, &current, value_scale);
+
+    let left = 60 + text_width(&current, value_scale) + 40;
+    let mut lines = Vec::new();
+    if let Some((time, value)) = noise::peak(&chart.samples) {
+        lines.push(format!(
+            "Peak {} at {}",
+            with_unit(value),
+            timestamps::format_clock(time, chart.offset_secs)
         ));
     }
-
-    if sensor_ids.len() > 10 {
-        return Err(AppError::BadRequest(
-            "Too many sensors (max 10 allowed)".to_string(),
+    if let Some(threshold) = chart.threshold {
+        let loud = noise::loud_in_quiet_hours(
+            &chart.samples,
+            threshold,
+            chart.quiet,
+            chart.now,
+            chart.offset_secs,
+        );
+        lines.push(format!(
+            "Loud in quiet hours: {}",
+            noise::format_duration(loud)
         ));
     }
+    // Smaller when a line doesn't fit beside a wide value
+    let available = width.saturating_sub(left + 40);
+    let line_scale = if lines
+        .iter()
+        .all(|line| text_width(line, label_scale + 1) <= available)
+    {
+        label_scale + 1
+    } else {
+        label_scale
+    };
+    for (i, line) in lines.iter().enumerate() {
+        let line = truncate_to_width(line, available, line_scale);
+        draw(&mut image, left, 78 + i as u32 * 26, &line, line_scale);
+    }
 
-    // Fetch all sensor states
-    let mut sensor_data = Vec::new();
-    for sensor_id in &sensor_ids {
-        match state.get_entity_state(sensor_id).await {
-            Ok(entity_state) => sensor_data.push(entity_state),
-            Err(e) => {
-                warn!("Failed to get state for sensor {}: {}", sensor_id, e);
-                // Continue with other sensors, we'll show an error for this one
-                sensor_data.push(EntityState {
-                    entity_id: sensor_id.clone(),
-                    state: "unavailable".to_string(),
-                    attributes: serde_json::Value::Object(serde_json::Map::new()),
-                });
-            }
-        }
+    // The chart, the axis range labelled on the left
+    let label_width = 70;
+    let area = chart::Area {
+        x: 40 + label_width,
+        y: 150,
+        width: width - 80 - label_width,
+        height: height - 210,
+    };
+    for band in noise::quiet_bands(chart.quiet, chart.start, chart.offset_secs) {
+        area.draw_band(&mut image, band, black);
     }
 
-    // Calculate dimensions
-    let width = params.width.unwrap_or(500);
-    let base_height = 80; // Header height
-    let line_height = 40; // Height per sensor
-    let padding = 20; // Bottom padding
-    let height = params
-        .height
-        .unwrap_or(base_height + (sensor_data.len() as u32 * line_height) + padding);
+    let values = chart.samples.iter().map(|&(_, value)| value);
+    let (low, high) = values
+        .chain(chart.threshold)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    if low > high {
+        draw(&mut image, 60, height / 2, "No history", 4);
+    } else {
+        let padding = if high > low { (high - low) * 0.1 } else { 1.0 };
+        let axis = (low - padding, high + padding);
+        let fraction = |value: f64| (value - axis.0) / (axis.1 - axis.0);
+        // The threshold is labelled too, in place of a range label it would
+        // overlap
+        let threshold_y = chart
+            .threshold
+            .map(|threshold| area.y_at(fraction(threshold)));
+        let labels = chart
+            .threshold
+            .into_iter()
+            .chain([high, low].into_iter().filter(|value| {
+                let y = area.y_at(fraction(*value));
+                threshold_y.is_none_or(|threshold_y| y.abs_diff(threshold_y) > 10 * label_scale)
+            }));
+        for value in labels {
+            let label =
+                truncate_to_width(&format_range_value(value), label_width - 10, label_scale);
+            let x = area.x - 10 - text_width(&label, label_scale);
+            let y = area.y_at(fraction(value)).saturating_sub(4 * label_scale);
+            draw(&mut image, x, y, &label, label_scale);
+        }
+        if let Some(threshold) = chart.threshold {
+            area.draw_level(&mut image, fraction(threshold), black);
+        }
 
-    // Generate the combined image
-    let image_data =
-        generate_multi_sensor_image(&sensor_data, width, height, params.title.as_deref())
-            .map_err(|e| AppError::Internal(format!("Failed to generate image: {}", e)))?;
+        let points = report::chart_points(&chart.samples, chart.start, chart.now, chart.now, axis);
+        area.draw_series(&mut image, &points, black, 3);
+    }
+    area.draw_axis(&mut image, black);
+
+    for (fraction, time) in history::hour_ticks(chart.start, noise::SPAN, 3, chart.offset_secs) {
+        area.draw_tick(&mut image, fraction, black);
+        let clock = timestamps::format_clock(time, chart.offset_secs);
+        let x = area
+            .x_at(fraction)
+            .saturating_sub(text_width(&clock, label_scale) / 2);
+        draw(&mut image, x, area.bottom() + 10, &clock, label_scale);
+    }
+
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
+    }
 
-    Ok(create_image_response(image_data, "image/png".to_string()))
+    output.finish(image)
 }
 
-async fn render_trmnl_sensors(
-    State(state): State<Arc<AppState>>,
-    Query(params): Query<TrmnlQuery>,
-) -> Result<Response, AppError> {
-    info!("Rendering TRMNL sensor display");
+/// Numbered rows, each name over a bar as long as its share of the
+/// leader's score, with the value on the right.
+fn generate_trmnl_leaderboard_image(
+    entries: &[leaderboard::Entry],
+    stat: leaderboard::Stat,
+    title: &str,
+    side_label: Option<&str>,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    let black = Luma([0u8]);
 
-    // Parse sensor list
-    let sensor_ids: Vec<String> = params
-        .sensors
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
+    let mut image = GrayImage::from_pixel(width, height, Luma([255u8]));
+    draw_trmnl_header(&mut image, title, accessible);
 
-    if sensor_ids.is_empty() {
-        return Err(AppError::BadRequest(
-            "No sensors provided. Use ?sensors=sensor1,sensor2".to_string(),
-        ));
+    let draw = |image: &mut GrayImage, x: u32, y: u32, text: &str, scale: u32| {
+        if accessible {
+            draw_trmnl_text_bold(image, x, y, text, black, scale);
+        } else {
+            draw_trmnl_text(image, x, y, text, black, scale);
+        }
+    };
+
+    draw_trmnl_text(&mut image, 40, 76, stat.caption(), Luma([90u8]), 2);
+
+    let (name_scale, rank_scale) = if accessible { (3, 4) } else { (2, 3) };
+    let content_start_y = 104;
+    let line_height = ((height - 20 - content_start_y) / entries.len().max(1) as u32)
+        .min(if accessible { 110 } else { 80 });
+    let name_x = 40 + text_width("10", rank_scale) + 20;
+    let value_width = if accessible { 260 } else { 200 };
+    let bar_right = width - 40 - value_width - 20;
+    let bar_height = (line_height / 4).clamp(6, 20);
+
+    for (i, (entry, fraction)) in entries
+        .iter()
+        .zip(leaderboard::bar_fractions(entries))
+        .enumerate()
+    {
+        let y = content_start_y + i as u32 * line_height;
+        let name_height = 8 * name_scale;
+        let bar_top = y + name_height + 8;
+
+        let rank = (i + 1).to_string();
+        // Centered on the name and bar together
+        let rank_y = (y + bar_top + bar_height).saturating_sub(8 * rank_scale) / 2;
+        draw_trmnl_text_bold(&mut image, 40, rank_y, &rank, black, rank_scale);
+
+        let name = abbreviations.shorten(&entry.name, bar_right - name_x, name_scale);
+        draw(&mut image, name_x, y, &name, name_scale);
+
+        // Outlined track, filled up to the entry's share
+        let filled = name_x + ((bar_right - name_x) as f64 * fraction).round() as u32;
+        for py in bar_top..bar_top + bar_height {
+            for px in name_x..=bar_right {
+                let edge = py == bar_top || py + 1 == bar_top + bar_height || px == bar_right;
+                if px < filled || edge {
+                    image.put_pixel(px, py, black);
+                }
+            }
+        }
+
+        let value = truncate_to_width(&entry.value, value_width, name_scale);
+        let value_x = width - 40 - text_width(&value, name_scale);
+        let value_y = (bar_top + bar_height / 2).saturating_sub(4 * name_scale);
+        draw(&mut image, value_x, value_y, &value, name_scale);
     }
 
-    if sensor_ids.len() > 15 {
-        return Err(AppError::BadRequest(
-            "Too many sensors for TRMNL display (max 15 allowed)".to_string(),
-        ));
+    if let Some(label) = side_label {
+        draw_trmnl_side_label(&mut image, 80, height - 10, label, abbreviations);
     }
 
-    // Fetch all sensor states
-    let mut sensor_data = Vec::new();
-    for sensor_id in &sensor_ids {
-        match state.get_entity_state(sensor_id).await {
-            Ok(entity_state) => sensor_data.push(entity_state),
-            Err(e) => {
-                warn!("Failed to get state for sensor {}: {}", sensor_id, e);
-                sensor_data.push(EntityState {
-                    entity_id: sensor_id.clone(),
-                    state: "unavailable".to_string(),
-                    attributes: serde_json::Value::Object(serde_json::Map::new()),
-                });
+    output.finish(image)
+}
+
+/// A small crescent moon in the middle of a white frame, with the time the
+/// device wakes at below it.
+fn generate_trmnl_sleep_image(
+    until: Option<&str>,
+    output: &TrmnlOutput,
+) -> anyhow::Result<bytes::Bytes> {
+    let (width, height) = output.layout;
+    const MOON_RADIUS: i64 = 24;
+
+    let mut image = GrayImage::from_pixel(width, height, Luma([255u8]));
+    let (cx, cy) = (i64::from(width / 2), i64::from(height / 2) - 20);
+    // The moon's disc less the same disc shifted up and right
+    let (bite_x, bite_y) = (cx + MOON_RADIUS / 2, cy - MOON_RADIUS / 3);
+    for y in cy - MOON_RADIUS..=cy + MOON_RADIUS {
+        for x in cx - MOON_RADIUS..=cx + MOON_RADIUS {
+            let inside = |ox: i64, oy: i64| (x - ox).pow(2) + (y - oy).pow(2) <= MOON_RADIUS.pow(2);
+            if inside(cx, cy) && !inside(bite_x, bite_y) {
+                image.put_pixel(x as u32, y as u32, Luma([0u8]));
             }
         }
     }
 
-    // Generate TRMNL image (800x480, 1-bit)
-    let image_data = generate_trmnl_image(&sensor_data, params.title.as_deref())
-        .map_err(|e| AppError::Internal(format!("Failed to generate TRMNL image: {}", e)))?;
+    match until {
+        Some(until) => {
+            let text = format!("Sleeping until {}", until);
+            let x = width.saturating_sub(text_width(&text, 2)) / 2;
+            draw_trmnl_text(
+                &mut image,
+                x,
+                (cy + MOON_RADIUS) as u32 + 20,
+                &text,
+                Luma([0u8]),
+                2,
+            );
+        }
+        // Only the moon, by design
+        None => watchdog::textless(),
+    }
 
-    Ok(create_image_response(image_data, "image/png".to_string()))
+    output.finish(image)
 }
 
-fn generate_trmnl_image(
+fn generate_multi_sensor_image(
     sensors: &[EntityState],
+    width: u32,
+    height: u32,
     title: Option<&str>,
+    style: &RenderStyle,
 ) -> anyhow::Result<bytes::Bytes> {
-    const WIDTH: u32 = 800;
-    const HEIGHT: u32 = 480;
+    if style.svg {
+        return Ok(style.finish_svg(multi_sensor_svg(sensors, width, height, title, style)));
+    }
 
-    // Create a new grayscale image with white background
-    let mut image: GrayImage = ImageBuffer::from_fn(WIDTH, HEIGHT, |_x, _y| Luma([255u8]));
+    let image = style.finish(render_multi_sensor_frame(
+        sensors, width, height, title, style,
+    ));
 
-    // Draw header section
-    let header_text = title.unwrap_or("SENSOR STATUS");
-    draw_trmnl_header(&mut image, header_text);
+    style.encode(&image)
+}
 
-    // Calculate layout - larger line height for bigger titles
-    let content_start_y = 80;
-    let available_height = HEIGHT - content_start_y - 20;
-    let line_height = if sensors.len() > 6 {
-        (available_height / sensors.len() as u32).min(55)
-    } else {
-        65
-    };
+const MULTI_SENSOR_START_Y: u32 = 60;
+/// Default `/multi-status` width
+const MULTI_SENSOR_WIDTH: u32 = 500;
 
-    // Draw each sensor
+/// Vector version of the `/multi-status` layout.
+fn multi_sensor_svg(
+    sensors: &[EntityState],
+    width: u32,
+    height: u32,
+    title: Option<&str>,
+    style: &RenderStyle,
+) -> Svg {
+    let theme = &style.theme;
+    let scale = style.text_scale();
+    let mut svg = Svg::new(width, height);
+
+    let (bg_start, bg_end) = style.gradient(theme.background_top, theme.background_bottom);
+    svg.gradient_rect(0, 0, width, height, bg_start, bg_end);
+    svg.outline(0, 0, width, height, 3, theme.frame);
+
+    // Header
+    let (header_start, header_end) = style.gradient(theme.header_top, theme.header_bottom);
+    svg.gradient_rect(8, 8, width - 16, 42, header_start, header_end);
+    svg.outline(8, 8, width - 16, 42, 1, theme.header_border);
+    let title = truncate_to_width(
+        title.unwrap_or("Sensor Status"),
+        width.saturating_sub(30),
+        scale,
+    );
+    let text_x = centered_text_x(&title, width, scale);
+    style.svg_text(&mut svg, text_x, 29 - 4 * scale, &title, theme.header_text);
+
+    // Sensors
+    let line_height = style.multi_sensor_line_height();
+    let row_height = line_height - 5;
     for (i, sensor) in sensors.iter().enumerate() {
-        let y_pos = content_start_y + (i as u32 * line_height);
-        if y_pos + line_height <= HEIGHT - 10 {
-            draw_trmnl_sensor_line(&mut image, y_pos, line_height, sensor);
+        let y_pos = MULTI_SENSOR_START_Y + (i as u32 * line_height);
+        if y_pos + line_height - 10 >= height {
+            continue;
+        }
+
+        let row = SensorRow::new(sensor, width, style);
+        svg.rect(15, y_pos, width - 30, row_height, row.background);
+        svg.rect(15, y_pos, width - 30, 1, theme.panel_border);
+        svg.rect(
+            15,
+            y_pos + row_height - 1,
+            width - 30,
+            1,
+            theme.panel_border,
+        );
+        style.svg_text(&mut svg, 20, y_pos + 8, &row.name, row.name_color);
+        style.svg_text(
+            &mut svg,
+            row.value_x,
+            y_pos + 10 + 10 * scale,
+            &row.value,
+            row.value_color,
+        );
+        svg.circle((width - 21) as f32, (y_pos + 14) as f32, 4.0, row.indicator);
+    }
+
+    svg
+}
+
+/// Short one-shot GIF for LCD dashboards: rows whose value changed since the
+/// previous render flash a few times, then the animation settles on the
+/// normal frame.
+fn generate_multi_sensor_animation(
+    sensors: &[EntityState],
+    width: u32,
+    height: u32,
+    title: Option<&str>,
+    style: &RenderStyle,
+    changed: &HashSet<String>,
+) -> anyhow::Result<bytes::Bytes> {
+    use image::{Delay, Frame, codecs::gif::GifEncoder};
+
+    let base = render_multi_sensor_frame(sensors, width, height, title, style);
+
+    let line_height = style.multi_sensor_line_height();
+    let mut flashed = base.clone();
+    for (i, sensor) in sensors.iter().enumerate() {
+        let y_pos = MULTI_SENSOR_START_Y + (i as u32 * line_height);
+        if changed.contains(&sensor.entity_id) && y_pos + line_height - 10 < height {
+            for y in y_pos..(y_pos + line_height - 5).min(height) {
+                for x in 15..width.saturating_sub(15) {
+                    let pixel = flashed.get_pixel_mut(x, y);
+                    pixel.0 = [255 - pixel[0], 255 - pixel[1], 255 - pixel[2]];
+                }
+            }
         }
     }
 
-    // Draw border around entire display
-    draw_trmnl_border(&mut image);
+    let base = image::DynamicImage::ImageRgb8(style.finish(base)).to_rgba8();
+    let flashed = image::DynamicImage::ImageRgb8(style.finish(flashed)).to_rgba8();
+    let flash_delay = Delay::from_numer_denom_ms(350, 1);
 
-    // Convert to 1-bit PNG
-    let image_data = convert_to_1bit_png(&image)?;
+    let mut frames = Vec::new();
+    if !changed.is_empty() {
+        for _ in 0..3 {
+            frames.push(Frame::from_parts(flashed.clone(), 0, 0, flash_delay));
+            frames.push(Frame::from_parts(base.clone(), 0, 0, flash_delay));
+        }
+    }
+    frames.push(Frame::from_parts(
+        base,
+        0,
+        0,
+        Delay::from_numer_denom_ms(1000, 1),
+    ));
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut buffer);
+        encoder
+            .encode_frames(frames)
+            .map_err(|e| anyhow::anyhow!("Failed to encode GIF: {}", e))?;
+    }
 
-    Ok(bytes::Bytes::from(image_data))
+    Ok(bytes::Bytes::from(buffer))
 }
 
-fn generate_multi_sensor_image(
+fn render_multi_sensor_frame(
     sensors: &[EntityState],
     width: u32,
     height: u32,
     title: Option<&str>,
-) -> anyhow::Result<bytes::Bytes> {
+    style: &RenderStyle,
+) -> RgbImage {
+    let theme = &style.theme;
+
     // Create a new RGB image with white background
     let mut image: RgbImage =
         ImageBuffer::from_fn(width, height, |_x, _y| Rgb([255u8, 255u8, 255u8]));
 
     // Draw gradient background
-    let bg_start = Rgb([250u8, 250u8, 255u8]);
-    let bg_end = Rgb([240u8, 240u8, 250u8]);
+    let (bg_start, bg_end) = style.gradient(theme.background_top, theme.background_bottom);
     for y in 0..height {
         let blend_factor = y as f32 / height as f32;
         let blended_color = blend_colors(bg_start, bg_end, blend_factor);
@@ -546,40 +8785,29 @@ fn generate_multi_sensor_image(
     }
 
     // Draw border
-    draw_border(&mut image, width, height);
+    draw_border(&mut image, width, height, theme.frame);
 
     // Draw header
     let header_text = title.unwrap_or("Sensor Status");
-    draw_multi_sensor_header(&mut image, width, header_text);
+    draw_multi_sensor_header(&mut image, width, header_text, style);
 
     // Draw each sensor
-    let start_y = 60;
-    let line_height = 40;
-
+    let line_height = style.multi_sensor_line_height();
     for (i, sensor) in sensors.iter().enumerate() {
-        let y_pos = start_y + (i as u32 * line_height);
-        if y_pos + 30 < height {
-            draw_sensor_line(&mut image, width, y_pos, sensor);
+        let y_pos = MULTI_SENSOR_START_Y + (i as u32 * line_height);
+        if y_pos + line_height - 10 < height {
+            draw_sensor_line(&mut image, width, y_pos, sensor, style);
         }
     }
 
-    // Convert image to PNG bytes
-    let mut buffer = Vec::new();
-    {
-        let mut cursor = Cursor::new(&mut buffer);
-        image
-            .write_to(&mut cursor, image::ImageOutputFormat::Png)
-            .map_err(|e| anyhow::anyhow!("Failed to encode image: {}", e))?;
-    }
-
-    Ok(bytes::Bytes::from(buffer))
+    image
 }
 
-fn draw_multi_sensor_header(image: &mut RgbImage, width: u32, title: &str) {
-    // Draw header background
-    let header_start = Rgb([70u8, 70u8, 90u8]);
-    let header_end = Rgb([50u8, 50u8, 70u8]);
+fn draw_multi_sensor_header(image: &mut RgbImage, width: u32, title: &str, style: &RenderStyle) {
+    let theme = &style.theme;
 
+    // Draw header background
+    let (header_start, header_end) = style.gradient(theme.header_top, theme.header_bottom);
     for y in 8..50 {
         let blend_factor = (y - 8) as f32 / 42.0;
         let color = blend_colors(header_start, header_end, blend_factor);
@@ -589,7 +8817,7 @@ fn draw_multi_sensor_header(image: &mut RgbImage, width: u32, title: &str) {
     }
 
     // Draw border around header
-    let border_color = Rgb([100u8, 100u8, 120u8]);
+    let border_color = theme.header_border;
     for x in 8..(width - 8) {
         image.put_pixel(x, 8, border_color);
         image.put_pixel(x, 49, border_color);
@@ -602,82 +8830,133 @@ fn draw_multi_sensor_header(image: &mut RgbImage, width: u32, title: &str) {
     }
 
     // Center the title
-    let text_width = title.len() as u32 * 7;
-    let text_x = if text_width < width - 20 {
-        (width - text_width) / 2
-    } else {
-        15
-    };
+    let scale = style.text_scale();
+    let title = truncate_to_width(title, width.saturating_sub(30), scale);
+    let text_x = centered_text_x(&title, width, scale);
 
-    draw_text_pattern(image, text_x, 25, title, Rgb([255u8, 255u8, 255u8]));
+    style.draw_text(image, text_x, 29 - 4 * scale, &title, theme.header_text);
 }
 
-fn draw_sensor_line(image: &mut RgbImage, width: u32, y_pos: u32, sensor: &EntityState) {
-    // Get friendly name or use entity ID
-    let sensor_name = sensor
-        .attributes
-        .get("friendly_name")
-        .and_then(|v| v.as_str())
-        .unwrap_or(&sensor.entity_id);
+/// Text and colors of one `/multi-status` row.
+struct SensorRow {
+    name: String,
+    value: String,
+    /// Left edge of the right-aligned value
+    value_x: u32,
+    background: Rgb<u8>,
+    name_color: Rgb<u8>,
+    value_color: Rgb<u8>,
+    indicator: Rgb<u8>,
+}
 
-    // Format the sensor value
-    let formatted_value = format_sensor_value(sensor);
+impl SensorRow {
+    fn new(sensor: &EntityState, width: u32, style: &RenderStyle) -> Self {
+        let theme = &style.theme;
+
+        // Get friendly name or use entity ID
+        let sensor_name = sensor
+            .attributes
+            .get("friendly_name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(&sensor.entity_id);
+
+        // Determine colors based on state; attention states are inverted
+        let (background, name_color, value_color) = if is_attention(sensor) {
+            (theme.text, theme.panel, theme.panel)
+        } else if sensor.state == "unavailable" {
+            (
+                blend_colors(theme.panel, theme.inactive, 0.15),
+                theme.muted_text,
+                theme.critical,
+            )
+        } else {
+            (
+                theme.panel,
+                theme.text,
+                style
+                    .thresholds
+                    .color_for(sensor)
+                    .map(|color| darken(color, 0.1))
+                    .unwrap_or(theme.value),
+            )
+        };
 
-    // Determine colors based on state
-    let (bg_color, text_color, value_color) = if sensor.state == "unavailable" {
-        (
-            Rgb([240u8, 240u8, 240u8]),
-            Rgb([120u8, 120u8, 120u8]),
-            Rgb([180u8, 50u8, 50u8]),
-        )
-    } else {
-        (
-            Rgb([248u8, 248u8, 252u8]),
-            Rgb([60u8, 60u8, 60u8]),
-            Rgb([40u8, 120u8, 40u8]),
-        )
-    };
+        // Sensor name on the left, leaving room for the status indicator
+        let scale = style.text_scale();
+        let name = style
+            .abbreviations
+            .shorten(sensor_name, width.saturating_sub(60), scale);
+
+        // Sensor value on the right
+        let value = truncate_to_width(
+            &format_sensor_value(sensor, style.si),
+            width.saturating_sub(60),
+            scale,
+        );
+        let value_x = if width > 200 {
+            width - (150 * scale).min(text_width(&value, scale) + 20)
+        } else {
+            20
+        };
+
+        let indicator = if sensor.state == "unavailable" || is_attention(sensor) {
+            theme.critical
+        } else {
+            entity_accent_color(sensor, &style.thresholds).unwrap_or(match sensor.state.as_str() {
+                "on" | "open" | "home" => theme.active,
+                _ => theme.ok,
+            })
+        };
+
+        Self {
+            name,
+            value,
+            value_x,
+            background,
+            name_color,
+            value_color,
+            indicator,
+        }
+    }
+}
+
+fn draw_sensor_line(
+    image: &mut RgbImage,
+    width: u32,
+    y_pos: u32,
+    sensor: &EntityState,
+    style: &RenderStyle,
+) {
+    let theme = &style.theme;
+    let row = SensorRow::new(sensor, width, style);
 
     // Draw background for this sensor line
-    for y in y_pos..(y_pos + 35) {
+    let row_height = style.multi_sensor_line_height() - 5;
+    for y in y_pos..(y_pos + row_height) {
         for x in 15..(width - 15) {
-            image.put_pixel(x, y, bg_color);
+            image.put_pixel(x, y, row.background);
         }
     }
 
     // Draw subtle border
-    let line_border = Rgb([200u8, 200u8, 210u8]);
+    let line_border = theme.panel_border;
     for x in 15..(width - 15) {
         image.put_pixel(x, y_pos, line_border);
-        image.put_pixel(x, y_pos + 34, line_border);
+        image.put_pixel(x, y_pos + row_height - 1, line_border);
     }
 
-    // Draw sensor name (left side)
-    let name_text = if sensor_name.len() > 25 {
-        format!("{}...", &sensor_name[..22])
-    } else {
-        sensor_name.to_string()
-    };
-
-    draw_text_pattern(image, 20, y_pos + 8, &name_text, text_color);
-
-    // Draw sensor value (right side)
-    let value_x = if width > 200 {
-        width - 150.min(formatted_value.len() as u32 * 7 + 20)
-    } else {
-        20
-    };
-
-    draw_text_pattern(image, value_x, y_pos + 20, &formatted_value, value_color);
+    style.draw_text(image, 20, y_pos + 8, &row.name, row.name_color);
+    style.draw_text(
+        image,
+        row.value_x,
+        y_pos + 10 + 10 * style.text_scale(),
+        &row.value,
+        row.value_color,
+    );
 
     // Draw status indicator
     let indicator_x = width - 25;
     let indicator_y = y_pos + 10;
-    let indicator_color = if sensor.state == "unavailable" {
-        Rgb([200u8, 50u8, 50u8])
-    } else {
-        Rgb([50u8, 200u8, 50u8])
-    };
 
     // Draw small circle indicator
     for dy in 0..8 {
@@ -688,13 +8967,133 @@ fn draw_sensor_line(image: &mut RgbImage, width: u32, y_pos: u32, sensor: &Entit
                 + (py as i32 - (indicator_y + 4) as i32).pow(2)) as u32;
 
             if dist_sq <= 16 && px < image.width() && py < image.height() {
-                image.put_pixel(px, py, indicator_color);
+                image.put_pixel(px, py, row.indicator);
             }
         }
     }
 }
 
-fn format_sensor_value(sensor: &EntityState) -> String {
+/// Show an entity in the `?units=` asked for: a sensor's reading (with the
+/// decimals its new unit is shown with), or a weather entity's wind,
+/// pressure and precipitation.
+fn with_units(mut entity: EntityState, units: &Units) -> EntityState {
+    if units.is_empty() {
+        return entity;
+    }
+    if entity.entity_id.starts_with("weather.") {
+        units.convert_weather(&entity.entity_id, &mut entity.attributes);
+        return entity;
+    }
+
+    let Ok(value) = entity.state.parse::<f64>() else {
+        return entity;
+    };
+    let attribute = |name: &str| entity.attributes.get(name).and_then(|v| v.as_str());
+    let Some(unit) = attribute("unit_of_measurement") else {
+        return entity;
+    };
+    let Some(target) = units.target(&entity.entity_id, unit, attribute("device_class")) else {
+        return entity;
+    };
+    let Some(converted) = units::convert(value, unit, target) else {
+        return entity;
+    };
+
+    entity.state = converted.to_string();
+    if let Some(attributes) = entity.attributes.as_object_mut() {
+        attributes.insert("unit_of_measurement".to_string(), target.into());
+        attributes.insert(
+            units::DECIMALS_ATTRIBUTE.to_string(),
+            units::decimals(target).into(),
+        );
+    }
+    entity
+}
+
+/// A converted reading with its new unit's decimals: `29.92 inHg`.
+fn converted_value(sensor: &EntityState) -> Option<String> {
+    let decimals = sensor.attributes.get(units::DECIMALS_ATTRIBUTE)?.as_u64()? as usize;
+    let value = sensor.state.parse::<f64>().ok()?;
+    let unit = sensor.attributes.get("unit_of_measurement")?.as_str()?;
+    Some(format!("{:.*} {}", decimals, value, unit))
+}
+
+/// Set the `?priority=` of a sensor listed there.
+fn with_priority(mut entity: EntityState, priorities: &Priorities) -> EntityState {
+    if let Some(priority) = priorities.get(&entity.entity_id)
+        && let Some(attributes) = entity.attributes.as_object_mut()
+    {
+        attributes.insert(priority::ATTRIBUTE.to_string(), priority.name().into());
+    }
+    entity
+}
+
+/// How much a row matters when rows are left out: attention states first,
+/// then by `?priority=`.
+fn row_rank(sensor: &EntityState) -> (bool, Priority) {
+    let priority = sensor
+        .attributes
+        .get(priority::ATTRIBUTE)
+        .and_then(|v| v.as_str())
+        .and_then(Priority::parse)
+        .unwrap_or_default();
+    (is_attention(sensor), priority)
+}
+
+/// Whether the entity is marked as in one of the `ATTENTION_STATES`.
+fn is_attention(sensor: &EntityState) -> bool {
+    sensor
+        .attributes
+        .get(attention::ATTRIBUTE)
+        .and_then(|v| v.as_bool())
+        == Some(true)
+}
+
+fn is_monetary(sensor: &EntityState) -> bool {
+    sensor
+        .attributes
+        .get("device_class")
+        .and_then(|v| v.as_str())
+        == Some("monetary")
+}
+
+/// A monetary sensor's reading as `€1.234,56`.
+fn currency_value(sensor: &EntityState) -> Option<String> {
+    if !is_monetary(sensor) {
+        return None;
+    }
+    let value = sensor.state.parse::<f64>().ok()?;
+    let unit = sensor.attributes.get("unit_of_measurement")?.as_str()?;
+    let locale = sensor
+        .attributes
+        .get(currency::LOCALE_ATTRIBUTE)
+        .and_then(|v| v.as_str());
+    currency::format(value, unit, locale)
+}
+
+/// A duration sensor's reading as `1h 42m` or `02:13:45`.
+fn duration_value(sensor: &EntityState) -> Option<String> {
+    let value = sensor.state.parse::<f64>().ok()?;
+    let unit = sensor.attributes.get("unit_of_measurement")?.as_str()?;
+    let style = sensor
+        .attributes
+        .get(durations::FORMAT_ATTRIBUTE)
+        .and_then(|v| v.as_str());
+    durations::format(value, unit, style)
+}
+
+/// The reading with an engineering prefix, when `si` calls for one.
+fn prefixed_value(sensor: &EntityState, si: SiPrefixes) -> Option<String> {
+    let value = sensor.state.parse::<f64>().ok()?;
+    let unit = sensor.attributes.get("unit_of_measurement")?.as_str()?;
+    let device_class = sensor
+        .attributes
+        .get("device_class")
+        .and_then(|v| v.as_str());
+    si.format(value, unit, device_class)
+}
+
+fn format_sensor_value(sensor: &EntityState, si: SiPrefixes) -> String {
     if sensor.state == "unavailable" {
         return "Unavailable".to_string();
     }
@@ -705,11 +9104,62 @@ fn format_sensor_value(sensor: &EntityState) -> String {
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
+    if sensor.entity_id.starts_with("input_boolean.") {
+        return sensor.state.to_uppercase();
+    }
+
+    // Care log entries: "2h 10m ago"
+    if let Some(since) = sensor
+        .attributes
+        .get(care::SINCE_ATTRIBUTE)
+        .and_then(|v| v.as_u64())
+    {
+        return care::format_since(since);
+    }
+
+    // Budgeted meters: "180 L / 250 L"
+    if let Some((usage, budget)) = meter_budget(sensor) {
+        let with_unit = |value: f64| {
+            if unit.is_empty() {
+                format_range_value(value)
+            } else {
+                format!("{} {}", format_range_value(value), unit)
+            }
+        };
+        return format!("{} / {}", with_unit(usage), with_unit(budget));
+    }
+
+    if sensor.entity_id.starts_with("zone.")
+        && let Ok(count) = sensor.state.parse::<u32>()
+    {
+        return match count {
+            0 => "Empty".to_string(),
+            1 => "1 person".to_string(),
+            _ => format!("{} people", count),
+        };
+    }
+
     // Check if this is a percentage sensor
-    if is_percentage_sensor(sensor) {
-        if let Ok(num_value) = sensor.state.parse::<f64>() {
-            return format!("{:.0}%", num_value);
-        }
+    if is_percentage_sensor(sensor)
+        && let Ok(num_value) = sensor.state.parse::<f64>()
+    {
+        return format!("{:.0}%", num_value);
+    }
+
+    if let Some(money) = currency_value(sensor) {
+        return money;
+    }
+
+    if let Some(duration) = duration_value(sensor) {
+        return duration;
+    }
+
+    if let Some(converted) = converted_value(sensor) {
+        return converted;
+    }
+
+    if let Some(prefixed) = prefixed_value(sensor, si) {
+        return prefixed;
     }
 
     // Try to parse as number for better formatting
@@ -737,33 +9187,17 @@ fn format_sensor_value(sensor: &EntityState) -> String {
     }
 }
 
-fn get_status_gradient(state: &str) -> (Rgb<u8>, Rgb<u8>) {
-    match state.to_lowercase().as_str() {
-        "on" | "open" | "active" | "home" | "detected" => {
-            (Rgb([230u8, 255u8, 230u8]), Rgb([200u8, 255u8, 200u8])) // Green gradient
-        }
-        "off" | "closed" | "inactive" | "away" | "clear" => {
-            (Rgb([255u8, 230u8, 230u8]), Rgb([255u8, 200u8, 200u8])) // Red gradient
-        }
-        "unavailable" | "unknown" => {
-            (Rgb([240u8, 240u8, 240u8]), Rgb([220u8, 220u8, 220u8])) // Gray gradient
-        }
-        _ => {
-            (Rgb([250u8, 250u8, 255u8]), Rgb([240u8, 240u8, 255u8])) // Light blue gradient
-        }
-    }
-}
-
-fn blend_colors(color1: Rgb<u8>, color2: Rgb<u8>, factor: f32) -> Rgb<u8> {
-    let r = (color1[0] as f32 * (1.0 - factor) + color2[0] as f32 * factor) as u8;
-    let g = (color1[1] as f32 * (1.0 - factor) + color2[1] as f32 * factor) as u8;
-    let b = (color1[2] as f32 * (1.0 - factor) + color2[2] as f32 * factor) as u8;
-    Rgb([r, g, b])
+/// Background gradient for `/status`, tinted towards the state color.
+fn get_status_gradient(state: &str, style: &RenderStyle) -> (Rgb<u8>, Rgb<u8>) {
+    let theme = &style.theme;
+    let tint = theme.state_color(state);
+    style.gradient(
+        blend_colors(theme.background_top, tint, 0.12),
+        blend_colors(theme.background_bottom, tint, 0.22),
+    )
 }
 
-fn draw_border(image: &mut RgbImage, width: u32, height: u32) {
-    let border_color = Rgb([80u8, 80u8, 80u8]);
-
+fn draw_border(image: &mut RgbImage, width: u32, height: u32, border_color: Rgb<u8>) {
     // Top and bottom borders (3 pixels thick)
     for thickness in 0..3 {
         for x in 0..width {
@@ -789,11 +9223,11 @@ fn draw_border(image: &mut RgbImage, width: u32, height: u32) {
     }
 }
 
-fn draw_header_section(image: &mut RgbImage, width: u32, entity_name: &str) {
-    // Draw header background with gradient effect
-    let header_start = Rgb([60u8, 60u8, 80u8]);
-    let header_end = Rgb([40u8, 40u8, 60u8]);
+fn draw_header_section(image: &mut RgbImage, width: u32, entity_name: &str, style: &RenderStyle) {
+    let theme = &style.theme;
 
+    // Draw header background with gradient effect
+    let (header_start, header_end) = style.gradient(theme.header_top, theme.header_bottom);
     for y in 8..40 {
         let blend_factor = (y - 8) as f32 / 32.0;
         let color = blend_colors(header_start, header_end, blend_factor);
@@ -803,7 +9237,7 @@ fn draw_header_section(image: &mut RgbImage, width: u32, entity_name: &str) {
     }
 
     // Draw border around header
-    let border_color = Rgb([100u8, 100u8, 120u8]);
+    let border_color = theme.header_border;
     for x in 8..(width - 8) {
         image.put_pixel(x, 8, border_color);
         image.put_pixel(x, 39, border_color);
@@ -816,28 +9250,32 @@ fn draw_header_section(image: &mut RgbImage, width: u32, entity_name: &str) {
     }
 
     // Center the entity name
-    let text_width = entity_name.len() as u32 * 7; // 6 char width + 1 spacing
-    let text_x = if text_width < width - 20 {
-        (width - text_width) / 2
-    } else {
-        15
-    };
-
-    draw_text_pattern(image, text_x, 20, entity_name, Rgb([255u8, 255u8, 255u8]));
+    let scale = style.text_scale();
+    let entity_name = truncate_to_width(entity_name, width.saturating_sub(30), scale);
+    let text_x = centered_text_x(&entity_name, width, scale);
+
+    style.draw_text(
+        image,
+        text_x,
+        24 - 4 * scale,
+        &entity_name,
+        theme.header_text,
+    );
 }
 
-fn draw_status_section(image: &mut RgbImage, width: u32, status: &str, state: &str) {
-    // Status section background with better colors
-    let (status_start, status_end) = match state.to_lowercase().as_str() {
-        "on" | "open" | "active" | "home" | "detected" => {
-            (Rgb([80u8, 180u8, 80u8]), Rgb([60u8, 160u8, 60u8]))
-        }
-        "off" | "closed" | "inactive" | "away" | "clear" => {
-            (Rgb([180u8, 80u8, 80u8]), Rgb([160u8, 60u8, 60u8]))
-        }
-        "unavailable" | "unknown" => (Rgb([140u8, 140u8, 140u8]), Rgb([120u8, 120u8, 120u8])),
-        _ => (Rgb([80u8, 130u8, 180u8]), Rgb([60u8, 110u8, 160u8])),
-    };
+fn draw_status_section(
+    image: &mut RgbImage,
+    width: u32,
+    status: &str,
+    state: &str,
+    style: &RenderStyle,
+) {
+    let theme = &style.theme;
+
+    // Status section background in a muted shade of the state color
+    let state_color = theme.state_color(state);
+    let (status_start, status_end) =
+        style.gradient(darken(state_color, 0.15), darken(state_color, 0.25));
 
     // Draw gradient background
     for y in 48..85 {
@@ -849,7 +9287,7 @@ fn draw_status_section(image: &mut RgbImage, width: u32, status: &str, state: &s
     }
 
     // Draw border around status section
-    let border_color = Rgb([200u8, 200u8, 200u8]);
+    let border_color = theme.panel_border;
     for x in 8..(width - 8) {
         image.put_pixel(x, 48, border_color);
         image.put_pixel(x, 84, border_color);
@@ -861,23 +9299,36 @@ fn draw_status_section(image: &mut RgbImage, width: u32, status: &str, state: &s
         }
     }
 
-    // Center the status text
-    let text_width = status.len() as u32 * 7;
-    let text_x = if text_width < width - 20 {
-        (width - text_width) / 2
-    } else {
-        15
-    };
+    // Center the status text, keeping clear of the status indicator
+    let scale = style.text_scale();
+    let status = truncate_to_width(status, width.saturating_sub(70), scale);
+    let text_x = centered_text_x(&status, width, scale);
 
-    // Add text shadow effect
-    draw_text_pattern(image, text_x + 1, 66, status, Rgb([0u8, 0u8, 0u8]));
-    draw_text_pattern(image, text_x, 65, status, Rgb([255u8, 255u8, 255u8]));
+    // Add text shadow effect (skipped in the accessibility profile)
+    let text_y = 69 - 4 * scale;
+    if !style.accessible {
+        draw_text_pattern(
+            image,
+            text_x + 1,
+            text_y + 1,
+            &status,
+            Rgb([0u8, 0u8, 0u8]),
+            1,
+        );
+    }
+    style.draw_text(image, text_x, text_y, &status, theme.header_text);
 }
 
-fn draw_entity_info(image: &mut RgbImage, width: u32, height: u32, entity: &EntityState) {
-    let mut y_pos = 95;
-    let line_height = 18;
-    let info_bg = Rgb([245u8, 245u8, 250u8]);
+fn draw_entity_info(
+    image: &mut RgbImage,
+    width: u32,
+    height: u32,
+    entity: &EntityState,
+    style: &RenderStyle,
+) {
+    let theme = &style.theme;
+    let scale = style.text_scale();
+    let info_bg = theme.panel;
 
     // Draw info section background
     for y in 92..(height - 8) {
@@ -887,7 +9338,7 @@ fn draw_entity_info(image: &mut RgbImage, width: u32, height: u32, entity: &Enti
     }
 
     // Draw border around info section
-    let border_color = Rgb([180u8, 180u8, 180u8]);
+    let border_color = theme.panel_border;
     for x in 8..(width - 8) {
         image.put_pixel(x, 92, border_color);
         if height > 8 {
@@ -901,21 +9352,31 @@ fn draw_entity_info(image: &mut RgbImage, width: u32, height: u32, entity: &Enti
         }
     }
 
-    // Draw entity ID with better formatting
-    if y_pos + line_height < height - 10 {
-        let entity_id_short = if entity.entity_id.len() > 35 {
-            format!("{}...", &entity.entity_id[..32])
-        } else {
-            entity.entity_id.clone()
-        };
+    for (y, line) in entity_info_lines(entity, width, height, scale) {
+        style.draw_text(image, 15, y, &line, theme.text);
+    }
+}
 
-        draw_text_pattern(
-            image,
-            15,
-            y_pos,
-            &format!("Entity: {}", entity_id_short),
-            Rgb([40u8, 40u8, 40u8]),
-        );
+/// Detail lines of the `/status` layout with their positions: the entity ID,
+/// then whichever common attributes the entity has, as many as fit.
+fn entity_info_lines(
+    entity: &EntityState,
+    width: u32,
+    height: u32,
+    scale: u32,
+) -> Vec<(u32, String)> {
+    let mut lines = Vec::new();
+    let mut y_pos = 95;
+    let line_height = 10 + 8 * scale;
+    let max_text_width = width.saturating_sub(30);
+
+    // Draw entity ID, wrapped over up to three lines when it is long
+    let entity_text = format!("Entity: {}", entity.entity_id);
+    for line in wrap_to_width(&entity_text, max_text_width, scale, 3) {
+        if y_pos + line_height >= height - 10 {
+            break;
+        }
+        lines.push((y_pos, line));
         y_pos += line_height;
     }
 
@@ -925,8 +9386,13 @@ fn draw_entity_info(image: &mut RgbImage, width: u32, height: u32, entity: &Enti
         ("unit_of_measurement", "Unit"),
         ("temperature", "Temp"),
         ("humidity", "Humidity"),
+        ("wind_speed", "Wind"),
+        ("pressure", "Pressure"),
         ("battery", "Battery"),
         ("brightness", "Brightness"),
+        ("min", "Min"),
+        ("max", "Max"),
+        ("options", "Options"),
         ("last_changed", "Changed"),
     ];
 
@@ -937,43 +9403,51 @@ fn draw_entity_info(image: &mut RgbImage, width: u32, height: u32, entity: &Enti
 
         if let Some(attr_value) = entity.attributes.get(*attr_key) {
             let attr_text = match attr_value {
-                serde_json::Value::String(s) => {
-                    if s.len() > 25 {
-                        format!("{}: {}...", display_name, &s[..22])
-                    } else {
-                        format!("{}: {}", display_name, s)
+                serde_json::Value::String(s) => format!("{}: {}", display_name, s),
+                // Weather entities name the unit in `<attribute>_unit`
+                serde_json::Value::Number(n) => match entity
+                    .attributes
+                    .get(format!("{}_unit", attr_key))
+                    .and_then(|v| v.as_str())
+                {
+                    Some(unit) => format!("{}: {} {}", display_name, n, unit),
+                    None => format!("{}: {}", display_name, n),
+                },
+                serde_json::Value::Bool(b) => format!("{}: {}", display_name, b),
+                serde_json::Value::Array(items) => {
+                    let items: Vec<&str> = items.iter().filter_map(|v| v.as_str()).collect();
+                    if items.is_empty() {
+                        continue;
                     }
+                    format!("{}: {}", display_name, items.join(", "))
                 }
-                serde_json::Value::Number(n) => format!("{}: {}", display_name, n),
-                serde_json::Value::Bool(b) => format!("{}: {}", display_name, b),
                 _ => continue,
             };
 
-            if attr_text.len() <= 45 {
-                // Only show if it fits reasonably
-                draw_text_pattern(image, 15, y_pos, &attr_text, Rgb([70u8, 70u8, 70u8]));
-                y_pos += line_height;
-            }
+            let attr_text = truncate_to_width(&attr_text, max_text_width, scale);
+            lines.push((y_pos, attr_text));
+            y_pos += line_height;
         }
     }
+
+    lines
 }
 
-fn draw_status_indicator(image: &mut RgbImage, width: u32, height: u32, state: &str) {
+fn draw_status_indicator(
+    image: &mut RgbImage,
+    width: u32,
+    height: u32,
+    state: &str,
+    accent: Option<Rgb<u8>>,
+    theme: &Theme,
+) {
     let indicator_size = 24;
     let x_pos = width - indicator_size - 15;
     let y_pos = 52;
 
     if x_pos + indicator_size < width && y_pos + indicator_size < height {
-        let (indicator_color, border_color) = match state.to_lowercase().as_str() {
-            "on" | "open" | "active" | "home" | "detected" => {
-                (Rgb([50u8, 205u8, 50u8]), Rgb([34u8, 139u8, 34u8]))
-            } // Green with border
-            "off" | "closed" | "inactive" | "away" | "clear" => {
-                (Rgb([220u8, 20u8, 60u8]), Rgb([178u8, 34u8, 34u8]))
-            } // Red with border
-            "unavailable" | "unknown" => (Rgb([169u8, 169u8, 169u8]), Rgb([105u8, 105u8, 105u8])), // Gray with border
-            _ => (Rgb([30u8, 144u8, 255u8]), Rgb([0u8, 100u8, 200u8])), // Blue with border
-        };
+        let indicator_color = accent.unwrap_or_else(|| theme.state_color(state));
+        let border_color = darken(indicator_color, 0.35);
 
         // Draw circular indicator with border
         let center_x = x_pos + indicator_size / 2;
@@ -1016,14 +9490,13 @@ fn draw_status_indicator(image: &mut RgbImage, width: u32, height: u32, state: &
     }
 }
 
-fn draw_text_pattern(image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>) {
-    let char_width = 6;
-    let char_height = 8;
-    let char_spacing = 1;
-    let mut offset = 0u32;
+fn draw_text_pattern(image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rgb<u8>, scale: u32) {
+    let char_width = 6 * scale;
+    let char_height = 8 * scale;
+    let char_spacing = scale;
 
-    for ch in text.chars().take(50) {
-        let char_x = x + (offset * (char_width + char_spacing));
+    for (offset, ch) in text.chars().take(50).enumerate() {
+        let char_x = x + (offset as u32 * (char_width + char_spacing));
         let char_y = y;
 
         if char_x + char_width >= image.width() || char_y + char_height >= image.height() {
@@ -1031,117 +9504,116 @@ fn draw_text_pattern(image: &mut RgbImage, x: u32, y: u32, text: &str, color: Rg
         }
 
         // Get bitmap for character
-        let char_bitmap = get_char_bitmap(ch);
+        let char_bitmap = font::glyph(ch);
 
-        // Draw the character bitmap
+        // Draw the character bitmap with scaling
         for (row_idx, &row) in char_bitmap.iter().enumerate() {
-            for col_idx in 0..char_width {
-                if row & (1 << (char_width - 1 - col_idx)) != 0 {
-                    let px = char_x + col_idx;
-                    let py = char_y + row_idx as u32;
-                    if px < image.width() && py < image.height() {
-                        image.put_pixel(px, py, color);
+            for col_idx in 0..6u32 {
+                if row & (1 << (5 - col_idx)) != 0 {
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            let px = char_x + (col_idx * scale) + sx;
+                            let py = char_y + (row_idx as u32 * scale) + sy;
+                            if px < image.width() && py < image.height() {
+                                image.put_pixel(px, py, color);
+                            }
+                        }
                     }
                 }
             }
         }
-        offset += 1;
-    }
-}
-
-fn get_char_bitmap(ch: char) -> [u8; 8] {
-    match ch {
-        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04, 0x00],
-        '"' => [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00],
-        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A, 0x00],
-        '$' => [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04, 0x00],
-        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03, 0x00],
-        '&' => [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D, 0x00],
-        '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00],
-        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02, 0x00],
-        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08, 0x00],
-        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00, 0x00],
-        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00, 0x00],
-        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08, 0x00],
-        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0x00],
-        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00],
-        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00, 0x00],
-        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E, 0x00],
-        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00],
-        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F, 0x00],
-        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E, 0x00],
-        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02, 0x00],
-        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E, 0x00],
-        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E, 0x00],
-        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08, 0x00],
-        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E, 0x00],
-        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C, 0x00],
-        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00, 0x00],
-        ';' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08, 0x00],
-        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02, 0x00],
-        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00, 0x00],
-        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08, 0x00],
-        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04, 0x00],
-        '@' => [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E, 0x00],
-        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x00],
-        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E, 0x00],
-        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E, 0x00],
-        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C, 0x00],
-        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F, 0x00],
-        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10, 0x00],
-        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F, 0x00],
-        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00],
-        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00],
-        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C, 0x00],
-        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11, 0x00],
-        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F, 0x00],
-        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11, 0x00],
-        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11, 0x00],
-        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E, 0x00],
-        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10, 0x00],
-        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D, 0x00],
-        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11, 0x00],
-        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E, 0x00],
-        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00],
-        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E, 0x00],
-        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04, 0x00],
-        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A, 0x00],
-        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11, 0x00],
-        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x00],
-        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F, 0x00],
-        'a' => [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F, 0x00],
-        'b' => [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E, 0x00],
-        'c' => [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E, 0x00],
-        'd' => [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F, 0x00],
-        'e' => [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E, 0x00],
-        'f' => [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08, 0x00],
-        'g' => [0x00, 0x00, 0x0F, 0x11, 0x0F, 0x01, 0x0E, 0x00],
-        'h' => [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00],
-        'i' => [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E, 0x00],
-        'j' => [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C, 0x00],
-        'k' => [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12, 0x00],
-        'l' => [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00],
-        'm' => [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11, 0x00],
-        'n' => [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00],
-        'o' => [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E, 0x00],
-        'p' => [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10, 0x00],
-        'q' => [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01, 0x00],
-        'r' => [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10, 0x00],
-        's' => [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E, 0x00],
-        't' => [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06, 0x00],
-        'u' => [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D, 0x00],
-        'v' => [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04, 0x00],
-        'w' => [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A, 0x00],
-        'x' => [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x00],
-        'y' => [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E, 0x00],
-        'z' => [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F, 0x00],
-        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x00],
-        _ => [0x00, 0x00, 0x0A, 0x04, 0x0A, 0x00, 0x00, 0x00], // Unknown char
-    }
-}
-
-fn format_entity_status(entity: &EntityState) -> String {
+    }
+}
+
+/// Horizontal advance of one bitmap glyph at scale 1 (6px glyph + 1px gap).
+const GLYPH_ADVANCE: u32 = 7;
+
+/// Rendered width in pixels of `text` using the bitmap font.
+fn text_width(text: &str, scale: u32) -> u32 {
+    text.chars().count() as u32 * GLYPH_ADVANCE * scale
+}
+
+/// Cut `text` on a character boundary so that it fits in `max_width` pixels,
+/// appending "…" only when something was actually removed.
+fn truncate_to_width(text: &str, max_width: u32, scale: u32) -> String {
+    if text_width(text, scale) <= max_width {
+        return text.to_string();
+    }
+
+    let max_chars = (max_width / (GLYPH_ADVANCE * scale.max(1))) as usize;
+    if max_chars == 0 {
+        return String::new();
+    }
+
+    let kept: String = text.chars().take(max_chars - 1).collect();
+    format!("{}…", kept.trim_end())
+}
+
+/// Split `text` into at most `max_lines` lines that fit in `max_width`
+/// pixels. Lines break at spaces, or inside long words after `_`, `.`, `-`
+/// and `/` or between camelCase humps, so entity IDs such as
+/// `sensor.freezer_power` stay readable. A word with none of those is
+/// hyphenated, and the last line is ellipsized when text is left over.
+fn wrap_to_width(text: &str, max_width: u32, scale: u32, max_lines: usize) -> Vec<String> {
+    let max_chars = (max_width / (GLYPH_ADVANCE * scale.max(1))) as usize;
+    let mut lines = Vec::new();
+    let mut rest: Vec<char> = text.trim().chars().collect();
+
+    while !rest.is_empty() && max_chars > 0 && lines.len() < max_lines {
+        if rest.len() <= max_chars {
+            lines.push(rest.iter().collect());
+            break;
+        }
+        if lines.len() + 1 == max_lines {
+            let remaining: String = rest.iter().collect();
+            lines.push(truncate_to_width(&remaining, max_width, scale));
+            break;
+        }
+
+        let (line, next) = match line_break(&rest, max_chars) {
+            Some(at) => (rest[..at].iter().collect::<String>(), at),
+            None if max_chars > 1 => {
+                let at = max_chars - 1;
+                (rest[..at].iter().chain(['-'].iter()).collect(), at)
+            }
+            None => (rest[..max_chars].iter().collect(), max_chars),
+        };
+        lines.push(line.trim_end().to_string());
+        let skip = rest[next..]
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        rest.drain(..next + skip);
+    }
+
+    lines
+}
+
+/// Latest position within the first `max_chars` characters where `chars`
+/// can be broken without hyphenating. Breaks inside a word are only taken
+/// once at least three characters of it are on the line.
+fn line_break(chars: &[char], max_chars: usize) -> Option<usize> {
+    let mut word_start = 0;
+    let mut best = None;
+
+    for at in 1..=max_chars.min(chars.len() - 1) {
+        let (before, after) = (chars[at - 1], chars[at]);
+        if before.is_whitespace() {
+            word_start = at;
+        }
+        let in_word = at - word_start >= 3 && !after.is_whitespace();
+        let soft = matches!(before, '_' | '.' | '-' | '/')
+            || (before.is_lowercase() && after.is_uppercase());
+
+        if after.is_whitespace() || before.is_whitespace() || (in_word && soft) {
+            best = Some(at);
+        }
+    }
+
+    best.filter(|&at| chars[..at].iter().any(|c| !c.is_whitespace()))
+}
+
+fn format_entity_status(entity: &EntityState, si: SiPrefixes) -> String {
     let state = &entity.state;
     let unit = entity
         .attributes
@@ -1154,7 +9626,17 @@ fn format_entity_status(entity: &EntityState) -> String {
 
     match domain {
         "sensor" => {
-            if let Ok(num_value) = state.parse::<f64>() {
+            if meter_budget(entity).is_some() {
+                format!("Today: {}", format_sensor_value(entity, si))
+            } else if let Some(money) = currency_value(entity) {
+                money
+            } else if let Some(duration) = duration_value(entity) {
+                duration
+            } else if let Some(converted) = converted_value(entity) {
+                converted
+            } else if let Some(prefixed) = prefixed_value(entity, si) {
+                prefixed
+            } else if let Ok(num_value) = state.parse::<f64>() {
                 if unit.is_empty() {
                     format!("Value: {:.1}", num_value)
                 } else {
@@ -1164,7 +9646,7 @@ fn format_entity_status(entity: &EntityState) -> String {
                 format!("State: {}", state)
             }
         }
-        "switch" | "light" | "fan" => match state.to_lowercase().as_str() {
+        "switch" | "light" | "fan" | "input_boolean" => match state.to_lowercase().as_str() {
             "on" => "ON".to_string(),
             "off" => "OFF".to_string(),
             _ => format!("State: {}", state.to_uppercase()),
@@ -1211,6 +9693,24 @@ fn format_entity_status(entity: &EntityState) -> String {
                 format!("Weather: {}", state.to_uppercase())
             }
         }
+        "input_number" | "number" => {
+            let value = if unit.is_empty() {
+                state.clone()
+            } else {
+                format!("{} {}", state, unit)
+            };
+            match input_number_range(entity) {
+                Some((min, max)) => format!(
+                    "{} ({}-{})",
+                    value,
+                    format_range_value(min),
+                    format_range_value(max)
+                ),
+                None => value,
+            }
+        }
+        "input_select" | "select" => format!("Selected: {}", state),
+        "zone" => format!("People: {}", state),
         "media_player" => match state.to_lowercase().as_str() {
             "playing" => "PLAYING".to_string(),
             "paused" => "PAUSED".to_string(),
@@ -1229,27 +9729,34 @@ fn format_entity_status(entity: &EntityState) -> String {
     }
 }
 
-fn draw_trmnl_header(image: &mut GrayImage, title: &str) {
-    const WIDTH: u32 = 800;
+fn draw_trmnl_header(image: &mut GrayImage, title: &str, accessible: bool) {
+    let width = image.width();
 
     // Draw thick top border
     for y in 5..15 {
-        for x in 20..(WIDTH - 20) {
+        for x in 20..(width - 20) {
             image.put_pixel(x, y, Luma([0u8])); // Black
         }
     }
 
     // Draw title - larger text for TRMNL
-    let title_x = if title.len() * 12 < WIDTH as usize - 40 {
-        (WIDTH - (title.len() as u32 * 12)) / 2
+    let scale = if accessible { 3 } else { 2 };
+    let title = truncate_to_width(title, width - 60, scale);
+    let title_width = text_width(&title, scale);
+    let title_x = if title_width < width - 40 {
+        (width - title_width) / 2
     } else {
         30
     };
 
-    draw_trmnl_text(image, title_x, 25, title, Luma([0u8]), 2); // Double size
+    if accessible {
+        draw_trmnl_text_bold(image, title_x, 25, &title, Luma([0u8]), scale);
+    } else {
+        draw_trmnl_text(image, title_x, 25, &title, Luma([0u8]), scale); // Double size
+    }
 
     // Draw separator line
-    for x in 40..(WIDTH - 40) {
+    for x in 40..(width - 40) {
         image.put_pixel(x, 65, Luma([0u8]));
         image.put_pixel(x, 66, Luma([0u8]));
     }
@@ -1260,8 +9767,16 @@ fn draw_trmnl_sensor_line(
     y_pos: u32,
     line_height: u32,
     sensor: &EntityState,
+    abbreviations: &Abbreviations,
+    accessible: bool,
+    si: SiPrefixes,
 ) {
-    const WIDTH: u32 = 800;
+    let width = image.width();
+
+    if accessible {
+        draw_trmnl_sensor_line_accessible(image, y_pos, line_height, sensor, abbreviations, si);
+        return;
+    }
 
     // Get sensor name
     let sensor_name = sensor
@@ -1271,31 +9786,65 @@ fn draw_trmnl_sensor_line(
         .unwrap_or(&sensor.entity_id);
 
     // Format value
-    let formatted_value = format_sensor_value(sensor);
+    let formatted_value = format_sensor_value(sensor, si);
 
-    // Check if this is a percentage sensor for gauge display
-    let is_percentage = is_percentage_sensor(sensor);
+    // Percentages and input_number helpers get a gauge, input_select
+    // helpers their list of options, other numbers their sparkline if any
+    let has_gauge = gauge_fraction(sensor).is_some();
+    let options = select_options(sensor);
+    let sparkline = sparkline_points(sensor).filter(|_| !has_gauge && options.is_none());
 
-    // Truncate name if too long (shorter for gauge sensors)
-    let max_name_len = if is_percentage { 25 } else { 35 };
-    let display_name = if sensor_name.len() > max_name_len {
-        format!("{}...", &sensor_name[..max_name_len - 3])
+    // Shorten name if too long (narrower for gauge, option and sparkline
+    // sensors)
+    let name_scale = 2; // Make titles larger for distance readability
+    let max_name_width = if has_gauge || options.is_some() || sparkline.is_some() {
+        width - 450
     } else {
-        sensor_name.to_string()
+        width - 310
     };
+    // Long names wrap onto a second line when the row is tall enough
+    let name_lines = if line_height >= 48 { 2 } else { 1 };
+    let display_name = abbreviations.wrap(sensor_name, max_name_width, name_scale, name_lines);
 
     // Draw sensor name (left side) - larger for better readability
-    let name_scale = 2; // Make titles larger for distance readability
-    draw_trmnl_text(image, 40, y_pos + 8, &display_name, Luma([0u8]), name_scale);
+    for (i, line) in display_name.iter().enumerate() {
+        let line_y = y_pos + 8 + i as u32 * 20;
+        draw_trmnl_text(image, 40, line_y, line, Luma([0u8]), name_scale);
+    }
 
-    if is_percentage && sensor.state != "unavailable" {
-        // Draw gauge for percentage sensors
-        draw_trmnl_gauge(image, y_pos, line_height, sensor, &formatted_value);
+    if has_gauge {
+        draw_trmnl_gauge(image, y_pos, line_height, sensor, &formatted_value, false);
+    } else if let Some(options) = options {
+        draw_trmnl_options(image, y_pos, &options, &sensor.state);
     } else {
+        // Sparkline in a column of its own between the name and the value
+        let max_value_width = match sparkline {
+            Some(points) => {
+                let height = line_height.saturating_sub(16).clamp(10, 36);
+                let top = (y_pos + 33)
+                    .saturating_sub(height / 2)
+                    .min((y_pos + line_height).saturating_sub(height + 4))
+                    .max(y_pos + 4);
+                draw_sparkline(
+                    image,
+                    chart::Area {
+                        x: width - 400,
+                        y: top,
+                        width: 120,
+                        height,
+                    },
+                    &points,
+                );
+                230
+            }
+            None => width - 120,
+        };
+
         // Draw larger value (right side) for non-percentage sensors
         let value_scale = 2; // Double size for better readability
-        let value_width = formatted_value.len() as u32 * 7 * value_scale;
-        let value_x = WIDTH - value_width - 40;
+        let formatted_value = truncate_to_width(&formatted_value, max_value_width, value_scale);
+        let value_width = text_width(&formatted_value, value_scale);
+        let value_x = width - value_width - 40;
         draw_trmnl_text(
             image,
             value_x,
@@ -1315,9 +9864,9 @@ fn draw_trmnl_sensor_line(
         // Draw status dot
         for dy in 0..6 {
             for dx in 0..6 {
-                let px = WIDTH - 25 + dx;
+                let px = width - 25 + dx;
                 let py = y_pos + 25 + dy;
-                if px < WIDTH && py < image.height() {
+                if px < width && py < image.height() {
                     image.put_pixel(px, py, indicator_color);
                 }
             }
@@ -1326,44 +9875,196 @@ fn draw_trmnl_sensor_line(
 
     // Draw subtle separator line
     if y_pos + line_height < image.height() - 20 {
-        for x in 60..(WIDTH - 60) {
+        for x in 60..(width - 60) {
             image.put_pixel(x, y_pos + line_height - 2, Luma([200u8]));
         }
     }
 }
 
-fn draw_trmnl_border(image: &mut GrayImage) {
-    const WIDTH: u32 = 800;
-    const HEIGHT: u32 = 480;
+/// The `(x, y)` fractions of a sensor's sparkline, if it has one.
+fn sparkline_points(sensor: &EntityState) -> Option<Vec<(f64, f64)>> {
+    let points = sensor
+        .attributes
+        .get(history::SPARKLINE_ATTRIBUTE)?
+        .as_array()?
+        .iter()
+        .filter_map(|point| Some((point.get(0)?.as_f64()?, point.get(1)?.as_f64()?)))
+        .collect::<Vec<_>>();
+    (points.len() >= 2).then_some(points)
+}
+
+/// A row's trend: its history as a line ending in a dot at the current
+/// value.
+fn draw_sparkline(image: &mut GrayImage, area: chart::Area, points: &[(f64, f64)]) {
+    area.draw_series(image, points, Luma([0u8]), 2);
+    if let Some(&(x, y)) = points.last() {
+        let (x, y) = (area.x_at(x), area.y_at(y));
+        for py in y.saturating_sub(2)..=y + 2 {
+            for px in x.saturating_sub(2)..=x + 2 {
+                if px < image.width() && py < image.height() {
+                    image.put_pixel(px, py, Luma([0u8]));
+                }
+            }
+        }
+    }
+}
+
+/// Accessibility layout: name and value on their own lines in bold triple
+/// size text, solid gauges and full-strength separators.
+fn draw_trmnl_sensor_line_accessible(
+    image: &mut GrayImage,
+    y_pos: u32,
+    line_height: u32,
+    sensor: &EntityState,
+    abbreviations: &Abbreviations,
+    si: SiPrefixes,
+) {
+    let width = image.width();
+    let scale = 3;
+
+    let sensor_name = sensor
+        .attributes
+        .get("friendly_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(&sensor.entity_id);
+    let formatted_value = format_sensor_value(sensor, si);
+
+    // The value sits on its own line, so the name gets the full width
+    let display_name = abbreviations.shorten(sensor_name, width - 80, scale);
+    draw_trmnl_text_bold(image, 40, y_pos + 8, &display_name, Luma([0u8]), scale);
+
+    if gauge_fraction(sensor).is_some() {
+        draw_trmnl_gauge(image, y_pos, line_height, sensor, &formatted_value, true);
+    } else {
+        // Sparkline at the start of the value's line
+        let max_value_width = match sparkline_points(sensor) {
+            Some(points) => {
+                draw_sparkline(
+                    image,
+                    chart::Area {
+                        x: 40,
+                        y: y_pos + 42,
+                        width: 160,
+                        height: 30,
+                    },
+                    &points,
+                );
+                width - 260
+            }
+            None => width - 80,
+        };
+        let formatted_value = truncate_to_width(&formatted_value, max_value_width, scale);
+        let value_x = width - text_width(&formatted_value, scale) - 40;
+        draw_trmnl_text_bold(
+            image,
+            value_x,
+            y_pos + 45,
+            &formatted_value,
+            Luma([0u8]),
+            scale,
+        );
+    }
+
+    // Solid separator line
+    if y_pos + line_height < image.height() - 20 {
+        for x in 40..(width - 40) {
+            image.put_pixel(x, y_pos + line_height - 3, Luma([0u8]));
+            image.put_pixel(x, y_pos + line_height - 2, Luma([0u8]));
+        }
+    }
+}
+
+fn draw_trmnl_border(image: &mut GrayImage) {
+    let width = image.width();
+    let height = image.height();
+
+    // Draw border - thick lines for TRMNL
+    for thickness in 0..3 {
+        // Top and bottom
+        for x in 0..width {
+            if thickness < height {
+                image.put_pixel(x, thickness, Luma([0u8]));
+                image.put_pixel(x, height - 1 - thickness, Luma([0u8]));
+            }
+        }
+
+        // Left and right
+        for y in 0..height {
+            if thickness < width {
+                image.put_pixel(thickness, y, Luma([0u8]));
+                image.put_pixel(width - 1 - thickness, y, Luma([0u8]));
+            }
+        }
+    }
+}
+
+/// Small black-on-white stamp in the bottom-right corner, boxed so it stays
+/// legible over camera photos.
+fn draw_trmnl_stamp(image: &mut GrayImage, text: &str) {
+    let (width, height) = image.dimensions();
 
-    // Draw border - thick lines for TRMNL
-    for thickness in 0..3 {
-        // Top and bottom
-        for x in 0..WIDTH {
-            if thickness < HEIGHT {
-                image.put_pixel(x, thickness, Luma([0u8]));
-                image.put_pixel(x, HEIGHT - 1 - thickness, Luma([0u8]));
-            }
+    let x = width - text_width(text, 1) - 8;
+    let y = height - 14;
+    for py in (y - 2)..(height - 3) {
+        for px in (x - 3)..(width - 3) {
+            image.put_pixel(px, py, Luma([255u8]));
+        }
+    }
+    draw_trmnl_text(image, x, y, text, Luma([0u8]), 1);
+}
+
+/// `LOW BATTERY 8%` white on black in the top-right corner, with a nearly
+/// empty battery beside it.
+fn draw_low_battery_badge(image: &mut GrayImage, volts: f32) {
+    let (width, _) = image.dimensions();
+    let text = format!("LOW BATTERY {}%", devices::battery_percent(volts));
+    let (icon_width, icon_height) = (30, 16);
+    let badge_width = text_width(&text, 2) + icon_width + 30;
+    let (left, top, bottom) = (width.saturating_sub(badge_width + 12), 12, 44);
+
+    for y in top..bottom {
+        for x in left..(left + badge_width).min(width) {
+            image.put_pixel(x, y, Luma([0u8]));
         }
+    }
 
-        // Left and right
-        for y in 0..HEIGHT {
-            if thickness < WIDTH {
-                image.put_pixel(thickness, y, Luma([0u8]));
-                image.put_pixel(WIDTH - 1 - thickness, y, Luma([0u8]));
+    // Battery outline with its terminal and a sliver of charge
+    let (icon_x, icon_y) = (left + 10, top + (bottom - top - icon_height) / 2);
+    for y in icon_y..(icon_y + icon_height) {
+        for x in icon_x..(icon_x + icon_width - 4) {
+            let edge = y < icon_y + 2
+                || y + 2 >= icon_y + icon_height
+                || x < icon_x + 2
+                || x + 2 >= icon_x + icon_width - 4;
+            let charge = x < icon_x + 7;
+            if edge || charge {
+                image.put_pixel(x, y, Luma([255u8]));
             }
         }
     }
+    for y in (icon_y + 4)..(icon_y + icon_height - 4) {
+        for x in (icon_x + icon_width - 4)..(icon_x + icon_width - 1) {
+            image.put_pixel(x, y, Luma([255u8]));
+        }
+    }
+
+    draw_trmnl_text(
+        image,
+        icon_x + icon_width + 8,
+        top + 8,
+        &text,
+        Luma([255u8]),
+        2,
+    );
 }
 
 fn draw_trmnl_text(image: &mut GrayImage, x: u32, y: u32, text: &str, color: Luma<u8>, scale: u32) {
     let char_width = 6 * scale;
     let char_height = 8 * scale;
-    let char_spacing = 1 * scale;
-    let mut offset = 0u32;
+    let char_spacing = scale;
 
-    for ch in text.chars().take(60) {
-        let char_x = x + (offset * (char_width + char_spacing));
+    for (offset, ch) in text.chars().take(60).enumerate() {
+        let char_x = x + (offset as u32 * (char_width + char_spacing));
         let char_y = y;
 
         if char_x + char_width >= image.width() || char_y + char_height >= image.height() {
@@ -1371,11 +10072,11 @@ fn draw_trmnl_text(image: &mut GrayImage, x: u32, y: u32, text: &str, color: Lum
         }
 
         // Get bitmap for character
-        let char_bitmap = get_char_bitmap(ch);
+        let char_bitmap = font::glyph(ch);
 
         // Draw the character bitmap with scaling
         for (row_idx, &row) in char_bitmap.iter().enumerate() {
-            for col_idx in 0..(6u32) {
+            for col_idx in 0..6u32 {
                 if row & (1 << (5 - col_idx)) != 0 {
                     // Draw scaled pixel
                     for sy in 0..scale {
@@ -1390,10 +10091,97 @@ fn draw_trmnl_text(image: &mut GrayImage, x: u32, y: u32, text: &str, color: Lum
                 }
             }
         }
-        offset += 1;
     }
 }
 
+/// Draw a 12x12 icon (rows of 12 bits, leftmost column in bit 11) in black.
+fn draw_trmnl_icon(image: &mut GrayImage, x: u32, y: u32, icon: &[u16; 12], scale: u32) {
+    for (row_idx, &row) in icon.iter().enumerate() {
+        for col_idx in 0..12u32 {
+            if row & (1 << (11 - col_idx)) == 0 {
+                continue;
+            }
+            for sy in 0..scale {
+                for sx in 0..scale {
+                    let px = x + col_idx * scale + sx;
+                    let py = y + row_idx as u32 * scale + sy;
+                    if px < image.width() && py < image.height() {
+                        image.put_pixel(px, py, Luma([0u8]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Faux-bold text: the glyphs are drawn twice, offset horizontally.
+fn draw_trmnl_text_bold(
+    image: &mut GrayImage,
+    x: u32,
+    y: u32,
+    text: &str,
+    color: Luma<u8>,
+    scale: u32,
+) {
+    draw_trmnl_text(image, x, y, text, color, scale);
+    draw_trmnl_text(image, x + scale.div_ceil(2), y, text, color, scale);
+}
+
+/// Draw `text` rotated 90° counter-clockwise so it reads bottom-to-top.
+/// `(x, y)` is the top-left corner of the resulting column, which is
+/// `8 * scale` pixels wide and `text_width(text, scale)` pixels tall.
+fn draw_trmnl_text_vertical(
+    image: &mut GrayImage,
+    x: u32,
+    y: u32,
+    text: &str,
+    color: Luma<u8>,
+    scale: u32,
+) {
+    let advance = GLYPH_ADVANCE * scale;
+    let column_height = text_width(text, scale);
+
+    for (offset, ch) in text.chars().enumerate() {
+        // First character sits at the bottom of the column
+        let char_top = y + column_height - (offset as u32 + 1) * advance;
+        let char_bitmap = font::glyph(ch);
+
+        for (row_idx, &row) in char_bitmap.iter().enumerate() {
+            for col_idx in 0..6u32 {
+                if row & (1 << (5 - col_idx)) != 0 {
+                    // Glyph rows become columns; glyph columns run upwards
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            let px = x + (row_idx as u32 * scale) + sx;
+                            let py = char_top + scale + ((5 - col_idx) * scale) + sy;
+                            if px < image.width() && py < image.height() {
+                                image.put_pixel(px, py, color);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn draw_trmnl_side_label(
+    image: &mut GrayImage,
+    top: u32,
+    bottom: u32,
+    label: &str,
+    abbreviations: &Abbreviations,
+) {
+    let scale = 2;
+    let available = bottom.saturating_sub(top);
+    let label = abbreviations.shorten(label, available, scale);
+    let label_height = text_width(&label, scale);
+
+    // Center vertically within the content area, between border and sensor names
+    let y = top + (available.saturating_sub(label_height)) / 2;
+    draw_trmnl_text_vertical(image, 12, y, &label, Luma([0u8]), scale);
+}
+
 fn is_percentage_sensor(sensor: &EntityState) -> bool {
     // Check if sensor has percentage unit only
     let unit = sensor
@@ -1406,30 +10194,100 @@ fn is_percentage_sensor(sensor: &EntityState) -> bool {
     unit == "%"
 }
 
+/// `min`/`max` of `input_number` and `number` entities.
+fn input_number_range(sensor: &EntityState) -> Option<(f64, f64)> {
+    let domain = sensor.entity_id.split('.').next().unwrap_or("");
+    if !matches!(domain, "input_number" | "number") {
+        return None;
+    }
+
+    let min = sensor.attributes.get("min")?.as_f64()?;
+    let max = sensor.attributes.get("max")?.as_f64()?;
+    (max > min).then_some((min, max))
+}
+
+/// Today's usage and the daily budget of a meter listed in `METER_BUDGETS`.
+fn meter_budget(sensor: &EntityState) -> Option<(f64, f64)> {
+    let usage = sensor.attributes.get(meters::USAGE_ATTRIBUTE)?.as_f64()?;
+    let budget = sensor.attributes.get(meters::BUDGET_ATTRIBUTE)?.as_f64()?;
+    Some((usage, budget))
+}
+
+/// How full a gauge for this sensor should be (0.0-1.0), or `None` if the
+/// sensor isn't shown as a gauge.
+fn gauge_fraction(sensor: &EntityState) -> Option<f64> {
+    if let Some(progress) = sensor
+        .attributes
+        .get(appliances::PROGRESS_ATTRIBUTE)
+        .and_then(|v| v.as_f64())
+    {
+        return Some((progress / 100.0).clamp(0.0, 1.0));
+    }
+
+    if let Some((usage, budget)) = meter_budget(sensor) {
+        return Some((usage / budget).clamp(0.0, 1.0));
+    }
+
+    let value = sensor.state.parse::<f64>().ok()?;
+
+    if is_percentage_sensor(sensor) {
+        Some((value / 100.0).clamp(0.0, 1.0))
+    } else {
+        let (min, max) = input_number_range(sensor)?;
+        Some(((value - min) / (max - min)).clamp(0.0, 1.0))
+    }
+}
+
+/// Choices of `input_select` and `select` entities.
+fn select_options(sensor: &EntityState) -> Option<Vec<&str>> {
+    let domain = sensor.entity_id.split('.').next().unwrap_or("");
+    if !matches!(domain, "input_select" | "select") || sensor.state == "unavailable" {
+        return None;
+    }
+
+    let options: Vec<&str> = sensor
+        .attributes
+        .get("options")?
+        .as_array()?
+        .iter()
+        .filter_map(|option| option.as_str())
+        .collect();
+    (!options.is_empty()).then_some(options)
+}
+
+fn format_range_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
 fn draw_trmnl_gauge(
     image: &mut GrayImage,
     y_pos: u32,
-    _line_height: u32,
+    line_height: u32,
     sensor: &EntityState,
     formatted_value: &str,
+    accessible: bool,
 ) {
-    const WIDTH: u32 = 800;
+    let width = image.width();
 
-    // Parse percentage value
-    let percentage = if let Ok(val) = sensor.state.parse::<f64>() {
-        val.clamp(0.0, 100.0)
-    } else {
-        0.0
-    };
+    let percentage = gauge_fraction(sensor).unwrap_or(0.0) * 100.0;
 
-    // Gauge dimensions
+    // Gauge dimensions (taller, below the name in the accessibility profile),
+    // moved left when the value next to it is longer than a percentage
     let gauge_width = 200;
-    let gauge_height = 16;
-    let gauge_x = WIDTH - gauge_width - 120;
-    let gauge_y = y_pos + 30;
+    let (gauge_height, gauge_x, gauge_y, border) = if accessible {
+        let value_space = (text_width(formatted_value, 3) + 76).max(160);
+        (24, width - gauge_width - value_space, y_pos + 45, 3)
+    } else {
+        let value_space = (text_width(formatted_value, 2) + 50).max(120);
+        (16, width - gauge_width - value_space, y_pos + 30, 2)
+    };
 
     // Draw gauge border (thick for 1-bit display)
-    for thickness in 0..2 {
+    for thickness in 0..border {
         // Top and bottom borders
         for x in gauge_x..(gauge_x + gauge_width) {
             if gauge_y + thickness < image.height() {
@@ -1442,10 +10300,10 @@ fn draw_trmnl_gauge(
 
         // Left and right borders
         for y in gauge_y..(gauge_y + gauge_height) {
-            if gauge_x + thickness < WIDTH && y < image.height() {
+            if gauge_x + thickness < width && y < image.height() {
                 image.put_pixel(gauge_x + thickness, y, Luma([0u8]));
             }
-            if gauge_x + gauge_width - 1 - thickness < WIDTH && y < image.height() {
+            if gauge_x + gauge_width - 1 - thickness < width && y < image.height() {
                 image.put_pixel(gauge_x + gauge_width - 1 - thickness, y, Luma([0u8]));
             }
         }
@@ -1455,9 +10313,12 @@ fn draw_trmnl_gauge(
     let fill_width = ((gauge_width - 6) as f64 * percentage / 100.0) as u32;
     for y in (gauge_y + 3)..(gauge_y + gauge_height - 3) {
         for x in (gauge_x + 3)..(gauge_x + 3 + fill_width) {
-            if x < WIDTH && y < image.height() {
+            if x < width && y < image.height() {
                 // Create pattern for different percentage ranges
-                let pattern = if percentage < 25.0 {
+                let pattern = if accessible {
+                    // Always solid so the level reads at a distance
+                    true
+                } else if percentage < 25.0 {
                     // Low: sparse dots
                     (x + y) % 4 == 0
                 } else if percentage < 75.0 {
@@ -1475,9 +10336,50 @@ fn draw_trmnl_gauge(
         }
     }
 
-    // Draw percentage value next to gauge (larger text)
+    // Draw percentage value next to gauge (larger text), white on black
+    // once a meter is over its budget
     let value_x = gauge_x + gauge_width + 10;
-    draw_trmnl_text(image, value_x, y_pos + 25, formatted_value, Luma([0u8]), 2);
+    let (value_y, scale) = if accessible {
+        (gauge_y, 3)
+    } else {
+        (y_pos + 25, 2)
+    };
+    let over_budget = meter_budget(sensor).is_some_and(|(usage, budget)| usage > budget);
+    let value_color = if over_budget {
+        let box_right = (value_x + text_width(formatted_value, scale) + 4).min(width - 8);
+        for y in (value_y - 4)..(value_y + 8 * scale + 3) {
+            for x in (value_x - 5)..box_right {
+                image.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+        Luma([255u8])
+    } else {
+        Luma([0u8])
+    };
+    if accessible {
+        draw_trmnl_text_bold(image, value_x, value_y, formatted_value, value_color, scale);
+    } else {
+        draw_trmnl_text(image, value_x, value_y, formatted_value, value_color, scale);
+    }
+
+    // Label the ends of input_number ranges when the row has room
+    if let Some((min, max)) = input_number_range(sensor)
+        && !accessible
+        && line_height >= 60
+    {
+        let label_y = gauge_y + gauge_height + 3;
+        draw_trmnl_text(
+            image,
+            gauge_x,
+            label_y,
+            &format_range_value(min),
+            Luma([0u8]),
+            1,
+        );
+        let max = format_range_value(max);
+        let max_x = gauge_x + gauge_width - text_width(&max, 1);
+        draw_trmnl_text(image, max_x, label_y, &max, Luma([0u8]), 1);
+    }
 
     // Draw percentage markers (tick marks)
     let tick_positions = [25, 50, 75]; // 25%, 50%, 75% marks
@@ -1485,33 +10387,440 @@ fn draw_trmnl_gauge(
         let tick_x = gauge_x + 3 + ((gauge_width - 6) * tick_pct / 100);
         // Draw small tick mark above gauge
         for dy in 0..4 {
-            if gauge_y > dy && tick_x < WIDTH {
+            if gauge_y > dy && tick_x < width {
                 image.put_pixel(tick_x, gauge_y - dy - 1, Luma([0u8]));
             }
         }
     }
 }
 
-fn convert_to_1bit_png(gray_image: &GrayImage) -> anyhow::Result<Vec<u8>> {
-    // Convert to 1-bit by thresholding
-    let threshold = 128u8;
-    let mut binary_image: GrayImage = ImageBuffer::new(gray_image.width(), gray_image.height());
+/// Options of a select as a row of boxes, right-aligned, with the current
+/// one inverted. When they don't all fit, the ones furthest from the
+/// current option are left out.
+fn draw_trmnl_options(image: &mut GrayImage, y_pos: u32, options: &[&str], current: &str) {
+    let width = image.width();
+    const AREA_WIDTH: u32 = 360;
+    const GAP: u32 = 6;
+
+    let row_width = |options: &[&str], scale: u32| -> u32 {
+        options
+            .iter()
+            .map(|option| text_width(option, scale) + 4 * scale)
+            .sum::<u32>()
+            + GAP * options.len().saturating_sub(1) as u32
+    };
+
+    let selected = options.iter().position(|option| *option == current);
+    let (scale, shown) = if row_width(options, 2) <= AREA_WIDTH {
+        (2, options)
+    } else if row_width(options, 1) <= AREA_WIDTH {
+        (1, options)
+    } else {
+        // Grow a window around the current option while it fits
+        let center = selected.unwrap_or(0);
+        let (mut start, mut end) = (center, center + 1);
+        loop {
+            let mut grown = false;
+            if end < options.len() && row_width(&options[start..=end], 1) <= AREA_WIDTH {
+                end += 1;
+                grown = true;
+            }
+            if start > 0 && row_width(&options[start - 1..end], 1) <= AREA_WIDTH {
+                start -= 1;
+                grown = true;
+            }
+            if !grown {
+                break;
+            }
+        }
+        (1, &options[start..end])
+    };
+
+    let text_y = y_pos + 25;
+    let box_top = text_y - 2 * scale;
+    let box_bottom = (text_y + 10 * scale).min(image.height() - 1);
+    let mut x = width - 40 - row_width(shown, scale).min(AREA_WIDTH);
+
+    for option in shown {
+        let box_width = text_width(option, scale) + 4 * scale;
+        let box_right = (x + box_width).min(width - 1);
+        let is_current = *option == current;
+
+        for py in box_top..=box_bottom {
+            for px in x..box_right {
+                let edge = py == box_top || py == box_bottom || px == x || px == box_right - 1;
+                if is_current || edge {
+                    image.put_pixel(px, py, Luma([0u8]));
+                }
+            }
+        }
+        let text_color = if is_current {
+            Luma([255u8])
+        } else {
+            Luma([0u8])
+        };
+        let label = truncate_to_width(option, AREA_WIDTH - 4 * scale, scale);
+        draw_trmnl_text(image, x + 2 * scale, text_y, &label, text_color, scale);
 
-    for (x, y, pixel) in gray_image.enumerate_pixels() {
-        let binary_value = if pixel[0] > threshold { 255u8 } else { 0u8 };
-        binary_image.put_pixel(x, y, Luma([binary_value]));
+        x += box_width + GAP;
     }
+}
 
-    // Encode to PNG
-    let mut buffer = Vec::new();
+/// Final steps shared by every TRMNL render.
+struct TrmnlOutput {
+    /// Size the screen is laid out at (`TRMNL_WIDTH` x `TRMNL_HEIGHT`)
+    layout: (u32, u32),
+    dither: Dither,
+    /// Version/render time drawn in the bottom-right corner
+    stamp: Option<String>,
+    rotation: Option<Rotation>,
+    /// White on black, applied after dithering so photos invert exactly
+    invert: bool,
+    post: PostProcess,
+    format: BitmapFormat,
+    /// Output size and margin when not the layout's own
+    canvas: Option<Canvas>,
+}
+
+impl TrmnlOutput {
+    /// Draw the display border, fit to the output size, badge a low battery,
+    /// rotate, stamp and convert to a 1-bit (or `depth`-bit) image.
+    fn finish(&self, mut image: GrayImage) -> anyhow::Result<bytes::Bytes> {
+        watchdog::inspect_gray(&image);
+
+        // Draw border around entire display
+        draw_trmnl_border(&mut image);
+
+        if let Some(canvas) = self.canvas {
+            image = canvas.apply(&image);
+        }
+        // Before rotating, so the badge is upright on a rotated panel
+        if let Ok(volts) = LOW_BATTERY.try_with(|volts| *volts) {
+            draw_low_battery_badge(&mut image, volts);
+        }
+
+        if let Some(rotation) = self.rotation {
+            image = rotation.apply(&image);
+        }
+        if let Some(stamp) = &self.stamp {
+            draw_trmnl_stamp(&mut image, stamp);
+        }
+
+        let image_data = convert_to_1bit(image, self.dither, self.post, self.invert, self.format)?;
+
+        Ok(bytes::Bytes::from(image_data))
+    }
+}
+
+fn convert_to_1bit(
+    mut gray_image: GrayImage,
+    dither: Dither,
+    post: PostProcess,
+    invert: bool,
+    format: BitmapFormat,
+) -> anyhow::Result<Vec<u8>> {
+    post.before_dither(&mut gray_image);
+
+    // Convert to 1-bit (or the raw depth) with the requested dithering
+    let mut binary_image = dither.quantize(&gray_image, format.levels());
+    post.after_dither(&gray_image, &mut binary_image, format.levels());
+    if invert {
+        image::imageops::invert(&mut binary_image);
+    }
+
+    format.encode(&binary_image)
+}
+
+/// Processing applied to proxied camera/entity/URL images.
+struct ImageProcessing {
+    adjustments: Adjustments,
+    crop: Option<Crop>,
+    resize: Option<Resize>,
+    rotation: Option<Rotation>,
+    palette: Option<Palette>,
+    grayscale: Option<Grayscale>,
+    format: Option<PhotoFormat>,
+}
+
+impl ImageProcessing {
+    fn new(
+        adjustments: Adjustments,
+        width: Option<u32>,
+        height: Option<u32>,
+        fit: Option<&str>,
+        background: Option<&str>,
+        crop: Option<&str>,
+        rotate: Option<u32>,
+    ) -> Result<Self, AppError> {
+        let mode = match fit {
+            Some(name) => FitMode::by_name(name).ok_or_else(|| {
+                AppError::BadRequest(format!("Unknown fit '{}'. Use fit, fill or stretch", name))
+            })?,
+            None => FitMode::Fit,
+        };
+        let background = match background {
+            Some(color) => theme::parse_color(color).ok_or_else(|| {
+                AppError::BadRequest(format!("Unknown background color '{}'", color))
+            })?,
+            None => Rgb([0, 0, 0]),
+        };
+        let background = image::Rgba([background[0], background[1], background[2], 255]);
+
+        let resize = Resize::new(width, height, mode, background).map_err(AppError::BadRequest)?;
+
+        let crop = crop
+            .map(Crop::parse)
+            .transpose()
+            .map_err(AppError::BadRequest)?;
+
+        Ok(Self {
+            adjustments,
+            crop,
+            resize,
+            rotation: parse_rotation(rotate)?,
+            palette: None,
+            grayscale: None,
+            format: None,
+        })
+    }
+
+    /// Reduce the output to a panel palette (`?palette=`).
+    fn with_palette(mut self, palette: Option<&str>) -> Result<Self, AppError> {
+        self.palette = parse_palette(palette)?;
+        Ok(self)
+    }
+
+    /// Reduce the output to `?depth=` bits of gray, dithered with
+    /// `?dither=` (Atkinson by default, as for TRMNL camera screens) and
+    /// inverted with `?invert=true`.
+    fn with_depth(
+        mut self,
+        depth: Option<u8>,
+        dither: Option<&str>,
+        invert: Option<bool>,
+    ) -> Result<Self, AppError> {
+        self.grayscale = parse_grayscale(depth, dither, invert, self.palette, Dither::Atkinson)?;
+        Ok(self)
+    }
+
+    /// Re-encode the output (`?format=`, `?quality=`), even when nothing
+    /// else changes. Palette and depth output are always PNG.
+    fn with_format(
+        mut self,
+        format: Option<&str>,
+        quality: Option<u8>,
+        headers: &HeaderMap,
+    ) -> Result<Self, AppError> {
+        self.format = parse_photo_format(
+            format,
+            quality,
+            png_only(self.palette, self.grayscale),
+            headers,
+        )?;
+        Ok(self)
+    }
+
+    /// Processed images are re-encoded as PNG (or the requested format);
+    /// images that need no processing are passed through as fetched.
+    fn apply(
+        &self,
+        image_data: bytes::Bytes,
+        content_type: String,
+        png: &PngOptions,
+    ) -> anyhow::Result<(bytes::Bytes, String)> {
+        if self.adjustments.is_identity()
+            && self.crop.is_none()
+            && self.resize.is_none()
+            && self.rotation.is_none()
+            && self.palette.is_none()
+            && self.grayscale.is_none()
+            && self.format.is_none()
+        {
+            return Ok((image_data, content_type));
+        }
+
+        let mut image = image::load_from_memory(&image_data)
+            .map_err(|e| anyhow::anyhow!("Failed to decode image: {}", e))?;
+        if let Some(crop) = &self.crop {
+            let target_aspect = self.resize.as_ref().and_then(Resize::aspect_ratio);
+            image = crop.apply(&image, target_aspect)?;
+        }
+        if let Some(resize) = &self.resize {
+            image = resize.apply(&image);
+        }
+        let mut processed = self.adjustments.apply(&image);
+        if let Some(rotation) = &self.rotation {
+            processed = image::DynamicImage::ImageRgba8(rotation.apply(&processed));
+        }
+        if let Some(palette) = self.palette {
+            let mut image = processed.to_rgb8();
+            palette.quantize(&mut image);
+            let buffer = palette.encode_png(&image)?;
+            return Ok((bytes::Bytes::from(buffer), "image/png".to_string()));
+        }
+        if let Some(grayscale) = self.grayscale {
+            let buffer = grayscale.encode(&processed)?;
+            return Ok((bytes::Bytes::from(buffer), "image/png".to_string()));
+        }
+
+        let format = self.format.unwrap_or(PhotoFormat::Png);
+        let buffer = format.encode(&processed, png)?;
+
+        Ok((
+            bytes::Bytes::from(buffer),
+            format.content_type().to_string(),
+        ))
+    }
+}
+
+/// `?format=` and `?quality=` of a color image, falling back to a PNG or
+/// JPEG `Accept` header; `None` when none of them is set. `png_only` names
+/// the parameter (`palette` or `depth`) that rules out other formats.
+fn parse_photo_format(
+    format: Option<&str>,
+    quality: Option<u8>,
+    png_only: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Option<PhotoFormat>, AppError> {
+    let format = match (format, quality) {
+        (Some(format), quality) => {
+            Some(PhotoFormat::parse(format, quality).map_err(AppError::BadRequest)?)
+        }
+        (None, Some(_)) => {
+            return Err(AppError::BadRequest(
+                "quality only applies to format=jpeg".to_string(),
+            ));
+        }
+        // Palette and depth output are PNG whatever the client accepts
+        (None, None) if png_only.is_some() => None,
+        (None, None) => accept::preferred(headers, &["png", "jpeg"])
+            .map(|name| PhotoFormat::parse(name, None).map_err(AppError::BadRequest))
+            .transpose()?,
+    };
+
+    if let Some(reduced) = png_only
+        && !matches!(format, None | Some(PhotoFormat::Png))
     {
-        let mut cursor = Cursor::new(&mut buffer);
-        binary_image
-            .write_to(&mut cursor, image::ImageOutputFormat::Png)
-            .map_err(|e| anyhow::anyhow!("Failed to encode 1-bit PNG: {}", e))?;
+        return Err(AppError::BadRequest(format!(
+            "{} output is always PNG",
+            reduced
+        )));
+    }
+    Ok(format)
+}
+
+/// Which of `palette` or `depth` fixes the output to PNG, if either.
+fn png_only(palette: Option<Palette>, grayscale: Option<Grayscale>) -> Option<&'static str> {
+    match (palette, grayscale) {
+        (Some(_), _) => Some("palette"),
+        (None, Some(_)) => Some("depth"),
+        (None, None) => None,
+    }
+}
+
+/// `?depth=`, `?dither=` and `?invert=` of a color render or photo; `None`
+/// without a depth, which leaves the output in color.
+fn parse_grayscale(
+    depth: Option<u8>,
+    dither: Option<&str>,
+    invert: Option<bool>,
+    palette: Option<Palette>,
+    default_dither: Dither,
+) -> Result<Option<Grayscale>, AppError> {
+    let dither = parse_dither(dither)?;
+    let Some(depth) = depth else {
+        if dither.is_some() {
+            return Err(AppError::BadRequest(
+                "dither only applies with depth".to_string(),
+            ));
+        }
+        if invert == Some(true) {
+            return Err(AppError::BadRequest(
+                "invert only applies with depth".to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+    if palette.is_some() {
+        return Err(AppError::BadRequest(
+            "Use either ?palette= or ?depth=, not both".to_string(),
+        ));
     }
 
-    Ok(buffer)
+    Ok(Some(Grayscale {
+        depth: GrayDepth::from_bits(depth).map_err(AppError::BadRequest)?,
+        dither: dither.unwrap_or(default_dither),
+        invert: invert.unwrap_or(false),
+    }))
+}
+
+fn parse_dither(name: Option<&str>) -> Result<Option<Dither>, AppError> {
+    name.map(|name| {
+        Dither::by_name(name).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unknown dither '{}'. Use floyd, atkinson, bayer or none",
+                name
+            ))
+        })
+    })
+    .transpose()
+}
+
+/// `?units=` preset and per-entity overrides.
+fn parse_units(spec: Option<&str>) -> Result<Units, AppError> {
+    Units::parse(spec).map_err(AppError::BadRequest)
+}
+
+fn parse_priorities(spec: Option<&str>) -> Result<Priorities, AppError> {
+    Priorities::parse(spec).map_err(AppError::BadRequest)
+}
+
+/// `?post=` steps of a TRMNL screen.
+fn parse_post(spec: Option<&str>) -> Result<PostProcess, AppError> {
+    spec.map(PostProcess::parse)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(AppError::BadRequest)
+}
+
+fn parse_palette(name: Option<&str>) -> Result<Option<Palette>, AppError> {
+    name.map(|name| {
+        Palette::by_name(name).ok_or_else(|| {
+            AppError::BadRequest(format!("Unknown palette '{}'. Use bwr or spectra6", name))
+        })
+    })
+    .transpose()
+}
+
+/// `?format=` of a TRMNL screen, else a BMP or PNG `Accept` header, else PNG.
+fn parse_bitmap_format(
+    name: Option<&str>,
+    headers: &HeaderMap,
+    depth: Option<u8>,
+    bit_order: Option<&str>,
+) -> Result<BitmapFormat, AppError> {
+    let name = name
+        .or_else(|| accept::preferred(headers, &["png", "bmp"]))
+        .unwrap_or("png");
+    BitmapFormat::parse(name, depth, bit_order).map_err(AppError::BadRequest)
+}
+
+/// `?width=`, `?height=` and `?margin=` of a TRMNL screen.
+fn parse_canvas(
+    width: Option<u32>,
+    height: Option<u32>,
+    margin: Option<u32>,
+) -> Result<Option<Canvas>, AppError> {
+    Canvas::new(width, height, margin).map_err(AppError::BadRequest)
+}
+
+/// `?rotate=` in degrees clockwise.
+fn parse_rotation(degrees: Option<u32>) -> Result<Option<Rotation>, AppError> {
+    degrees
+        .map(Rotation::from_degrees)
+        .transpose()
+        .map(Option::flatten)
+        .map_err(AppError::BadRequest)
 }
 
 fn create_image_response(image_data: bytes::Bytes, content_type: String) -> Response {
@@ -1520,6 +10829,8 @@ fn create_image_response(image_data: bytes::Bytes, content_type: String) -> Resp
         [
             (header::CONTENT_TYPE, content_type),
             (header::CACHE_CONTROL, "public, max-age=300".to_string()),
+            // The format may have been negotiated from `Accept`
+            (header::VARY, "Accept".to_string()),
         ],
         image_data,
     )
@@ -1554,6 +10865,77 @@ impl IntoResponse for AppError {
     }
 }
 
+fn app(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/image/entity/:entity_id", get(serve_entity_image))
+        .route("/image/url", get(serve_image_by_url))
+        .route("/status/:entity_id", get(render_entity_status))
+        .route("/history/:entity_id", get(render_history_chart))
+        .route("/statistics/:statistic_id", get(render_statistics_chart))
+        .route("/multi-status", get(render_multi_sensor_status))
+        .route("/trmnl", get(render_trmnl_sensors))
+        .route("/trmnl/auto", get(render_trmnl_auto))
+        .route("/trmnl/markup", get(render_trmnl_markup))
+        .route("/trmnl/activity", get(render_trmnl_activity))
+        .route("/trmnl/events", get(render_trmnl_events))
+        .route("/trmnl/delta", get(render_trmnl_delta))
+        .route("/trmnl/pollen", get(render_trmnl_pollen))
+        .route("/trmnl/tide", get(render_trmnl_tide))
+        .route("/trmnl/flights", get(render_trmnl_flights))
+        .route("/trmnl/carbon", get(render_trmnl_carbon))
+        .route("/trmnl/fleet", get(render_trmnl_fleet))
+        .route("/trmnl/reminders", get(render_trmnl_reminders))
+        .route("/trmnl/timetable", get(render_trmnl_timetable))
+        .route("/trmnl/noise", get(render_trmnl_noise))
+        .route("/trmnl/leaderboard", get(render_trmnl_leaderboard))
+        .route("/trmnl/sleep", get(render_trmnl_sleep))
+        .route("/summary", get(render_sensor_summary))
+        .route("/report/daily.pdf", get(render_daily_report))
+        .route("/cameras", get(list_camera_entities))
+        .route("/dashboards/:name", get(render_dashboard))
+        .route("/dashboards/:name/summary", get(dashboard_summary))
+        .route("/dashboards/:name/checksum", get(dashboard_checksum))
+        .route("/dashboards/:name/tiles/:index", get(render_dashboard_tile))
+        .route("/dashboards/:name/encodings", get(dashboard_encodings))
+        .route("/share/:name", get(share_dashboard))
+        .route("/cycle", get(render_cycle))
+        .route("/admin/warm", post(warm_dashboards))
+        .route("/admin/validate", get(validate_dashboards))
+        .route("/admin/watchdog", get(watchdog_report))
+        .route("/api/display", get(trmnl_display))
+        .route("/api/screens/:name", get(screen_display))
+        .route("/devices", get(list_devices).post(register_device))
+        .route("/devices/:device_id", patch(update_device))
+        .route("/devices/:device_id/history", get(device_history))
+        .route(
+            "/devices/:device_id/history/:seq",
+            get(device_history_frame),
+        )
+        .fallback(unknown_endpoint)
+        .layer(middleware::from_fn_with_state(state.clone(), watch_renders))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            apply_device_preset,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), track_devices))
+        .layer(middleware::from_fn(compress_framebuffer))
+        .layer(middleware::from_fn(json_envelope))
+        .layer(CorsLayer::permissive())
+        .with_state(state)
+}
+
+/// Optional numeric setting with a default.
+fn env_number<T: std::str::FromStr>(name: &str, default: T) -> anyhow::Result<T> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid {} '{}'", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Load .env file if it exists
@@ -1583,17 +10965,51 @@ async fn main() -> anyhow::Result<()> {
     // Initialize application state
     let app_state = Arc::new(AppState::new()?);
 
+    // Get through the proxy in front of Home Assistant before serving
+    if let Some(auth) = &app_state.proxy_auth {
+        let config = &app_state.ha_config;
+        let lifetime = match config
+            .extra_headers
+            .refresh(
+                auth,
+                &app_state.http_client,
+                &config.base_url,
+                &config.token,
+            )
+            .await
+        {
+            Ok(lifetime) => {
+                info!("Got a Home Assistant proxy token");
+                Some(lifetime)
+            }
+            Err(e) => {
+                warn!("Failed to get a Home Assistant proxy token: {}", e);
+                None
+            }
+        };
+        tokio::spawn(proxy_auth::keep_fresh(
+            config.extra_headers.clone(),
+            auth.clone(),
+            app_state.http_client.clone(),
+            config.base_url.clone(),
+            config.token.clone(),
+            lifetime,
+        ));
+    }
+
+    // Watch for devices that stop polling
+    tokio::spawn(watch_devices(app_state.clone()));
+
+    if app_state.dashboard_cache.enabled() {
+        tokio::spawn(watch_dashboard_entities(app_state.clone()));
+    }
+
+    if !app_state.webhooks.is_empty() {
+        tokio::spawn(push_webhooks(app_state.clone()));
+    }
+
     // Build our application with routes
-    let app = Router::new()
-        .route("/health", get(health_check))
-        .route("/image/entity/:entity_id", get(serve_entity_image))
-        .route("/image/url", get(serve_image_by_url))
-        .route("/status/:entity_id", get(render_entity_status))
-        .route("/multi-status", get(render_multi_sensor_status))
-        .route("/trmnl", get(render_trmnl_sensors))
-        .route("/cameras", get(list_camera_entities))
-        .layer(CorsLayer::permissive())
-        .with_state(app_state);
+    let app = app(app_state);
 
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "3000".to_string())
@@ -1609,8 +11025,57 @@ async fn main() -> anyhow::Result<()> {
     info!("  GET /image/url?url={{url}} - Serve image from Home Assistant URL");
     info!("  GET /status/{{entity_id}} - Render entity status as static image");
     info!("  GET /multi-status?sensors={{sensor1,sensor2}} - Render multiple sensors");
-    info!("  GET /trmnl?sensors={{sensor1,sensor2}} - Render TRMNL 1-bit 800x480 display");
+    info!("  GET /trmnl?sensors={{sensor1,sensor2}} - Render TRMNL 1-bit display");
+    info!("  GET /history/{{entity_id}}.png?hours=24 - Line chart of a sensor's history");
+    info!(
+        "  GET /statistics/{{statistic_id}}.png?period=day&count=14 - Chart of long-term statistics"
+    );
+    info!(
+        "  GET /trmnl/auto?domains={{sensor,binary_sensor}}&area={{area}} - TRMNL display of matching entities"
+    );
+    info!(
+        "  GET /trmnl/markup?sensors={{sensor1,sensor2}} - TRMNL framework HTML for a private plugin"
+    );
+    info!("  GET /trmnl/activity - Scenes and scripts with when they last ran");
+    info!(
+        "  GET /trmnl/events?events={{Label=sensor1,Label=sensor2:attribute}} - Upcoming events with countdowns"
+    );
+    info!("  GET /trmnl/delta?a={{sensor1}}&b={{sensor2}} - Difference between two sensors");
+    info!("  GET /trmnl/pollen?allergens={{Grass=sensor1,Birch=sensor2}} - Pollen forecast");
+    info!(
+        "  GET /trmnl/tide?tide={{sensor}}&marine={{sensor1,sensor2}} - Tide chart and marine conditions"
+    );
+    info!("  GET /trmnl/flights?sensor={{sensor}} - Aircraft overhead");
+    info!(
+        "  GET /trmnl/carbon?sensor={{sensor}} - Carbon intensity or price with when to run appliances"
+    );
+    info!("  GET /trmnl/fleet - Battery, signal and screen of every TRMNL device");
+    info!("  GET /trmnl/reminders?items={{items}} - Today's medication or reminder checklist");
+    info!("  GET /trmnl/timetable?calendar={{calendar}} - Weekly school timetable grid");
+    info!("  GET /trmnl/noise?sensor={{sensor}} - Sound level history with quiet hours");
+    info!("  GET /trmnl/leaderboard?sensors={{sensor1,sensor2}} - Entities ranked with bars");
+    info!("  GET /trmnl/sleep - Mostly white frame for a device's quiet hours");
+    info!("  GET /summary?sensors={{sensor1,sensor2}} - Plain-text sensor summary");
+    info!("  GET /report/daily.pdf?sensors={{sensor1,sensor2}} - Printable A4 daily report");
     info!("  GET /cameras - List all camera entities");
+    info!("  GET /dashboards/{{name}} - Render a dashboard configured in DASHBOARDS");
+    info!("  GET /dashboards/{{name}}/summary - Text summary of a dashboard");
+    info!("  GET /dashboards/{{name}}/checksum - Checksum of a dashboard's current render");
+    info!("  GET /dashboards/{{name}}/tiles/{{index}} - One panel of a tiled dashboard");
+    info!("  GET /dashboards/{{name}}/encodings - Size and encode time of a dashboard per format");
+    info!("  GET /share/{{name}} - A dashboard with private entities hidden, for posting publicly");
+    info!("  GET /cycle?screens=a,b,c - Animated GIF cycling through dashboards");
+    info!("  POST /admin/warm - Render all configured dashboards once");
+    info!("  GET /admin/validate - Check that every dashboard names a supported endpoint");
+    info!("  GET /admin/watchdog - Renders the watchdog found suspicious");
+    info!(
+        "  GET /api/display - TRMNL display API serving each device its DEVICE_SCREENS_FILE screen"
+    );
+    info!("  GET /api/screens/{{name}} - TRMNL display API for a named screen or dashboard");
+    info!("  GET /devices - Devices seen, with last check-in and telemetry");
+    info!("  POST /devices - Register a device with a name, preset and screen");
+    info!("  PATCH /devices/{{device_id}} - Change a device's name, preset or screen");
+    info!("  GET /devices/{{device_id}}/history - Frames recently served to a device");
     info!("");
     info!("🧪 Test your setup:");
     info!("  Open test.html in your browser for visual testing");
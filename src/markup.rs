@@ -0,0 +1,104 @@
+//! `/trmnl/markup`: sensors as HTML using the TRMNL framework's classes, for
+//! a TRMNL private plugin that polls this server for its markup and leaves
+//! the rendering to TRMNL's cloud.
+
+use crate::html_escape;
+
+/// Rows a single column holds before the items are split into columns
+const COLUMN_ROWS: usize = 6;
+
+/// One sensor of the markup, already formatted.
+pub struct Item {
+    pub name: String,
+    pub value: String,
+    /// In an attention state: the label is inverted
+    pub attention: bool,
+}
+
+/// TRMNL plugin view the markup is laid out for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    Full,
+    HalfHorizontal,
+    HalfVertical,
+    Quadrant,
+}
+
+impl View {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('-', "_").as_str() {
+            "full" => Some(Self::Full),
+            "half_horizontal" => Some(Self::HalfHorizontal),
+            "half_vertical" => Some(Self::HalfVertical),
+            "quadrant" => Some(Self::Quadrant),
+            _ => None,
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            Self::Full => "view--full",
+            Self::HalfHorizontal => "view--half_horizontal",
+            Self::HalfVertical => "view--half_vertical",
+            Self::Quadrant => "view--quadrant",
+        }
+    }
+}
+
+/// A `view` of the items with a title bar, in columns of up to
+/// `COLUMN_ROWS` items on the full view.
+pub fn render(items: &[Item], title: &str, instance: &str, view: View) -> String {
+    let value_class = match view {
+        View::Full => "value value--large value--tnums",
+        _ => "value value--small value--tnums",
+    };
+    let layout = |items: &[Item], indent: &str| {
+        let mut html = format!(
+            "{}<div class=\"layout layout--col gap--space-between\">\n",
+            indent
+        );
+        for item in items {
+            let label_class = if item.attention {
+                "label label--inverted"
+            } else {
+                "label"
+            };
+            html.push_str(&format!(
+                "{0}  <div class=\"item\">\n\
+                 {0}    <div class=\"meta\"></div>\n\
+                 {0}    <div class=\"content\">\n\
+                 {0}      <span class=\"{1}\">{2}</span>\n\
+                 {0}      <span class=\"{3}\">{4}</span>\n\
+                 {0}    </div>\n\
+                 {0}  </div>\n",
+                indent,
+                value_class,
+                html_escape(&item.value),
+                label_class,
+                html_escape(&item.name)
+            ));
+        }
+        html.push_str(&format!("{}</div>\n", indent));
+        html
+    };
+
+    let mut html = format!("<div class=\"view {}\">\n", view.class());
+    if view == View::Full && items.len() > COLUMN_ROWS {
+        html.push_str("  <div class=\"columns\">\n");
+        for chunk in items.chunks(COLUMN_ROWS) {
+            html.push_str("    <div class=\"column\">\n");
+            html.push_str(&layout(chunk, "      "));
+            html.push_str("    </div>\n");
+        }
+        html.push_str("  </div>\n");
+    } else {
+        html.push_str(&layout(items, "  "));
+    }
+    html.push_str(&format!(
+        "  <div class=\"title_bar\">\n    <span class=\"title\">{}</span>\n    <span class=\"instance\">{}</span>\n  </div>\n</div>\n",
+        html_escape(title),
+        html_escape(instance)
+    ));
+    html
+}
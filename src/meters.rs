@@ -0,0 +1,41 @@
+//! Daily budgets for water, gas and energy meters, configured through
+//! `METER_BUDGETS`, e.g. `METER_BUDGETS=sensor.water_today=250,sensor.gas_meter=4.5`.
+//! Budgets are in the sensor's own unit.
+
+use std::collections::HashMap;
+
+/// Attributes added to a budgeted meter's state before rendering
+pub const USAGE_ATTRIBUTE: &str = "daily_usage";
+pub const BUDGET_ATTRIBUTE: &str = "daily_budget";
+
+pub fn parse(spec: &str) -> Result<HashMap<String, f64>, String> {
+    let mut budgets = HashMap::new();
+
+    for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+        let (entity_id, budget) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let entity_id = entity_id.trim();
+        let budget = budget
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|budget| *budget > 0.0)
+            .ok_or_else(|| entry.to_string())?;
+
+        if !entity_id.contains('.') || budgets.insert(entity_id.to_string(), budget).is_some() {
+            return Err(entry.to_string());
+        }
+    }
+
+    Ok(budgets)
+}
+
+/// Whether the sensor counts up forever (a meter reading) rather than
+/// resetting every cycle like a `utility_meter`, so today's usage has to be
+/// worked out from its history.
+pub fn is_running_total(attributes: &serde_json::Value) -> bool {
+    let state_class = attributes.get("state_class").and_then(|v| v.as_str());
+    let is_utility_meter =
+        attributes.get("meter_period").is_some() || attributes.get("source").is_some();
+
+    matches!(state_class, Some("total" | "total_increasing")) && !is_utility_meter
+}
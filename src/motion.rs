@@ -0,0 +1,144 @@
+//! Motion check for camera screens. Each snapshot is compared with the one
+//! behind the last render of the same URL on a small grayscale thumbnail;
+//! a still scene reuses that render instead of being adjusted and dithered
+//! again, and keeps reporting the time the scene last changed.
+
+use image::{DynamicImage, GrayImage, imageops::FilterType};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// Thumbnail size the scenes are compared at
+const THUMBNAIL_WIDTH: u32 = 32;
+const THUMBNAIL_HEIGHT: u32 = 24;
+
+/// Mean difference per thumbnail pixel (0-255) still counted as the same
+/// scene; absorbs sensor noise and JPEG artifacts
+const MOTION_THRESHOLD: f64 = 2.0;
+
+/// Renders kept, one per URL
+const MAX_FRAMES: usize = 32;
+
+/// Downscaled grayscale copy of a snapshot.
+pub struct Signature(GrayImage);
+
+impl Signature {
+    fn of(photo: &DynamicImage) -> Self {
+        Self(
+            photo
+                .resize_exact(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, FilterType::Triangle)
+                .to_luma8(),
+        )
+    }
+
+    fn difference(&self, other: &Self) -> f64 {
+        let total: u64 = self
+            .0
+            .pixels()
+            .zip(other.0.pixels())
+            .map(|(a, b)| a[0].abs_diff(b[0]) as u64)
+            .sum();
+        total as f64 / (THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT) as f64
+    }
+}
+
+struct Frame {
+    /// Hash of the latest snapshot bytes, so a repeated snapshot isn't
+    /// even decoded
+    digest: u64,
+    signature: Signature,
+    output: bytes::Bytes,
+    changed_at: SystemTime,
+}
+
+pub enum Check {
+    /// Same scene as the last render
+    Unchanged {
+        output: bytes::Bytes,
+        changed_at: SystemTime,
+    },
+    /// New scene (or first render), decoded for rendering
+    Changed {
+        photo: DynamicImage,
+        signature: Signature,
+    },
+}
+
+#[derive(Clone, Default)]
+pub struct CameraFrames {
+    frames: Arc<Mutex<HashMap<String, Frame>>>,
+}
+
+impl CameraFrames {
+    /// Compare `snapshot` with the scene last rendered for `key`.
+    pub fn check(&self, key: &str, snapshot: &[u8]) -> anyhow::Result<Check> {
+        let digest = digest(snapshot);
+        if let Some(frame) = self.frames.lock().unwrap().get(key)
+            && frame.digest == digest
+        {
+            return Ok(Check::Unchanged {
+                output: frame.output.clone(),
+                changed_at: frame.changed_at,
+            });
+        }
+
+        let photo = image::load_from_memory(snapshot)?;
+        let signature = Signature::of(&photo);
+
+        let mut frames = self.frames.lock().unwrap();
+        if let Some(frame) = frames.get_mut(key)
+            && frame.signature.difference(&signature) <= MOTION_THRESHOLD
+        {
+            // Keep comparing against the rendered scene, so slow drift
+            // still adds up to a change
+            frame.digest = digest;
+            return Ok(Check::Unchanged {
+                output: frame.output.clone(),
+                changed_at: frame.changed_at,
+            });
+        }
+
+        Ok(Check::Changed { photo, signature })
+    }
+
+    /// Remember the render of a changed scene; returns its change time.
+    pub fn store(
+        &self,
+        key: &str,
+        snapshot: &[u8],
+        signature: Signature,
+        output: bytes::Bytes,
+    ) -> SystemTime {
+        let changed_at = SystemTime::now();
+        let mut frames = self.frames.lock().unwrap();
+        if frames.len() >= MAX_FRAMES && !frames.contains_key(key) {
+            let oldest = frames
+                .iter()
+                .min_by_key(|(_, frame)| frame.changed_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                frames.remove(&oldest);
+            }
+        }
+
+        frames.insert(
+            key.to_string(),
+            Frame {
+                digest: digest(snapshot),
+                signature,
+                output,
+                changed_at,
+            },
+        );
+        changed_at
+    }
+}
+
+fn digest(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,90 @@
+//! Sound level history for nurseries and apartments: the last 24 hours of
+//! a noise sensor, with its quiet hours shaded and how long it stayed
+//! above a threshold during them - the crying spell at 3am, or the
+//! neighbours' party past 22:00.
+
+use crate::refresh::Window;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How far back the chart reaches
+pub const SPAN: Duration = Duration::from_secs(24 * 3600);
+
+/// Minute of the day `time` shows at `offset_secs` from UTC.
+fn local_minute(time: SystemTime, offset_secs: i64) -> u32 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    ((secs + offset_secs).div_euclid(60)).rem_euclid(24 * 60) as u32
+}
+
+/// Parts of the chart from `start` to `start + SPAN` that fall in the
+/// quiet hours, as `(left, right)` fractions across it.
+pub fn quiet_bands(quiet: Window, start: SystemTime, offset_secs: i64) -> Vec<(f64, f64)> {
+    let minutes = SPAN.as_secs() / 60;
+    let mut bands: Vec<(f64, f64)> = Vec::new();
+    let mut open: Option<u64> = None;
+
+    for minute in 0..=minutes {
+        let time = start + Duration::from_secs(minute * 60);
+        let inside = minute < minutes && quiet.contains(local_minute(time, offset_secs));
+        match (inside, open) {
+            (true, None) => open = Some(minute),
+            (false, Some(from)) => {
+                bands.push((from as f64 / minutes as f64, minute as f64 / minutes as f64));
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    bands
+}
+
+/// How long `samples` (each held until the next, the last until `now`)
+/// stayed above `threshold` during the quiet hours, to the minute.
+pub fn loud_in_quiet_hours(
+    samples: &[(SystemTime, f64)],
+    threshold: f64,
+    quiet: Window,
+    now: SystemTime,
+    offset_secs: i64,
+) -> Duration {
+    let Some(&(first, _)) = samples.first() else {
+        return Duration::ZERO;
+    };
+    let mut loud = 0;
+    let mut next = 0;
+    let mut time = first;
+
+    while time < now {
+        while samples.get(next + 1).is_some_and(|&(at, _)| at <= time) {
+            next += 1;
+        }
+        if samples[next].1 > threshold && quiet.contains(local_minute(time, offset_secs)) {
+            loud += 1;
+        }
+        time += Duration::from_secs(60);
+    }
+    Duration::from_secs(loud * 60)
+}
+
+/// The loudest sample and when it was reached.
+pub fn peak(samples: &[(SystemTime, f64)]) -> Option<(SystemTime, f64)> {
+    samples
+        .iter()
+        .copied()
+        .fold(None, |peak, sample| match peak {
+            Some((_, loudest)) if loudest >= sample.1 => peak,
+            _ => Some(sample),
+        })
+}
+
+/// `1h 12m`, or `12m` under an hour.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    }
+}
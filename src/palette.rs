@@ -0,0 +1,119 @@
+//! Fixed-palette output for color e-paper panels.
+
+use crate::dither::{FLOYD_STEINBERG, Kernel};
+use crate::encode;
+use image::{Rgb, RgbImage};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    /// Black/white/red panels (e.g. Waveshare B/W/R)
+    BlackWhiteRed,
+    /// Six-color Spectra 6 panels: black, white, yellow, red, blue, green
+    Spectra6,
+}
+
+/// Pixels at least this far from gray count as an accent
+const ACCENT_CHROMA: u8 = 80;
+const THRESHOLD: u8 = 128;
+
+impl Palette {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "bwr" | "black-white-red" | "tri-color" => Some(Self::BlackWhiteRed),
+            "spectra6" | "spectra-6" | "6-color" => Some(Self::Spectra6),
+            _ => None,
+        }
+    }
+
+    /// Panel colors in palette index order.
+    pub fn colors(self) -> &'static [Rgb<u8>] {
+        match self {
+            Self::BlackWhiteRed => &[Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([255, 0, 0])],
+            Self::Spectra6 => &[
+                Rgb([0, 0, 0]),
+                Rgb([255, 255, 255]),
+                Rgb([255, 255, 0]),
+                Rgb([255, 0, 0]),
+                Rgb([0, 0, 255]),
+                Rgb([0, 255, 0]),
+            ],
+        }
+    }
+
+    /// Palette index for a color. On black/white/red panels saturated colors
+    /// become the accent and the rest black or white by brightness; other
+    /// palettes take the nearest color.
+    fn index_of(self, pixel: Rgb<u8>) -> u8 {
+        if self != Self::BlackWhiteRed {
+            return self.nearest([pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]);
+        }
+
+        let [r, g, b] = pixel.0;
+        let chroma = r.max(g).max(b) - r.min(g).min(b);
+        if chroma >= ACCENT_CHROMA {
+            return 2;
+        }
+        let luminance = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+        if luminance > THRESHOLD as u32 { 1 } else { 0 }
+    }
+
+    /// Index of the closest panel color, weighted towards green like the eye.
+    fn nearest(self, [r, g, b]: [f32; 3]) -> u8 {
+        let distance = |color: &Rgb<u8>| {
+            let [cr, cg, cb] = color.0.map(f32::from);
+            2.0 * (r - cr).powi(2) + 4.0 * (g - cg).powi(2) + 3.0 * (b - cb).powi(2)
+        };
+        self.colors()
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map_or(0, |(index, _)| index as u8)
+    }
+
+    /// Reduce every pixel to a panel color. Black/white/red output is snapped
+    /// so text stays crisp; palettes with more colors are dithered so photos
+    /// and gradients keep their shading.
+    pub fn quantize(self, image: &mut RgbImage) {
+        match self {
+            Self::BlackWhiteRed => {
+                let colors = self.colors();
+                for pixel in image.pixels_mut() {
+                    *pixel = colors[self.index_of(*pixel) as usize];
+                }
+            }
+            Self::Spectra6 => self.error_diffusion(image, FLOYD_STEINBERG),
+        }
+    }
+
+    fn error_diffusion(self, image: &mut RgbImage, kernel: Kernel) {
+        let (w, h) = (image.width() as usize, image.height() as usize);
+        let colors = self.colors();
+        let mut levels: Vec<[f32; 3]> = image.pixels().map(|p| p.0.map(f32::from)).collect();
+
+        for y in 0..h {
+            for x in 0..w {
+                let old = levels[y * w + x];
+                let new = colors[self.nearest(old) as usize];
+                image.put_pixel(x as u32, y as u32, new);
+
+                let error = [0, 1, 2].map(|c| old[c] - new[c] as f32);
+                for &(dx, dy, weight) in kernel {
+                    let nx = x as isize + dx;
+                    let ny = y + dy;
+                    if nx >= 0 && (nx as usize) < w && ny < h {
+                        let level = &mut levels[ny * w + nx as usize];
+                        for c in 0..3 {
+                            level[c] += error[c] * weight;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Encode as an indexed PNG whose palette is exactly the panel colors.
+    pub fn encode_png(self, image: &RgbImage) -> anyhow::Result<Vec<u8>> {
+        let indices: Vec<u8> = image.pixels().map(|p| self.index_of(*p)).collect();
+        encode::png_indexed(image.width(), image.height(), &indices, self.colors())
+    }
+}
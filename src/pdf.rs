@@ -0,0 +1,93 @@
+//! Minimal PDF writer for the printable reports: one page showing one
+//! grayscale raster, stretched over the whole page. The report is laid out
+//! at print resolution with the bitmap font, so no PDF fonts are needed.
+
+use flate2::{Compression, write::ZlibEncoder};
+use image::GrayImage;
+use std::io::Write;
+
+/// A4 portrait in PDF points (1/72 inch)
+pub const A4: (f32, f32) = (595.28, 841.89);
+
+/// PDF document with `image` filling a single page of `page` points.
+pub fn single_page(image: &GrayImage, page: (f32, f32), title: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(image.as_raw())?;
+    let pixels = encoder.finish()?;
+
+    let (page_width, page_height) = page;
+    let content = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", page_width, page_height);
+
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>",
+            page_width, page_height
+        )
+        .into_bytes(),
+        stream(
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8 /Filter /FlateDecode",
+                image.width(),
+                image.height()
+            ),
+            &pixels,
+        ),
+        stream("", content.as_bytes()),
+        format!("<< /Title ({}) >>", escape(title)).into_bytes(),
+    ];
+
+    // Header with a binary comment line, as the spec recommends
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            objects.len(),
+            xref
+        )
+        .as_bytes(),
+    );
+    Ok(pdf)
+}
+
+/// Stream object with `entries` added to its dictionary.
+fn stream(entries: &str, data: &[u8]) -> Vec<u8> {
+    let dictionary = if entries.is_empty() {
+        format!("<< /Length {} >>", data.len())
+    } else {
+        format!("<< {} /Length {} >>", entries, data.len())
+    };
+    let mut object = format!("{}\nstream\n", dictionary).into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+/// PDF literal string contents; characters outside ASCII are dropped since
+/// the title is only shown by viewers.
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control())
+        .flat_map(|c| match c {
+            '(' | ')' | '\\' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
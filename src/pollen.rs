@@ -0,0 +1,136 @@
+//! Pollen forecasts from the various Home Assistant integrations. Each
+//! integration reports today's and tomorrow's level in its own place and on
+//! its own scale; an `Adapter` says where to find them.
+
+use serde_json::Value;
+
+/// One allergen's row: levels as fractions of the scale, `None` when the
+/// integration doesn't report them.
+pub struct Forecast {
+    pub label: String,
+    pub today: Option<f64>,
+    pub tomorrow: Option<f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Adapter {
+    /// Attribute path of today's level, `None` for the entity state
+    today: Option<String>,
+    /// Attribute path of tomorrow's level, e.g. `state_tomorrow` or
+    /// `forecast.1.value`
+    tomorrow: Option<String>,
+    /// Top of the integration's numeric scale
+    max: f64,
+}
+
+impl Adapter {
+    /// Built-in adapters: `dwd` (DWD Pollenflug, 0-3 with tomorrow in
+    /// `state_tomorrow`) and `default` (0-5, tomorrow in `tomorrow`).
+    pub fn preset(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "default" => Some(Self {
+                today: None,
+                tomorrow: Some("tomorrow".to_string()),
+                max: 5.0,
+            }),
+            "dwd" | "pollenflug" => Some(Self {
+                today: None,
+                tomorrow: Some("state_tomorrow".to_string()),
+                max: 3.0,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Replace parts of the preset; `today=state` reads the entity state.
+    pub fn with_overrides(
+        mut self,
+        today: Option<&str>,
+        tomorrow: Option<&str>,
+        max: Option<f64>,
+    ) -> Result<Self, String> {
+        if let Some(today) = today {
+            self.today = (today != "state").then(|| today.to_string());
+        }
+        if let Some(tomorrow) = tomorrow {
+            self.tomorrow = Some(tomorrow.to_string());
+        }
+        if let Some(max) = max {
+            if max.is_nan() || max <= 0.0 {
+                return Err(format!("max must be above 0, got {}", max));
+            }
+            self.max = max;
+        }
+        Ok(self)
+    }
+
+    /// Blocks in a severity bar: one per step of small integer scales.
+    pub fn segments(&self) -> u32 {
+        if self.max.fract() == 0.0 && (2.0..=10.0).contains(&self.max) {
+            self.max as u32
+        } else {
+            5
+        }
+    }
+
+    /// Today's level as a fraction of the scale.
+    pub fn today(&self, state: &str, attributes: &Value) -> Option<f64> {
+        match &self.today {
+            Some(path) => self.severity(lookup(attributes, path)?),
+            None => self.severity(&Value::String(state.to_string())),
+        }
+    }
+
+    /// Tomorrow's level as a fraction of the scale.
+    pub fn tomorrow(&self, attributes: &Value) -> Option<f64> {
+        self.severity(lookup(attributes, self.tomorrow.as_deref()?)?)
+    }
+
+    /// Numbers (or ranges such as `1-2`) relative to `max`, or level words
+    /// such as `moderate` or `very_high`.
+    fn severity(&self, value: &Value) -> Option<f64> {
+        let value = match value {
+            Value::Number(number) => number.as_f64()?,
+            Value::String(text) => {
+                let text = text.trim().to_lowercase().replace(['_', ' '], "");
+                match text.as_str() {
+                    "none" => return Some(0.0),
+                    "verylow" => return Some(0.2),
+                    "low" => return Some(0.4),
+                    "moderate" | "medium" => return Some(0.6),
+                    "high" => return Some(0.8),
+                    "veryhigh" | "extreme" => return Some(1.0),
+                    _ => {}
+                }
+                match text.split_once('-') {
+                    Some((low, high)) if !low.is_empty() => {
+                        (low.parse::<f64>().ok()? + high.parse::<f64>().ok()?) / 2.0
+                    }
+                    _ => text.parse().ok()?,
+                }
+            }
+            _ => return None,
+        };
+        Some((value / self.max).clamp(0.0, 1.0))
+    }
+}
+
+/// Follow a dotted attribute path; numeric parts index into lists.
+pub fn lookup<'a>(attributes: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(attributes, |value, key| match value {
+            Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+            _ => value.get(key),
+        })
+}
+
+/// Level word for a fraction of the scale.
+pub fn label(fraction: f64) -> &'static str {
+    match fraction {
+        f if f <= 0.0 => "None",
+        f if f < 0.5 => "Low",
+        f if f < 0.7 => "Moderate",
+        f if f < 0.9 => "High",
+        _ => "Very high",
+    }
+}
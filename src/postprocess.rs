@@ -0,0 +1,174 @@
+//! Post-processing for the e-paper screens, picked per request with
+//! `?post=` or per panel in a device preset. Sharpening and edge
+//! enhancement run on the grayscale screen just before dithering;
+//! despeckling cleans up the dithered result.
+
+use crate::adjust::unsharp_mask;
+use image::{GrayImage, Luma};
+use serde::Deserialize;
+use std::fmt;
+
+/// Unsharp mask strength of the `sharpen` step
+const SHARPEN_AMOUNT: f32 = 1.0;
+
+/// Steps to run, always in the order sharpen, edges, dither, despeckle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PostProcess {
+    /// Unsharp mask, for soft photos
+    sharpen: bool,
+    /// 3x3 edge enhancement, which darkens the outline of thin text
+    edges: bool,
+    /// Drop isolated pixels scattered by error diffusion
+    despeckle: bool,
+}
+
+impl PostProcess {
+    pub const NONE: Self = Self {
+        sharpen: false,
+        edges: false,
+        despeckle: false,
+    };
+
+    /// Comma-separated step names, e.g. `despeckle,sharpen`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut post = Self::NONE;
+        for name in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match name.to_lowercase().as_str() {
+                "sharpen" => post.sharpen = true,
+                "edges" | "edge-enhance" => post.edges = true,
+                "despeckle" => post.despeckle = true,
+                "none" => {}
+                _ => {
+                    return Err(format!(
+                        "Unknown post-processing step '{}'. Use sharpen, edges, despeckle or none",
+                        name
+                    ));
+                }
+            }
+        }
+        Ok(post)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Steps that work on the grayscale screen.
+    pub fn before_dither(&self, image: &mut GrayImage) {
+        if self.sharpen {
+            unsharp_mask(image, SHARPEN_AMOUNT, 1);
+        }
+        if self.edges {
+            *image = edge_enhance(image);
+        }
+    }
+
+    /// Steps that work on the dithered screen; `source` is the grayscale
+    /// screen it was dithered from.
+    pub fn after_dither(&self, source: &GrayImage, image: &mut GrayImage, levels: u16) {
+        if self.despeckle {
+            *image = despeckle(source, image, levels);
+        }
+    }
+}
+
+impl TryFrom<String> for PostProcess {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, String> {
+        Self::parse(&spec)
+    }
+}
+
+impl fmt::Display for PostProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<&str> = [
+            (self.sharpen, "sharpen"),
+            (self.edges, "edges"),
+            (self.despeckle, "despeckle"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect();
+        if steps.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&steps.join(","))
+        }
+    }
+}
+
+/// `[-1 -1 -1; -1 10 -1; -1 -1 -1] / 2`, with the border pixels repeated
+/// outwards so the screen edge isn't darkened.
+fn edge_enhance(image: &GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    GrayImage::from_fn(width, height, |x, y| {
+        let mut total = 0i32;
+        for (nx, ny) in neighbours(x, y, width, height, true) {
+            total -= image.get_pixel(nx, ny)[0] as i32;
+        }
+        total += 10 * image.get_pixel(x, y)[0] as i32;
+        Luma([(total / 2).clamp(0, 255) as u8])
+    })
+}
+
+/// Give a pixel the majority value of its neighbours when none of them
+/// shares its value and dithering put it there: a pixel already at the
+/// level nearest its gray (a drawn dot, such as a period) is kept.
+fn despeckle(source: &GrayImage, image: &GrayImage, levels: u16) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let half_step = 255.0 / (levels.max(2) - 1) as f32 / 2.0;
+    let mut output = image.clone();
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let value = pixel[0];
+        if (value as f32 - source.get_pixel(x, y)[0] as f32).abs() <= half_step {
+            continue;
+        }
+
+        let values: Vec<u8> = neighbours(x, y, width, height, false)
+            .map(|(nx, ny)| image.get_pixel(nx, ny)[0])
+            .collect();
+        if values.contains(&value) {
+            continue;
+        }
+
+        let majority = values.iter().copied().find(|candidate| {
+            values.iter().filter(|&&v| v == *candidate).count() * 2 > values.len()
+        });
+        if let Some(majority) = majority {
+            output.put_pixel(x, y, Luma([majority]));
+        }
+    }
+
+    output
+}
+
+/// The 8 pixels around `(x, y)`. Off-image neighbours are clamped to the
+/// edge when `clamp` is set, and skipped otherwise.
+fn neighbours(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    clamp: bool,
+) -> impl Iterator<Item = (u32, u32)> {
+    (-1i64..=1)
+        .flat_map(|dy| (-1i64..=1).map(move |dx| (dx, dy)))
+        .filter(|&offset| offset != (0, 0))
+        .filter_map(move |(dx, dy)| {
+            let nx = x as i64 + dx;
+            let ny = y as i64 + dy;
+            if clamp {
+                Some((
+                    nx.clamp(0, width as i64 - 1) as u32,
+                    ny.clamp(0, height as i64 - 1) as u32,
+                ))
+            } else if (0..width as i64).contains(&nx) && (0..height as i64).contains(&ny) {
+                Some((nx as u32, ny as u32))
+            } else {
+                None
+            }
+        })
+}
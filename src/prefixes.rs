@@ -0,0 +1,118 @@
+//! Engineering (SI) prefixes for readings, so `12400 W` is shown as
+//! `12.4 kW` and `3450000 B` as `3.45 MB`. Power, energy, data and
+//! frequency sensors get them by default; `?si=` turns them on for every
+//! reading with a metric unit, or off entirely.
+
+/// Device classes whose readings are prefixed unless `si=false`
+const DEFAULT_DEVICE_CLASSES: &[&str] = &[
+    "power",
+    "apparent_power",
+    "reactive_power",
+    "energy",
+    "data_size",
+    "data_rate",
+    "frequency",
+];
+
+/// Units that take prefixes, without their prefix
+const BASE_UNITS: &[&str] = &[
+    "W", "Wh", "VA", "VAh", "var", "varh", "V", "A", "Hz", "B", "B/s", "bit", "bit/s", "Ω",
+];
+
+/// Prefixes from the base unit upwards, one per factor of 1000
+const PREFIXES: &[&str] = &["", "k", "M", "G", "T"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SiPrefixes {
+    /// Only the device classes in `DEFAULT_DEVICE_CLASSES`
+    #[default]
+    DeviceClass,
+    Always,
+    Never,
+}
+
+impl SiPrefixes {
+    /// `?si=true` / `?si=false`, or the per-device-class default.
+    pub fn from_query(si: Option<bool>) -> Self {
+        match si {
+            None => Self::DeviceClass,
+            Some(true) => Self::Always,
+            Some(false) => Self::Never,
+        }
+    }
+
+    /// `value unit` rescaled to the prefix that keeps the number between 1
+    /// and 1000, to three significant digits. `None` when the reading should
+    /// keep its usual formatting: prefixes are off for it, the unit isn't
+    /// metric, or it is already in range.
+    pub fn format(self, value: f64, unit: &str, device_class: Option<&str>) -> Option<String> {
+        let enabled = match self {
+            Self::Never => false,
+            Self::Always => true,
+            Self::DeviceClass => {
+                device_class.is_some_and(|class| DEFAULT_DEVICE_CLASSES.contains(&class))
+            }
+        };
+        if !enabled || !value.is_finite() {
+            return None;
+        }
+
+        let (power, base) = split_unit(unit)?;
+        let mut magnitude = power;
+        let mut scaled = value;
+        while scaled.abs() >= 1000.0 && magnitude + 1 < PREFIXES.len() {
+            scaled /= 1000.0;
+            magnitude += 1;
+        }
+        while scaled != 0.0 && scaled.abs() < 1.0 && magnitude > 0 {
+            scaled *= 1000.0;
+            magnitude -= 1;
+        }
+        if magnitude == power {
+            return None;
+        }
+
+        // Rounding can carry into the next prefix (999.96 -> "1000")
+        let mut number = significant(scaled);
+        if number.trim_start_matches('-') == "1000" && magnitude + 1 < PREFIXES.len() {
+            number = significant(scaled / 1000.0);
+            magnitude += 1;
+        }
+        Some(format!("{} {}{}", number, PREFIXES[magnitude], base))
+    }
+}
+
+/// Index of the unit's prefix in `PREFIXES` and its base unit. `kB` and
+/// `KB` both count as kilobytes.
+fn split_unit(unit: &str) -> Option<(usize, &str)> {
+    if BASE_UNITS.contains(&unit) {
+        return Some((0, unit));
+    }
+    let mut chars = unit.chars();
+    let prefix = chars.next()?;
+    let base = chars.as_str();
+    if !BASE_UNITS.contains(&base) {
+        return None;
+    }
+    let prefix = if prefix == 'K' { 'k' } else { prefix };
+    PREFIXES
+        .iter()
+        .position(|candidate| candidate.starts_with(prefix) && candidate.len() == prefix.len_utf8())
+        .filter(|&power| power > 0)
+        .map(|power| (power, base))
+}
+
+/// Three significant digits without trailing zeros: `12.4`, `3.45`, `124`.
+fn significant(value: f64) -> String {
+    let decimals = match value.abs() {
+        v if v >= 100.0 => 0,
+        v if v >= 10.0 => 1,
+        _ => 2,
+    };
+    let text = format!("{:.*}", decimals, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
@@ -0,0 +1,278 @@
+//! Device presets: the output size, bit depth, rotation, margin and
+//! post-processing of a
+//! known panel, selected with `?preset=<name>` (or a `?device=` ID that
+//! names a preset) instead of spelling out every parameter.
+
+use crate::{
+    grayscale::GrayDepth,
+    postprocess::PostProcess,
+    resize::{MAX_DIMENSION, Rotation},
+};
+use axum::{extract::Query, http::Uri};
+use image::{GrayImage, Luma, imageops, imageops::FilterType};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// One panel's output settings. `width` and `height` are the render size
+/// before `rotate` is applied.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub width: u32,
+    pub height: u32,
+    /// Bits per pixel (1, 2, 4 or 8)
+    #[serde(default = "default_depth")]
+    pub depth: u8,
+    /// Degrees clockwise
+    #[serde(default)]
+    pub rotate: Option<u32>,
+    /// White border kept around TRMNL screens, in output pixels
+    #[serde(default)]
+    pub margin: u32,
+    /// Post-processing of TRMNL screens, e.g. `"despeckle"` for a panel
+    /// that shows stray dithering dots
+    #[serde(default)]
+    pub post: PostProcess,
+}
+
+fn default_depth() -> u8 {
+    1
+}
+
+impl Preset {
+    const fn new(width: u32, height: u32, depth: u8, rotate: Option<u32>) -> Self {
+        Self {
+            width,
+            height,
+            depth,
+            rotate,
+            margin: 0,
+            post: PostProcess::NONE,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("width", self.width), ("height", self.height)] {
+            if !(1..=MAX_DIMENSION).contains(&value) {
+                return Err(format!(
+                    "{} must be between 1 and {}, got {}",
+                    name, MAX_DIMENSION, value
+                ));
+            }
+        }
+        GrayDepth::from_bits(self.depth)?;
+        if let Some(rotate) = self.rotate {
+            Rotation::from_degrees(rotate)?;
+        }
+        check_margin(self.margin)?;
+        if self.margin * 2 >= self.width.min(self.height) {
+            return Err(format!(
+                "margin {} leaves no room in {}x{}",
+                self.margin, self.width, self.height
+            ));
+        }
+        Ok(())
+    }
+
+    /// Query parameters the preset stands for.
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("width", self.width.to_string()),
+            ("height", self.height.to_string()),
+            ("depth", self.depth.to_string()),
+            ("margin", self.margin.to_string()),
+        ];
+        if let Some(rotate) = self.rotate {
+            params.push(("rotate", rotate.to_string()));
+        }
+        if !self.post.is_empty() {
+            params.push(("post", self.post.to_string()));
+        }
+        params
+    }
+}
+
+/// Built-in presets, overridable by name from `DEVICE_PRESETS_FILE`
+const BUILT_IN: &[(&str, Preset)] = &[
+    ("trmnl", Preset::new(800, 480, 1, None)),
+    ("trmnl_v2", Preset::new(800, 480, 2, None)),
+    // 1072x1448 portrait panel, rendered landscape and turned
+    ("kindle_pw3", Preset::new(1448, 1072, 4, Some(90))),
+    ("inkplate10", Preset::new(1200, 825, 1, None)),
+    ("waveshare_7in5", Preset::new(800, 480, 1, None)),
+];
+
+#[derive(Clone, Debug)]
+pub struct Presets {
+    presets: HashMap<String, Preset>,
+}
+
+impl Presets {
+    /// Built-in presets plus those in the JSON object at `path`, e.g.
+    /// `{"my_panel": {"width": 960, "height": 540, "depth": 2}}`.
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let mut presets: HashMap<String, Preset> = BUILT_IN
+            .iter()
+            .map(|(name, preset)| (name.to_string(), *preset))
+            .collect();
+
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+            let custom: HashMap<String, Preset> = serde_json::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid device presets in {}: {}", path, e))?;
+            for (name, preset) in custom {
+                let name = name.trim().to_lowercase();
+                // Names go into query strings as they are
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    return Err(anyhow::anyhow!(
+                        "Invalid device preset name '{}': use letters, digits, '_' and '-'",
+                        name
+                    ));
+                }
+                preset
+                    .validate()
+                    .map_err(|e| anyhow::anyhow!("Invalid device preset '{}': {}", name, e))?;
+                presets.insert(name, preset);
+            }
+        }
+
+        Ok(Self { presets })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets.get(&name.trim().to_lowercase())
+    }
+
+    /// `uri` with the parameters of its `preset` (or a `device` naming a
+    /// preset) appended, or `None` when it doesn't select one. Parameters
+    /// already in the query are kept.
+    pub fn expand(&self, uri: &Uri) -> Result<Option<Uri>, String> {
+        let Ok(Query(params)) = Query::<HashMap<String, String>>::try_from_uri(uri) else {
+            return Ok(None);
+        };
+
+        let preset = match params.get("preset") {
+            Some(name) => self.get(name).ok_or_else(|| {
+                let mut names: Vec<&str> = self.presets.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                format!("Unknown preset '{}'. Use {}", name, names.join(", "))
+            })?,
+            None => match params.get("device").and_then(|id| self.get(id)) {
+                Some(preset) => preset,
+                None => return Ok(None),
+            },
+        };
+
+        let mut query = uri.query().unwrap_or_default().to_string();
+        for (name, value) in preset.params() {
+            if !params.contains_key(name) {
+                query.push_str(&format!("&{}={}", name, value));
+            }
+        }
+
+        let path_and_query = format!("{}?{}", uri.path(), query.trim_start_matches('&'));
+        let mut parts = uri.clone().into_parts();
+        parts.path_and_query = Some(
+            path_and_query
+                .parse()
+                .map_err(|e| format!("Invalid query: {}", e))?,
+        );
+        Uri::from_parts(parts)
+            .map(Some)
+            .map_err(|e| format!("Invalid query: {}", e))
+    }
+}
+
+/// `margin` if it's no wider than the largest output, which also keeps
+/// doubling it from overflowing.
+pub fn check_margin(margin: u32) -> Result<u32, String> {
+    if margin > MAX_DIMENSION {
+        return Err(format!(
+            "margin must be at most {}, got {}",
+            MAX_DIMENSION, margin
+        ));
+    }
+    Ok(margin)
+}
+
+/// Output geometry of a TRMNL screen on a panel of another size: the
+/// layout is scaled to fit inside `margin` pixels of white border.
+#[derive(Clone, Copy, Debug)]
+pub struct Canvas {
+    width: Option<u32>,
+    height: Option<u32>,
+    margin: u32,
+}
+
+impl Canvas {
+    /// `None` when nothing changes the layout's own size. A missing
+    /// dimension keeps the layout's.
+    pub fn new(
+        width: Option<u32>,
+        height: Option<u32>,
+        margin: Option<u32>,
+    ) -> Result<Option<Self>, String> {
+        for (name, value) in [("width", width), ("height", height)] {
+            if let Some(value) = value
+                && !(1..=MAX_DIMENSION).contains(&value)
+            {
+                return Err(format!(
+                    "{} must be between 1 and {}, got {}",
+                    name, MAX_DIMENSION, value
+                ));
+            }
+        }
+
+        let margin = check_margin(margin.unwrap_or(0))?;
+        if let Some(smallest) = width.into_iter().chain(height).min()
+            && margin * 2 >= smallest
+        {
+            return Err(format!("margin {} leaves no room for the screen", margin));
+        }
+
+        if width.is_none() && height.is_none() && margin == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            width,
+            height,
+            margin,
+        }))
+    }
+
+    pub fn apply(&self, image: &GrayImage) -> GrayImage {
+        let (image_width, image_height) = image.dimensions();
+        let width = self.width.unwrap_or(image_width);
+        let height = self.height.unwrap_or(image_height);
+        let inner_width = width.saturating_sub(self.margin * 2).max(1);
+        let inner_height = height.saturating_sub(self.margin * 2).max(1);
+
+        let scale = (inner_width as f64 / image_width as f64)
+            .min(inner_height as f64 / image_height as f64);
+        let scaled_width = ((image_width as f64 * scale).round() as u32).clamp(1, inner_width);
+        let scaled_height = ((image_height as f64 * scale).round() as u32).clamp(1, inner_height);
+
+        let scaled = if (scaled_width, scaled_height) == (image_width, image_height) {
+            image.clone()
+        } else {
+            // Whole-number factors keep the 1-bit text crisp
+            let filter = if scale.fract() == 0.0 || (1.0 / scale).fract() == 0.0 {
+                FilterType::Nearest
+            } else {
+                FilterType::Triangle
+            };
+            imageops::resize(image, scaled_width, scaled_height, filter)
+        };
+
+        let mut canvas = GrayImage::from_pixel(width, height, Luma([255u8]));
+        let x = (width - scaled_width) / 2;
+        let y = (height - scaled_height) / 2;
+        imageops::replace(&mut canvas, &scaled, x as i64, y as i64);
+        canvas
+    }
+}
@@ -0,0 +1,86 @@
+//! Row priorities (`?priority=`): when a TRMNL screen has more sensors than
+//! rows fit, low-priority rows are dropped first and the last row says how
+//! many were left out, e.g. `?priority=sensor.outdoor_temp=high,sensor.uptime=low`.
+//! Sensors not listed are `normal`; rows in an attention state are kept
+//! before any of them.
+
+use std::collections::HashMap;
+
+/// Attribute a sensor's priority is added to its state under
+pub const ATTRIBUTE: &str = "priority";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "low" => Some(Self::Low),
+            "normal" => Some(Self::Normal),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Priorities(HashMap<String, Priority>);
+
+impl Priorities {
+    /// `?priority=`: comma-separated `entity_id=low|normal|high` entries.
+    pub fn parse(spec: Option<&str>) -> Result<Self, String> {
+        let mut priorities = HashMap::new();
+
+        for entry in spec
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let (entity_id, level) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid priority '{}'", entry))?;
+            let entity_id = entity_id.trim();
+            if !entity_id.contains('.') {
+                return Err(format!("Invalid priority '{}'", entry));
+            }
+            let level = Priority::parse(level).ok_or_else(|| {
+                format!(
+                    "Unknown priority '{}' for {}. Use low, normal or high",
+                    level.trim(),
+                    entity_id
+                )
+            })?;
+            priorities.insert(entity_id.to_string(), level);
+        }
+
+        Ok(Self(priorities))
+    }
+
+    pub fn get(&self, entity_id: &str) -> Option<Priority> {
+        self.0.get(entity_id).copied()
+    }
+}
+
+/// Indices of the `capacity` rows kept out of `ranks` (higher ranks first,
+/// earlier rows among equals), in their original order.
+pub fn keep<R: Ord>(ranks: &[R], capacity: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..ranks.len()).collect();
+    order.sort_by(|&a, &b| ranks[b].cmp(&ranks[a]));
+    order.truncate(capacity);
+    order.sort_unstable();
+    order
+}
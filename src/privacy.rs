@@ -0,0 +1,107 @@
+//! Private entities, hidden from dashboards rendered for sharing
+//! (`/share/{name}`). `ENTITIES_FILE` holds per-entity settings keyed by
+//! entity ID or by domain, e.g.
+//!
+//! ```json
+//! {
+//!   "person.anna": { "private": true },
+//!   "camera": { "private": true }
+//! }
+//! ```
+//!
+//! A private entity keeps its place on the screen under a generic name
+//! (`Person`, `Temperature`) with its value hidden; cameras show a
+//! placeholder and calendars only `Busy`.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// State shown in place of a private entity's value
+pub const HIDDEN_STATE: &str = "hidden";
+/// Summary shown in place of a private calendar's events
+pub const BUSY: &str = "Busy";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Settings {
+    #[serde(default)]
+    private: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Privacy {
+    /// Entity IDs and domains marked private
+    private: Vec<String>,
+}
+
+impl Privacy {
+    /// Settings from `ENTITIES_FILE`; nothing is private without one.
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let settings: HashMap<String, Settings> = serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid entity settings in {}: {}", path, e))?;
+
+        let mut private: Vec<String> = settings
+            .into_iter()
+            .filter(|(_, settings)| settings.private)
+            .map(|(key, _)| key.trim().to_string())
+            .collect();
+        private.sort_unstable();
+        Ok(Self { private })
+    }
+
+    /// Whether `entity_id`, or its whole domain, is marked private.
+    pub fn is_private(&self, entity_id: &str) -> bool {
+        let domain = entity_id.split('.').next().unwrap_or("");
+        self.private
+            .iter()
+            .any(|key| key == entity_id || key == domain)
+    }
+}
+
+/// Attributes left of a private entity: a generic `friendly_name` from its
+/// device class or domain, and the device class itself so it's still drawn
+/// with the right icon and colors.
+pub fn redact_attributes(entity_id: &str, attributes: &Value) -> Value {
+    let device_class = attributes.get("device_class").and_then(Value::as_str);
+    let kind = device_class.unwrap_or_else(|| entity_id.split('.').next().unwrap_or("entity"));
+    let mut name = kind.replace('_', " ");
+    if let Some(first) = name.get(..1) {
+        name = first.to_uppercase() + &name[1..];
+    }
+
+    let mut redacted = Map::new();
+    redacted.insert("friendly_name".to_string(), Value::String(name));
+    if let Some(device_class) = device_class {
+        redacted.insert(
+            "device_class".to_string(),
+            Value::String(device_class.to_string()),
+        );
+    }
+    Value::Object(redacted)
+}
+
+/// Calendar events with only their times kept, each summary `Busy`.
+pub fn redact_events(events: &Value) -> Value {
+    let events = events
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|event| {
+            let mut kept = Map::new();
+            for key in ["start", "end"] {
+                if let Some(value) = event.get(key) {
+                    kept.insert(key.to_string(), value.clone());
+                }
+            }
+            kept.insert("summary".to_string(), Value::String(BUSY.to_string()));
+            Value::Object(kept)
+        })
+        .collect();
+    Value::Array(events)
+}
@@ -0,0 +1,260 @@
+//! Credentials for an identity-aware proxy in front of Home Assistant, for
+//! instances only reachable through one. `HA_PROXY_AUTH` picks how they are
+//! obtained with `HA_PROXY_CLIENT_ID` and `HA_PROXY_CLIENT_SECRET`:
+//!
+//! - `cloudflare`: the Cloudflare Access service token is exchanged for an
+//!   application token (the `CF_Authorization` cookie Access sets), sent as
+//!   `cf-access-token`
+//! - `oauth2`: the OAuth2 client-credentials grant at `HA_PROXY_TOKEN_URL`
+//!   (with `HA_PROXY_SCOPE`), the access token sent as
+//!   `Proxy-Authorization: Bearer ...` or in `HA_PROXY_HEADER`
+//!
+//! Tokens are fetched before the server starts and refreshed in the
+//! background ahead of their expiry. They are sent along with the
+//! long-lived `HA_TOKEN`, which Home Assistant itself still checks.
+
+use base64::Engine;
+use reqwest::{
+    Client,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Lifetime assumed for tokens that don't say when they expire
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+/// Wait after a failed refresh before trying again
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug)]
+enum Method {
+    Cloudflare,
+    OAuth2 {
+        token_url: String,
+        scope: Option<String>,
+        header: HeaderName,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct ProxyAuth {
+    method: Method,
+    client_id: String,
+    client_secret: String,
+}
+
+impl ProxyAuth {
+    /// `HA_PROXY_AUTH` and its settings; `None` without one.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(name) = std::env::var("HA_PROXY_AUTH") else {
+            return Ok(None);
+        };
+        let required = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .ok_or_else(|| anyhow::anyhow!("{} is required with HA_PROXY_AUTH={}", var, name))
+        };
+
+        let method = match name.trim().to_lowercase().as_str() {
+            "" | "none" => return Ok(None),
+            "cloudflare" => Method::Cloudflare,
+            "oauth2" => {
+                let token_url = required("HA_PROXY_TOKEN_URL")?;
+                if reqwest::Url::parse(&token_url)
+                    .map_or(true, |url| !matches!(url.scheme(), "http" | "https"))
+                {
+                    return Err(anyhow::anyhow!(
+                        "Invalid HA_PROXY_TOKEN_URL '{}'",
+                        token_url
+                    ));
+                }
+                let header = match std::env::var("HA_PROXY_HEADER") {
+                    Ok(header) => HeaderName::from_bytes(header.trim().as_bytes())
+                        .map_err(|_| anyhow::anyhow!("Invalid HA_PROXY_HEADER '{}'", header))?,
+                    Err(_) => reqwest::header::PROXY_AUTHORIZATION,
+                };
+                Method::OAuth2 {
+                    token_url,
+                    scope: std::env::var("HA_PROXY_SCOPE").ok(),
+                    header,
+                }
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown HA_PROXY_AUTH '{}' (cloudflare, oauth2)",
+                    name
+                ));
+            }
+        };
+
+        Ok(Some(Self {
+            method,
+            client_id: required("HA_PROXY_CLIENT_ID")?,
+            client_secret: required("HA_PROXY_CLIENT_SECRET")?,
+        }))
+    }
+
+    /// Headers carrying a fresh token, and how long it is valid.
+    async fn fetch(
+        &self,
+        client: &Client,
+        base_url: &str,
+        token: &str,
+    ) -> anyhow::Result<(HeaderMap, Duration)> {
+        match &self.method {
+            Method::Cloudflare => {
+                // Access answers any request carrying the service token with
+                // the application token as a cookie
+                let response = client
+                    .get(format!("{}/api/", base_url))
+                    .header("CF-Access-Client-Id", &self.client_id)
+                    .header("CF-Access-Client-Secret", &self.client_secret)
+                    .bearer_auth(token)
+                    .send()
+                    .await?;
+                let jwt = response
+                    .headers()
+                    .get_all(reqwest::header::SET_COOKIE)
+                    .iter()
+                    .filter_map(|cookie| cookie.to_str().ok())
+                    .find_map(|cookie| {
+                        let (pair, _) = cookie.split_once(';').unwrap_or((cookie, ""));
+                        pair.trim().strip_prefix("CF_Authorization=")
+                    })
+                    .map(str::to_string)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Cloudflare Access set no CF_Authorization cookie (status {})",
+                            response.status()
+                        )
+                    })?;
+
+                let mut headers = HeaderMap::new();
+                headers.insert("cf-access-token", HeaderValue::from_str(&jwt)?);
+                Ok((headers, jwt_lifetime(&jwt).unwrap_or(DEFAULT_LIFETIME)))
+            }
+            Method::OAuth2 {
+                token_url,
+                scope,
+                header,
+            } => {
+                let mut form = vec![
+                    ("grant_type", "client_credentials"),
+                    ("client_id", &self.client_id),
+                    ("client_secret", &self.client_secret),
+                ];
+                if let Some(scope) = scope {
+                    form.push(("scope", scope));
+                }
+                let reply: serde_json::Value = client
+                    .post(token_url)
+                    .form(&form)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                let access_token = reply
+                    .get("access_token")
+                    .and_then(|token| token.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("Token response has no access_token"))?;
+                let lifetime = reply
+                    .get("expires_in")
+                    .and_then(|secs| secs.as_u64())
+                    .map_or(DEFAULT_LIFETIME, Duration::from_secs);
+
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    header.clone(),
+                    HeaderValue::from_str(&format!("Bearer {}", access_token))?,
+                );
+                Ok((headers, lifetime))
+            }
+        }
+    }
+}
+
+/// Seconds until a JWT's `exp`, read without verifying it.
+fn jwt_lifetime(jwt: &str) -> Option<Duration> {
+    let payload = jwt.split('.').nth(1)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    let expires = claims.get("exp")?.as_u64()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(Duration::from_secs(expires.saturating_sub(now)))
+}
+
+/// Headers every Home Assistant request carries: the fixed `HA_HEADERS`
+/// and the current proxy token, shared with the refresh task.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyHeaders {
+    fixed: HeaderMap,
+    token: Arc<RwLock<HeaderMap>>,
+}
+
+impl ProxyHeaders {
+    pub fn new(fixed: HeaderMap) -> Self {
+        Self {
+            fixed,
+            token: Arc::default(),
+        }
+    }
+
+    pub fn current(&self) -> HeaderMap {
+        let mut headers = self.fixed.clone();
+        for (name, value) in self.token.read().unwrap().iter() {
+            headers.insert(name.clone(), value.clone());
+        }
+        headers
+    }
+
+    /// Fetch a token into the headers; returns how long it is valid.
+    pub async fn refresh(
+        &self,
+        auth: &ProxyAuth,
+        client: &Client,
+        base_url: &str,
+        token: &str,
+    ) -> anyhow::Result<Duration> {
+        let (headers, lifetime) = auth.fetch(client, base_url, token).await?;
+        *self.token.write().unwrap() = headers;
+        Ok(lifetime)
+    }
+}
+
+/// Keep the proxy token fresh: renewed at four fifths of its lifetime, and
+/// retried every `RETRY_DELAY` while that fails (the old token is kept
+/// until then).
+pub async fn keep_fresh(
+    headers: ProxyHeaders,
+    auth: ProxyAuth,
+    client: Client,
+    base_url: String,
+    token: String,
+    mut lifetime: Option<Duration>,
+) {
+    loop {
+        let wait = lifetime.map_or(RETRY_DELAY, |lifetime| (lifetime * 4 / 5).max(RETRY_DELAY));
+        tokio::time::sleep(wait).await;
+
+        lifetime = match headers.refresh(&auth, &client, &base_url, &token).await {
+            Ok(lifetime) => {
+                info!("Refreshed the Home Assistant proxy token");
+                Some(lifetime)
+            }
+            Err(e) => {
+                warn!("Failed to refresh the Home Assistant proxy token: {}", e);
+                None
+            }
+        };
+    }
+}
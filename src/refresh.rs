@@ -0,0 +1,98 @@
+//! Refresh-rate hints for `/api/display`: how soon a device polls again,
+//! from what its screen shows and the time of day, so a battery device
+//! doesn't wake every few minutes for a calendar that changes twice a day.
+//! A screen with fast-changing power sensors, a camera or the world clock
+//! is refreshed every 5 minutes, one with only calendars, to-do lists,
+//! weather or the sun every hour, anything else at
+//! `DEVICE_CHECKIN_INTERVAL`; overnight the device sleeps longer. `DEVICE_SCREENS_FILE` overrides both per screen.
+
+/// Seconds between polls for screens with fast-changing content
+pub const FAST: u64 = 300;
+/// Seconds between polls for screens with slow-changing content, and at night
+pub const SLOW: u64 = 3600;
+
+/// Device classes of sensors that change from minute to minute
+const FAST_DEVICE_CLASSES: &[&str] = &["power", "apparent_power", "reactive_power", "current"];
+/// Domains whose entities are always worth a fast refresh
+const FAST_DOMAINS: &[&str] = &["camera", "clock"];
+/// Domains whose entities change a few times a day
+const SLOW_DOMAINS: &[&str] = &["calendar", "todo", "weather", "sun"];
+
+/// Refresh rate suited to a screen of `entities` (entity ID and device
+/// class), or `None` for the default.
+pub fn content_rate(entities: &[(String, Option<String>)]) -> Option<u64> {
+    let domain = |entity_id: &str| entity_id.split('.').next().unwrap_or("").to_string();
+
+    let fast = entities.iter().any(|(entity_id, device_class)| {
+        FAST_DOMAINS.contains(&domain(entity_id).as_str())
+            || device_class
+                .as_deref()
+                .is_some_and(|class| FAST_DEVICE_CLASSES.contains(&class))
+    });
+    if fast {
+        return Some(FAST);
+    }
+
+    let slow = !entities.is_empty()
+        && entities
+            .iter()
+            .all(|(entity_id, _)| SLOW_DOMAINS.contains(&domain(entity_id).as_str()));
+    slow.then_some(SLOW)
+}
+
+/// Time-of-day range such as `23:00-07:00`, which may wrap past midnight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct Window {
+    /// Minutes after midnight
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    /// Night used for screens that don't set one
+    pub const NIGHT: Self = Self {
+        start: 23 * 60,
+        end: 7 * 60,
+    };
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let (start, end) = text.split_once('-')?;
+        let (start, end) = (clock_minutes(start)?, clock_minutes(end)?);
+        (start != end).then_some(Self { start, end })
+    }
+
+    /// `HH:MM` the window ends at.
+    pub fn end_clock(&self) -> String {
+        format!("{:02}:{:02}", self.end / 60, self.end % 60)
+    }
+
+    /// Minutes from `minute` (after midnight) until the window ends.
+    pub fn minutes_until_end(&self, minute: u32) -> u32 {
+        (self.end + 24 * 60 - minute) % (24 * 60)
+    }
+
+    /// Whether `minute` (after midnight) falls in the window.
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+/// Minutes after midnight of a `HH:MM` time.
+pub fn clock_minutes(clock: &str) -> Option<u32> {
+    let (hours, minutes) = clock.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl TryFrom<String> for Window {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::parse(&text).ok_or_else(|| format!("invalid time window '{}', use HH:MM-HH:MM", text))
+    }
+}
@@ -0,0 +1,126 @@
+//! Daily medication and reminder checklists. Reminders are listed in the
+//! URL as `HH:MM Label=input_boolean.entity` entries or come from today's
+//! events of a calendar, each ticked off while its `input_boolean` is on.
+//! A helper switched on before today counts as not taken, so the list
+//! starts over at midnight even when nothing resets the helpers.
+
+use crate::{refresh, timestamps};
+use serde_json::Value;
+use std::time::SystemTime;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reminder {
+    /// Minutes after midnight; `None` for all-day calendar events
+    pub minute: Option<u32>,
+    pub label: String,
+    /// Helper that is on once the reminder is done
+    pub entity: Option<String>,
+}
+
+impl Reminder {
+    /// `08:00`, or `All day`.
+    pub fn time(&self) -> String {
+        match self.minute {
+            Some(minute) => format!("{:02}:{:02}", minute / 60, minute % 60),
+            None => "All day".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Taken,
+    /// Its time has passed without it being ticked off
+    Missed,
+    Pending,
+}
+
+/// Comma-separated `HH:MM Label=entity_id` entries; the entity is optional.
+pub fn parse(spec: &str) -> Result<Vec<Reminder>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let invalid = || {
+                format!(
+                    "Invalid reminder '{}'. Use HH:MM Label=input_boolean.entity",
+                    entry
+                )
+            };
+            let (text, entity) = match entry.rsplit_once('=') {
+                Some((text, entity)) if entity.trim().contains('.') => {
+                    (text, Some(entity.trim().to_string()))
+                }
+                Some(_) => return Err(invalid()),
+                None => (entry, None),
+            };
+            let (clock, label) = text.trim().split_once(' ').ok_or_else(invalid)?;
+            let minute = refresh::clock_minutes(clock).ok_or_else(invalid)?;
+            let label = label.trim();
+            if label.is_empty() {
+                return Err(invalid());
+            }
+            Ok(Reminder {
+                minute: Some(minute),
+                label: label.to_string(),
+                entity,
+            })
+        })
+        .collect()
+}
+
+/// Reminders for the events Home Assistant's calendar API returned for
+/// today, `start_of_today` being midnight in its time zone. The helper is
+/// the first `input_boolean.*` named in an event's description.
+pub fn from_calendar(events: &Value, start_of_today: SystemTime) -> Vec<Reminder> {
+    events
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|event| {
+            let label = event.get("summary")?.as_str()?.trim().to_string();
+            let start = event.get("start")?;
+            let minute = match start.get("dateTime").and_then(|v| v.as_str()) {
+                Some(text) => {
+                    let time = timestamps::parse(text)?;
+                    // Events that began before today are shown at midnight
+                    let elapsed = time.duration_since(start_of_today).unwrap_or_default();
+                    Some((elapsed.as_secs() / 60).min(24 * 60 - 1) as u32)
+                }
+                None => None,
+            };
+            let entity = event
+                .get("description")
+                .and_then(|v| v.as_str())
+                .and_then(|text| {
+                    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                        .find(|word| word.starts_with("input_boolean.") && word.len() > 14)
+                })
+                .map(|word| word.trim_end_matches('.').to_string());
+            Some(Reminder {
+                minute,
+                label,
+                entity,
+            })
+        })
+        .collect()
+}
+
+/// Whether a reminder is done: its helper is `on` and was switched on
+/// today, at or after `start_of_today`.
+pub fn taken(state: &str, last_changed: Option<&str>, start_of_today: SystemTime) -> bool {
+    state == "on"
+        && last_changed
+            .and_then(timestamps::parse)
+            .is_some_and(|changed| changed >= start_of_today)
+}
+
+/// Where a reminder stands at `minute_now`; `taken` is `None` for ones
+/// without a helper, which are never missed.
+pub fn status(reminder: &Reminder, taken: Option<bool>, minute_now: u32) -> Status {
+    match taken {
+        Some(true) => Status::Taken,
+        Some(false) if reminder.minute.is_some_and(|minute| minute <= minute_now) => Status::Missed,
+        _ => Status::Pending,
+    }
+}
@@ -0,0 +1,137 @@
+//! Daily report statistics: a sensor's numeric history over one day reduced
+//! to its minimum, maximum and time-weighted average, plus the points of its
+//! chart.
+
+use crate::timestamps;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+pub struct DayStats {
+    pub min: f64,
+    pub max: f64,
+    /// Average weighted by how long each value was held
+    pub mean: f64,
+    pub last: f64,
+    /// Sum of the rises, for meters: a daily `utility_meter` dropping back
+    /// to zero at midnight doesn't count against it
+    pub increase: f64,
+}
+
+impl DayStats {
+    /// Bottom and top of the chart's value axis: the day's range with a
+    /// little headroom, or one unit either side of a constant value.
+    pub fn axis(&self) -> (f64, f64) {
+        if self.max > self.min {
+            let padding = (self.max - self.min) * 0.1;
+            (self.min - padding, self.max + padding)
+        } else {
+            (self.min - 1.0, self.max + 1.0)
+        }
+    }
+}
+
+/// Numeric samples per entity from a `/api/history/period` response (one
+/// list per entity, the entity ID only on its first entry with
+/// `minimal_response`). Non-numeric states such as `unavailable` are left
+/// out, and samples from before `start` (the state the day began with) are
+/// moved to `start`.
+pub fn parse_history(
+    history: &[Vec<serde_json::Value>],
+    start: SystemTime,
+) -> HashMap<String, Vec<(SystemTime, f64)>> {
+    let mut series = HashMap::new();
+
+    for entries in history {
+        let Some(entity_id) = entries
+            .iter()
+            .find_map(|entry| entry.get("entity_id")?.as_str())
+        else {
+            continue;
+        };
+
+        let samples: Vec<(SystemTime, f64)> = entries
+            .iter()
+            .filter_map(|entry| {
+                let value = entry.get("state")?.as_str()?.parse::<f64>().ok()?;
+                let time = entry
+                    .get("last_changed")
+                    .or_else(|| entry.get("last_updated"))
+                    .and_then(|v| v.as_str())
+                    .and_then(timestamps::parse)
+                    .unwrap_or(start)
+                    .max(start);
+                value.is_finite().then_some((time, value))
+            })
+            .collect();
+        series.insert(entity_id.to_string(), samples);
+    }
+
+    series
+}
+
+/// Statistics of `samples` held until `end`; `None` without samples.
+pub fn day_stats(samples: &[(SystemTime, f64)], end: SystemTime) -> Option<DayStats> {
+    let (_, last) = *samples.last()?;
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut weighted, mut held) = (0.0, 0.0);
+    let mut increase = 0.0;
+
+    for (i, &(time, value)) in samples.iter().enumerate() {
+        min = min.min(value);
+        max = max.max(value);
+
+        let until = samples.get(i + 1).map_or(end, |&(next, _)| next);
+        let seconds = until.duration_since(time).unwrap_or_default().as_secs_f64();
+        weighted += value * seconds;
+        held += seconds;
+
+        if i > 0 {
+            increase += (value - samples[i - 1].1).max(0.0);
+        }
+    }
+
+    let mean = if held > 0.0 {
+        weighted / held
+    } else {
+        samples.iter().map(|&(_, value)| value).sum::<f64>() / samples.len() as f64
+    };
+
+    Some(DayStats {
+        min,
+        max,
+        mean,
+        last,
+        increase,
+    })
+}
+
+/// Chart points as `(x, y)` fractions: time across the day from `start`
+/// to `day_end`, values across `axis`. Each value is held until the next
+/// one (or `data_end`), drawn as steps.
+pub fn chart_points(
+    samples: &[(SystemTime, f64)],
+    start: SystemTime,
+    day_end: SystemTime,
+    data_end: SystemTime,
+    (low, high): (f64, f64),
+) -> Vec<(f64, f64)> {
+    let span = day_end
+        .duration_since(start)
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(1.0);
+    let x = |time: SystemTime| time.duration_since(start).unwrap_or_default().as_secs_f64() / span;
+    let y = |value: f64| (value - low) / (high - low);
+
+    let mut points = Vec::with_capacity(samples.len() * 2 + 1);
+    for (i, &(time, value)) in samples.iter().enumerate() {
+        if i > 0 {
+            points.push((x(time), y(samples[i - 1].1)));
+        }
+        points.push((x(time), y(value)));
+    }
+    if let Some(&(_, last)) = samples.last() {
+        points.push((x(data_end.min(day_end)), y(last)));
+    }
+    points
+}
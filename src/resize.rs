@@ -0,0 +1,333 @@
+//! Server-side cropping and resizing of proxied images to a display's
+//! dimensions, and rotation of finished renders for portrait-mounted
+//! displays.
+
+use image::{
+    DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgba, RgbaImage, imageops,
+    imageops::FilterType,
+};
+
+/// Largest width/height accepted for a resize target
+pub const MAX_DIMENSION: u32 = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FitMode {
+    /// Scale to fit inside the target and letterbox the rest
+    Fit,
+    /// Scale to cover the target and crop the overflow
+    Fill,
+    /// Scale each axis independently, ignoring the aspect ratio
+    Stretch,
+}
+
+impl FitMode {
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "fit" | "contain" | "letterbox" => Some(Self::Fit),
+            "fill" | "cover" => Some(Self::Fill),
+            "stretch" => Some(Self::Stretch),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Resize {
+    width: Option<u32>,
+    height: Option<u32>,
+    mode: FitMode,
+    /// Letterbox padding color for `FitMode::Fit`
+    background: Rgba<u8>,
+}
+
+impl Resize {
+    /// `None` when neither dimension is given. With a single dimension the
+    /// other one follows the image's aspect ratio.
+    pub fn new(
+        width: Option<u32>,
+        height: Option<u32>,
+        mode: FitMode,
+        background: Rgba<u8>,
+    ) -> Result<Option<Self>, String> {
+        for (name, value) in [("width", width), ("height", height)] {
+            if let Some(value) = value
+                && !(1..=MAX_DIMENSION).contains(&value)
+            {
+                return Err(format!(
+                    "{} must be between 1 and {}, got {}",
+                    name, MAX_DIMENSION, value
+                ));
+            }
+        }
+
+        if width.is_none() && height.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            width,
+            height,
+            mode,
+            background,
+        }))
+    }
+
+    /// Target width/height ratio when both dimensions are given.
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        Some(self.width? as f32 / self.height? as f32)
+    }
+
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let filter = FilterType::Triangle;
+
+        let (width, height) = match (self.width, self.height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => return image.resize(width, u32::MAX, filter),
+            (None, Some(height)) => return image.resize(u32::MAX, height, filter),
+            (None, None) => return image.clone(),
+        };
+
+        match self.mode {
+            FitMode::Stretch => image.resize_exact(width, height, filter),
+            FitMode::Fill => image.resize_to_fill(width, height, filter),
+            FitMode::Fit => {
+                let scaled = image.resize(width, height, filter).to_rgba8();
+                let mut canvas = RgbaImage::from_pixel(width, height, self.background);
+                let x = (width - scaled.width()) / 2;
+                let y = (height - scaled.height()) / 2;
+                image::imageops::replace(&mut canvas, &scaled, x as i64, y as i64);
+                DynamicImage::ImageRgba8(canvas)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Crop {
+    /// Explicit pixel region `x,y,w,h`, clipped to the image
+    Region {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Band with the target aspect ratio (or half the image height when no
+    /// target size is known), anchored at the top, center or bottom
+    Anchor(Anchor),
+    /// The same band, placed over the part of the image with the most
+    /// edges - usually whoever is standing in front of a doorbell camera
+    Detail,
+}
+
+impl Crop {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid crop '{}'. Use x,y,w,h or center, top, bottom, detail",
+                spec
+            )
+        };
+
+        match spec.trim().to_lowercase().as_str() {
+            "top" => return Ok(Self::Anchor(Anchor::Top)),
+            "center" | "middle" => return Ok(Self::Anchor(Anchor::Center)),
+            "bottom" => return Ok(Self::Anchor(Anchor::Bottom)),
+            "detail" | "auto" => return Ok(Self::Detail),
+            _ => {}
+        }
+
+        let values = spec
+            .split(',')
+            .map(|v| v.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        match values[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Ok(Self::Region {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn apply(
+        &self,
+        image: &DynamicImage,
+        target_aspect: Option<f32>,
+    ) -> anyhow::Result<DynamicImage> {
+        let (image_width, image_height) = (image.width(), image.height());
+
+        let (x, y, width, height) = match *self {
+            Self::Region {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if x >= image_width || y >= image_height {
+                    return Err(anyhow::anyhow!(
+                        "Crop region starts outside the {}x{} image",
+                        image_width,
+                        image_height
+                    ));
+                }
+                (
+                    x,
+                    y,
+                    width.min(image_width - x),
+                    height.min(image_height - y),
+                )
+            }
+            Self::Anchor(_) | Self::Detail => {
+                let (width, height) = match target_aspect {
+                    Some(aspect) if (image_width as f32 / image_height as f32) > aspect => (
+                        ((image_height as f32) * aspect).round() as u32,
+                        image_height,
+                    ),
+                    Some(aspect) => (image_width, ((image_width as f32) / aspect).round() as u32),
+                    None => (image_width, image_height.div_ceil(2)),
+                };
+                let (width, height) = (width.clamp(1, image_width), height.clamp(1, image_height));
+
+                let (x, y) = match *self {
+                    Self::Anchor(anchor) => (
+                        (image_width - width) / 2,
+                        match anchor {
+                            Anchor::Top => 0,
+                            Anchor::Center => (image_height - height) / 2,
+                            Anchor::Bottom => image_height - height,
+                        },
+                    ),
+                    _ => most_detailed(image, width, height),
+                };
+                (x, y, width, height)
+            }
+        };
+
+        Ok(image.crop_imm(x, y, width, height))
+    }
+}
+
+/// Top-left corner of a `width`x`height` window centered on the detail in
+/// the most detailed part of the image, measured as edge density on a
+/// small grayscale copy. Featureless images keep the centered window.
+fn most_detailed(image: &DynamicImage, width: u32, height: u32) -> (u32, u32) {
+    /// Longest side of the copy the edges are measured on
+    const SAMPLE_SIZE: f64 = 160.0;
+
+    let (image_width, image_height) = image.dimensions();
+    let scale = (SAMPLE_SIZE / image_width.max(image_height) as f64).min(1.0);
+    let sample_width = ((image_width as f64 * scale).round() as u32).max(2);
+    let sample_height = ((image_height as f64 * scale).round() as u32).max(2);
+    let sample = image
+        .resize_exact(sample_width, sample_height, FilterType::Triangle)
+        .to_luma8();
+
+    // Horizontal plus vertical gradient of every pixel
+    let edges: Vec<u64> = sample
+        .enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            let value = pixel[0] as i32;
+            let right = sample.get_pixel((x + 1).min(sample_width - 1), y)[0] as i32;
+            let below = sample.get_pixel(x, (y + 1).min(sample_height - 1))[0] as i32;
+            ((right - value).abs() + (below - value).abs()) as u64
+        })
+        .collect();
+    let edge = |x: u32, y: u32| edges[(y * sample_width + x) as usize];
+
+    // Summed-area table, so every window's total is four lookups
+    let stride = sample_width as usize + 1;
+    let mut sums = vec![0u64; stride * (sample_height as usize + 1)];
+    for y in 0..sample_height {
+        let mut row = 0u64;
+        for x in 0..sample_width {
+            row += edge(x, y);
+            let (x, y) = (x as usize + 1, y as usize + 1);
+            sums[y * stride + x] = sums[(y - 1) * stride + x] + row;
+        }
+    }
+
+    let window_width = ((width as f64 * scale).round() as u32).clamp(1, sample_width);
+    let window_height = ((height as f64 * scale).round() as u32).clamp(1, sample_height);
+    let total = |x: u32, y: u32| {
+        let (left, top) = (x as usize, y as usize);
+        let (right, bottom) = (left + window_width as usize, top + window_height as usize);
+        sums[bottom * stride + right] + sums[top * stride + left]
+            - sums[top * stride + right]
+            - sums[bottom * stride + left]
+    };
+
+    let center = (
+        (sample_width - window_width) / 2,
+        (sample_height - window_height) / 2,
+    );
+    let (mut best, mut best_total) = (center, total(center.0, center.1));
+    for y in 0..=(sample_height - window_height) {
+        for x in 0..=(sample_width - window_width) {
+            if total(x, y) > best_total {
+                (best, best_total) = ((x, y), total(x, y));
+            }
+        }
+    }
+    if best_total == 0 {
+        return ((image_width - width) / 2, (image_height - height) / 2);
+    }
+
+    // A window holding a small subject is not the only one holding it;
+    // center on the subject's edges rather than the first such window
+    let (mut sum_x, mut sum_y) = (0.0, 0.0);
+    for y in best.1..(best.1 + window_height) {
+        for x in best.0..(best.0 + window_width) {
+            sum_x += (x as f64 + 0.5) * edge(x, y) as f64;
+            sum_y += (y as f64 + 0.5) * edge(x, y) as f64;
+        }
+    }
+    let center_x = sum_x / best_total as f64 / scale;
+    let center_y = sum_y / best_total as f64 / scale;
+    (
+        (center_x - width as f64 / 2.0).clamp(0.0, (image_width - width) as f64) as u32,
+        (center_y - height as f64 / 2.0).clamp(0.0, (image_height - height) as f64) as u32,
+    )
+}
+
+/// Clockwise rotation applied as the last step of a render.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    /// `None` for 0 degrees.
+    pub fn from_degrees(degrees: u32) -> Result<Option<Self>, String> {
+        match degrees {
+            0 => Ok(None),
+            90 => Ok(Some(Self::Cw90)),
+            180 => Ok(Some(Self::Cw180)),
+            270 => Ok(Some(Self::Cw270)),
+            _ => Err(format!("Invalid rotate '{}'. Use 90, 180 or 270", degrees)),
+        }
+    }
+
+    pub fn apply<I>(&self, image: &I) -> ImageBuffer<I::Pixel, Vec<<I::Pixel as Pixel>::Subpixel>>
+    where
+        I: GenericImageView,
+        I::Pixel: 'static,
+    {
+        match self {
+            Self::Cw90 => imageops::rotate90(image),
+            Self::Cw180 => imageops::rotate180(image),
+            Self::Cw270 => imageops::rotate270(image),
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Per-device screens for the TRMNL display API (`/api/display`), so several
+//! devices polling the same server each get their own layout.
+//! `DEVICE_SCREENS_FILE` is a JSON object keyed by a device's MAC address
+//! (its `ID` header) or API key (its `Access-Token` header), each naming a
+//! dashboard from `DASHBOARDS`, e.g.
+//!
+//! ```json
+//! {
+//!   "AA:BB:CC:DD:EE:FF": { "dashboard": "kitchen" },
+//!   "my-api-key": { "dashboard": "office", "refresh_rate": 300, "night": "22:30-06:00" },
+//!   "bedroom": { "dashboard": "weather", "sleep": "23:00-07:00" },
+//!   "*": { "dashboard": "power" }
+//! }
+//! ```
+//!
+//! `*` is the screen of devices that aren't listed. Instead of one
+//! `dashboard`, a screen can have a `playlist` the device steps through
+//! with each poll, staying on an entry for its `polls`:
+//!
+//! ```json
+//! { "*": { "playlist": [{ "dashboard": "power", "polls": 2 }, { "dashboard": "kitchen" }] } }
+//! ```
+
+use crate::{dashboards::Dashboard, refresh::Window, standby::Escalation};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+/// Key of the screen served to devices that aren't listed
+const FALLBACK: &str = "*";
+
+/// One dashboard of a screen's playlist.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Slot {
+    /// Dashboard from `DASHBOARDS`
+    pub dashboard: String,
+    /// Panel of a tiled dashboard (`DASHBOARD_TILES`) the device shows
+    #[serde(default)]
+    pub tile: Option<u32>,
+    /// Polls the device stays on this dashboard before the next one
+    #[serde(default = "one_poll")]
+    pub polls: u32,
+}
+
+fn one_poll() -> u32 {
+    1
+}
+
+/// Home Assistant service called when the device's button is pressed,
+/// e.g. `light.toggle` with `{ "entity_id": "light.kitchen" }`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceCall {
+    pub service: String,
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+}
+
+/// A screen as written in `DEVICE_SCREENS_FILE`: a single dashboard
+/// (`dashboard` and `tile`) or a `playlist`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    dashboard: Option<String>,
+    tile: Option<u32>,
+    #[serde(default)]
+    playlist: Vec<Slot>,
+    refresh_rate: Option<u64>,
+    night: Option<Window>,
+    night_refresh_rate: Option<u64>,
+    sleep: Option<Window>,
+    escalate: Option<Escalation>,
+    special_function: Option<String>,
+    button: Option<ServiceCall>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "Entry")]
+pub struct Screen {
+    /// Dashboards shown in turn; just one unless a `playlist` was given
+    pub playlist: Vec<Slot>,
+    /// Seconds until the device polls again during the day; hinted from
+    /// the dashboard's content if unset
+    pub refresh_rate: Option<u64>,
+    /// Hours the device polls at `night_refresh_rate`, `23:00-07:00` if unset
+    pub night: Option<Window>,
+    /// Seconds until the device polls again at night; an hour if unset
+    pub night_refresh_rate: Option<u64>,
+    /// Quiet hours the device shows a blank sleeping frame instead of the
+    /// dashboard, polling again when they end
+    pub sleep: Option<Window>,
+    /// Dashboard that replaces the screen while something needs attention
+    pub escalate: Option<Escalation>,
+    /// Firmware special function sent to the device, `none` if unset
+    pub special_function: Option<String>,
+    /// Service called when the device reports a special function press
+    pub button: Option<ServiceCall>,
+}
+
+impl Screen {
+    /// A screen showing just `dashboard`, everything else left at its
+    /// default.
+    pub fn of_dashboard(dashboard: &str) -> Self {
+        Self {
+            playlist: vec![Slot {
+                dashboard: dashboard.to_string(),
+                tile: None,
+                polls: 1,
+            }],
+            refresh_rate: None,
+            night: None,
+            night_refresh_rate: None,
+            sleep: None,
+            escalate: None,
+            special_function: None,
+            button: None,
+        }
+    }
+}
+
+impl TryFrom<Entry> for Screen {
+    type Error = String;
+
+    fn try_from(entry: Entry) -> Result<Self, Self::Error> {
+        let playlist = match (entry.dashboard, entry.playlist.is_empty()) {
+            (Some(dashboard), true) => vec![Slot {
+                dashboard,
+                tile: entry.tile,
+                polls: 1,
+            }],
+            (None, false) if entry.tile.is_none() => entry.playlist,
+            (None, false) => return Err("tile goes on the playlist's entries".to_string()),
+            (Some(_), false) => return Err("use either dashboard or playlist".to_string()),
+            (None, true) => return Err("missing dashboard or playlist".to_string()),
+        };
+        Ok(Self {
+            playlist,
+            refresh_rate: entry.refresh_rate,
+            night: entry.night,
+            night_refresh_rate: entry.night_refresh_rate,
+            sleep: entry.sleep,
+            escalate: entry.escalate,
+            special_function: entry.special_function,
+            button: entry.button,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Screens {
+    /// Keyed by lowercase MAC address or API key
+    screens: HashMap<String, Screen>,
+    /// Each device's place in its playlist: the entry, and how many polls
+    /// it has been shown for
+    positions: Arc<Mutex<HashMap<String, (usize, u32)>>>,
+    /// Devices currently showing their screen's `escalate` dashboard
+    escalated: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Screens {
+    /// `DEVICE_SCREENS_FILE`, checked against the configured dashboards.
+    pub fn load(path: Option<&str>, dashboards: &[Dashboard]) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let entries: HashMap<String, Screen> = serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid device screens in {}: {}", path, e))?;
+
+        let mut screens = HashMap::new();
+        for (key, screen) in entries {
+            let check_dashboard = |name: &str, tile: Option<u32>| {
+                let dashboard = dashboards.iter().find(|d| d.name == name).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid device screen '{}': unknown dashboard '{}'",
+                        key,
+                        name
+                    )
+                })?;
+                if let Some(tile) = tile {
+                    let count = dashboard.tiles.map_or(0, |tiles| tiles.count());
+                    if tile >= count {
+                        return Err(anyhow::anyhow!(
+                            "Invalid device screen '{}': dashboard '{}' has no tile {}",
+                            key,
+                            dashboard.name,
+                            tile
+                        ));
+                    }
+                }
+                Ok(())
+            };
+
+            for slot in &screen.playlist {
+                check_dashboard(&slot.dashboard, slot.tile)?;
+                if slot.polls == 0 {
+                    return Err(anyhow::anyhow!(
+                        "Invalid device screen '{}': polls of '{}' must be at least 1",
+                        key,
+                        slot.dashboard
+                    ));
+                }
+            }
+            if let Some(escalate) = &screen.escalate {
+                check_dashboard(&escalate.dashboard, escalate.tile)?;
+                if escalate.when.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Invalid device screen '{}': escalate has no rules",
+                        key
+                    ));
+                }
+                for rule in &escalate.when {
+                    rule.check()
+                        .map_err(|e| anyhow::anyhow!("Invalid device screen '{}': {}", key, e))?;
+                }
+            }
+            if let Some(button) = &screen.button {
+                let valid = button
+                    .service
+                    .split_once('.')
+                    .is_some_and(|(domain, service)| {
+                        [domain, service].iter().all(|part| {
+                            !part.is_empty()
+                                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                        })
+                    });
+                if !valid {
+                    return Err(anyhow::anyhow!(
+                        "Invalid device screen '{}': invalid button service '{}'",
+                        key,
+                        button.service
+                    ));
+                }
+                if button.data.as_ref().is_some_and(|data| !data.is_object()) {
+                    return Err(anyhow::anyhow!(
+                        "Invalid device screen '{}': button data must be an object",
+                        key
+                    ));
+                }
+            }
+            for (name, rate) in [
+                ("refresh_rate", screen.refresh_rate),
+                ("night_refresh_rate", screen.night_refresh_rate),
+            ] {
+                if rate == Some(0) {
+                    return Err(anyhow::anyhow!(
+                        "Invalid device screen '{}': {} must be at least 1",
+                        key,
+                        name
+                    ));
+                }
+            }
+            screens.insert(key.trim().to_lowercase(), screen);
+        }
+
+        Ok(Self {
+            screens,
+            positions: Arc::default(),
+            escalated: Arc::default(),
+        })
+    }
+
+    /// Screen of the device with MAC address `device_id` or API key
+    /// `access_token`, whichever is listed (the MAC address first), or the
+    /// `*` screen.
+    pub fn find(&self, device_id: Option<&str>, access_token: Option<&str>) -> Option<&Screen> {
+        [device_id, access_token]
+            .into_iter()
+            .flatten()
+            .find_map(|key| self.screens.get(&key.trim().to_lowercase()))
+            .or_else(|| self.screens.get(FALLBACK))
+    }
+
+    /// Screen listed under `key` itself, without falling back to `*`.
+    pub fn get(&self, key: &str) -> Option<&Screen> {
+        self.screens.get(&key.trim().to_lowercase())
+    }
+
+    /// Entry of `screen`'s playlist for this poll of `device`, moving on to
+    /// the next entry once the current one has been shown for its `polls`.
+    pub fn next_slot<'a>(&self, device: &str, screen: &'a Screen) -> &'a Slot {
+        if screen.playlist.len() == 1 {
+            return &screen.playlist[0];
+        }
+
+        let mut positions = self.positions.lock().unwrap();
+        let (index, shown) = positions
+            .entry(device.trim().to_lowercase())
+            .or_insert((0, 0));
+        *index %= screen.playlist.len();
+        if *shown >= screen.playlist[*index].polls {
+            *index = (*index + 1) % screen.playlist.len();
+            *shown = 0;
+        }
+        *shown += 1;
+        &screen.playlist[*index]
+    }
+
+    /// Record whether `device` shows its escalation dashboard; returns
+    /// whether that changed.
+    pub fn set_escalated(&self, device: &str, escalated: bool) -> bool {
+        let mut devices = self.escalated.lock().unwrap();
+        let device = device.trim().to_lowercase();
+        if escalated {
+            devices.insert(device)
+        } else {
+            devices.remove(&device)
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Standby screens with escalation: a device shows a calm screen (a clock,
+//! the weather) while all is well and switches to a detailed dashboard as
+//! soon as an entity is out of the ordinary, going back once it's resolved.
+//! A screen's `escalate` names that dashboard and the rules, written like
+//! Home Assistant's state and numeric state conditions:
+//!
+//! ```json
+//! "escalate": {
+//!   "dashboard": "house",
+//!   "when": [
+//!     { "entity": "binary_sensor.front_door", "state": "on", "for": 600 },
+//!     { "entity": "sensor.freezer_temperature", "above": -12 },
+//!     { "entity": "sensor.living_room_temperature", "below": 16 }
+//!   ]
+//! }
+//! ```
+
+use serde::Deserialize;
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Escalation {
+    /// Dashboard from `DASHBOARDS` shown while a rule matches
+    pub dashboard: String,
+    /// Panel of a tiled dashboard (`DASHBOARD_TILES`) to show
+    #[serde(default)]
+    pub tile: Option<u32>,
+    /// Any one matching escalates
+    pub when: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub entity: String,
+    /// States that match, `|` separating several
+    #[serde(default)]
+    state: Option<String>,
+    /// Numeric states above this match
+    #[serde(default)]
+    above: Option<f64>,
+    /// Numeric states below this match
+    #[serde(default)]
+    below: Option<f64>,
+    /// Seconds the entity must have been in `state`
+    #[serde(default, rename = "for")]
+    for_secs: Option<u64>,
+}
+
+impl Rule {
+    /// Why the rule can't be evaluated, if it can't.
+    pub fn check(&self) -> Result<(), String> {
+        if !self.entity.contains('.') {
+            return Err(format!("invalid entity '{}'", self.entity));
+        }
+        match (&self.state, self.above.is_some() || self.below.is_some()) {
+            (Some(_), true) => Err(format!(
+                "rule for {} has both state and above/below",
+                self.entity
+            )),
+            (None, false) => Err(format!(
+                "rule for {} needs state, above or below",
+                self.entity
+            )),
+            (None, true) if self.for_secs.is_some() => Err(format!(
+                "rule for {} can only use for with state",
+                self.entity
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the entity's `state`, last changed at `last_changed`,
+    /// matches.
+    pub fn matches(&self, state: &str, last_changed: Option<SystemTime>) -> bool {
+        match &self.state {
+            Some(states) => {
+                let in_state = states
+                    .split('|')
+                    .any(|s| s.trim().eq_ignore_ascii_case(state));
+                let long_enough = match self.for_secs {
+                    Some(secs) => last_changed.is_some_and(|changed| {
+                        changed.elapsed().unwrap_or_default() >= Duration::from_secs(secs)
+                    }),
+                    None => true,
+                };
+                in_state && long_enough
+            }
+            None => state.trim().parse::<f64>().is_ok_and(|value| {
+                self.above.is_none_or(|above| value > above)
+                    && self.below.is_none_or(|below| value < below)
+            }),
+        }
+    }
+
+    /// The rule as `binary_sensor.door=on for 600s`, `sensor.temp>-12` or
+    /// `-30<sensor.temp<-15`, for logs.
+    pub fn describe(&self) -> String {
+        let mut text = match self.above {
+            Some(above) if self.below.is_some() => format!("{}<{}", above, self.entity),
+            Some(above) => format!("{}>{}", self.entity, above),
+            None => self.entity.clone(),
+        };
+        if let Some(below) = self.below {
+            text.push_str(&format!("<{}", below));
+        }
+        if let Some(state) = &self.state {
+            text.push_str(&format!("={}", state));
+        }
+        if let Some(secs) = self.for_secs {
+            text.push_str(&format!(" for {}s", secs));
+        }
+        text
+    }
+}
@@ -0,0 +1,183 @@
+//! Live copy of every entity's state, kept over the Home Assistant
+//! WebSocket so renders read their sensors from memory rather than
+//! fetching each from the REST API in turn. The copy is fetched with
+//! `get_states` on every connection and kept current by `state_changed`
+//! events. While the socket is down or has gone quiet, or until the first
+//! copy arrives, the cache is cold and states come from the REST API as
+//! before.
+
+use crate::{timestamps, websocket::HaSocket};
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{sync::watch, time::Instant};
+use tracing::{info, warn};
+
+/// How long to wait before fetching the copy again after `get_states` failed
+const RETRY: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct StateCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    socket: HaSocket,
+    connections: watch::Receiver<u64>,
+    states: RwLock<BTreeMap<String, Value>>,
+    /// Connection the copy was fetched on, zero before the first
+    seeded: AtomicU64,
+}
+
+impl StateCache {
+    /// Subscribe to state changes over `socket` and keep the copy from
+    /// then on.
+    pub fn start(socket: HaSocket) -> Self {
+        let inner = Arc::new(Inner {
+            connections: socket.connections(),
+            socket,
+            states: RwLock::new(BTreeMap::new()),
+            seeded: AtomicU64::new(0),
+        });
+        tokio::spawn(keep(inner.clone()));
+        Self { inner }
+    }
+
+    /// State object of an entity as `/api/states/<id>` returns it, `Some(None)`
+    /// for an entity Home Assistant doesn't have, or `None` while the cache
+    /// is cold.
+    pub fn get(&self, entity_id: &str) -> Option<Option<Value>> {
+        self.is_warm().then(|| {
+            let states = self.inner.states.read().unwrap();
+            states.get(entity_id).cloned()
+        })
+    }
+
+    /// Every state object, as `/api/states` returns them but ordered by
+    /// entity ID, or `None` while the cache is cold.
+    pub fn all(&self) -> Option<Vec<Value>> {
+        self.is_warm().then(|| {
+            let states = self.inner.states.read().unwrap();
+            states.values().cloned().collect()
+        })
+    }
+
+    /// Whether the copy was fetched on the connection that is up now, so no
+    /// events have been missed since, and that connection has lately shown
+    /// it's alive: a half-open socket would keep serving frozen states.
+    fn is_warm(&self) -> bool {
+        let seeded = self.inner.seeded.load(Ordering::Relaxed);
+        seeded != 0 && self.inner.socket.is_alive() && seeded == *self.inner.connections.borrow()
+    }
+}
+
+/// Apply state changes as they come, fetching the whole copy again after
+/// every reconnect.
+async fn keep(inner: Arc<Inner>) {
+    let mut events = inner.socket.subscribe(json!({
+        "type": "subscribe_events",
+        "event_type": "state_changed",
+    }));
+    let mut connections = inner.socket.connections();
+    let mut retry: Option<Instant> = None;
+
+    loop {
+        let connection = tokio::select! {
+            changed = connections.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                *connections.borrow_and_update()
+            }
+            _ = tokio::time::sleep_until(retry.unwrap_or_else(Instant::now)), if retry.is_some() => {
+                *connections.borrow()
+            }
+            event = events.recv() => {
+                match event {
+                    Some(event) => inner.apply(&event),
+                    None => return,
+                }
+                continue;
+            }
+        };
+
+        retry = match inner.seed(connection).await {
+            Ok(count) => {
+                info!("Cached the states of {} entities", count);
+                None
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to fetch states for the cache: {} (retrying in {}s)",
+                    e,
+                    RETRY.as_secs()
+                );
+                Some(Instant::now() + RETRY)
+            }
+        };
+    }
+}
+
+impl Inner {
+    /// Replace the copy with the states Home Assistant has now. The cache
+    /// only turns warm if `connection` is still the one up afterwards.
+    async fn seed(&self, connection: u64) -> anyhow::Result<usize> {
+        let result = self.socket.command(json!({ "type": "get_states" })).await?;
+        let Value::Array(list) = result else {
+            return Err(anyhow::anyhow!("get_states returned {}", result));
+        };
+
+        let states: BTreeMap<String, Value> = list
+            .into_iter()
+            .filter_map(|state| {
+                let entity_id = state.get("entity_id")?.as_str()?.to_string();
+                Some((entity_id, state))
+            })
+            .collect();
+        let count = states.len();
+        *self.states.write().unwrap() = states;
+        self.seeded.store(connection, Ordering::Relaxed);
+        Ok(count)
+    }
+
+    /// Apply a `state_changed` event: a `new_state` of `null` means the
+    /// entity was removed.
+    fn apply(&self, event: &Value) {
+        let Some(data) = event.get("data") else {
+            return;
+        };
+        let Some(entity_id) = data.get("entity_id").and_then(Value::as_str) else {
+            return;
+        };
+
+        let mut states = self.states.write().unwrap();
+        match data.get("new_state").filter(|state| !state.is_null()) {
+            Some(new_state) => {
+                // Events queued while the copy was fetched may be older than it
+                let updated = |state: &Value| {
+                    state
+                        .get("last_updated")
+                        .and_then(Value::as_str)
+                        .and_then(timestamps::parse)
+                };
+                let stale = states
+                    .get(entity_id)
+                    .and_then(updated)
+                    .zip(updated(new_state))
+                    .is_some_and(|(cached, new)| new < cached);
+                if !stale {
+                    states.insert(entity_id.to_string(), new_state.clone());
+                }
+            }
+            None => {
+                states.remove(entity_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Long-term statistics: the hourly mean, min and max of measurement
+//! sensors, and the change of meters, which Home Assistant keeps long after
+//! their detailed history is purged and adds up into days, weeks or months
+//! on request - a fortnight of daily energy use is 14 numbers rather than
+//! thousands of recorded states. They're only served over the WebSocket
+//! API (`recorder/statistics_during_period`).
+
+use crate::timestamps;
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Length of the buckets statistics are added up into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "hour" => Ok(Self::Hour),
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(format!(
+                "Unknown period '{}'. Use hour, day, week or month",
+                name
+            )),
+        }
+    }
+
+    /// Name of the period in Home Assistant's commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    /// Buckets shown without `?count=`
+    pub fn default_count(self) -> u32 {
+        match self {
+            Self::Hour => 24,
+            Self::Day => 14,
+            Self::Week | Self::Month => 12,
+        }
+    }
+
+    /// Most buckets a chart shows, about a week of hours, a quarter of days
+    /// or two years of weeks and months
+    pub fn max_count(self) -> u32 {
+        match self {
+            Self::Hour => 168,
+            Self::Day => 90,
+            Self::Week => 104,
+            Self::Month => 24,
+        }
+    }
+
+    /// Days before today to ask from for `count` buckets: whole weeks and
+    /// months don't start on a fixed day, so they're asked for with one
+    /// bucket to spare and only the last `count` kept. `None` for hours,
+    /// which are counted back from now.
+    pub fn days_back(self, count: u32) -> Option<i64> {
+        let count = i64::from(count);
+        match self {
+            Self::Hour => None,
+            Self::Day => Some(count - 1),
+            Self::Week => Some(7 * count),
+            Self::Month => Some(31 * count),
+        }
+    }
+
+    /// `Last 14 days`, for above the chart.
+    pub fn describe(self, count: u32) -> String {
+        match (self, count) {
+            (Self::Day, 1) => "Today".to_string(),
+            (_, 1) => format!("This {}", self.name()),
+            _ => format!("Last {} {}s", count, self.name()),
+        }
+    }
+
+    /// Axis label of the bucket starting at `start`: its clock time, date
+    /// or month.
+    pub fn label(self, start: SystemTime, offset_secs: i64) -> String {
+        let secs = start
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64
+            + offset_secs;
+        let (_, month, day) = timestamps::civil_from_days(secs.div_euclid(86_400));
+        let month = MONTHS[month as usize - 1];
+        match self {
+            Self::Hour => timestamps::format_clock(start, offset_secs),
+            Self::Day | Self::Week => format!("{} {}", month, day),
+            Self::Month => month.to_string(),
+        }
+    }
+}
+
+/// Which statistic of each bucket is charted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    /// What a meter counted during the bucket, drawn as bars
+    Change,
+    /// The average, drawn as a line over each bucket's range
+    Mean,
+    Min,
+    Max,
+}
+
+impl Stat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "change" => Ok(Self::Change),
+            "mean" => Ok(Self::Mean),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            _ => Err(format!(
+                "Unknown stat '{}'. Use change, mean, min or max",
+                name
+            )),
+        }
+    }
+
+    /// Statistic types asked for: the mean comes with its range.
+    pub fn types(self) -> &'static [&'static str] {
+        match self {
+            Self::Change => &["change"],
+            Self::Mean => &["mean", "min", "max"],
+            Self::Min => &["min"],
+            Self::Max => &["max"],
+        }
+    }
+
+    /// Label of the figure summing up all buckets.
+    pub fn caption(self) -> &'static str {
+        match self {
+            Self::Change => "Total",
+            Self::Mean => "Average",
+            Self::Min => "Lowest",
+            Self::Max => "Highest",
+        }
+    }
+}
+
+/// What Home Assistant records about a statistic, from
+/// `recorder/get_statistics_metadata`.
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    /// Set for statistics imported by integrations; entities' own have
+    /// their friendly name instead
+    pub name: Option<String>,
+    pub unit: Option<String>,
+    /// A meter, whose change is what counts
+    pub has_sum: bool,
+}
+
+impl Metadata {
+    /// The metadata of `statistic_id` in a `get_statistics_metadata` result.
+    pub fn parse(result: &Value, statistic_id: &str) -> Option<Self> {
+        let metadata = result.as_array()?.iter().find(|metadata| {
+            metadata.get("statistic_id").and_then(Value::as_str) == Some(statistic_id)
+        })?;
+        let text = |key: &str| {
+            metadata
+                .get(key)
+                .and_then(Value::as_str)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+        Some(Self {
+            name: text("name"),
+            unit: text("display_unit_of_measurement")
+                .or_else(|| text("statistics_unit_of_measurement")),
+            has_sum: metadata
+                .get("has_sum")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        })
+    }
+
+    /// What a chart of this statistic shows without `?stat=`.
+    pub fn default_stat(&self) -> Stat {
+        if self.has_sum {
+            Stat::Change
+        } else {
+            Stat::Mean
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Bucket {
+    pub start: SystemTime,
+    pub end: Option<SystemTime>,
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub change: Option<f64>,
+}
+
+impl Bucket {
+    pub fn value(&self, stat: Stat) -> Option<f64> {
+        match stat {
+            Stat::Change => self.change,
+            Stat::Mean => self.mean,
+            Stat::Min => self.min,
+            Stat::Max => self.max,
+        }
+    }
+
+    /// Whether the bucket is still being added to.
+    pub fn is_current(&self, now: SystemTime) -> bool {
+        self.end.is_some_and(|end| end > now)
+    }
+}
+
+/// Buckets of `statistic_id` in a `statistics_during_period` result, oldest
+/// first. Times are milliseconds since the epoch, or timestamps from Home
+/// Assistant before 2023.3.
+pub fn parse_buckets(result: &Value, statistic_id: &str) -> Vec<Bucket> {
+    let time = |value: Option<&Value>| match value? {
+        Value::Number(ms) => {
+            Some(UNIX_EPOCH + Duration::from_millis(ms.as_f64().filter(|ms| *ms >= 0.0)? as u64))
+        }
+        Value::String(text) => timestamps::parse(text),
+        _ => None,
+    };
+    let number = |bucket: &Value, key: &str| {
+        bucket
+            .get(key)
+            .and_then(Value::as_f64)
+            .filter(|value| value.is_finite())
+    };
+
+    let mut buckets: Vec<Bucket> = result
+        .get(statistic_id)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|bucket| {
+            Some(Bucket {
+                start: time(bucket.get("start"))?,
+                end: time(bucket.get("end")),
+                mean: number(bucket, "mean"),
+                min: number(bucket, "min"),
+                max: number(bucket, "max"),
+                change: number(bucket, "change"),
+            })
+        })
+        .collect();
+    buckets.sort_by_key(|bucket| bucket.start);
+    buckets
+}
+
+/// The figure summing up `buckets`: the total change, the average of the
+/// means, or the lowest min and highest max.
+pub fn summary(buckets: &[Bucket], stat: Stat) -> Option<f64> {
+    let mut values = buckets.iter().filter_map(|bucket| bucket.value(stat));
+    let first = values.next()?;
+    Some(match stat {
+        Stat::Change => values.fold(first, |total, value| total + value),
+        Stat::Mean => {
+            let (total, count) = values.fold((first, 1), |(total, count), value| {
+                (total + value, count + 1)
+            });
+            total / count as f64
+        }
+        Stat::Min => values.fold(first, f64::min),
+        Stat::Max => values.fold(first, f64::max),
+    })
+}
@@ -0,0 +1,205 @@
+//! Minimal SVG writer for vector versions of the RGB layouts. Shapes take
+//! the raster layouts' pixel coordinates, and text is set in a monospace
+//! font stretched to the bitmap font's advance, so lines fit and truncate
+//! exactly as they do in the PNG.
+
+use crate::resize::Rotation;
+use image::Rgb;
+use std::fmt::Write;
+
+/// Horizontal advance of one glyph at scale 1, as in the bitmap font
+const GLYPH_ADVANCE: u32 = 7;
+const FONT_FAMILY: &str = "DejaVu Sans Mono, Menlo, Consolas, monospace";
+
+pub struct Svg {
+    width: u32,
+    height: u32,
+    defs: String,
+    body: String,
+    gradients: usize,
+}
+
+impl Svg {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            defs: String::new(),
+            body: String::new(),
+            gradients: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn rect(&mut self, x: u32, y: u32, width: u32, height: u32, fill: Rgb<u8>) {
+        let _ = write!(
+            self.body,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            x,
+            y,
+            width,
+            height,
+            hex(fill)
+        );
+    }
+
+    /// Rectangle shaded from `top` to `bottom`.
+    pub fn gradient_rect(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        top: Rgb<u8>,
+        bottom: Rgb<u8>,
+    ) {
+        if top == bottom {
+            self.rect(x, y, width, height, top);
+            return;
+        }
+
+        self.gradients += 1;
+        let _ = write!(
+            self.defs,
+            r#"<linearGradient id="g{}" x1="0" y1="0" x2="0" y2="1"><stop offset="0" stop-color="{}"/><stop offset="1" stop-color="{}"/></linearGradient>"#,
+            self.gradients,
+            hex(top),
+            hex(bottom)
+        );
+        let _ = write!(
+            self.body,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="url(#g{})"/>"#,
+            x, y, width, height, self.gradients
+        );
+    }
+
+    /// Frame of `thickness` pixels along the inside of the rectangle, like
+    /// the raster borders.
+    pub fn outline(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        thickness: u32,
+        color: Rgb<u8>,
+    ) {
+        let inset = thickness as f32 / 2.0;
+        let _ = write!(
+            self.body,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-width="{}"/>"#,
+            x as f32 + inset,
+            y as f32 + inset,
+            width.saturating_sub(thickness),
+            height.saturating_sub(thickness),
+            hex(color),
+            thickness
+        );
+    }
+
+    pub fn circle(&mut self, cx: f32, cy: f32, radius: f32, fill: Rgb<u8>) {
+        let _ = write!(
+            self.body,
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+            cx,
+            cy,
+            radius,
+            hex(fill)
+        );
+    }
+
+    /// Text whose glyph cells start at `(x, y)`, the same origin the bitmap
+    /// font is drawn from.
+    pub fn text(&mut self, x: u32, y: u32, text: &str, color: Rgb<u8>, scale: u32, bold: bool) {
+        let length = text.chars().count() as u32;
+        if length == 0 {
+            return;
+        }
+
+        let _ = write!(
+            self.body,
+            r#"<text x="{}" y="{}" fill="{}" font-size="{}"{} textLength="{}" lengthAdjust="spacingAndGlyphs">{}</text>"#,
+            x,
+            y + 7 * scale,
+            hex(color),
+            9 * scale,
+            if bold { r#" font-weight="bold""# } else { "" },
+            (length * GLYPH_ADVANCE - 1) * scale,
+            escape(text)
+        );
+    }
+
+    /// Surround everything drawn so far with `margin` pixels of `color`.
+    pub fn frame(&mut self, margin: u32, color: Rgb<u8>) {
+        self.width += margin * 2;
+        self.height += margin * 2;
+        self.body = format!(
+            r#"<rect width="{}" height="{}" fill="{}"/><g transform="translate({} {})">{}</g>"#,
+            self.width,
+            self.height,
+            hex(color),
+            margin,
+            margin,
+            self.body
+        );
+    }
+
+    /// Stretch everything drawn so far to `width` x `height`.
+    pub fn scale_to(&mut self, width: u32, height: u32) {
+        let x = width as f32 / self.width as f32;
+        let y = height as f32 / self.height as f32;
+        (self.width, self.height) = (width, height);
+        self.body = format!(r#"<g transform="scale({} {})">{}</g>"#, x, y, self.body);
+    }
+
+    /// Turn everything drawn so far clockwise.
+    pub fn rotate(&mut self, rotation: Rotation) {
+        let (width, height) = (self.width, self.height);
+        let (transform, size) = match rotation {
+            Rotation::Cw90 => (
+                format!("translate({} 0) rotate(90)", height),
+                (height, width),
+            ),
+            Rotation::Cw180 => (
+                format!("translate({} {}) rotate(180)", width, height),
+                (width, height),
+            ),
+            Rotation::Cw270 => (
+                format!("translate(0 {}) rotate(270)", width),
+                (height, width),
+            ),
+        };
+        (self.width, self.height) = size;
+        self.body = format!(r#"<g transform="{}">{}</g>"#, transform, self.body);
+    }
+
+    pub fn finish(self) -> String {
+        let defs = if self.defs.is_empty() {
+            String::new()
+        } else {
+            format!("<defs>{}</defs>", self.defs)
+        };
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="{2}" xml:space="preserve">{3}{4}</svg>"#,
+            self.width, self.height, FONT_FAMILY, defs, self.body
+        )
+    }
+}
+
+fn hex(color: Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
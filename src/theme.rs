@@ -0,0 +1,252 @@
+//! Color palettes for the RGB renderers (`/status` and `/multi-status`).
+
+use image::Rgb;
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub background_top: Rgb<u8>,
+    pub background_bottom: Rgb<u8>,
+    pub frame: Rgb<u8>,
+    pub header_top: Rgb<u8>,
+    pub header_bottom: Rgb<u8>,
+    pub header_border: Rgb<u8>,
+    pub header_text: Rgb<u8>,
+    pub panel: Rgb<u8>,
+    pub panel_border: Rgb<u8>,
+    pub text: Rgb<u8>,
+    pub muted_text: Rgb<u8>,
+    pub value: Rgb<u8>,
+    pub ok: Rgb<u8>,
+    pub warn: Rgb<u8>,
+    pub critical: Rgb<u8>,
+    pub inactive: Rgb<u8>,
+    pub info: Rgb<u8>,
+    /// Indicator for on/open/home states
+    pub active: Rgb<u8>,
+    /// Indicator for off/closed/away states
+    pub idle: Rgb<u8>,
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        Self {
+            background_top: Rgb([250, 250, 255]),
+            background_bottom: Rgb([240, 240, 250]),
+            frame: Rgb([80, 80, 80]),
+            header_top: Rgb([60, 60, 80]),
+            header_bottom: Rgb([40, 40, 60]),
+            header_border: Rgb([100, 100, 120]),
+            header_text: Rgb([255, 255, 255]),
+            panel: Rgb([245, 245, 250]),
+            panel_border: Rgb([190, 190, 200]),
+            text: Rgb([40, 40, 40]),
+            muted_text: Rgb([120, 120, 120]),
+            value: Rgb([40, 120, 40]),
+            ok: Rgb([50, 205, 50]),
+            warn: Rgb([230, 150, 20]),
+            critical: Rgb([220, 20, 60]),
+            inactive: Rgb([169, 169, 169]),
+            info: Rgb([30, 144, 255]),
+            active: Rgb([50, 205, 50]),
+            idle: Rgb([220, 20, 60]),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            background_top: Rgb([30, 32, 40]),
+            background_bottom: Rgb([20, 22, 28]),
+            frame: Rgb([90, 90, 100]),
+            header_top: Rgb([55, 60, 80]),
+            header_bottom: Rgb([40, 44, 60]),
+            header_border: Rgb([90, 95, 120]),
+            header_text: Rgb([235, 235, 245]),
+            panel: Rgb([38, 40, 50]),
+            panel_border: Rgb([70, 72, 85]),
+            text: Rgb([220, 220, 228]),
+            muted_text: Rgb([140, 140, 150]),
+            value: Rgb([120, 220, 120]),
+            ok: Rgb([70, 200, 90]),
+            warn: Rgb([240, 170, 40]),
+            critical: Rgb([240, 70, 80]),
+            inactive: Rgb([110, 110, 120]),
+            info: Rgb([80, 160, 255]),
+            active: Rgb([70, 200, 90]),
+            idle: Rgb([240, 70, 80]),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            background_top: Rgb([255, 255, 255]),
+            background_bottom: Rgb([255, 255, 255]),
+            frame: Rgb([0, 0, 0]),
+            header_top: Rgb([0, 0, 0]),
+            header_bottom: Rgb([0, 0, 0]),
+            header_border: Rgb([0, 0, 0]),
+            header_text: Rgb([255, 255, 255]),
+            panel: Rgb([255, 255, 255]),
+            panel_border: Rgb([0, 0, 0]),
+            text: Rgb([0, 0, 0]),
+            muted_text: Rgb([0, 0, 0]),
+            value: Rgb([0, 0, 0]),
+            ok: Rgb([0, 128, 0]),
+            warn: Rgb([200, 100, 0]),
+            critical: Rgb([200, 0, 0]),
+            inactive: Rgb([90, 90, 90]),
+            info: Rgb([0, 0, 200]),
+            active: Rgb([0, 128, 0]),
+            idle: Rgb([200, 0, 0]),
+        }
+    }
+
+    /// Black and white with alerts and "on" states in pure red, for
+    /// tri-color e-paper. Anything saturated ends up red once quantized, so
+    /// every other role is kept gray.
+    pub fn tri_color() -> Self {
+        let red = Rgb([255, 0, 0]);
+        let black = Rgb([0, 0, 0]);
+        Self {
+            ok: black,
+            warn: red,
+            critical: red,
+            inactive: Rgb([90, 90, 90]),
+            info: black,
+            active: red,
+            idle: black,
+            ..Self::high_contrast()
+        }
+    }
+
+    /// Flat fills in the exact Spectra 6 panel colors, so status renders
+    /// need no dithering on six-color e-paper.
+    pub fn six_color() -> Self {
+        Self {
+            ok: Rgb([0, 255, 0]),
+            warn: Rgb([255, 255, 0]),
+            critical: Rgb([255, 0, 0]),
+            inactive: Rgb([0, 0, 0]),
+            info: Rgb([0, 0, 255]),
+            active: Rgb([0, 255, 0]),
+            idle: Rgb([255, 0, 0]),
+            ..Self::high_contrast()
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "light" => Some(Self::light()),
+            "dark" => Some(Self::dark()),
+            "high-contrast" | "contrast" => Some(Self::high_contrast()),
+            "tri-color" | "bwr" => Some(Self::tri_color()),
+            "six-color" | "spectra6" => Some(Self::six_color()),
+            _ => None,
+        }
+    }
+
+    /// Apply `key:color` overrides such as `header:#223344,ok:green`.
+    /// Returns the offending entry on failure.
+    pub fn apply_overrides(&mut self, spec: &str) -> Result<(), String> {
+        for pair in spec.split(',').filter(|p| !p.trim().is_empty()) {
+            let (key, color) = pair.split_once(':').ok_or_else(|| pair.to_string())?;
+            let color = parse_color(color).ok_or_else(|| pair.to_string())?;
+
+            match key.trim() {
+                "background" => {
+                    self.background_top = color;
+                    self.background_bottom = color;
+                }
+                "background_top" => self.background_top = color,
+                "background_bottom" => self.background_bottom = color,
+                "frame" => self.frame = color,
+                "header" => {
+                    self.header_top = color;
+                    self.header_bottom = color;
+                }
+                "header_top" => self.header_top = color,
+                "header_bottom" => self.header_bottom = color,
+                "header_border" => self.header_border = color,
+                "header_text" => self.header_text = color,
+                "panel" => self.panel = color,
+                "panel_border" => self.panel_border = color,
+                "text" => self.text = color,
+                "muted_text" => self.muted_text = color,
+                "value" => self.value = color,
+                "ok" => self.ok = color,
+                "warn" => self.warn = color,
+                "critical" => self.critical = color,
+                "inactive" => self.inactive = color,
+                "info" => self.info = color,
+                "active" => self.active = color,
+                "idle" => self.idle = color,
+                _ => return Err(pair.to_string()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolve a color by palette role (`ok`, `warn`, `critical`, ...) or
+    /// fall back to a named/hex color.
+    pub fn color(&self, name: &str) -> Option<Rgb<u8>> {
+        match name.trim() {
+            "ok" => Some(self.ok),
+            "warn" => Some(self.warn),
+            "critical" => Some(self.critical),
+            "inactive" => Some(self.inactive),
+            "info" => Some(self.info),
+            "active" => Some(self.active),
+            "idle" => Some(self.idle),
+            other => parse_color(other),
+        }
+    }
+
+    /// Indicator fill for an entity state.
+    pub fn state_color(&self, state: &str) -> Rgb<u8> {
+        match state.to_lowercase().as_str() {
+            "on" | "open" | "active" | "home" | "detected" => self.active,
+            "off" | "closed" | "inactive" | "away" | "clear" => self.idle,
+            "unavailable" | "unknown" => self.inactive,
+            _ => self.info,
+        }
+    }
+}
+
+/// Parse a named color or a `#rrggbb` / `rrggbb` hex value.
+pub fn parse_color(text: &str) -> Option<Rgb<u8>> {
+    let text = text.trim();
+    let named = match text.to_lowercase().as_str() {
+        "black" => Some([0, 0, 0]),
+        "white" => Some([255, 255, 255]),
+        "gray" | "grey" => Some([128, 128, 128]),
+        "red" => Some([200, 40, 40]),
+        "orange" => Some([230, 130, 20]),
+        "yellow" => Some([220, 190, 20]),
+        "green" => Some([40, 160, 40]),
+        "teal" => Some([20, 150, 150]),
+        "blue" => Some([40, 100, 210]),
+        "purple" => Some([140, 60, 180]),
+        _ => None,
+    };
+    if let Some(rgb) = named {
+        return Some(Rgb(rgb));
+    }
+
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+pub fn blend_colors(color1: Rgb<u8>, color2: Rgb<u8>, factor: f32) -> Rgb<u8> {
+    let r = (color1[0] as f32 * (1.0 - factor) + color2[0] as f32 * factor) as u8;
+    let g = (color1[1] as f32 * (1.0 - factor) + color2[1] as f32 * factor) as u8;
+    let b = (color1[2] as f32 * (1.0 - factor) + color2[2] as f32 * factor) as u8;
+    Rgb([r, g, b])
+}
+
+pub fn darken(color: Rgb<u8>, factor: f32) -> Rgb<u8> {
+    blend_colors(color, Rgb([0u8, 0u8, 0u8]), factor)
+}
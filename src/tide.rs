@@ -0,0 +1,101 @@
+//! Tide curves from the next high and low tide reported by tide
+//! integrations (e.g. NOAA Tides). Other tides of the day are extrapolated
+//! one tidal cycle apart and joined with cosine curves.
+
+use crate::timestamps;
+use serde_json::Value;
+use std::time::{Duration, SystemTime};
+
+/// Mean semidiurnal tidal cycle (12h 25m)
+const TIDAL_CYCLE: Duration = Duration::from_secs(12 * 3600 + 25 * 60);
+
+#[derive(Clone, Copy, Debug)]
+pub struct TideEvent {
+    pub time: SystemTime,
+    pub high: bool,
+    /// Water height, `None` when the integration doesn't report it
+    pub height: Option<f64>,
+}
+
+/// Attribute names of the next high and low tide, defaulting to the NOAA
+/// Tides integration's.
+#[derive(Clone, Debug)]
+pub struct Attributes {
+    pub high_time: String,
+    pub high_height: String,
+    pub low_time: String,
+    pub low_height: String,
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Self {
+            high_time: "high_tide_time".to_string(),
+            high_height: "high_tide_height".to_string(),
+            low_time: "low_tide_time".to_string(),
+            low_height: "low_tide_height".to_string(),
+        }
+    }
+}
+
+impl Attributes {
+    /// The next high and low tide, or `None` unless both times parse.
+    /// Times without a UTC offset are local, `offset_secs` ahead of UTC.
+    pub fn events(&self, attributes: &Value, offset_secs: i64) -> Option<(TideEvent, TideEvent)> {
+        let event = |time: &str, height: &str, high: bool| {
+            let time = attributes.get(time)?.as_str()?;
+            Some(TideEvent {
+                time: timestamps::parse_local(time, offset_secs)?,
+                high,
+                height: attributes.get(height).and_then(|value| match value {
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(text) => text.trim().parse().ok(),
+                    _ => None,
+                }),
+            })
+        };
+
+        Some((
+            event(&self.high_time, &self.high_height, true)?,
+            event(&self.low_time, &self.low_height, false)?,
+        ))
+    }
+}
+
+/// Tides around `[start, end]`: the two known events plus ones a whole
+/// cycle before and after them, in time order.
+pub fn events_between(
+    high: TideEvent,
+    low: TideEvent,
+    start: SystemTime,
+    end: SystemTime,
+) -> Vec<TideEvent> {
+    let mut events = Vec::new();
+    for known in [high, low] {
+        // Walk back to the first cycle before `start`, then forward past `end`
+        let mut time = known.time;
+        while time > start {
+            time -= TIDAL_CYCLE;
+        }
+        while time < end + TIDAL_CYCLE {
+            events.push(TideEvent { time, ..known });
+            time += TIDAL_CYCLE;
+        }
+    }
+    events.sort_by_key(|event| event.time);
+    events
+}
+
+/// Water level at `time` between 0.0 (low) and 1.0 (high), following a
+/// half cosine between the surrounding events.
+pub fn level_at(events: &[TideEvent], time: SystemTime) -> Option<f64> {
+    let pair = events
+        .windows(2)
+        .find(|pair| pair[0].time <= time && time <= pair[1].time)?;
+    let (from, to) = (pair[0], pair[1]);
+
+    let span = to.time.duration_since(from.time).ok()?.as_secs_f64();
+    let progress = time.duration_since(from.time).ok()?.as_secs_f64() / span.max(1.0);
+    let rising = (1.0 - (progress * std::f64::consts::PI).cos()) / 2.0;
+    Some(if from.high { 1.0 - rising } else { rising })
+}
@@ -0,0 +1,170 @@
+//! Home Assistant timestamps (`2026-10-16T12:03:45.123456+00:00`) and
+//! relative "2h ago" / "in 2h 15m" formatting.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parse an ISO 8601 / RFC 3339 timestamp as reported by Home Assistant.
+/// Fractional seconds are ignored.
+pub fn parse(text: &str) -> Option<SystemTime> {
+    let text = text.trim();
+    let (date, time) = text.split_once(['T', ' '])?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+
+    // Split the UTC offset off the time of day
+    let (clock, offset_secs) = if let Some(clock) = time.strip_suffix('Z') {
+        (clock, 0)
+    } else if let Some(pos) = time.rfind(['+', '-']) {
+        let (clock, offset) = time.split_at(pos);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+        let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+        (clock, sign * offset)
+    } else {
+        (time, 0)
+    };
+
+    let clock = clock.split('.').next()?;
+    let mut clock_parts = clock.splitn(3, ':');
+    let hour: i64 = clock_parts.next()?.parse().ok()?;
+    let minute: i64 = clock_parts.next()?.parse().ok()?;
+    let second: i64 = clock_parts.next().unwrap_or("0").parse().ok()?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+        - offset_secs;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Days since 1970-01-01 (Howard Hinnant's `days_from_civil`).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of a day count since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`), the inverse of `days_from_civil`.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// RFC 3339 timestamp in UTC, to the second: `2026-10-16T12:03:45Z`.
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let seconds = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Short relative time: `just now`, `5m ago`, `2h ago`, `3d ago`.
+pub fn format_ago(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86_400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Short countdown: `now`, `in 5m`, `in 2h 15m`, `in 3d 4h`. Times in
+/// the past count as now.
+pub fn format_until(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::now())
+        .unwrap_or_default()
+        .as_secs();
+
+    match secs {
+        0..60 => "now".to_string(),
+        60..3600 => format!("in {}m", secs / 60),
+        3600..86_400 => format!("in {}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("in {}d {}h", secs / 86_400, secs % 86_400 / 3600),
+    }
+}
+
+/// UTC offset in seconds of a timestamp such as `2026-10-16T00:00:00+02:00`.
+pub fn offset_of(text: &str) -> Option<i64> {
+    let (_, time) = text.trim().split_once(['T', ' '])?;
+    if time.ends_with('Z') {
+        return Some(0);
+    }
+    let pos = time.rfind(['+', '-'])?;
+    let (hours, minutes) = time[pos + 1..]
+        .split_once(':')
+        .unwrap_or((&time[pos + 1..], "0"));
+    let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+    Some(if time[pos..].starts_with('-') {
+        -offset
+    } else {
+        offset
+    })
+}
+
+/// Like `parse`, but times without a UTC offset (as some integrations
+/// report them) are taken to be `offset_secs` ahead of UTC.
+pub fn parse_local(text: &str, offset_secs: i64) -> Option<SystemTime> {
+    let time = parse(text)?;
+    if offset_of(text).is_some() {
+        return Some(time);
+    }
+    let shift = Duration::from_secs(offset_secs.unsigned_abs());
+    if offset_secs >= 0 {
+        time.checked_sub(shift)
+    } else {
+        time.checked_add(shift)
+    }
+}
+
+/// Time of day as `HH:MM` at `offset_secs` from UTC.
+pub fn format_clock(time: SystemTime, offset_secs: i64) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        + offset_secs;
+    let minutes = secs.rem_euclid(86_400) / 60;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,142 @@
+//! Weekly school timetables from calendar entities. Each timed event of
+//! the week is a lesson, placed in its weekday's column on the row of its
+//! start time; all-day events (a school trip, a holiday) stand for their
+//! whole day. With the weekend hidden, Saturday and Sunday show the
+//! coming week.
+
+use crate::timestamps;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// A week of lessons, Monday first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Week {
+    /// Days shown: 5 (Monday to Friday) or 7
+    pub days: usize,
+    /// Column of today, if it is in the week shown
+    pub today: Option<usize>,
+    /// Day number (since 1970-01-01) of the week's Monday
+    pub monday: i64,
+    /// Lessons by start time (minutes after midnight), one per day
+    pub slots: BTreeMap<u32, Vec<Option<String>>>,
+    /// Summary of the all-day events of each day
+    pub all_day: Vec<Option<String>>,
+}
+
+impl Week {
+    /// The week containing `today`, a day number since 1970-01-01 in Home
+    /// Assistant's time zone, or the next one when `today` falls on a day
+    /// not shown.
+    pub fn new(today: i64, days: usize) -> Self {
+        let weekday = today.rem_euclid(7) as usize;
+        // 1970-01-01 was a Thursday
+        let weekday = (weekday + 3) % 7;
+        let monday = today - weekday as i64;
+        let (monday, today) = if weekday < days {
+            (monday, Some(weekday))
+        } else {
+            (monday + 7, None)
+        };
+        Self {
+            days,
+            today,
+            monday,
+            slots: BTreeMap::new(),
+            all_day: vec![None; days],
+        }
+    }
+
+    /// Start and end to ask the calendar API for, in UTC. Widened by the
+    /// largest UTC offsets so no time zone misses the week's edges; events
+    /// beyond them are dropped when placed.
+    pub fn range(&self) -> (String, String) {
+        const MARGIN: u64 = 14 * 3600;
+        let day = |n: i64| UNIX_EPOCH + Duration::from_secs(n.max(0) as u64 * 86_400);
+        (
+            timestamps::format_utc(day(self.monday) - Duration::from_secs(MARGIN)),
+            timestamps::format_utc(
+                day(self.monday + self.days as i64) + Duration::from_secs(MARGIN),
+            ),
+        )
+    }
+
+    /// Column of a day number, if the week shows it.
+    fn column(&self, day: i64) -> Option<usize> {
+        let column = day - self.monday;
+        (0..self.days as i64)
+            .contains(&column)
+            .then_some(column as usize)
+    }
+
+    /// Place the events Home Assistant's calendar API returned; ones
+    /// outside the week are ignored. Events sharing a day and start time
+    /// (from two calendars) are joined with `/`.
+    pub fn add_events(&mut self, events: &Value) {
+        for event in events.as_array().into_iter().flatten() {
+            let Some(summary) = event.get("summary").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let summary = summary.trim();
+            let Some(start) = event.get("start") else {
+                continue;
+            };
+
+            let date = |value: Option<&Value>| {
+                let text = value?.get("date")?.as_str()?;
+                local_day(&format!("{}T00:00:00Z", text))
+            };
+            if let Some(first) = date(Some(start)) {
+                // All day, until the end date (exclusive) for ones spanning days
+                let end = date(event.get("end")).unwrap_or(first + 1).max(first + 1);
+                for day in first..end {
+                    if let Some(column) = self.column(day) {
+                        append(&mut self.all_day[column], summary);
+                    }
+                }
+                continue;
+            }
+
+            let Some(minutes) = start
+                .get("dateTime")
+                .and_then(|v| v.as_str())
+                .and_then(local_minutes)
+            else {
+                continue;
+            };
+            let Some(column) = self.column(minutes.div_euclid(1440)) else {
+                continue;
+            };
+            let days = self.days;
+            let slot = self
+                .slots
+                .entry(minutes.rem_euclid(1440) as u32)
+                .or_insert_with(|| vec![None; days]);
+            append(&mut slot[column], summary);
+        }
+    }
+}
+
+/// Fill a cell, after what's already in it.
+fn append(cell: &mut Option<String>, summary: &str) {
+    *cell = Some(match cell.take() {
+        Some(existing) => format!("{} / {}", existing, summary),
+        None => summary.to_string(),
+    });
+}
+
+/// Day number of a Home Assistant timestamp in its own time zone, the
+/// date it shows.
+pub fn local_day(text: &str) -> Option<i64> {
+    local_minutes(text).map(|minutes| minutes.div_euclid(1440))
+}
+
+/// Minutes since 1970-01-01 of the date and time a timestamp shows, its
+/// UTC offset applied.
+fn local_minutes(text: &str) -> Option<i64> {
+    let time = timestamps::parse(text)?;
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some((secs + timestamps::offset_of(text).unwrap_or(0)).div_euclid(60))
+}
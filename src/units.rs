@@ -0,0 +1,238 @@
+//! Weather unit conversion (`?units=`): wind speed between m/s, km/h, mph
+//! and knots, precipitation between mm and inches, and pressure between hPa
+//! and inHg. A preset converts every sensor of those device classes (and
+//! weather entities' wind, pressure and precipitation), and
+//! `entity_id=unit` entries override single sensors, e.g.
+//! `?units=imperial,sensor.harbour_wind=kn`.
+
+use std::collections::HashMap;
+
+/// Attribute the decimals of a converted reading are added to its state under
+pub const DECIMALS_ATTRIBUTE: &str = "converted_decimals";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Quantity {
+    Speed,
+    Precipitation,
+    PrecipitationRate,
+    Pressure,
+}
+
+/// Unit, what it measures, its size in the quantity's first unit, and the
+/// decimals a converted reading is shown with.
+const UNITS: &[(&str, Quantity, f64, usize)] = &[
+    ("m/s", Quantity::Speed, 1.0, 1),
+    ("km/h", Quantity::Speed, 1.0 / 3.6, 0),
+    ("mph", Quantity::Speed, 0.447_04, 0),
+    ("kn", Quantity::Speed, 1852.0 / 3600.0, 0),
+    ("ft/s", Quantity::Speed, 0.3048, 0),
+    ("mm", Quantity::Precipitation, 1.0, 1),
+    ("cm", Quantity::Precipitation, 10.0, 2),
+    ("in", Quantity::Precipitation, 25.4, 2),
+    ("mm/h", Quantity::PrecipitationRate, 1.0, 1),
+    ("in/h", Quantity::PrecipitationRate, 25.4, 2),
+    ("hPa", Quantity::Pressure, 1.0, 0),
+    ("mbar", Quantity::Pressure, 1.0, 0),
+    ("kPa", Quantity::Pressure, 10.0, 1),
+    ("inHg", Quantity::Pressure, 33.863_886, 2),
+    ("mmHg", Quantity::Pressure, 1.333_224, 0),
+    ("psi", Quantity::Pressure, 68.947_573, 1),
+];
+
+/// Units of each preset, one per quantity in `Quantity` order
+const PRESETS: &[(&str, [&str; 4])] = &[
+    ("metric", ["km/h", "mm", "mm/h", "hPa"]),
+    ("imperial", ["mph", "in", "in/h", "inHg"]),
+    ("marine", ["kn", "mm", "mm/h", "hPa"]),
+];
+
+/// Weather entity attributes converted, with the attribute naming their
+/// unit and the device class a sensor of the same reading has
+const WEATHER_ATTRIBUTES: &[(&str, &str, &str)] = &[
+    ("wind_speed", "wind_speed_unit", "wind_speed"),
+    ("wind_gust_speed", "wind_speed_unit", "wind_speed"),
+    ("pressure", "pressure_unit", "atmospheric_pressure"),
+    ("precipitation", "precipitation_unit", "precipitation"),
+];
+
+fn lookup(unit: &str) -> Option<(&'static str, Quantity, f64, usize)> {
+    let unit = match unit.trim() {
+        "kt" | "kts" | "knots" => "kn",
+        "mbar" | "mb" => "mbar",
+        "in Hg" => "inHg",
+        unit => unit,
+    };
+    UNITS
+        .iter()
+        .find(|(name, ..)| name.eq_ignore_ascii_case(unit))
+        .copied()
+}
+
+/// Quantity a sensor's device class puts it under for a preset
+fn preset_quantity(device_class: &str) -> Option<Quantity> {
+    match device_class {
+        "wind_speed" | "speed" => Some(Quantity::Speed),
+        "precipitation" => Some(Quantity::Precipitation),
+        "precipitation_intensity" => Some(Quantity::PrecipitationRate),
+        "pressure" | "atmospheric_pressure" => Some(Quantity::Pressure),
+        _ => None,
+    }
+}
+
+/// `value` in `from` as `to`; `None` unless both are known units of the
+/// same quantity.
+pub fn convert(value: f64, from: &str, to: &str) -> Option<f64> {
+    let (_, from_quantity, from_factor, _) = lookup(from)?;
+    let (_, to_quantity, to_factor, _) = lookup(to)?;
+    (from_quantity == to_quantity).then(|| value * from_factor / to_factor)
+}
+
+/// Decimals a reading converted to `unit` is shown with.
+pub fn decimals(unit: &str) -> usize {
+    lookup(unit).map_or(1, |(.., decimals)| decimals)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Units {
+    /// Units of the preset, one per quantity
+    preset: Option<[&'static str; 4]>,
+    /// Units single entities are shown in; a weather entity can have one
+    /// per quantity
+    overrides: HashMap<String, Vec<&'static str>>,
+}
+
+impl Units {
+    /// `?units=`: a preset name and/or comma-separated `entity_id=unit`
+    /// overrides.
+    pub fn parse(spec: Option<&str>) -> Result<Self, String> {
+        let mut units = Self::default();
+
+        for entry in spec
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            match entry.split_once('=') {
+                Some((entity_id, unit)) => {
+                    let entity_id = entity_id.trim();
+                    if !entity_id.contains('.') {
+                        return Err(format!("Invalid units override '{}'", entry));
+                    }
+                    let (unit, ..) = lookup(unit).ok_or_else(|| {
+                        format!("Unknown unit '{}' for {}", unit.trim(), entity_id)
+                    })?;
+                    units
+                        .overrides
+                        .entry(entity_id.to_string())
+                        .or_default()
+                        .push(unit);
+                }
+                None => {
+                    if units.preset.is_some() {
+                        return Err("Only one units preset can be given".to_string());
+                    }
+                    let (_, preset) = PRESETS
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(entry))
+                        .ok_or_else(|| {
+                            format!("Unknown units '{}'. Use metric, imperial or marine", entry)
+                        })?;
+                    units.preset = Some(*preset);
+                }
+            }
+        }
+
+        Ok(units)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.preset.is_none() && self.overrides.is_empty()
+    }
+
+    /// Unit a reading of `entity_id` in `unit` is shown in, when it changes:
+    /// the entity's override for the unit's quantity, else the preset's
+    /// when `device_class` is one it covers. Without a device class, speeds,
+    /// pressures and rates are converted, but not millimetres and inches,
+    /// which may as well be distances.
+    pub fn target(
+        &self,
+        entity_id: &str,
+        unit: &str,
+        device_class: Option<&str>,
+    ) -> Option<&'static str> {
+        let (from, quantity, ..) = lookup(unit)?;
+        let overridden = self.overrides.get(entity_id).and_then(|units| {
+            units
+                .iter()
+                .copied()
+                .find(|target| lookup(target).is_some_and(|(_, q, ..)| q == quantity))
+        });
+        let target = overridden.or_else(|| {
+            let covered = match device_class {
+                Some(class) => preset_quantity(class) == Some(quantity),
+                None => quantity != Quantity::Precipitation,
+            };
+            let preset = self.preset?;
+            covered.then_some(preset[quantity as usize])
+        })?;
+        (target != from).then_some(target)
+    }
+
+    /// Convert a weather entity's wind, pressure and precipitation, and
+    /// those of its `forecast` entries.
+    pub fn convert_weather(&self, entity_id: &str, attributes: &mut serde_json::Value) {
+        let Some(attributes) = attributes.as_object_mut() else {
+            return;
+        };
+
+        for (value_key, unit_key, device_class) in WEATHER_ATTRIBUTES {
+            let Some(unit) = attributes.get(*unit_key).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let Some(target) = self.target(entity_id, unit, Some(device_class)) else {
+                continue;
+            };
+            let unit = unit.to_string();
+
+            let mut convert_entry = |entry: &mut serde_json::Value| {
+                if let Some(value) = entry.get(*value_key).and_then(|v| v.as_f64())
+                    && let Some(converted) = convert(value, &unit, target)
+                {
+                    entry[*value_key] = rounded(converted, target);
+                }
+            };
+            if let Some(forecast) = attributes
+                .get_mut("forecast")
+                .and_then(|f| f.as_array_mut())
+            {
+                forecast.iter_mut().for_each(&mut convert_entry);
+            }
+            if let Some(value) = attributes.get(*value_key).and_then(|v| v.as_f64())
+                && let Some(converted) = convert(value, &unit, target)
+            {
+                attributes.insert(value_key.to_string(), rounded(converted, target));
+            }
+        }
+
+        // Units last, as wind and gusts share one
+        for (_, unit_key, device_class) in WEATHER_ATTRIBUTES {
+            if let Some(unit) = attributes.get(*unit_key).and_then(|v| v.as_str())
+                && let Some(target) = self.target(entity_id, unit, Some(device_class))
+            {
+                attributes.insert(unit_key.to_string(), target.into());
+            }
+        }
+    }
+}
+
+/// `value` to the decimals of `unit`, whole units as integers.
+fn rounded(value: f64, unit: &str) -> serde_json::Value {
+    match decimals(unit) {
+        0 => (value.round() as i64).into(),
+        decimals => {
+            let scale = 10f64.powi(decimals as i32);
+            ((value * scale).round() / scale).into()
+        }
+    }
+}
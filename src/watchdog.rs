@@ -0,0 +1,278 @@
+//! Render watchdog: every screen composed for a request is checked before
+//! it's encoded for signs of a broken render - nothing drawn at all, a
+//! light screen almost entirely black, or not a single glyph of text - so a
+//! font or layout regression shows up in the log (and `/admin/watchdog`)
+//! rather than on the wall. `RENDER_WATCHDOG` is `log` (the default),
+//! `fallback` to also serve the last good render of the same URL in place
+//! of a suspicious one, or `off`.
+
+use crate::timestamps;
+use axum::http::HeaderMap;
+use image::{GrayImage, Rgb, RgbImage};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::SystemTime,
+};
+
+/// Share of black pixels above which a light screen is suspicious
+const MAX_BLACK: f64 = 0.95;
+/// Good renders kept to fall back on, one per URL and content type
+const MAX_FRAMES: usize = 64;
+/// Anomalies listed by `/admin/watchdog`
+const MAX_RECENT: usize = 20;
+
+tokio::task_local! {
+    /// What the request being watched has drawn so far
+    static WATCH: Arc<Watch>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Off,
+    Log,
+    Fallback,
+}
+
+impl Mode {
+    pub fn parse(name: Option<&str>) -> Result<Self, String> {
+        match name.map(|name| name.trim().to_lowercase()).as_deref() {
+            None | Some("log") => Ok(Self::Log),
+            Some("fallback") => Ok(Self::Fallback),
+            Some("off") => Ok(Self::Off),
+            Some(_) => Err(format!(
+                "Unknown RENDER_WATCHDOG '{}'. Use log, fallback or off",
+                name.unwrap_or_default()
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Log => "log",
+            Self::Fallback => "fallback",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anomaly {
+    /// Every pixel the same
+    Blank,
+    /// More than `MAX_BLACK` of a light screen black
+    MostlyBlack,
+    /// Not one glyph with any ink drawn
+    NoText,
+}
+
+impl Anomaly {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Blank => "blank",
+            Self::MostlyBlack => "mostly black",
+            Self::NoText => "no text",
+        }
+    }
+}
+
+/// How a watched request's renders turned out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing was composed, e.g. a cached render or a proxied photo
+    Untouched,
+    Good,
+    Suspicious(Anomaly),
+}
+
+#[derive(Default)]
+struct Watch {
+    /// Glyphs with ink drawn since the last screen was checked
+    glyphs: AtomicUsize,
+    /// Set by a screen that has no text by design
+    textless: AtomicBool,
+    composed: AtomicBool,
+    anomaly: Mutex<Option<Anomaly>>,
+}
+
+/// Run a request's handler, noting what its renders draw.
+pub async fn watch<F: Future>(future: F) -> (F::Output, Outcome) {
+    let watch = Arc::new(Watch::default());
+    let output = WATCH.scope(watch.clone(), future).await;
+
+    let outcome = match *watch.anomaly.lock().unwrap() {
+        Some(anomaly) => Outcome::Suspicious(anomaly),
+        None if watch.composed.load(Ordering::Relaxed) => Outcome::Good,
+        None => Outcome::Untouched,
+    };
+    (output, outcome)
+}
+
+/// Count a glyph drawn by the bitmap font.
+pub fn glyph_drawn() {
+    let _ = WATCH.try_with(|watch| watch.glyphs.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Mark the screen being drawn as having no text on purpose, such as the
+/// sleep frame.
+pub fn textless() {
+    let _ = WATCH.try_with(|watch| watch.textless.store(true, Ordering::Relaxed));
+}
+
+/// Check a composed TRMNL screen, drawn black on white.
+pub fn inspect_gray(image: &GrayImage) {
+    inspect(image.pixels().map(|pixel| pixel[0]), true);
+}
+
+/// Check a composed color render on `background`.
+pub fn inspect_rgb(image: &RgbImage, background: Rgb<u8>) {
+    let luma = |Rgb([r, g, b]): Rgb<u8>| {
+        ((u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000) as u8
+    };
+    inspect(
+        image.pixels().map(|&pixel| luma(pixel)),
+        luma(background) >= 128,
+    );
+}
+
+fn inspect(mut lumas: impl Iterator<Item = u8>, light: bool) {
+    let _ = WATCH.try_with(|watch| {
+        let glyphs = watch.glyphs.swap(0, Ordering::Relaxed);
+        let textless = watch.textless.swap(false, Ordering::Relaxed);
+        watch.composed.store(true, Ordering::Relaxed);
+
+        let Some(first) = lumas.next() else {
+            return;
+        };
+        let (mut total, mut black, mut uniform) = (1usize, usize::from(first < 64), true);
+        for luma in lumas {
+            total += 1;
+            black += usize::from(luma < 64);
+            uniform &= luma == first;
+        }
+
+        let anomaly = if uniform {
+            Some(Anomaly::Blank)
+        } else if light && black as f64 > total as f64 * MAX_BLACK {
+            Some(Anomaly::MostlyBlack)
+        } else if glyphs == 0 && !textless {
+            Some(Anomaly::NoText)
+        } else {
+            None
+        };
+        let mut recorded = watch.anomaly.lock().unwrap();
+        if recorded.is_none() {
+            *recorded = anomaly;
+        }
+    });
+}
+
+#[derive(Clone, Serialize)]
+struct Incident {
+    path: String,
+    anomaly: Anomaly,
+    at: String,
+    served_previous: bool,
+}
+
+struct Frame {
+    kept_at: SystemTime,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+}
+
+#[derive(Clone)]
+pub struct Watchdog {
+    mode: Mode,
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Last good render per URL and content type
+    frames: HashMap<String, Frame>,
+    counts: HashMap<Anomaly, u64>,
+    recent: VecDeque<Incident>,
+}
+
+impl Watchdog {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            inner: Arc::new(Mutex::new(Inner::default())),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Keep a good render of `path` to fall back on, replacing the oldest
+    /// when `MAX_FRAMES` are kept.
+    pub fn keep(&self, path: &str, content_type: &str, headers: HeaderMap, body: bytes::Bytes) {
+        let mut inner = self.inner.lock().unwrap();
+        let key = format!("{} {}", path, content_type);
+        if inner.frames.len() >= MAX_FRAMES
+            && !inner.frames.contains_key(&key)
+            && let Some(oldest) = inner
+                .frames
+                .iter()
+                .min_by_key(|(_, frame)| frame.kept_at)
+                .map(|(key, _)| key.clone())
+        {
+            inner.frames.remove(&oldest);
+        }
+        inner.frames.insert(
+            key,
+            Frame {
+                kept_at: SystemTime::now(),
+                headers,
+                body,
+            },
+        );
+    }
+
+    /// The last good render of `path` in `content_type`.
+    pub fn previous(&self, path: &str, content_type: &str) -> Option<(HeaderMap, bytes::Bytes)> {
+        let inner = self.inner.lock().unwrap();
+        let frame = inner.frames.get(&format!("{} {}", path, content_type))?;
+        Some((frame.headers.clone(), frame.body.clone()))
+    }
+
+    pub fn record(&self, path: &str, anomaly: Anomaly, served_previous: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.counts.entry(anomaly).or_default() += 1;
+        if inner.recent.len() >= MAX_RECENT {
+            inner.recent.pop_front();
+        }
+        inner.recent.push_back(Incident {
+            path: path.to_string(),
+            anomaly,
+            at: timestamps::format_utc(SystemTime::now()),
+            served_previous,
+        });
+    }
+
+    /// `{"mode", "anomalies", "recent"}`: counts per anomaly since startup
+    /// and the latest incidents, newest first.
+    pub fn report(&self) -> serde_json::Value {
+        let inner = self.inner.lock().unwrap();
+        let count = |anomaly| inner.counts.get(&anomaly).copied().unwrap_or(0);
+        let recent: Vec<&Incident> = inner.recent.iter().rev().collect();
+        json!({
+            "mode": self.mode.name(),
+            "anomalies": {
+                "blank": count(Anomaly::Blank),
+                "mostly_black": count(Anomaly::MostlyBlack),
+                "no_text": count(Anomaly::NoText),
+            },
+            "recent": recent,
+        })
+    }
+}
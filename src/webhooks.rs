@@ -0,0 +1,76 @@
+//! Scheduled pushes to TRMNL private plugin webhooks, for TRMNL cloud
+//! devices that can't poll the LAN. `TRMNL_WEBHOOKS` names a dashboard and
+//! the webhook URL it is pushed to, every `TRMNL_WEBHOOK_INTERVAL` seconds,
+//! e.g.
+//! `TRMNL_WEBHOOKS=power=https://usetrmnl.com/api/custom_plugins/<uuid>;office:merge=https://...`.
+//!
+//! A dashboard is pushed as its rendered screen (`image`, the default: a
+//! data URI the plugin markup shows with `<img src="{{ image }}">`) or as
+//! the values of its summary (`merge`) for markup of the plugin's own.
+
+use crate::dashboards::Dashboard;
+
+/// What a webhook is sent as its `merge_variables`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Payload {
+    /// `image`, `rendered_at`, `width` and `height` of the render
+    Image,
+    /// `name`, `summary` and `entities` of `/dashboards/{name}/summary`
+    Merge,
+}
+
+impl Payload {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "image" => Some(Self::Image),
+            "merge" => Some(Self::Merge),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    pub dashboard: String,
+    pub url: String,
+    pub payload: Payload,
+}
+
+/// `TRMNL_WEBHOOKS`: `dashboard[:image|merge]=url` entries separated by
+/// `;`, naming dashboards in `dashboards`. The offending entry is returned
+/// on error.
+pub fn parse(spec: &str, dashboards: &[Dashboard]) -> Result<Vec<Webhook>, String> {
+    let mut webhooks = Vec::new();
+
+    for entry in spec.split(';').filter(|e| !e.trim().is_empty()) {
+        let (target, url) = entry.split_once('=').ok_or_else(|| entry.to_string())?;
+        let (name, payload) = match target.split_once(':') {
+            Some((name, payload)) => (
+                name.trim(),
+                Payload::parse(payload).ok_or_else(|| entry.to_string())?,
+            ),
+            None => (target.trim(), Payload::Image),
+        };
+        let url = url.trim();
+
+        if !dashboards.iter().any(|d| d.name == name)
+            || reqwest::Url::parse(url)
+                .map_or(true, |url| !matches!(url.scheme(), "http" | "https"))
+        {
+            return Err(entry.to_string());
+        }
+
+        webhooks.push(Webhook {
+            dashboard: name.to_string(),
+            url: url.to_string(),
+            payload,
+        });
+    }
+
+    Ok(webhooks)
+}
+
+/// Request body of a push.
+pub fn body(merge_variables: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({ "merge_variables": merge_variables })
+}
@@ -0,0 +1,582 @@
+//! Home Assistant WebSocket API client. A single connection is shared by
+//! every command: each request is tagged with an `id` and matched with its
+//! `result`, so concurrent renders don't each open a socket. The connection
+//! is opened on first use and reopened with backoff whenever it drops or
+//! goes quiet (no answer to pings, or several commands in a row timing
+//! out); event subscriptions are renewed on every new connection, and the count of
+//! connections tells subscribers when events may have been missed.
+
+use crate::proxy_auth::ProxyHeaders;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU8, AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    net::TcpStream,
+    sync::{mpsc, oneshot, watch},
+    time::Instant,
+};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream,
+    tungstenite::{
+        Message,
+        client::IntoClientRequest,
+        handshake::client::Request as ClientRequest,
+        http::{HeaderName, HeaderValue},
+        protocol::WebSocketConfig,
+    },
+};
+use tracing::{info, warn};
+
+/// How long a command may wait for its result
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How often a connection is checked with a ping
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+/// Silence, pongs included, after which a connection is taken for dead, e.g.
+/// one a router dropped without telling either end
+const SILENCE_LIMIT: Duration = Duration::from_secs(45);
+/// Commands timing out in a row before the connection is reopened
+const MAX_TIMEOUTS: u32 = 3;
+/// Largest message accepted, e.g. `get_states` of a big installation
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Connection state shared with callers, so commands fail fast while the
+/// socket is down instead of each waiting out the timeout
+const IDLE: u8 = 0;
+const CONNECTED: u8 = 1;
+const DOWN: u8 = 2;
+
+struct Request {
+    message: Value,
+    reply: Reply,
+}
+
+enum Reply {
+    /// The command's `result`
+    Result(oneshot::Sender<anyhow::Result<Value>>),
+    /// The first event of a subscription (e.g. `render_template`), which
+    /// is then ended
+    FirstEvent(oneshot::Sender<anyhow::Result<Value>>),
+    /// Every event of a subscription, for as long as the receiver is kept
+    Events(mpsc::UnboundedSender<Value>),
+}
+
+impl Reply {
+    fn is_closed(&self) -> bool {
+        match self {
+            Reply::Result(sender) | Reply::FirstEvent(sender) => sender.is_closed(),
+            Reply::Events(sender) => sender.is_closed(),
+        }
+    }
+
+    fn fail(self, error: anyhow::Error) {
+        match self {
+            Reply::Result(sender) | Reply::FirstEvent(sender) => {
+                let _ = sender.send(Err(error));
+            }
+            Reply::Events(_) => {}
+        }
+    }
+}
+
+/// A subscription kept across reconnects
+struct Subscription {
+    message: Value,
+    events: mpsc::UnboundedSender<Value>,
+}
+
+/// How the connection is doing, shared by callers and the connection task
+struct Health {
+    state: AtomicU8,
+    /// Commands that timed out since the last one answered
+    timeouts: AtomicU32,
+    /// When the connection last received anything, pongs included
+    heard: Mutex<Instant>,
+}
+
+impl Health {
+    fn timed_out(&self) -> Option<anyhow::Error> {
+        (self.timeouts.load(Ordering::Relaxed) >= MAX_TIMEOUTS)
+            .then(|| anyhow::anyhow!("{} commands in a row timed out", MAX_TIMEOUTS))
+    }
+}
+
+#[derive(Clone)]
+pub struct HaSocket {
+    requests: mpsc::UnboundedSender<Request>,
+    health: Arc<Health>,
+    /// Connections opened so far, bumped once each has renewed its
+    /// subscriptions
+    connections: watch::Receiver<u64>,
+}
+
+impl HaSocket {
+    /// Socket for the Home Assistant at `base_url` (`http://` or `https://`),
+    /// sending the current `headers` with each upgrade request. Nothing
+    /// connects until the first command.
+    pub fn new(base_url: &str, token: &str, headers: ProxyHeaders) -> Self {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let health = Arc::new(Health {
+            state: AtomicU8::new(IDLE),
+            timeouts: AtomicU32::new(0),
+            heard: Mutex::new(Instant::now()),
+        });
+        let (opened, connections) = watch::channel(0);
+        tokio::spawn(run(
+            base_url.to_string(),
+            token.to_string(),
+            headers,
+            receiver,
+            health.clone(),
+            opened,
+        ));
+        Self {
+            requests,
+            health,
+            connections,
+        }
+    }
+
+    /// Whether the socket is up right now.
+    pub fn is_connected(&self) -> bool {
+        self.health.state.load(Ordering::Relaxed) == CONNECTED
+    }
+
+    /// Whether the socket is up and has shown it's alive within
+    /// `SILENCE_LIMIT`, which the pings see to on an idle connection.
+    pub fn is_alive(&self) -> bool {
+        self.is_connected() && self.health.heard.lock().unwrap().elapsed() <= SILENCE_LIMIT
+    }
+
+    /// Count of connections opened so far, changing on each reconnect, for
+    /// state that has to be fetched again after events may have been missed.
+    pub fn connections(&self) -> watch::Receiver<u64> {
+        self.connections.clone()
+    }
+
+    /// Send a command such as `get_states` and return its result.
+    pub async fn command(&self, message: Value) -> anyhow::Result<Value> {
+        self.request(message, false).await
+    }
+
+    /// Render a template, returning its output as text (Home Assistant
+    /// reports lists and numbers as JSON values).
+    pub async fn render_template(
+        &self,
+        template: &str,
+        variables: Value,
+    ) -> anyhow::Result<String> {
+        let event = self
+            .request(
+                json!({
+                    "type": "render_template",
+                    "template": template,
+                    "variables": variables,
+                    "report_errors": true,
+                }),
+                true,
+            )
+            .await?;
+
+        if let Some(error) = event.get("error") {
+            return Err(anyhow::anyhow!("Template error: {}", error));
+        }
+        match event.get("result") {
+            Some(Value::String(text)) => Ok(text.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => Err(anyhow::anyhow!("Template event without a result")),
+        }
+    }
+
+    /// Events of a subscription command such as `subscribe_entities`, until
+    /// the receiver is dropped. The command is sent again after every
+    /// reconnect, so events missed while the socket was down are lost.
+    pub fn subscribe(&self, message: Value) -> mpsc::UnboundedReceiver<Value> {
+        let (events, receiver) = mpsc::unbounded_channel();
+        // The task only stops once every `HaSocket` is gone
+        let _ = self.requests.send(Request {
+            message,
+            reply: Reply::Events(events),
+        });
+        receiver
+    }
+
+    async fn request(&self, message: Value, first_event: bool) -> anyhow::Result<Value> {
+        if self.health.state.load(Ordering::Relaxed) == DOWN {
+            return Err(anyhow::anyhow!("Home Assistant WebSocket is not connected"));
+        }
+
+        let (reply, response) = oneshot::channel();
+        let reply = if first_event {
+            Reply::FirstEvent(reply)
+        } else {
+            Reply::Result(reply)
+        };
+        self.requests
+            .send(Request { message, reply })
+            .map_err(|_| anyhow::anyhow!("Home Assistant WebSocket task stopped"))?;
+
+        match tokio::time::timeout(COMMAND_TIMEOUT, response).await {
+            Ok(Ok(result)) => {
+                self.health.timeouts.store(0, Ordering::Relaxed);
+                result
+            }
+            Ok(Err(_)) => Err(anyhow::anyhow!("Home Assistant WebSocket closed")),
+            Err(_) => {
+                self.health.timeouts.fetch_add(1, Ordering::Relaxed);
+                Err(anyhow::anyhow!(
+                    "Home Assistant WebSocket command timed out"
+                ))
+            }
+        }
+    }
+}
+
+/// Keep a connection up for as long as there are requests, reconnecting
+/// with exponential backoff.
+async fn run(
+    base_url: String,
+    token: String,
+    headers: ProxyHeaders,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    health: Arc<Health>,
+    opened: watch::Sender<u64>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    let mut subscriptions: Vec<Subscription> = Vec::new();
+    // Wait for the first request before connecting
+    let mut waiting = requests.recv().await;
+    if waiting.is_none() {
+        return;
+    }
+
+    loop {
+        match connect(&base_url, &token, &headers.current()).await {
+            Ok(stream) => {
+                info!("Connected to the Home Assistant WebSocket API");
+                health.state.store(CONNECTED, Ordering::Relaxed);
+                backoff = MIN_BACKOFF;
+                let serving = serve(
+                    stream,
+                    waiting.take(),
+                    &mut requests,
+                    &mut subscriptions,
+                    &health,
+                    &opened,
+                );
+                match serving.await {
+                    Ok(()) => return,
+                    Err(e) => warn!("Home Assistant WebSocket disconnected: {}", e),
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to connect to the Home Assistant WebSocket API: {} (retrying in {}s)",
+                    e,
+                    backoff.as_secs()
+                );
+                if let Some(request) = waiting.take() {
+                    reject(request, e, &mut subscriptions);
+                }
+            }
+        }
+
+        // Requests fail fast until the next attempt
+        health.state.store(DOWN, Ordering::Relaxed);
+        fail_queued(&mut requests, &mut subscriptions);
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        fail_queued(&mut requests, &mut subscriptions);
+        health.state.store(IDLE, Ordering::Relaxed);
+    }
+}
+
+fn fail_queued(
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+    subscriptions: &mut Vec<Subscription>,
+) {
+    while let Ok(request) = requests.try_recv() {
+        reject(
+            request,
+            anyhow::anyhow!("Home Assistant WebSocket is not connected"),
+            subscriptions,
+        );
+    }
+}
+
+/// Fail a request that can't be sent; subscriptions wait for the next
+/// connection instead.
+fn reject(request: Request, error: anyhow::Error, subscriptions: &mut Vec<Subscription>) {
+    match request.reply {
+        Reply::Events(events) => subscriptions.push(Subscription {
+            message: request.message,
+            events,
+        }),
+        reply => reply.fail(error),
+    }
+}
+
+type Stream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Open the socket and authenticate, giving up on a server that doesn't
+/// get through both within `CONNECT_TIMEOUT`.
+async fn connect(
+    base_url: &str,
+    token: &str,
+    headers: &reqwest::header::HeaderMap,
+) -> anyhow::Result<Stream> {
+    let mut url = reqwest::Url::parse(base_url)?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme)
+        .map_err(|_| anyhow::anyhow!("HA_URL can't be turned into a WebSocket URL"))?;
+    let path = format!("{}/api/websocket", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+
+    let mut request = url.as_str().into_client_request()?;
+    for (name, value) in headers {
+        request.headers_mut().append(
+            HeaderName::from_bytes(name.as_str().as_bytes())?,
+            HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+    tokio::time::timeout(CONNECT_TIMEOUT, handshake(request, token))
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out connecting to {}", url))?
+}
+
+async fn handshake(request: ClientRequest, token: &str) -> anyhow::Result<Stream> {
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_MESSAGE_SIZE),
+        ..Default::default()
+    };
+    let (mut stream, _) =
+        tokio_tungstenite::connect_async_with_config(request, Some(config), false).await?;
+
+    // auth_required, auth, then auth_ok or auth_invalid
+    read_json(&mut stream).await?;
+    send(
+        &mut stream,
+        &json!({ "type": "auth", "access_token": token }),
+    )
+    .await?;
+    let reply = read_json(&mut stream).await?;
+    match reply.get("type").and_then(Value::as_str) {
+        Some("auth_ok") => Ok(stream),
+        _ => Err(anyhow::anyhow!(
+            "Authentication failed: {}",
+            reply.get("message").and_then(Value::as_str).unwrap_or("")
+        )),
+    }
+}
+
+/// Relay requests and their results until the connection drops (`Err`) or
+/// every `HaSocket` is gone (`Ok`). A connection that stays silent past
+/// `SILENCE_LIMIT` despite pings, or leaves `MAX_TIMEOUTS` commands in a row
+/// unanswered, counts as dropped.
+async fn serve(
+    mut stream: Stream,
+    first: Option<Request>,
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+    subscriptions: &mut Vec<Subscription>,
+    health: &Health,
+    opened: &watch::Sender<u64>,
+) -> anyhow::Result<()> {
+    let mut pending: HashMap<u64, Reply> = HashMap::new();
+    let mut next_id = 1u64;
+    let mut queued = first;
+    *health.heard.lock().unwrap() = Instant::now();
+    let mut ping = tokio::time::interval_at(Instant::now() + PING_INTERVAL, PING_INTERVAL);
+    health.timeouts.store(0, Ordering::Relaxed);
+
+    // Renew the subscriptions of earlier connections
+    subscriptions.retain(|subscription| !subscription.events.is_closed());
+    for subscription in subscriptions.iter() {
+        let id = next_id;
+        next_id += 1;
+        let mut message = subscription.message.clone();
+        message["id"] = id.into();
+        send(&mut stream, &message).await?;
+        pending.insert(id, Reply::Events(subscription.events.clone()));
+    }
+    opened.send_modify(|count| *count += 1);
+
+    let result = loop {
+        let request = match queued.take() {
+            Some(request) => request,
+            // A partly read message stays buffered in the stream when
+            // `select!` drops `next()`
+            None => tokio::select! {
+                request = requests.recv() => match request {
+                    Some(request) => request,
+                    None => break Ok(()),
+                },
+                message = stream.next() => {
+                    *health.heard.lock().unwrap() = Instant::now();
+                    match handle(message, &mut pending, &mut stream, &mut next_id).await {
+                        Ok(()) => continue,
+                        Err(e) => break Err(e),
+                    }
+                }
+                _ = ping.tick() => {
+                    let silence = health.heard.lock().unwrap().elapsed();
+                    if silence > SILENCE_LIMIT {
+                        break Err(anyhow::anyhow!("nothing heard for {}s", silence.as_secs()));
+                    }
+                    if let Some(e) = health.timed_out() {
+                        break Err(e);
+                    }
+                    match stream.send(Message::Ping(Vec::new())).await {
+                        Ok(()) => continue,
+                        Err(e) => break Err(e.into()),
+                    }
+                }
+            },
+        };
+
+        // The caller gave up waiting
+        if request.reply.is_closed() {
+            continue;
+        }
+
+        // Reconnect rather than have this one wait out its timeout too
+        if let Some(e) = health.timed_out() {
+            reject(request, anyhow::anyhow!("{}", e), subscriptions);
+            break Err(e);
+        }
+
+        if let Reply::Events(events) = &request.reply {
+            subscriptions.push(Subscription {
+                message: request.message.clone(),
+                events: events.clone(),
+            });
+        }
+
+        let id = next_id;
+        next_id += 1;
+        let mut message = request.message;
+        message["id"] = id.into();
+        if let Err(e) = send(&mut stream, &message).await {
+            request.reply.fail(anyhow::anyhow!("{}", e));
+            break Err(e);
+        }
+        pending.insert(id, request.reply);
+    };
+
+    for (_, waiting) in pending {
+        waiting.fail(anyhow::anyhow!("Home Assistant WebSocket disconnected"));
+    }
+    result
+}
+
+/// Route results and events to their requests; pings are answered by
+/// tungstenite itself.
+async fn handle(
+    message: Option<Result<Message, tokio_tungstenite::tungstenite::Error>>,
+    pending: &mut HashMap<u64, Reply>,
+    stream: &mut Stream,
+    next_id: &mut u64,
+) -> anyhow::Result<()> {
+    match message {
+        Some(Ok(Message::Text(text))) => {
+            if let Some(subscription) = dispatch(&text, pending) {
+                let message = json!({
+                    "id": *next_id,
+                    "type": "unsubscribe_events",
+                    "subscription": subscription,
+                });
+                *next_id += 1;
+                send(stream, &message).await?;
+            }
+        }
+        Some(Ok(Message::Close(_))) | None => {
+            return Err(anyhow::anyhow!("closed by Home Assistant"));
+        }
+        Some(Ok(_)) => {}
+        Some(Err(e)) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// Hand a message to the request it answers. Returns the subscription to
+/// end once a first-event request has its event, or nobody listens to its
+/// events any more.
+fn dispatch(text: &str, pending: &mut HashMap<u64, Reply>) -> Option<u64> {
+    let message: Value = serde_json::from_str(text).ok()?;
+    let id = message.get("id")?.as_u64()?;
+
+    match message.get("type")?.as_str()? {
+        "result" => {
+            let success = message.get("success").and_then(Value::as_bool) == Some(true);
+            if success && !matches!(pending.get(&id)?, Reply::Result(_)) {
+                return None;
+            }
+            let result = if success {
+                Ok(message.get("result").cloned().unwrap_or(Value::Null))
+            } else {
+                let error = &message["error"];
+                Err(anyhow::anyhow!(
+                    "{}: {}",
+                    error["code"].as_str().unwrap_or("error"),
+                    error["message"].as_str().unwrap_or("command failed")
+                ))
+            };
+            match pending.remove(&id)? {
+                Reply::Result(sender) | Reply::FirstEvent(sender) => {
+                    let _ = sender.send(result);
+                }
+                Reply::Events(_) => {
+                    if let Err(e) = result {
+                        warn!("Home Assistant refused a subscription: {}", e);
+                    }
+                }
+            }
+            None
+        }
+        "event" => {
+            let event = message.get("event").cloned().unwrap_or(Value::Null);
+            match pending.get(&id)? {
+                Reply::Events(events) => {
+                    if events.send(event).is_ok() {
+                        return None;
+                    }
+                    pending.remove(&id);
+                }
+                Reply::FirstEvent(_) => {
+                    if let Some(Reply::FirstEvent(sender)) = pending.remove(&id) {
+                        let _ = sender.send(Ok(event));
+                    }
+                }
+                Reply::Result(_) => return None,
+            }
+            Some(id)
+        }
+        _ => None,
+    }
+}
+
+async fn send(stream: &mut Stream, message: &Value) -> anyhow::Result<()> {
+    stream.send(Message::Text(message.to_string())).await?;
+    Ok(())
+}
+
+/// Next text message, skipping pings and pongs.
+async fn read_json(stream: &mut Stream) -> anyhow::Result<Value> {
+    loop {
+        match stream.next().await {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+            Some(Ok(_)) | None => return Err(anyhow::anyhow!("Expected a text message")),
+            Some(Err(e)) => return Err(e.into()),
+        }
+    }
+}
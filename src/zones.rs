@@ -0,0 +1,44 @@
+//! IANA time zones (`America/New_York`) for clocks of other zones than Home
+//! Assistant's, looked up with `jiff`: from the system's tz database when
+//! there is one (`/usr/share/zoneinfo` or `TZDIR`), otherwise from the copy
+//! built into the binary.
+//!
+//! `WORLD_CLOCKS` lists labelled zones, e.g.
+//! `NYC=America/New_York,London=Europe/London,Tokyo=Asia/Tokyo`, shown as one
+//! `NYC 08:12 • London 13:12 • Tokyo 21:12` row when `clock.world` is among
+//! a screen's sensors.
+
+use jiff::{Timestamp, tz::TimeZone};
+use std::time::SystemTime;
+
+/// `WORLD_CLOCKS`: comma-separated `Label=Area/City` entries; without a
+/// label the city is shown. The offending entry and why are returned on
+/// error.
+pub fn parse_clocks(spec: &str) -> Result<Vec<(String, TimeZone)>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (label, name) = match entry.split_once('=') {
+                Some((label, name)) => (label.trim().to_string(), name.trim()),
+                None => {
+                    let city = entry.rsplit('/').next().unwrap_or(entry);
+                    (city.replace('_', " "), entry)
+                }
+            };
+            if label.is_empty() {
+                return Err(format!("'{}': missing label", entry));
+            }
+            let zone = TimeZone::get(name)
+                .map_err(|e| format!("'{}': unknown time zone ({})", entry, e))?;
+            Ok((label, zone))
+        })
+        .collect()
+}
+
+/// UTC offset of `zone` in seconds at `time`, east positive.
+pub fn offset_at(zone: &TimeZone, time: SystemTime) -> i64 {
+    Timestamp::try_from(time)
+        .map(|time| zone.to_offset(time).seconds() as i64)
+        .unwrap_or(0)
+}